@@ -1,42 +1,136 @@
 //! Struct for creating and accessing bathymetry data from an array.
 //!
-//! This is only used in testing purposes when we purposely want to access out
-//! of bounds.
+//! This is mostly used in testing purposes when we purposely want to access
+//! out of bounds, or as an in-memory stand-in for file-based bathymetry.
 
 use super::BathymetryData;
 use crate::{datatype::{Gradient, Point}, error::Result};
 
 pub(crate) struct ArrayDepth {
+    /// the depth, where `array[i][j]` is the depth at (i dx, j dy)
     array: Vec<Vec<f32>>,
+    /// the grid spacing (dx, dy) \[m\]. Default is (1.0, 1.0).
+    spacing: (f32, f32),
 }
 
 // TODO: to make this `ArrayDepth` useful for use outside generating out of
-// bounds values in tests, we need to define grid spacing in both x and y
-// directions and map those to cell indexes in the array. Then implement an
-// interpolation, and return a valid gradient.
+// bounds values in tests, we need to implement an interpolation. The depth is
+// the one of the grid point at the lower left of the point.
 impl BathymetryData for ArrayDepth {
     fn depth(&self, point: &Point<f32>) -> Result<f32> {
-        let x = *point.x() as usize;
-        let y = *point.y() as usize;
-        if x >= self.array.len() || y >= self.array.len() {
-            return Ok(f32::NAN);
+        match self.indexes(point) {
+            Some((i, j)) => Ok(self.array[i][j]),
+            None => Ok(f32::NAN),
         }
-        Ok(self.array[x][y])
     }
 
     fn depth_and_gradient(&self, point: &Point<f32>) -> Result<(f32, Gradient<f32>)> {
-        let x = *point.x() as usize;
-        let y = *point.y() as usize;
-        if x >= self.array.len() || y >= self.array.len() {
-            return Ok((f32::NAN, Gradient::new(f32::NAN, f32::NAN)));
+        match self.indexes(point) {
+            Some((i, j)) => Ok((self.array[i][j], self.gradient(i, j))),
+            None => Ok((f32::NAN, Gradient::new(f32::NAN, f32::NAN))),
         }
-        Ok((self.array[x][y], Gradient::new(0.0, 0.0)))
     }
 }
 
 #[allow(dead_code)]
 impl ArrayDepth {
+    /// Construct an `ArrayDepth` with a grid spacing of 1 m
+    ///
+    /// # Arguments
+    /// `array` : `Vec<Vec<f32>>`
+    /// - the depth, where `array[i][j]` is the depth at (i, j)
     pub(crate) fn new(array: Vec<Vec<f32>>) -> Self {
-        ArrayDepth { array }
+        ArrayDepth {
+            array,
+            spacing: (1.0, 1.0),
+        }
+    }
+
+    /// Set the grid spacing
+    ///
+    /// # Arguments
+    /// `dx` : `f32`
+    /// - the spacing \[m\] between the rows of the array, i.e. in x
+    ///
+    /// `dy` : `f32`
+    /// - the spacing \[m\] between the columns of the array, i.e. in y
+    pub(crate) fn with_spacing(mut self, dx: f32, dy: f32) -> Self {
+        self.spacing = (dx, dy);
+        self
+    }
+
+    /// Indexes of the grid point at the lower left of the point, or `None`
+    /// if it is outside of the array
+    fn indexes(&self, point: &Point<f32>) -> Option<(usize, usize)> {
+        let i = (*point.x() / self.spacing.0) as usize;
+        let j = (*point.y() / self.spacing.1) as usize;
+        let row = self.array.get(i)?;
+        (j < row.len()).then_some((i, j))
+    }
+
+    /// Gradient at the grid point (i, j) from central differences, or
+    /// one-sided differences at the edges of the array, as the central
+    /// difference of `CartesianNetcdf3`. The gradient is zero along an axis
+    /// with a single point.
+    fn gradient(&self, i: usize, j: usize) -> Gradient<f32> {
+        let (i_low, i_high) = (i.saturating_sub(1), (i + 1).min(self.array.len() - 1));
+        let (j_low, j_high) = (j.saturating_sub(1), (j + 1).min(self.array[i].len() - 1));
+        let dhdx = if i_high > i_low {
+            (self.array[i_high][j] - self.array[i_low][j])
+                / ((i_high - i_low) as f32 * self.spacing.0)
+        } else {
+            0.0
+        };
+        let dhdy = if j_high > j_low {
+            (self.array[i][j_high] - self.array[i][j_low])
+                / ((j_high - j_low) as f32 * self.spacing.1)
+        } else {
+            0.0
+        };
+        Gradient::new(dhdx, dhdy)
+    }
+}
+
+#[cfg(test)]
+mod test_array_depth {
+    use super::ArrayDepth;
+    use crate::{bathymetry::BathymetryData, datatype::Point};
+
+    #[test]
+    /// the gradient of a linear slope is the slope, at the edges too
+    fn test_linear_slope_gradient() {
+        // depth = 100 - 2 x + 0.5 y, with dx = 10 m and dy = 20 m
+        let array: Vec<Vec<f32>> = (0..5)
+            .map(|i| {
+                (0..4)
+                    .map(|j| 100.0 - 2.0 * (i as f32 * 10.0) + 0.5 * (j as f32 * 20.0))
+                    .collect()
+            })
+            .collect();
+        let data = ArrayDepth::new(array).with_spacing(10.0, 20.0);
+
+        for i in 0..5 {
+            for j in 0..4 {
+                let point = Point::new(i as f32 * 10.0, j as f32 * 20.0);
+                let (h, gradient) = data.depth_and_gradient(&point).unwrap();
+                assert_eq!(h, data.depth(&point).unwrap());
+                assert!((h - (100.0 - 2.0 * *point.x() + 0.5 * *point.y())).abs() < 1e-4);
+                assert!((*gradient.dx() + 2.0).abs() < 1e-5, "i: {}, j: {}", i, j);
+                assert!((*gradient.dy() - 0.5).abs() < 1e-5, "i: {}, j: {}", i, j);
+            }
+        }
+
+        // outside of the array
+        assert!(data.depth(&Point::new(50.0, 0.0)).unwrap().is_nan());
+        assert!(data.depth(&Point::new(0.0, 80.0)).unwrap().is_nan());
+    }
+
+    #[test]
+    /// an axis with a single point has zero gradient
+    fn test_single_row() {
+        let data = ArrayDepth::new(vec![vec![10.0, 20.0, 30.0]]);
+        let (_, gradient) = data.depth_and_gradient(&Point::new(0.0, 1.0)).unwrap();
+        assert_eq!(*gradient.dx(), 0.0);
+        assert_eq!(*gradient.dy(), 10.0);
     }
 }
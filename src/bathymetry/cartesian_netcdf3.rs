@@ -5,15 +5,25 @@
 
 use std::path::Path;
 
-use netcdf3::{DataType, FileReader};
+use ndarray::{Array1, Array2};
+use netcdf3::{DataSet, FileReader, NC_FILL_F32, NC_FILL_F64};
 
 use super::BathymetryData;
 use crate::{
     datatype::{Gradient, Point},
     error::{Error, Result},
     interpolator,
+    io::{read_var_as_f32, read_var_as_f64},
 };
 
+/// Relative tolerance of a fractional index to be considered on a grid point
+/// by `four_corners`
+const GRID_POINT_TOLERANCE: f32 = 1e-6;
+
+/// Relative tolerance of the spacing of the coordinates to be considered
+/// equally spaced by `from_ndarray`
+const SPACING_TOLERANCE: f64 = 1e-4;
+
 /// A struct that stores a netcdf3 dataset with methods to access, find nearest
 /// values, interpolate, and return depth and gradient.
 ///
@@ -24,10 +34,9 @@ use crate::{
 /// let data = CartesianNetcdf3::open(&path, "x", "y", "depth").unwrap();
 ///
 /// # Note
-/// Currently, the methods do not know the difference between an out of bounds
-/// point and a point within one grid space from the edge. The nearest to each
-/// of these will be on the edge, so it will return None for both. In the
-/// future, the methods should be able to distinguish these two cases.
+/// Any point within the coordinate range, including the first and last half
+/// grid cells, is interpolated using the last full cell. Only a point outside
+/// of the coordinate range is out of bounds.
 ///
 /// In this struct, None is used when the function will not panic, but the value
 /// is not useful to the other structs. Error is used when the function would
@@ -37,9 +46,116 @@ pub(crate) struct CartesianNetcdf3 {
     x: Vec<f32>,
     /// a vector containing the y values from the netcdf3 file
     y: Vec<f32>,
-    /// a vector containing the depth values from the netcdf3 file. Note this is
-    /// a flattened 2d array and is accessed by the function `depth_from_array`.
-    depth: Vec<f64>,
+    /// the depth values from the netcdf3 file. Note this is a flattened 2d
+    /// array and is accessed by the function `depth_at_indexes`.
+    depth: DepthArray,
+    /// the finite difference scheme used to calculate the depth gradient
+    gradient_method: GradientMethod,
+    /// whether the values in the file are depth or elevation
+    sign_convention: SignConvention,
+    /// the boundary condition of the x axis
+    boundary_x: BoundaryX,
+    /// how the depth is interpolated between the grid points
+    interpolation_mode: InterpolationMode,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Finite difference scheme used to calculate the depth gradient
+pub(crate) enum GradientMethod {
+    /// One-sided difference across the cell enclosing the point. The gradient
+    /// is constant within each cell. This is the default.
+    #[default]
+    OneSided,
+    /// Central difference using the four neighbors (N, S, E, W) of the grid
+    /// point nearest to the point, i.e. second order accurate at the grid
+    /// points.
+    CentralDifference,
+    /// Central difference at the four corners of the cell enclosing the
+    /// point, bilinearly interpolated to the point. The gradient varies
+    /// continuously within and across the cells, so the ray turns smoothly.
+    InterpolatedCentralDifference,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Boundary condition of the x axis
+pub(crate) enum BoundaryX {
+    /// A point outside of the x coordinate range is out of bounds. This is
+    /// the default.
+    #[default]
+    Clamp,
+    /// The x axis is periodic, e.g. the longitude of a global grid. The
+    /// period is the number of x points times the spacing, so that the cell
+    /// between the last and the first x points closes the seam, and a point
+    /// outside of the x range is wrapped into it.
+    Periodic,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// How the depth is interpolated between the grid points
+pub(crate) enum InterpolationMode {
+    /// Bilinear interpolation of the four corners of the cell enclosing the
+    /// point. This is the default.
+    #[default]
+    Bilinear,
+    /// Depth of the grid point nearest to the point, with zero gradient, e.g.
+    /// to validate against an idealized step. The depth is discontinuous
+    /// halfway between the grid points, where the gradient is not defined, so
+    /// the ray is not refracted by the bathymetry.
+    Nearest,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Sign convention of the depth variable in the file
+///
+/// Internally, the depth is always positive in water.
+pub(crate) enum SignConvention {
+    /// The values are depth, positive down, i.e. positive in water. This is
+    /// the default.
+    #[default]
+    Depth,
+    /// The values are elevation, positive up, i.e. negative in water, as in
+    /// many bathymetry products such as ETOPO. The sign is flipped when
+    /// accessed.
+    Elevation,
+}
+
+impl SignConvention {
+    /// factor that converts the values of the file to depth
+    fn sign(&self) -> f64 {
+        match self {
+            SignConvention::Depth => 1.0,
+            SignConvention::Elevation => -1.0,
+        }
+    }
+}
+
+/// Storage of the flattened depth array
+///
+/// `Double` keeps the depth in double precision, which is the default when
+/// opening a file. `Single` keeps the depth in single precision, halving the
+/// memory used by large grids. In both cases, the values are promoted to f64
+/// when accessed, so that the physics (e.g. the gradient) is always computed
+/// in double precision.
+enum DepthArray {
+    /// depth stored in double precision
+    Double(Vec<f64>),
+    /// depth stored in single precision
+    Single(Vec<f32>),
+}
+
+impl DepthArray {
+    /// depth at the flattened `index` promoted to f64, or `None` if the index
+    /// is out of bounds.
+    fn get(&self, index: usize) -> Option<f64> {
+        match self {
+            DepthArray::Double(v) => v.get(index).copied(),
+            DepthArray::Single(v) => v.get(index).map(|h| *h as f64),
+        }
+    }
 }
 
 impl BathymetryData for CartesianNetcdf3 {
@@ -62,12 +178,25 @@ impl BathymetryData for CartesianNetcdf3 {
     /// input give an out of bounds output during the `interpolate` method.
     /// - `Error::InvalidArgument` : this error is returned from
     ///   `interpolator::bilinear` due to incorrect argument passed.
+    /// - `Error::Land` : one of the corners of the cell is a land cell.
     fn depth(&self, point: &Point<f32>) -> Result<f32> {
-        let x = point.x();
+        self.depth_f64(&Point::new(*point.x() as f64, *point.y() as f64))
+    }
+
+    /// Depth at the inputted (x, y) point in double precision.
+    ///
+    /// Same as `depth`, but the cell and the interpolation weights are found
+    /// in double precision, so that large coordinates, e.g. UTM, are not
+    /// rounded to the f32 resolution before finding the cell.
+    fn depth_f64(&self, point: &Point<f64>) -> Result<f32> {
+        let x = &self.wrap_x_f64(*point.x());
         let y = point.y();
         if x.is_nan() || y.is_nan() {
             return Ok(f32::NAN);
         }
+        if self.interpolation_mode == InterpolationMode::Nearest {
+            return self.nearest_depth(x, y);
+        }
 
         let corner_points = match self.four_corners(x, y) {
             Ok(point) => point,
@@ -95,12 +224,25 @@ impl BathymetryData for CartesianNetcdf3 {
     /// `x` or `y` input give an out of bounds output.
     /// - `Error::InvalidArgument` : this error is returned from
     ///   `interpolator::bilinear` due to incorrect argument passed.
+    /// - `Error::Land` : one of the points used for the depth or the gradient
+    ///   is a land cell.
     fn depth_and_gradient(&self, point: &Point<f32>) -> Result<(f32, Gradient<f32>)> {
-        let x = point.x();
+        self.depth_and_gradient_f64(&Point::new(*point.x() as f64, *point.y() as f64))
+    }
+
+    /// Depth and gradient at the given (x, y) coordinate in double precision.
+    ///
+    /// Same as `depth_and_gradient`, but the cell and the interpolation
+    /// weights are found in double precision, see `depth_f64`.
+    fn depth_and_gradient_f64(&self, point: &Point<f64>) -> Result<(f32, Gradient<f32>)> {
+        let x = &self.wrap_x_f64(*point.x());
         let y = point.y();
         if x.is_nan() || y.is_nan() {
             return Ok((f32::NAN, Gradient::new(f32::NAN, f32::NAN)));
         }
+        if self.interpolation_mode == InterpolationMode::Nearest {
+            return Ok((self.nearest_depth(x, y)?, Gradient::new(0.0, 0.0)));
+        }
 
         let corner_points = match self.four_corners(x, y) {
             Ok(point) => point,
@@ -111,27 +253,88 @@ impl BathymetryData for CartesianNetcdf3 {
         let depth = self.interpolate(&corner_points, &(*x, *y))?;
 
         // get the gradient
+        let (x_gradient, y_gradient) = match self.gradient_method {
+            GradientMethod::OneSided => self.one_sided_gradient(&corner_points)?,
+            GradientMethod::CentralDifference => self.central_gradient(x, y)?,
+            GradientMethod::InterpolatedCentralDifference => {
+                self.interpolated_gradient(&corner_points, &(*x, *y))?
+            }
+        };
 
-        // Note: the gradient assumes that the depth is linear in both the x
-        // and y directions, and since bilinear interpolation is used to
-        // interpolate the depth at any given point, this is a good
-        // approximation.
-        let x_space = self.x[1] as f64 - self.x[0] as f64;
-        let y_space = self.y[1] as f64 - self.y[0] as f64;
+        Ok((depth, Gradient::new(x_gradient as f32, y_gradient as f32)))
+    }
 
-        let sw_point = &corner_points[0];
-        let nw_point = &corner_points[1];
-        let se_point = &corner_points[3];
+    /// Depth and gradient at many (x, y) points.
+    ///
+    /// Same as `depth_and_gradient` for each point but, with the one-sided
+    /// gradient and the bilinear interpolation, the points are sorted by cell
+    /// so that the gradient, which is constant in a cell, is computed once
+    /// per cell.
+    fn depth_and_gradient_batch(&self, points: &[Point<f32>]) -> Vec<Result<(f32, Gradient<f32>)>> {
+        if self.gradient_method != GradientMethod::OneSided
+            || self.interpolation_mode != InterpolationMode::Bilinear
+        {
+            return points
+                .iter()
+                .map(|point| self.depth_and_gradient(point))
+                .collect();
+        }
 
-        let x_gradient = (self.depth_at_indexes(&se_point.0, &se_point.1)?
-            - self.depth_at_indexes(&sw_point.0, &sw_point.1)?)
-            / x_space;
+        // the index, wrapped coordinates, and cell of each point, where the
+        // cell is `None` for a NaN point
+        let mut cells: Vec<_> = points
+            .iter()
+            .enumerate()
+            .map(|(i, point)| {
+                let x = self.wrap_x_f64(*point.x() as f64);
+                let y = *point.y() as f64;
+                let corner_points = if x.is_nan() || y.is_nan() {
+                    None
+                } else {
+                    Some(self.four_corners(&x, &y))
+                };
+                (i, x, y, corner_points)
+            })
+            .collect();
+        cells.sort_by_key(|(_, _, _, corner_points)| {
+            corner_points
+                .as_ref()
+                .and_then(|c| c.as_ref().ok())
+                .map(|c| c[0])
+        });
 
-        let y_gradient = (self.depth_at_indexes(&nw_point.0, &nw_point.1)?
-            - self.depth_at_indexes(&sw_point.0, &sw_point.1)?)
-            / y_space;
+        let mut results: Vec<_> = (0..points.len()).map(|_| None).collect();
+        // the cell of the previous point and its gradient
+        let mut cached: Option<((usize, usize), (f64, f64))> = None;
+        for (i, x, y, corner_points) in cells {
+            let Some(corner_points) = corner_points else {
+                results[i] = Some(Ok((f32::NAN, Gradient::new(f32::NAN, f32::NAN))));
+                continue;
+            };
+            results[i] = Some(corner_points.and_then(|corner_points| {
+                let depth = self.interpolate(&corner_points, &(x, y))?;
+                let (x_gradient, y_gradient) = match cached {
+                    Some((cell, gradient)) if cell == corner_points[0] => gradient,
+                    _ => {
+                        let gradient = self.one_sided_gradient(&corner_points)?;
+                        cached = Some((corner_points[0], gradient));
+                        gradient
+                    }
+                };
+                Ok((depth, Gradient::new(x_gradient as f32, y_gradient as f32)))
+            }));
+        }
+        results.into_iter().flatten().collect()
+    }
 
-        Ok((depth, Gradient::new(x_gradient as f32, y_gradient as f32)))
+    /// Mean spacing of the x and y coordinates, or `None` if there is a
+    /// single point in either direction.
+    fn grid_spacing(&self) -> Option<(f64, f64)> {
+        let spacing = |v: &[f32]| match v {
+            [first, .., last] => Some((last - first) as f64 / (v.len() - 1) as f64),
+            _ => None,
+        };
+        Some((spacing(&self.x)?, spacing(&self.y)?))
     }
 }
 
@@ -154,111 +357,546 @@ impl CartesianNetcdf3 {
     /// - the name of the depth variable in the netcdf3 file
     ///
     /// # Returns
-    /// `Result<Self>` : an initialized CartesianNetCDF3 struct or a `ReadError`
-    /// from the netcdf3 crate.
+    /// `Result<Self>` : an initialized CartesianNetCDF3 struct or an error.
     ///
-    /// # Panics
-    /// `new` will panic if the data type is invalid or if any of the names are
-    /// invalid.
+    /// # Errors
+    /// - `Error::IOError` : the file could not be opened, e.g. it does not
+    ///   exist.
+    /// - `Error::VariableNotFound` : one of the variables is not in the file.
+    /// - `Error::UnsupportedDataType` : one of the variables can't be
+    ///   converted to a floating point array.
+    /// - `Error::ReadError` : the file is not a valid NetCDF3 file.
+    /// - `Error::DimensionMismatch` : the length of the depth is not the
+    ///   length of x times the length of y.
     ///
     /// # Note
-    /// in the future, be able to check attributes and verify that the file is
-    /// correct.
+    /// The sign convention is detected from the attributes of the depth
+    /// variable: an elevation `standard_name` (e.g.
+    /// "height_above_reference_ellipsoid") or `positive = "up"` means the
+    /// values are elevation, and their sign is flipped. Otherwise, the values
+    /// are assumed to be depth. Use `with_sign_convention` to override it.
+    ///
+    /// The depth is usually stored with the dimensions (y, x). If the
+    /// dimensions of the depth variable are instead the dimensions of the x
+    /// and y variables in the order (x, y), the depth is transposed when
+    /// loaded.
     pub(crate) fn open(path: &Path, xname: &str, yname: &str, depth_name: &str) -> Result<Self> {
         let mut data = FileReader::open(path)?;
+        let sign_convention = sign_convention(data.data_set(), depth_name).unwrap_or_default();
 
-        let x = data.read_var(xname)?;
-        let x = match x.data_type() {
-            DataType::I16 => x
-                .get_i16_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f32)
-                .collect(),
-            DataType::I8 => x.get_i8_into().unwrap().iter().map(|x| *x as f32).collect(),
-            DataType::U8 => x.get_u8_into().unwrap().iter().map(|x| *x as f32).collect(),
-            DataType::I32 => x
-                .get_i32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f32)
-                .collect(),
-            DataType::F32 => x.get_f32_into().unwrap(),
-            DataType::F64 => x
-                .get_f64_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f32)
-                .collect(),
+        let x = read_var_as_f32(&mut data, xname)?;
+        let y = read_var_as_f32(&mut data, yname)?;
+
+        let depth = read_var_as_f64(&mut data, depth_name)?;
+        check_dimensions(&x, &y, depth.len())?;
+        let depth = if is_transposed(data.data_set(), xname, yname, depth_name) {
+            transpose(depth, x.len(), y.len())
+        } else {
+            depth
         };
 
-        let y = data.read_var(yname)?;
-        let y = match y.data_type() {
-            DataType::I16 => y
-                .get_i16_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f32)
-                .collect(),
-            DataType::I8 => y.get_i8_into().unwrap().iter().map(|x| *x as f32).collect(),
-            DataType::U8 => y.get_u8_into().unwrap().iter().map(|x| *x as f32).collect(),
-            DataType::I32 => y
-                .get_i32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f32)
-                .collect(),
-            DataType::F32 => y.get_f32_into().unwrap(),
-            DataType::F64 => y
-                .get_f64_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f32)
-                .collect(),
+        Ok(CartesianNetcdf3 {
+            x,
+            y,
+            depth: DepthArray::Double(depth),
+            gradient_method: GradientMethod::default(),
+            sign_convention,
+            boundary_x: BoundaryX::default(),
+            interpolation_mode: InterpolationMode::default(),
+        })
+    }
+
+    #[allow(dead_code)]
+    /// Initialize the CartesianNetCDF3 struct from arrays in memory
+    ///
+    /// # Arguments
+    /// `x` : `Vec<f32>`
+    /// - the x coordinates, equally spaced in ascending order
+    ///
+    /// `y` : `Vec<f32>`
+    /// - the y coordinates, equally spaced in ascending order
+    ///
+    /// `depth` : `Vec<f64>`
+    /// - the flattened depth, positive in water, where the row is y and the
+    ///   column is x
+    ///
+    /// # Returns
+    /// `Result<Self>` : an initialized CartesianNetCDF3 struct or an error.
+    ///
+    /// # Errors
+    /// - `Error::DimensionMismatch` : the length of `depth` is not the length
+    ///   of `x` times the length of `y`.
+    /// - `Error::InvalidArgument` : `x` or `y` is not equally spaced in
+    ///   ascending order.
+    pub(crate) fn from_arrays(x: Vec<f32>, y: Vec<f32>, depth: Vec<f64>) -> Result<Self> {
+        check_dimensions(&x, &y, depth.len())?;
+        if !equally_spaced(&x) || !equally_spaced(&y) {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(CartesianNetcdf3 {
+            x,
+            y,
+            depth: DepthArray::Double(depth),
+            gradient_method: GradientMethod::default(),
+            sign_convention: SignConvention::default(),
+            boundary_x: BoundaryX::default(),
+            interpolation_mode: InterpolationMode::default(),
+        })
+    }
+
+    #[allow(dead_code)]
+    /// Initialize the CartesianNetCDF3 struct from ndarray arrays
+    ///
+    /// Same as `from_arrays`, but the depth is a 2D array, e.g. from a
+    /// preprocessing step such as smoothing or masking, and it is kept in
+    /// single precision as in `open_single_precision`.
+    ///
+    /// # Arguments
+    /// `x` : `Array1<f32>`
+    /// - the x coordinates, equally spaced in ascending order
+    ///
+    /// `y` : `Array1<f32>`
+    /// - the y coordinates, equally spaced in ascending order
+    ///
+    /// `depth` : `Array2<f32>`
+    /// - the depth, positive in water, with shape (y, x), i.e. `depth[[j, i]]`
+    ///   is the depth at `(x[i], y[j])`, as returned by `depth_grid`.
+    ///
+    /// # Returns
+    /// `Result<Self>` : an initialized CartesianNetCDF3 struct or an error.
+    ///
+    /// # Errors
+    /// - `Error::DimensionMismatch` : the shape of `depth` is not the length
+    ///   of `y` by the length of `x`. The error has the expected and actual
+    ///   length of the first mismatched dimension.
+    /// - `Error::InvalidArgument` : `x` or `y` is not equally spaced in
+    ///   ascending order.
+    pub(crate) fn from_ndarray(x: Array1<f32>, y: Array1<f32>, depth: Array2<f32>) -> Result<Self> {
+        let (rows, columns) = depth.dim();
+        if rows != y.len() {
+            return Err(Error::DimensionMismatch {
+                expected: y.len(),
+                got: rows,
+            });
+        }
+        if columns != x.len() {
+            return Err(Error::DimensionMismatch {
+                expected: x.len(),
+                got: columns,
+            });
+        }
+        let (x, y) = (x.to_vec(), y.to_vec());
+        if !equally_spaced(&x) || !equally_spaced(&y) {
+            return Err(Error::InvalidArgument);
+        }
+
+        Ok(CartesianNetcdf3 {
+            x,
+            y,
+            // iterates in logical order, whatever the memory layout
+            depth: DepthArray::Single(depth.iter().copied().collect()),
+            gradient_method: GradientMethod::default(),
+            sign_convention: SignConvention::default(),
+            boundary_x: BoundaryX::default(),
+            interpolation_mode: InterpolationMode::default(),
+        })
+    }
+
+    #[allow(dead_code)]
+    /// Initialize the CartesianNetCDF3 struct detecting the variables from
+    /// their CF attributes
+    ///
+    /// Variable names vary across datasets ("z", "elevation", "bathymetry",
+    /// "Band1", ...), so instead of names, the variables are detected by their
+    /// CF attributes:
+    /// - x : `axis = "X"` or `standard_name = "projection_x_coordinate"`
+    /// - y : `axis = "Y"` or `standard_name = "projection_y_coordinate"`
+    /// - depth : a depth `standard_name`, such as
+    ///   "sea_floor_depth_below_sea_surface", or a 2D variable with `units` of
+    ///   meters and `positive = "down"`.
+    /// - elevation : an elevation `standard_name`, such as
+    ///   "height_above_mean_sea_level", or a 2D variable with `units` of
+    ///   meters and `positive = "up"`. The sign convention is set to
+    ///   `SignConvention::Elevation`.
+    ///
+    /// If more than one variable matches, the first one in the file is used.
+    ///
+    /// # Arguments
+    /// `path` : `&Path`
+    /// - a path to the location of the netcdf3 file
+    ///
+    /// # Returns
+    /// `Result<Self>` : an initialized CartesianNetCDF3 struct or an error.
+    ///
+    /// # Errors
+    /// - `Error::VariableNotDetected` : the x, y, or depth variable could not
+    ///   be detected. The error lists the variables available in the file.
+    /// - `Error::ReadError` : error reading the file.
+    /// - `Error::DimensionMismatch` : the length of the depth is not the
+    ///   length of x times the length of y.
+    pub(crate) fn open_cf(path: &Path) -> Result<Self> {
+        let mut data = FileReader::open(path)?;
+        let (xname, yname, depth_name, sign_convention) = detect_cf_variables(data.data_set())?;
+
+        let x = read_var_as_f32(&mut data, &xname)?;
+        let y = read_var_as_f32(&mut data, &yname)?;
+        let depth = read_var_as_f64(&mut data, &depth_name)?;
+        check_dimensions(&x, &y, depth.len())?;
+        let depth = if is_transposed(data.data_set(), &xname, &yname, &depth_name) {
+            transpose(depth, x.len(), y.len())
+        } else {
+            depth
         };
 
-        let depth = data.read_var(depth_name)?;
-        let depth = match depth.data_type() {
-            DataType::I16 => depth
-                .get_i16_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I8 => depth
-                .get_i8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::U8 => depth
-                .get_u8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I32 => depth
-                .get_i32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F32 => depth
-                .get_f32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F64 => depth.get_f64_into().unwrap(),
+        Ok(CartesianNetcdf3 {
+            x,
+            y,
+            depth: DepthArray::Double(depth),
+            gradient_method: GradientMethod::default(),
+            sign_convention,
+            boundary_x: BoundaryX::default(),
+            interpolation_mode: InterpolationMode::default(),
+        })
+    }
+
+    #[allow(dead_code)]
+    /// Initialize the CartesianNetCDF3 struct storing the depth in single
+    /// precision
+    ///
+    /// Same as `open`, but the depth is kept in memory as f32, which halves
+    /// the memory required by large grids, such as f32 NetCDF files of
+    /// regional bathymetry. The depth is promoted to f64 only when accessed,
+    /// thus the gradient is still computed with double precision arithmetic.
+    ///
+    /// # Arguments
+    /// `path` : `&Path`
+    /// - a path to the location of the netcdf3 file
+    ///
+    /// `xname` : `&str`
+    /// - the name of the x variable in the netcdf3 file
+    ///
+    /// `yname` : `&str`
+    /// - the name of the y variable in the netcdf3 file
+    ///
+    /// `depth_name` : `&str`
+    /// - the name of the depth variable in the netcdf3 file
+    ///
+    /// # Returns
+    /// `Result<Self>` : an initialized CartesianNetCDF3 struct or a `ReadError`
+    /// from the netcdf3 crate.
+    ///
+    /// # Note
+    /// Depth stored in the file as f64 is rounded to f32 when loaded. The
+    /// sign convention is detected as in `open`.
+    pub(crate) fn open_single_precision(
+        path: &Path,
+        xname: &str,
+        yname: &str,
+        depth_name: &str,
+    ) -> Result<Self> {
+        let mut data = FileReader::open(path)?;
+        let sign_convention = sign_convention(data.data_set(), depth_name).unwrap_or_default();
+
+        let x = read_var_as_f32(&mut data, xname)?;
+        let y = read_var_as_f32(&mut data, yname)?;
+        let depth = read_var_as_f32(&mut data, depth_name)?;
+        check_dimensions(&x, &y, depth.len())?;
+        let depth = if is_transposed(data.data_set(), xname, yname, depth_name) {
+            transpose(depth, x.len(), y.len())
+        } else {
+            depth
         };
 
-        Ok(CartesianNetcdf3 { x, y, depth })
+        Ok(CartesianNetcdf3 {
+            x,
+            y,
+            depth: DepthArray::Single(depth),
+            gradient_method: GradientMethod::default(),
+            sign_convention,
+            boundary_x: BoundaryX::default(),
+            interpolation_mode: InterpolationMode::default(),
+        })
+    }
+
+    #[allow(dead_code)]
+    /// The raw grid of the dataset
+    ///
+    /// Useful to verify the file was loaded correctly and to cross-check the
+    /// interpolated depth against the grid values.
+    ///
+    /// # Returns
+    /// `(&[f32], &[f32], Array2<f32>)` : the x axis, the y axis, and the depth
+    /// with shape (y, x), i.e. `depth[[j, i]]` is the depth at `(x[i], y[j])`.
+    /// The depth follows the sign convention, i.e. it is positive in water.
+    pub(crate) fn depth_grid(&self) -> (&[f32], &[f32], Array2<f32>) {
+        let sign = self.sign_convention.sign();
+        let depth = Array2::from_shape_fn((self.y.len(), self.x.len()), |(j, i)| {
+            (sign * self.depth_at_indexes(&i, &j).unwrap_or(f64::NAN)) as f32
+        });
+        (&self.x, &self.y, depth)
+    }
+
+    #[allow(dead_code)]
+    /// Contour of the depth at the given level
+    ///
+    /// The contour is extracted with marching squares: in each grid cell, the
+    /// points where the contour crosses the cell edges are linearly
+    /// interpolated from the depth at the corners, and connected into
+    /// segments. In a saddle cell, the segments are chosen by the mean depth
+    /// of the cell. Cells with a land corner are skipped.
+    ///
+    /// Useful to compare the path of a ray with a depth contour, e.g. the
+    /// turning point of a ray with the depth where it is expected to turn.
+    ///
+    /// # Arguments
+    /// `level` : `f32`
+    /// - the depth \[m\] of the contour
+    ///
+    /// # Returns
+    /// `Vec<(f32, f32)>` : the (x, y) of the contour as consecutive pairs of
+    /// points, where each pair is a segment of the contour inside one grid
+    /// cell. The segments are ordered by cell, not along the contour.
+    pub(crate) fn depth_contour(&self, level: f32) -> Vec<(f32, f32)> {
+        let level = level as f64;
+        let mut points = Vec::new();
+        for j in 0..self.y.len().saturating_sub(1) {
+            for i in 0..self.x.len().saturating_sub(1) {
+                // counterclockwise, starting at the lower left corner
+                let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                let Ok(depths) = corners
+                    .iter()
+                    .map(|(ci, cj)| self.water_depth_at_indexes(ci, cj))
+                    .collect::<Result<Vec<f64>>>()
+                else {
+                    continue;
+                };
+                let inside: Vec<bool> = depths.iter().map(|h| *h >= level).collect();
+
+                // the crossing on each edge, from corner n to corner n + 1
+                let crossings: Vec<Option<(f32, f32)>> = (0..4)
+                    .map(|n| {
+                        let m = (n + 1) % 4;
+                        if inside[n] == inside[m] {
+                            return None;
+                        }
+                        let t = (level - depths[n]) / (depths[m] - depths[n]);
+                        let (xn, yn) = (self.x[corners[n].0] as f64, self.y[corners[n].1] as f64);
+                        let (xm, ym) = (self.x[corners[m].0] as f64, self.y[corners[m].1] as f64);
+                        Some(((xn + t * (xm - xn)) as f32, (yn + t * (ym - yn)) as f32))
+                    })
+                    .collect();
+
+                match crossings.iter().flatten().count() {
+                    2 => points.extend(crossings.iter().flatten()),
+                    4 => {
+                        let center_inside = depths.iter().sum::<f64>() / 4.0 >= level;
+                        let edges = if center_inside == inside[0] {
+                            [0, 1, 2, 3]
+                        } else {
+                            [3, 0, 1, 2]
+                        };
+                        points.extend(edges.iter().filter_map(|e| crossings[*e]));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        points
+    }
+
+    #[allow(dead_code)]
+    /// A copy of the dataset with the depth smoothed by a Gaussian filter
+    ///
+    /// Grid-scale noise in the bathymetry, e.g. survey artifacts, is amplified
+    /// by the gradient and produces spurious wiggles in the rays. The filter is
+    /// applied along x, then along y, with the kernel truncated at 3 sigma.
+    /// Land cells are kept as land and are excluded from the average, as are
+    /// the points beyond the edges of the grid, except across the seam of a
+    /// periodic x axis.
+    ///
+    /// # Arguments
+    /// `sigma_cells` : `f64`
+    /// - the standard deviation of the Gaussian kernel, in grid cells. A
+    ///   non-positive value returns an unchanged copy.
+    ///
+    /// # Returns
+    /// `Self` : the smoothed dataset, with the same grid, precision, and
+    /// settings.
+    pub(crate) fn smoothed(&self, sigma_cells: f64) -> Self {
+        let (nx, ny) = (self.x.len(), self.y.len());
+        let kernel = gaussian_kernel(sigma_cells);
+        let periodic = self.boundary_x == BoundaryX::Periodic;
+
+        let mut depth: Vec<f64> = (0..nx * ny)
+            .map(|index| self.depth.get(index).unwrap_or(f64::NAN))
+            .collect();
+        for row in depth.chunks_mut(nx.max(1)) {
+            let smoothed = smooth_line(row, &kernel, periodic);
+            row.copy_from_slice(&smoothed);
+        }
+        for i in 0..nx {
+            let column: Vec<f64> = (0..ny).map(|j| depth[j * nx + i]).collect();
+            for (j, h) in smooth_line(&column, &kernel, false).into_iter().enumerate() {
+                depth[j * nx + i] = h;
+            }
+        }
+
+        CartesianNetcdf3 {
+            x: self.x.clone(),
+            y: self.y.clone(),
+            depth: match self.depth {
+                DepthArray::Double(_) => DepthArray::Double(depth),
+                DepthArray::Single(_) => {
+                    DepthArray::Single(depth.into_iter().map(|h| h as f32).collect())
+                }
+            },
+            gradient_method: self.gradient_method,
+            sign_convention: self.sign_convention,
+            boundary_x: self.boundary_x,
+            interpolation_mode: self.interpolation_mode,
+        }
+    }
+
+    #[allow(dead_code)]
+    /// Set the finite difference scheme used to calculate the depth gradient
+    ///
+    /// # Arguments
+    /// `gradient_method` : `GradientMethod`
+    /// - the scheme used by `depth_and_gradient`
+    ///
+    /// # Returns
+    /// `Self` : the CartesianNetcdf3 struct using the given scheme
+    pub(crate) fn with_gradient_method(mut self, gradient_method: GradientMethod) -> Self {
+        self.gradient_method = gradient_method;
+        self
+    }
+
+    #[allow(dead_code)]
+    /// Set the sign convention of the values in the file
+    ///
+    /// Overrides the convention detected when opening the file, for instance,
+    /// for an elevation file without the `positive` attribute.
+    ///
+    /// # Arguments
+    /// `sign_convention` : `SignConvention`
+    /// - whether the values in the file are depth or elevation
+    ///
+    /// # Returns
+    /// `Self` : the CartesianNetcdf3 struct using the given convention
+    pub(crate) fn with_sign_convention(mut self, sign_convention: SignConvention) -> Self {
+        self.sign_convention = sign_convention;
+        self
+    }
+
+    #[allow(dead_code)]
+    /// Set the boundary condition of the x axis
+    ///
+    /// With `BoundaryX::Periodic`, the depth and gradient are continuous
+    /// across the seam between the last and the first x points. The x of the
+    /// ray keeps increasing (or decreasing) across the seam, so that the path
+    /// is continuous, and `wrap_x` maps it back into the grid.
+    ///
+    /// # Arguments
+    /// `boundary_x` : `BoundaryX`
+    /// - the boundary condition of the x axis
+    ///
+    /// # Returns
+    /// `Self` : the CartesianNetcdf3 struct using the given boundary condition
+    pub(crate) fn with_boundary_x(mut self, boundary_x: BoundaryX) -> Self {
+        self.boundary_x = boundary_x;
+        self
+    }
+
+    #[allow(dead_code)]
+    /// Set how the depth is interpolated between the grid points
+    ///
+    /// # Arguments
+    /// `interpolation_mode` : `InterpolationMode`
+    /// - the interpolation used by `depth` and `depth_and_gradient`
+    ///
+    /// # Returns
+    /// `Self` : the CartesianNetcdf3 struct using the given interpolation
+    pub(crate) fn with_interpolation_mode(mut self, interpolation_mode: InterpolationMode) -> Self {
+        self.interpolation_mode = interpolation_mode;
+        self
+    }
+
+    #[allow(dead_code)]
+    /// Wrap the x coordinate into the grid
+    ///
+    /// # Arguments
+    /// `x` : `f32`
+    /// - x location \[m\]
+    ///
+    /// # Returns
+    /// `f32` : with a periodic x axis, the equivalent x in
+    /// \[x_first, x_first + period), otherwise `x` unchanged.
+    pub(crate) fn wrap_x(&self, x: f32) -> f32 {
+        match (self.boundary_x, self.x.as_slice()) {
+            (BoundaryX::Periodic, [x0, x1, ..]) => {
+                let period = (x1 - x0) * self.x.len() as f32;
+                x0 + (x - x0).rem_euclid(period)
+            }
+            _ => x,
+        }
+    }
+
+    /// Wrap the x coordinate into the grid in double precision, see `wrap_x`
+    fn wrap_x_f64(&self, x: f64) -> f64 {
+        match (self.boundary_x, self.x.as_slice()) {
+            (BoundaryX::Periodic, [x0, x1, ..]) => {
+                let (x0, x1) = (*x0 as f64, *x1 as f64);
+                let period = (x1 - x0) * self.x.len() as f64;
+                x0 + (x - x0).rem_euclid(period)
+            }
+            _ => x,
+        }
+    }
+
+    /// Number of x points, including the point closing the seam of a
+    /// periodic x axis, which is the first x point shifted by one period.
+    fn x_len(&self) -> usize {
+        match self.boundary_x {
+            BoundaryX::Clamp => self.x.len(),
+            BoundaryX::Periodic => self.x.len() + 1,
+        }
+    }
+
+    /// The x coordinate at the index, which can be the point closing the seam
+    /// of a periodic x axis (see `x_len`).
+    fn x_at(&self, xindex: usize) -> f32 {
+        if xindex < self.x.len() {
+            self.x[xindex]
+        } else {
+            self.x[0] + (self.x[1] - self.x[0]) * xindex as f32
+        }
+    }
+
+    /// Fractional index of the x coordinate on a periodic x axis
+    ///
+    /// # Returns
+    /// `Result<f32>` : the index in \[0, number of x points), where an index
+    /// after the last x point is in the cell closing the seam.
+    ///
+    /// # Errors
+    /// `Error::IndexOutOfBounds` : there are less than two x points, so the
+    /// period is undefined.
+    fn periodic_index(&self, x: &f64) -> Result<f32> {
+        if self.x.len() < 2 {
+            return Err(Error::IndexOutOfBounds);
+        }
+        let n = self.x.len() as f64;
+        let (x0, x1) = (self.x[0] as f64, self.x[1] as f64);
+        let index = ((x - x0) / (x1 - x0)).rem_euclid(n);
+        // rem_euclid may round up to n for a tiny negative value
+        Ok(if index >= n { 0.0 } else { index as f32 })
     }
 
     /// Find the index of the closest value to the target in the array
     ///
     /// # Arguments
-    /// `target` : `&f32`
+    /// `target` : `&f64`
     /// - the value to find
     ///
     /// `arr` : `&[f32]`
@@ -271,7 +909,7 @@ impl CartesianNetcdf3 {
     /// This function assumes the array has equal spacing between all elements
     /// and is ordered from least to greatest. Given those two conditions, it is
     /// valid to have fractional indexes.
-    fn nearest(&self, target: &f32, array: &[f32]) -> Result<f32> {
+    fn nearest(&self, target: &f64, array: &[f32]) -> Result<f32> {
         // array has to have at least 1 element (prevent future divide by zero error)
         if array.is_empty() {
             return Err(Error::IndexOutOfBounds); // error
@@ -284,24 +922,29 @@ impl CartesianNetcdf3 {
 
         // we know the array has at least two elements, so the following line
         // will never panic
-        let spacing = (array[1] - array[0]).abs();
+        let first = array[0] as f64;
+        let last = array[array.len() - 1] as f64;
+        let spacing = (array[1] as f64 - first).abs();
+
+        // only a point outside of the coordinate range is out of bounds. the
+        // index is clamped, so that a point on the last coordinate is not
+        // rejected due to the rounding of the spacing.
+        if *target < first || *target > last {
+            return Err(Error::IndexOutOfBounds);
+        }
 
-        let index = (target - array[0]) / spacing;
+        let index = (target - first) / spacing;
 
-        if index < 0.0 || index > (array.len() - 1) as f32 {
-            Err(Error::IndexOutOfBounds)
-        } else {
-            Ok(index)
-        }
+        Ok(index.clamp(0.0, (array.len() - 1) as f64) as f32)
     }
 
     /// Returns the nearest (xindex, yindex) point to given (x ,y) point
     ///
     /// # Arguments
-    /// `x`: `&f32`
+    /// `x`: `&f64`
     /// - x location in meters
     ///
-    /// `y`: `&f32`
+    /// `y`: `&f64`
     /// - y location in meters
     ///
     /// # Returns
@@ -310,14 +953,30 @@ impl CartesianNetcdf3 {
     /// # Note
     /// This function assumes the x and y dimensions of the data are equally
     /// spaced arrays in ascending order. Therefore, fractional indexes are expected.
-    fn nearest_point(&self, x: &f32, y: &f32) -> Result<(f32, f32)> {
+    fn nearest_point(&self, x: &f64, y: &f64) -> Result<(f32, f32)> {
         // find floating point "index"
-        let xindex = self.nearest(x, &self.x)?;
+        let xindex = match self.boundary_x {
+            BoundaryX::Clamp => self.nearest(x, &self.x)?,
+            BoundaryX::Periodic => self.periodic_index(x)?,
+        };
         let yindex = self.nearest(y, &self.y)?;
 
         Ok((xindex, yindex))
     }
 
+    /// Depth of the grid point nearest to the given (x, y) point
+    ///
+    /// # Errors
+    /// - `Error::IndexOutOfBounds` : the point is outside of the coordinate
+    ///   range.
+    /// - `Error::Land` : the nearest grid point is a land cell.
+    fn nearest_depth(&self, x: &f64, y: &f64) -> Result<f32> {
+        let (xindex, yindex) = self.nearest_point(x, y)?;
+        let depth =
+            self.water_depth_at_indexes(&(xindex.round() as usize), &(yindex.round() as usize))?;
+        Ok(depth as f32)
+    }
+
     /// Get four adjacent points
     ///
     /// # Arguments
@@ -331,12 +990,21 @@ impl CartesianNetcdf3 {
     /// `Result<Vec<(usize, usize)>>`: returns a vector of the 4 points
     /// surrounding the target point. The points are in clockwise order starting
     /// with the bottom left point. Or it will return an out of bounds error.
-    fn four_corners(&self, x: &f32, y: &f32) -> Result<Vec<(usize, usize)>> {
+    ///
+    /// # Errors
+    /// `Error::IndexOutOfBounds` : the point is out of bounds, or an axis has
+    /// less than two points, so there is no cell around the point.
+    fn four_corners(&self, x: &f64, y: &f64) -> Result<Vec<(usize, usize)>> {
+        if self.x.len() < 2 || self.y.len() < 2 {
+            return Err(Error::IndexOutOfBounds);
+        }
         let (xindex, yindex) = self.nearest_point(x, y)?;
+        let xindex = snap_to_grid_point(xindex);
+        let yindex = snap_to_grid_point(yindex);
 
         // determine the edges
         let xlow = 0.0;
-        let xhigh = (self.x.len() - 1) as f32;
+        let xhigh = (self.x_len() - 1) as f32;
         let ylow = 0.0;
         let yhigh = (self.y.len() - 1) as f32;
 
@@ -389,59 +1057,186 @@ impl CartesianNetcdf3 {
         Ok(vec![(x1, y1), (x1, y2), (x2, y2), (x2, y1)])
     }
 
-    /// Interpolate the depth using crate::interpolator::bilinear
-    ///
-    /// First, the index points are converted to the x and y values at those
-    /// indexes, then the depth at that index is taken. Finally, these are used
-    /// as arguments to `interpolator::bilinear`.
+    /// Gradient from the one-sided differences across the enclosing cell
     ///
     /// # Arguments
-    /// `index_points`: `&Vec<(usize, usize)>`
-    /// - a vector of (x_index, y_index) points representing the indices of the
-    ///   corners that the target location is within.
-    ///
-    /// `target`: `&(f32, f32)`
-    /// - interpolate the depth at this (x, y) point
+    /// `corner_points` : `&[(usize, usize)]`
+    /// - the four corners of the cell returned by `four_corners`
     ///
     /// # Returns
-    /// `Result<f32>`
-    /// - `Ok(f32)` : the depth at the target point
-    /// - `Err(Error)` : cannot read depths from at coordinates in the `points`
-    ///   vector.
+    /// `Result<(f64, f64)>` : (dhdx, dhdy) or an error if the corners are out
+    /// of bounds.
     ///
-    /// # Errors
-    /// - `Error::IndexOutOfBounds` : one or more of the points passed to
-    /// `points` is out of bounds.
+    /// # Note
+    /// The gradient assumes that the depth is linear in both the x and y
+    /// directions, and since bilinear interpolation is used to interpolate the
+    /// depth at any given point, this is a good approximation.
+    fn one_sided_gradient(&self, corner_points: &[(usize, usize)]) -> Result<(f64, f64)> {
+        let x_space = self.x[1] as f64 - self.x[0] as f64;
+        let y_space = self.y[1] as f64 - self.y[0] as f64;
+
+        let sw_point = &corner_points[0];
+        let nw_point = &corner_points[1];
+        let se_point = &corner_points[3];
+
+        let x_gradient = (self.water_depth_at_indexes(&se_point.0, &se_point.1)?
+            - self.water_depth_at_indexes(&sw_point.0, &sw_point.1)?)
+            / x_space;
+
+        let y_gradient = (self.water_depth_at_indexes(&nw_point.0, &nw_point.1)?
+            - self.water_depth_at_indexes(&sw_point.0, &sw_point.1)?)
+            / y_space;
+
+        Ok((x_gradient, y_gradient))
+    }
+
+    /// Gradient from the central differences around the nearest grid point
+    ///
+    /// # Arguments
+    /// `x` : `&f64`
+    /// - x location \[m\]
+    ///
+    /// `y` : `&f64`
+    /// - y location \[m\]
+    ///
+    /// # Returns
+    /// `Result<(f64, f64)>` : (dhdx, dhdy) or an error.
+    ///
+    /// # Errors
+    /// `Error::IndexOutOfBounds` : the point is out of bounds.
+    ///
+    /// # Note
+    /// If the nearest grid point is on the edge, one of its neighbors is
+    /// missing, and the one-sided (forward or backward) difference with the
+    /// other neighbor is used instead.
+    fn central_gradient(&self, x: &f64, y: &f64) -> Result<(f64, f64)> {
+        let (xindex, yindex) = self.nearest_point(x, y)?;
+        self.grid_point_gradient(xindex.round() as usize, yindex.round() as usize)
+    }
+
+    /// Gradient from the central differences at the corners of the cell,
+    /// bilinearly interpolated to the target point
+    ///
+    /// # Arguments
+    /// `index_points` : `&[(usize, usize)]`
+    /// - the (x_index, y_index) corners of the cell enclosing the target, as
+    ///   returned by `four_corners`
+    ///
+    /// `target_point` : `&(f64, f64)`
+    /// - interpolate the gradient at this (x, y) point
+    ///
+    /// # Returns
+    /// `Result<(f64, f64)>` : (dhdx, dhdy) or an error.
+    ///
+    /// # Errors
+    /// - `Error::IndexOutOfBounds` : a neighbor of a corner is out of bounds.
+    /// - `Error::Land` : a neighbor of a corner is land.
+    fn interpolated_gradient(
+        &self,
+        index_points: &[(usize, usize)],
+        target_point: &(f64, f64),
+    ) -> Result<(f64, f64)> {
+        let gradients = index_points
+            .iter()
+            .map(|(i, j)| self.grid_point_gradient(*i, *j))
+            .collect::<Result<Vec<_>>>()?;
+        let corner = |n: usize| gradients[n];
+        let x_gradient = self.bilinear_at(index_points, target_point, |n| Ok(corner(n).0))?;
+        let y_gradient = self.bilinear_at(index_points, target_point, |n| Ok(corner(n).1))?;
+        Ok((x_gradient as f64, y_gradient as f64))
+    }
+
+    /// Gradient from the central differences around the grid point at the
+    /// indexes, see `central_gradient`
+    fn grid_point_gradient(&self, i: usize, j: usize) -> Result<(f64, f64)> {
+        // the x neighbors of a periodic axis wrap around the seam, so they
+        // are always two cells apart
+        let (i_west, i_east, x_cells) = match self.boundary_x {
+            BoundaryX::Clamp => {
+                let (i_west, i_east) = neighbors(i, self.x.len())?;
+                (i_west, i_east, i_east - i_west)
+            }
+            BoundaryX::Periodic => ((i + self.x.len() - 1) % self.x.len(), i + 1, 2),
+        };
+        let (j_south, j_north) = neighbors(j, self.y.len())?;
+
+        let x_space = x_cells as f64 * (self.x[1] as f64 - self.x[0] as f64);
+        let y_space = self.y[j_north] as f64 - self.y[j_south] as f64;
+
+        let x_gradient = (self.water_depth_at_indexes(&i_east, &j)?
+            - self.water_depth_at_indexes(&i_west, &j)?)
+            / x_space;
+        let y_gradient = (self.water_depth_at_indexes(&i, &j_north)?
+            - self.water_depth_at_indexes(&i, &j_south)?)
+            / y_space;
+
+        Ok((x_gradient, y_gradient))
+    }
+
+    /// Interpolate the depth using crate::interpolator::bilinear
+    ///
+    /// First, the index points are converted to the x and y values at those
+    /// indexes, then the depth at that index is taken. Finally, these are used
+    /// as arguments to `interpolator::bilinear`. The coordinates are relative
+    /// to the first corner, computed in double precision, so that the
+    /// interpolation weights keep their precision with large coordinates.
+    ///
+    /// # Arguments
+    /// `index_points`: `&Vec<(usize, usize)>`
+    /// - a vector of (x_index, y_index) points representing the indices of the
+    ///   corners that the target location is within.
+    ///
+    /// `target`: `&(f64, f64)`
+    /// - interpolate the depth at this (x, y) point
+    ///
+    /// # Returns
+    /// `Result<f32>`
+    /// - `Ok(f32)` : the depth at the target point
+    /// - `Err(Error)` : cannot read depths from at coordinates in the `points`
+    ///   vector.
+    ///
+    /// # Errors
+    /// - `Error::IndexOutOfBounds` : one or more of the points passed to
+    /// `points` is out of bounds.
     /// - `Error::InvalidArgument` : error during execution of
     /// `interpolator::bilinear` due to invalid arguments.
     fn interpolate(
         &self,
         index_points: &[(usize, usize)],
-        target_point: &(f32, f32),
+        target_point: &(f64, f64),
     ) -> Result<f32> {
-        let depth_points = vec![
-            (
-                self.x[index_points[0].0],
-                self.y[index_points[0].1],
-                self.depth_at_indexes(&index_points[0].0, &index_points[0].1)? as f32,
-            ),
-            (
-                self.x[index_points[1].0],
-                self.y[index_points[1].1],
-                self.depth_at_indexes(&index_points[1].0, &index_points[1].1)? as f32,
-            ),
-            (
-                self.x[index_points[2].0],
-                self.y[index_points[2].1],
-                self.depth_at_indexes(&index_points[2].0, &index_points[2].1)? as f32,
-            ),
-            (
-                self.x[index_points[3].0],
-                self.y[index_points[3].1],
-                self.depth_at_indexes(&index_points[3].0, &index_points[3].1)? as f32,
-            ),
-        ];
-        interpolator::bilinear(&depth_points, target_point)
+        self.bilinear_at(index_points, target_point, |n| {
+            let (xindex, yindex) = index_points[n];
+            self.water_depth_at_indexes(&xindex, &yindex)
+        })
+    }
+
+    /// Bilinear interpolation of a value given at the corners of the cell
+    ///
+    /// Same as `interpolate`, but the value at the `n`-th corner of
+    /// `index_points` is given by `value(n)`.
+    fn bilinear_at(
+        &self,
+        index_points: &[(usize, usize)],
+        target_point: &(f64, f64),
+        value: impl Fn(usize) -> Result<f64>,
+    ) -> Result<f32> {
+        let x_ref = self.x_at(index_points[0].0) as f64;
+        let y_ref = self.y[index_points[0].1] as f64;
+        let corner = |n: usize| -> Result<(f32, f32, f32)> {
+            let (xindex, yindex) = index_points[n];
+            Ok((
+                (self.x_at(xindex) as f64 - x_ref) as f32,
+                (self.y[yindex] as f64 - y_ref) as f32,
+                value(n)? as f32,
+            ))
+        };
+        let depth_points = vec![corner(0)?, corner(1)?, corner(2)?, corner(3)?];
+        let target = (
+            (target_point.0 - x_ref) as f32,
+            (target_point.1 - y_ref) as f32,
+        );
+        interpolator::bilinear(&depth_points, &target)
     }
 
     /// Access values in flattened array as you would a 2d array
@@ -462,25 +1257,342 @@ impl CartesianNetcdf3 {
     /// # Errors
     /// `Err(Error::IndexOutOfBounds)` : this error is returned when `x_index`
     /// and `y_index` produce a value outside of the depth array.
+    ///
+    /// # Note
+    /// With a periodic x axis, `x_index` is wrapped, so the index closing the
+    /// seam is the first x point.
     fn depth_at_indexes(&self, xindex: &usize, yindex: &usize) -> Result<f64> {
+        let xindex = match self.boundary_x {
+            BoundaryX::Clamp => *xindex,
+            BoundaryX::Periodic => xindex % self.x.len(),
+        };
         let index = self.x.len() * yindex + xindex;
-        if index >= self.depth.len() {
-            return Err(Error::IndexOutOfBounds);
+        self.depth.get(index).ok_or(Error::IndexOutOfBounds)
+    }
+
+    /// Depth at the indexes, which must be water
+    ///
+    /// The depth follows the sign convention, i.e. it is positive in water.
+    ///
+    /// # Errors
+    /// - `Error::IndexOutOfBounds` : the indexes are outside of the depth
+    ///   array.
+    /// - `Error::Land` : the depth is NaN or the NetCDF fill value, which
+    ///   mark land cells.
+    fn water_depth_at_indexes(&self, xindex: &usize, yindex: &usize) -> Result<f64> {
+        let depth = self.depth_at_indexes(xindex, yindex)?;
+        if is_land(depth) {
+            return Err(Error::Land);
         }
-        Ok(self.depth[index])
+        Ok(self.sign_convention.sign() * depth)
+    }
+}
+
+/// Indexes of the neighbors of `index` on an axis of length `len` used by a
+/// finite difference
+///
+/// # Returns
+/// `Result<(usize, usize)>` : (index - 1, index + 1) for an interior point,
+/// otherwise the point itself and its only neighbor, i.e. a one-sided
+/// difference.
+///
+/// # Errors
+/// `Error::IndexOutOfBounds` : the axis has less than two points.
+fn neighbors(index: usize, len: usize) -> Result<(usize, usize)> {
+    if len < 2 {
+        return Err(Error::IndexOutOfBounds);
+    }
+    Ok((index.saturating_sub(1), (index + 1).min(len - 1)))
+}
+
+/// Round a fractional index within `GRID_POINT_TOLERANCE` of a grid point
+///
+/// A coordinate on a grid line can be off by a few ULP after the conversion
+/// to a fractional index. Without rounding, it would take the branch of a
+/// point between grid points in `four_corners`, and select the cell on the
+/// other side of the grid line.
+fn snap_to_grid_point(index: f32) -> f32 {
+    let nearest = index.round();
+    if (index - nearest).abs() <= GRID_POINT_TOLERANCE * nearest.max(1.0) {
+        nearest
+    } else {
+        index
+    }
+}
+
+/// Whether the depth variable is stored with the dimensions (x, y)
+///
+/// The dimensions of the x and y variables are compared with the dimensions
+/// of the depth variable. If they can't be compared, e.g. x and y share the
+/// same dimension, the depth is assumed to be stored as (y, x).
+///
+/// # Arguments
+/// `data_set` : `&DataSet`
+/// - the definition of the netcdf3 file
+///
+/// `xname`, `yname`, `depth_name` : `&str`
+/// - the names of the x, y, and depth variables
+///
+/// # Returns
+/// `bool` : `true` if the dimensions of the depth are (x, y).
+fn is_transposed(data_set: &DataSet, xname: &str, yname: &str, depth_name: &str) -> bool {
+    let first_dim = |name: &str| {
+        data_set
+            .get_var(name)
+            .and_then(|var| var.dim_names().first().cloned())
+    };
+    let depth_dims = data_set.get_var(depth_name).map(|var| var.dim_names());
+    match (first_dim(xname), first_dim(yname), depth_dims) {
+        (Some(xdim), Some(ydim), Some(dims)) => xdim != ydim && dims == [xdim, ydim],
+        _ => false,
+    }
+}
+
+/// Transpose values flattened with x as the row into values flattened with y
+/// as the row
+///
+/// # Arguments
+/// `values` : `Vec<T>`
+/// - the values, where the value at `(x[i], y[j])` is at `i * ny + j`
+///
+/// `nx`, `ny` : `usize`
+/// - the lengths of x and y
+///
+/// # Returns
+/// `Vec<T>` : the values, where the value at `(x[i], y[j])` is at
+/// `j * nx + i`
+fn transpose<T: Copy>(values: Vec<T>, nx: usize, ny: usize) -> Vec<T> {
+    (0..ny)
+        .flat_map(|j| (0..nx).map(move |i| (i, j)))
+        .map(|(i, j)| values[i * ny + j])
+        .collect()
+}
+
+/// Whether the raw depth marks a land cell, i.e. it is NaN or the NetCDF fill
+/// value
+pub(super) fn is_land(depth: f64) -> bool {
+    depth.is_nan() || depth == NC_FILL_F64 || depth == NC_FILL_F32 as f64
+}
+
+/// Weights of a Gaussian kernel of standard deviation `sigma`, from the
+/// center to 3 sigma, not normalized
+///
+/// A non-positive or NaN `sigma` gives the identity kernel `[1.0]`.
+fn gaussian_kernel(sigma: f64) -> Vec<f64> {
+    if sigma.is_nan() || sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let radius = (3.0 * sigma).ceil() as usize;
+    (0..=radius)
+        .map(|offset| (-0.5 * (offset as f64 / sigma).powi(2)).exp())
+        .collect()
+}
+
+/// Convolve a line of the depth with a symmetric kernel
+///
+/// # Arguments
+/// `line` : `&[f64]`
+/// - the raw depth along a row or column of the grid
+///
+/// `kernel` : `&[f64]`
+/// - the weights from the center of the kernel outward, see
+///   `gaussian_kernel`
+///
+/// `periodic` : `bool`
+/// - whether the line wraps around, otherwise the kernel is truncated at the
+///   ends
+///
+/// # Returns
+/// `Vec<f64>` : the weighted average of the water values under the kernel at
+/// each water point. The land points are unchanged.
+fn smooth_line(line: &[f64], kernel: &[f64], periodic: bool) -> Vec<f64> {
+    let n = line.len() as isize;
+    let radius = kernel.len() as isize - 1;
+    (0..n)
+        .map(|center| {
+            if is_land(line[center as usize]) {
+                return line[center as usize];
+            }
+            let (mut sum, mut weights) = (0.0, 0.0);
+            for offset in -radius..=radius {
+                let index = match (center + offset, periodic) {
+                    (index, true) => index.rem_euclid(n),
+                    (index, false) if (0..n).contains(&index) => index,
+                    _ => continue,
+                };
+                let h = line[index as usize];
+                if !is_land(h) {
+                    let weight = kernel[offset.unsigned_abs()];
+                    sum += weight * h;
+                    weights += weight;
+                }
+            }
+            sum / weights
+        })
+        .collect()
+}
+
+/// Whether the coordinates are equally spaced in ascending order, within
+/// `SPACING_TOLERANCE`
+///
+/// The spacing is compared in double precision, allowing also for the
+/// rounding of the coordinates to single precision, which is several
+/// centimeters for UTM coordinates. A single coordinate is considered
+/// equally spaced.
+fn equally_spaced(v: &[f32]) -> bool {
+    let [first, .., last] = v else {
+        return true;
+    };
+    let (first, last) = (*first as f64, *last as f64);
+    let spacing = (last - first) / (v.len() - 1) as f64;
+    let rounding = 2.0 * f32::EPSILON as f64 * first.abs().max(last.abs());
+    spacing > 0.0
+        && v.windows(2).all(|w| {
+            ((w[1] as f64 - w[0] as f64) - spacing).abs() <= SPACING_TOLERANCE * spacing + rounding
+        })
+}
+
+/// Check that the flattened depth has a value at every grid point
+///
+/// # Errors
+/// `Error::DimensionMismatch` : `depth_len` is not the length of `x` times the
+/// length of `y`, e.g. the depth variable is truncated or defined on other
+/// dimensions.
+fn check_dimensions(x: &[f32], y: &[f32], depth_len: usize) -> Result<()> {
+    let expected = x.len() * y.len();
+    if depth_len != expected {
+        return Err(Error::DimensionMismatch {
+            expected,
+            got: depth_len,
+        });
+    }
+    Ok(())
+}
+
+/// CF standard names of the depth of the sea floor, positive down
+const DEPTH_STANDARD_NAMES: [&str; 4] = [
+    "sea_floor_depth",
+    "sea_floor_depth_below_sea_surface",
+    "sea_floor_depth_below_geoid",
+    "sea_floor_depth_below_mean_sea_level",
+];
+
+/// CF standard names of the elevation of the surface, positive up
+const ELEVATION_STANDARD_NAMES: [&str; 4] = [
+    "altitude",
+    "height_above_geoid",
+    "height_above_mean_sea_level",
+    "height_above_reference_ellipsoid",
+];
+
+/// Attribute of a variable as a trimmed lowercase string
+fn var_attr(data_set: &DataSet, var: &str, name: &str) -> Option<String> {
+    data_set
+        .get_var_attr_as_string(var, name)
+        .map(|a| a.trim().to_lowercase())
+}
+
+/// Sign convention of a variable given by its `standard_name`
+fn standard_name_convention(data_set: &DataSet, var: &str) -> Option<SignConvention> {
+    let standard_name = var_attr(data_set, var, "standard_name")?;
+    if DEPTH_STANDARD_NAMES.contains(&standard_name.as_str()) {
+        Some(SignConvention::Depth)
+    } else if ELEVATION_STANDARD_NAMES.contains(&standard_name.as_str()) {
+        Some(SignConvention::Elevation)
+    } else {
+        None
     }
 }
 
+/// Sign convention of a variable given by its `positive` attribute
+fn positive_convention(data_set: &DataSet, var: &str) -> Option<SignConvention> {
+    match var_attr(data_set, var, "positive").as_deref() {
+        Some("down") => Some(SignConvention::Depth),
+        Some("up") => Some(SignConvention::Elevation),
+        _ => None,
+    }
+}
+
+/// Sign convention of a variable from its `standard_name` or, if that is
+/// not a known depth or elevation, its `positive` attribute
+///
+/// # Returns
+/// `Option<SignConvention>` : the convention, or `None` if the attributes do
+/// not define it.
+fn sign_convention(data_set: &DataSet, var: &str) -> Option<SignConvention> {
+    standard_name_convention(data_set, var).or_else(|| positive_convention(data_set, var))
+}
+
+/// Detect the x, y, and depth variables from their CF attributes
+///
+/// See `CartesianNetcdf3::open_cf` for the rules used.
+///
+/// # Arguments
+/// `data_set` : `&DataSet`
+/// - the definition of the netcdf3 file
+///
+/// # Returns
+/// `Result<(String, String, String, SignConvention)>` : the names of the x,
+/// y, and depth variables, and the sign convention of the depth variable.
+///
+/// # Errors
+/// `Error::VariableNotDetected` : one of the variables was not detected.
+fn detect_cf_variables(data_set: &DataSet) -> Result<(String, String, String, SignConvention)> {
+    let names = data_set.get_var_names();
+    let not_detected = |role: &str| Error::VariableNotDetected(role.to_string(), names.clone());
+
+    let find_axis = |axis: &str, standard_name: &str| {
+        names
+            .iter()
+            .find(|v| {
+                var_attr(data_set, v, "axis").as_deref() == Some(axis)
+                    || var_attr(data_set, v, "standard_name").as_deref() == Some(standard_name)
+            })
+            .cloned()
+    };
+    let xname = find_axis("x", "projection_x_coordinate").ok_or_else(|| not_detected("x"))?;
+    let yname = find_axis("y", "projection_y_coordinate").ok_or_else(|| not_detected("y"))?;
+
+    // without a standard name, only a 2D variable in meters is considered
+    let depth_convention = |var: &str| {
+        standard_name_convention(data_set, var).or_else(|| {
+            let is_2d = data_set.get_var(var).is_some_and(|v| v.num_dims() == 2);
+            let in_meters = matches!(
+                var_attr(data_set, var, "units").as_deref(),
+                Some("m") | Some("meter") | Some("meters") | Some("metre") | Some("metres")
+            );
+            if is_2d && in_meters {
+                positive_convention(data_set, var)
+            } else {
+                None
+            }
+        })
+    };
+    let (depth_name, convention) = names
+        .iter()
+        .find_map(|v| depth_convention(v).map(|c| (v.clone(), c)))
+        .ok_or_else(|| not_detected("depth"))?;
+
+    Ok((xname, yname, depth_name, convention))
+}
+
 #[cfg(test)]
 mod test_cartesian_file {
 
+    use ndarray::{Array1, Array2};
     use tempfile::NamedTempFile;
 
     use crate::{
-        bathymetry::{cartesian_netcdf3::CartesianNetcdf3, BathymetryData},
-        datatype::Point,
+        bathymetry::{
+            cartesian_netcdf3::{
+                snap_to_grid_point, BoundaryX, CartesianNetcdf3, GradientMethod, InterpolationMode,
+                SignConvention,
+            },
+            BathymetryData,
+        },
+        datatype::{Gradient, Point},
         error::Error,
-        io::utility::create_netcdf3_bathymetry,
+        io::utility::{bathymetry_from_fn, create_netcdf3_bathymetry},
     };
 
     /// create a file with four quadrants each with a different depth
@@ -513,6 +1625,116 @@ mod test_cartesian_file {
         assert!((data.x[10] - 5000.0).abs() < f32::EPSILON)
     }
 
+    #[test]
+    // a missing file or variable is an error
+    fn test_open_errors() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+        create_netcdf3_bathymetry(&temp_path, 3, 3, 1.0, 1.0, four_depth_fn);
+
+        match CartesianNetcdf3::open(&temp_path, "x", "y", "z") {
+            Err(Error::VariableNotFound { name }) => assert_eq!(name, "z"),
+            _ => panic!("expected VariableNotFound"),
+        }
+
+        let missing = temp_path.with_extension("missing");
+        match CartesianNetcdf3::open(&missing, "x", "y", "depth") {
+            Err(Error::IOError(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+            _ => panic!("expected IOError"),
+        }
+    }
+
+    #[test]
+    // a depth variable with the wrong length is an error when opening
+    fn test_dimension_mismatch() {
+        use netcdf3::{DataSet, FileWriter, Version};
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        // the depth is truncated to 4 columns instead of 5
+        let x: Vec<f32> = (0..5).map(|i| i as f32).collect();
+        let y: Vec<f32> = (0..3).map(|j| j as f32).collect();
+        let depth = vec![10.0; 12];
+
+        let mut data_set = DataSet::new();
+        data_set.add_fixed_dim("x", x.len()).unwrap();
+        data_set.add_fixed_dim("y", y.len()).unwrap();
+        data_set.add_fixed_dim("n", 4).unwrap();
+        data_set.add_var_f32("x", &["x"]).unwrap();
+        data_set.add_var_f32("y", &["y"]).unwrap();
+        data_set.add_var_f64("depth", &["y", "n"]).unwrap();
+        let mut file_writer = FileWriter::open(&temp_path).unwrap();
+        file_writer.set_def(&data_set, Version::Classic, 0).unwrap();
+        file_writer.write_var_f32("x", &x).unwrap();
+        file_writer.write_var_f32("y", &y).unwrap();
+        file_writer.write_var_f64("depth", &depth).unwrap();
+        file_writer.close().unwrap();
+
+        assert!(matches!(
+            CartesianNetcdf3::open(&temp_path, "x", "y", "depth"),
+            Err(Error::DimensionMismatch {
+                expected: 15,
+                got: 12
+            })
+        ));
+        assert!(matches!(
+            CartesianNetcdf3::open_single_precision(&temp_path, "x", "y", "depth"),
+            Err(Error::DimensionMismatch { .. })
+        ));
+
+        match CartesianNetcdf3::open(&temp_path, "x", "y", "depth") {
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "Dimension mismatch: expected 15 values, got 12"
+            ),
+            Ok(_) => panic!("expected DimensionMismatch"),
+        }
+    }
+
+    #[test]
+    // a depth variable stored as (x, y) is transposed when opening, thus the
+    // lookups are the same as with the depth stored as (y, x)
+    fn test_transposed_depth() {
+        use netcdf3::{DataSet, FileWriter, Version};
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        // depth = 10 + x + 100 y, flattened with x as the row
+        let x: Vec<f32> = (0..5).map(|i| i as f32).collect();
+        let y: Vec<f32> = (0..3).map(|j| j as f32).collect();
+        let depth: Vec<f64> = x
+            .iter()
+            .flat_map(|x| y.iter().map(move |y| 10.0 + *x as f64 + 100.0 * *y as f64))
+            .collect();
+
+        let mut data_set = DataSet::new();
+        data_set.add_fixed_dim("x", x.len()).unwrap();
+        data_set.add_fixed_dim("y", y.len()).unwrap();
+        data_set.add_var_f32("x", &["x"]).unwrap();
+        data_set.add_var_f32("y", &["y"]).unwrap();
+        data_set.add_var_f64("depth", &["x", "y"]).unwrap();
+        let mut file_writer = FileWriter::open(&temp_path).unwrap();
+        file_writer.set_def(&data_set, Version::Classic, 0).unwrap();
+        file_writer.write_var_f32("x", &x).unwrap();
+        file_writer.write_var_f32("y", &y).unwrap();
+        file_writer.write_var_f64("depth", &depth).unwrap();
+        file_writer.close().unwrap();
+
+        let double = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let single =
+            CartesianNetcdf3::open_single_precision(&temp_path, "x", "y", "depth").unwrap();
+        for data in [double, single] {
+            assert_eq!(data.depth(&Point::new(0.0, 0.0)).unwrap(), 10.0);
+            assert_eq!(data.depth(&Point::new(4.0, 0.0)).unwrap(), 14.0);
+            assert_eq!(data.depth(&Point::new(1.0, 2.0)).unwrap(), 211.0);
+            assert_eq!(data.depth(&Point::new(2.5, 1.5)).unwrap(), 162.5);
+            let (_, _, grid) = data.depth_grid();
+            assert_eq!(grid[[2, 3]], 213.0);
+        }
+    }
+
     #[test]
     // test the and view the nearest function
     fn test_nearest() {
@@ -658,6 +1880,39 @@ mod test_cartesian_file {
         );
     }
 
+    #[test]
+    /// a point a few ULP away from a grid line selects the same cell as a
+    /// point exactly on it
+    fn test_get_corners_near_grid_point() {
+        // an index of one f32 ULP below and above 11
+        let below = f32::from_bits(11.0_f32.to_bits() - 1);
+        let above = f32::from_bits(11.0_f32.to_bits() + 1);
+        assert_eq!(snap_to_grid_point(below), 11.0);
+        assert_eq!(snap_to_grid_point(above), 11.0);
+        assert_eq!(snap_to_grid_point(10.75), 10.75);
+        // the last index of the grid
+        let last = f32::from_bits(100.0_f32.to_bits() - 1);
+        assert_eq!(snap_to_grid_point(last), 100.0);
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+        create_netcdf3_bathymetry(&temp_path, 101, 51, 500.0, 500.0, four_depth_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+
+        let on_grid = vec![(11, 11), (11, 12), (12, 12), (12, 11)];
+        for x in [5_500.0 - 5e-4, 5_500.0, 5_500.0 + 5e-4] {
+            for y in [5_500.0 - 5e-4, 5_500.0, 5_500.0 + 5e-4] {
+                let corners = data.four_corners(&x, &y).unwrap();
+                assert_eq!(corners, on_grid, "({}, {})", x, y);
+            }
+        }
+
+        // the right and top edges
+        let corner = vec![(99, 49), (99, 50), (100, 50), (100, 49)];
+        let (x, y) = (50_000.0 - 5e-4, 25_000.0 - 5e-4);
+        assert_eq!(data.four_corners(&x, &y).unwrap(), corner);
+    }
+
     #[test]
     // check values inside the four quadrants but not on grid points
     fn test_depth() {
@@ -832,4 +2087,939 @@ mod test_cartesian_file {
             }
         }
     }
+
+    #[test]
+    // verify that storing the depth in single precision gives the same depth
+    // and gradient as storing it in double precision
+    fn test_single_precision() {
+        // create temporary file
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn depth_fn(x: f32, y: f32) -> f64 {
+            10.0 + x as f64 * 1e-4 + y as f64 * 2e-4
+        }
+
+        create_netcdf3_bathymetry(&temp_path, 100, 100, 10.0, 10.0, depth_fn);
+
+        let double = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let single =
+            CartesianNetcdf3::open_single_precision(&temp_path, "x", "y", "depth").unwrap();
+
+        for x in (0..990).step_by(7) {
+            for y in (0..990).step_by(7) {
+                let point = Point::new(x as f32, y as f32);
+                let (h_double, g_double) = double.depth_and_gradient(&point).unwrap();
+                let (h_single, g_single) = single.depth_and_gradient(&point).unwrap();
+                assert!(
+                    (h_double - h_single).abs() < 1e-5,
+                    "Expected {}, but got {}",
+                    h_double,
+                    h_single
+                );
+                assert!(
+                    (g_single.dx() - 1e-4).abs() < 1e-6,
+                    "Expected {}, but got {}",
+                    g_double.dx(),
+                    g_single.dx()
+                );
+                assert!(
+                    (g_single.dy() - 2e-4).abs() < 1e-6,
+                    "Expected {}, but got {}",
+                    g_double.dy(),
+                    g_single.dy()
+                );
+            }
+        }
+    }
+
+    #[test]
+    // on a near-flat field with UTM-scale coordinates, the depth stored in
+    // single precision gives the depth and gradient of the double precision
+    // storage within the rounding of the stored depth, since the position,
+    // the interpolation and the differences are in double precision. The
+    // depth changes by 1 mm per cell, only about 500 ulp of a f32 at 20 m,
+    // and the same interpolation in pure f32 arithmetic misses the tolerances.
+    fn test_single_precision_large_coordinates() {
+        let (x0, y0) = (500_000.0, 4_200_000.0);
+        let exact = |x: f64, y: f64| 20.0 + 1e-4 * (x - x0) + 5e-5 * (y - y0);
+        let x: Vec<f32> = (0..100).map(|i| (x0 + 10.0 * i as f64) as f32).collect();
+        let y: Vec<f32> = (0..100).map(|j| (y0 + 10.0 * j as f64) as f32).collect();
+        let depth: Vec<f64> = y
+            .iter()
+            .flat_map(|y| x.iter().map(move |x| exact(*x as f64, *y as f64)))
+            .collect();
+
+        let single_depth =
+            Array2::from_shape_vec((100, 100), depth.iter().map(|h| *h as f32).collect()).unwrap();
+        let single = CartesianNetcdf3::from_ndarray(
+            x.clone().into(),
+            y.clone().into(),
+            single_depth.clone(),
+        )
+        .unwrap();
+        let double = CartesianNetcdf3::from_arrays(x, y, depth).unwrap();
+
+        // bilinear interpolation of the stored depth in pure f32 arithmetic
+        let f32_depth = |x: f32, y: f32| {
+            let (fi, fj) = ((x - x0 as f32) / 10.0, (y - y0 as f32) / 10.0);
+            let (i, j) = (fi.floor() as usize, fj.floor() as usize);
+            let (wx, wy) = (fi - i as f32, fj - j as f32);
+            let h = |i: usize, j: usize| single_depth[[j, i]];
+            (1.0 - wy) * ((1.0 - wx) * h(i, j) + wx * h(i + 1, j))
+                + wy * ((1.0 - wx) * h(i, j + 1) + wx * h(i + 1, j + 1))
+        };
+
+        // the rounding of a depth of about 20 m in single precision is
+        // 1e-6 m, i.e. 1e-7 on the gradient over a 10 m cell
+        let (depth_tolerance, gradient_tolerance) = (4e-6, 4e-7);
+        let (mut f32_depth_error, mut f32_gradient_error) = (0.0_f64, 0.0_f64);
+        for i in 0..60 {
+            let (px, py) = (x0 + 3.7 + 16.3 * i as f64, y0 + 5.1 + 15.7 * i as f64);
+            let point = Point::new(px, py);
+            let (h_double, g_double) = double.depth_and_gradient_f64(&point).unwrap();
+            let (h_single, g_single) = single.depth_and_gradient_f64(&point).unwrap();
+            assert!(
+                (h_double as f64 - exact(px, py)).abs() < depth_tolerance,
+                "Expected {}, but got {}",
+                exact(px, py),
+                h_double
+            );
+            assert!(
+                (h_double - h_single).abs() < depth_tolerance as f32,
+                "Expected {}, but got {}",
+                h_double,
+                h_single
+            );
+            assert!((g_double.dx() - 1e-4).abs() < 1e-9);
+            assert!((g_double.dy() - 5e-5).abs() < 1e-9);
+            assert!(
+                (g_double.dx() - g_single.dx()).abs() < gradient_tolerance as f32,
+                "Expected {}, but got {}",
+                g_double.dx(),
+                g_single.dx()
+            );
+            assert!(
+                (g_double.dy() - g_single.dy()).abs() < gradient_tolerance as f32,
+                "Expected {}, but got {}",
+                g_double.dy(),
+                g_single.dy()
+            );
+
+            // the position is rounded by up to 0.25 m at 4200 km, and the
+            // difference of two depths 1 m apart cancels most of their digits
+            let (fx, fy) = (px as f32, py as f32);
+            let h = f32_depth(fx, fy) as f64;
+            let dhdx = (f32_depth(fx + 0.5, fy) - f32_depth(fx - 0.5, fy)) as f64;
+            f32_depth_error = f32_depth_error.max((h - exact(px, py)).abs());
+            f32_gradient_error = f32_gradient_error.max((dhdx - 1e-4).abs());
+        }
+        assert!(f32_depth_error > depth_tolerance, "{}", f32_depth_error);
+        assert!(
+            f32_gradient_error > gradient_tolerance,
+            "{}",
+            f32_gradient_error
+        );
+    }
+
+    #[test]
+    // the central difference recovers the exact slope of a linear depth field,
+    // and the exact gradient of a quadratic field at the grid points, where the
+    // one-sided difference is off by half a cell.
+    fn test_central_difference() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn linear_fn(x: f32, y: f32) -> f64 {
+            x as f64 * 0.05 + y as f64 * 0.02
+        }
+
+        create_netcdf3_bathymetry(&temp_path, 20, 20, 10.0, 10.0, linear_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth")
+            .unwrap()
+            .with_gradient_method(GradientMethod::CentralDifference);
+
+        for x in (10..180).step_by(3) {
+            for y in (10..180).step_by(3) {
+                let (_, gradient) = data
+                    .depth_and_gradient(&Point::new(x as f32, y as f32))
+                    .unwrap();
+                assert!((gradient.dx() - 0.05).abs() < 1e-6);
+                assert!((gradient.dy() - 0.02).abs() < 1e-6);
+            }
+        }
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn quadratic_fn(x: f32, _y: f32) -> f64 {
+            (x as f64).powi(2) * 1e-3
+        }
+
+        create_netcdf3_bathymetry(&temp_path, 20, 20, 10.0, 10.0, quadratic_fn);
+        let one_sided = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let central = CartesianNetcdf3::open(&temp_path, "x", "y", "depth")
+            .unwrap()
+            .with_gradient_method(GradientMethod::CentralDifference);
+
+        for i in 1..19 {
+            let x = i as f32 * 10.0;
+            let point = Point::new(x, 50.0);
+            let exact = 2e-3 * x;
+            let (_, gradient) = central.depth_and_gradient(&point).unwrap();
+            assert!(
+                (gradient.dx() - exact).abs() < 1e-5,
+                "Expected {}, but got {}",
+                exact,
+                gradient.dx()
+            );
+            let (_, gradient) = one_sided.depth_and_gradient(&point).unwrap();
+            assert!((gradient.dx() - exact - 1e-2).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    // when the nearest grid point is on the edge, the central difference falls
+    // back to a one-sided difference, which is exact for a linear field
+    fn test_central_difference_edges() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn linear_fn(x: f32, y: f32) -> f64 {
+            10.0 + x as f64 * 0.05 + y as f64 * 0.02
+        }
+
+        create_netcdf3_bathymetry(&temp_path, 20, 20, 10.0, 10.0, linear_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth")
+            .unwrap()
+            .with_gradient_method(GradientMethod::CentralDifference);
+
+        // the first and last cells, and the corners
+        for (x, y) in [
+            (2.0, 50.0),
+            (188.0, 50.0),
+            (50.0, 0.0),
+            (50.0, 190.0),
+            (0.0, 0.0),
+            (190.0, 187.0),
+        ] {
+            let (_, gradient) = data.depth_and_gradient(&Point::new(x, y)).unwrap();
+            assert!(
+                (gradient.dx() - 0.05).abs() < 1e-6,
+                "Expected 0.05, but got {} at ({}, {})",
+                gradient.dx(),
+                x,
+                y
+            );
+            assert!(
+                (gradient.dy() - 0.02).abs() < 1e-6,
+                "Expected 0.02, but got {} at ({}, {})",
+                gradient.dy(),
+                x,
+                y
+            );
+        }
+
+        // only outside of the coordinate range is out of bounds
+        assert!(matches!(
+            data.depth_and_gradient(&Point::new(190.5, 50.0)),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    // on a quadratic depth field, the central differences interpolated from the
+    // corners of the cell recover the gradient between the grid points, where
+    // the one-sided difference is off by up to half a cell
+    fn test_interpolated_central_difference() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn quadratic_fn(x: f32, y: f32) -> f64 {
+            ((x as f64).powi(2) + (y as f64).powi(2)) * 1e-3
+        }
+
+        create_netcdf3_bathymetry(&temp_path, 20, 20, 10.0, 10.0, quadratic_fn);
+        let one_sided = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let interpolated = CartesianNetcdf3::open(&temp_path, "x", "y", "depth")
+            .unwrap()
+            .with_gradient_method(GradientMethod::InterpolatedCentralDifference);
+
+        let max_error = |data: &CartesianNetcdf3| {
+            let mut max_error: f32 = 0.0;
+            for x in (12..178).step_by(7) {
+                for y in (12..178).step_by(7) {
+                    let (_, gradient) = data
+                        .depth_and_gradient(&Point::new(x as f32, y as f32))
+                        .unwrap();
+                    max_error = max_error
+                        .max((gradient.dx() - 2e-3 * x as f32).abs())
+                        .max((gradient.dy() - 2e-3 * y as f32).abs());
+                }
+            }
+            max_error
+        };
+        assert!(max_error(&interpolated) < 1e-5);
+        assert!(max_error(&one_sided) > 5e-3);
+    }
+
+    #[test]
+    // the depth grid has the values from the file
+    fn test_depth_grid() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        create_netcdf3_bathymetry(&temp_path, 11, 6, 10.0, 20.0, four_depth_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+
+        let (x, y, depth) = data.depth_grid();
+        assert_eq!(x.len(), 11);
+        assert_eq!(y.len(), 6);
+        assert_eq!(depth.shape(), &[6, 11]);
+        for (j, yj) in y.iter().enumerate() {
+            for (i, xi) in x.iter().enumerate() {
+                assert_eq!(depth[[j, i]], four_depth_fn(*xi, *yj) as f32);
+                assert_eq!(depth[[j, i]], data.depth(&Point::new(*xi, *yj)).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    // points in the first and last half cells, and on the last coordinate, are
+    // within the domain, while points just outside are not
+    fn test_edge_half_cells() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn depth_fn(x: f32, y: f32) -> f64 {
+            10.0 + x as f64 + 2.0 * y as f64
+        }
+
+        // the spacing is not exactly representable
+        create_netcdf3_bathymetry(&temp_path, 11, 11, 0.1, 0.3, depth_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let x_last = data.x[10];
+        let y_last = data.y[10];
+
+        for (x, y) in [
+            (0.01, 0.01),
+            (0.04, 1.5),
+            (x_last - 0.04, 1.5),
+            (0.5, y_last - 0.1),
+            (x_last, y_last),
+            (0.0, y_last),
+        ] {
+            let (depth, gradient) = data.depth_and_gradient(&Point::new(x, y)).unwrap();
+            assert!((depth - depth_fn(x, y) as f32).abs() < 1e-4);
+            assert!((gradient.dx() - 1.0).abs() < 1e-3);
+            assert!((gradient.dy() - 2.0).abs() < 1e-3);
+        }
+
+        assert!(data.depth(&Point::new(-0.001, 0.5)).is_err());
+        assert!(data.depth(&Point::new(x_last + 0.001, 0.5)).is_err());
+        assert!(data.depth(&Point::new(0.5, y_last + 0.001)).is_err());
+    }
+
+    #[test]
+    // cells with NaN or the fill value are land, and any point whose
+    // interpolation uses them is on land
+    fn test_land() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn depth_fn(x: f32, y: f32) -> f64 {
+            if x == 5.0 && y == 5.0 {
+                f64::NAN
+            } else if x == 2.0 && y == 2.0 {
+                netcdf3::NC_FILL_F64
+            } else {
+                10.0
+            }
+        }
+
+        create_netcdf3_bathymetry(&temp_path, 11, 11, 1.0, 1.0, depth_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+
+        for (x, y) in [(5.0, 5.0), (4.5, 4.5), (5.5, 5.9), (2.0, 2.0), (1.1, 2.9)] {
+            assert!(matches!(data.depth(&Point::new(x, y)), Err(Error::Land)));
+            assert!(matches!(
+                data.depth_and_gradient(&Point::new(x, y)),
+                Err(Error::Land)
+            ));
+        }
+
+        // water next to the land cells
+        assert_eq!(data.depth(&Point::new(3.5, 3.5)).unwrap(), 10.0);
+        assert_eq!(data.depth(&Point::new(7.0, 5.0)).unwrap(), 10.0);
+    }
+
+    #[test]
+    // across a step, the nearest neighbor depth is discontinuous halfway
+    // between the grid points, while the bilinear depth is smeared over the
+    // cell.
+    fn test_nearest_interpolation() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        fn step_fn(x: f32, _y: f32) -> f64 {
+            if x >= 50.0 {
+                20.0
+            } else {
+                50.0
+            }
+        }
+
+        create_netcdf3_bathymetry(&temp_path, 100, 10, 1.0, 1.0, step_fn);
+        let bilinear = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let nearest = CartesianNetcdf3::open(&temp_path, "x", "y", "depth")
+            .unwrap()
+            .with_interpolation_mode(InterpolationMode::Nearest);
+
+        for (x, h_bilinear, h_nearest) in [
+            (10.0, 50.0, 50.0),
+            (49.4, 38.0, 50.0),
+            (49.6, 32.0, 20.0),
+            (80.0, 20.0, 20.0),
+        ] {
+            let point = Point::new(x, 5.0);
+            assert!((bilinear.depth(&point).unwrap() - h_bilinear).abs() < 1e-4);
+            assert_eq!(nearest.depth(&point).unwrap(), h_nearest);
+
+            let (h, gradient) = nearest.depth_and_gradient(&point).unwrap();
+            assert_eq!(h, h_nearest);
+            assert_eq!((*gradient.dx(), *gradient.dy()), (0.0, 0.0));
+        }
+        let (_, gradient) = bilinear.depth_and_gradient(&Point::new(49.6, 5.0)).unwrap();
+        assert!(*gradient.dx() < 0.0);
+
+        // out of bounds as the bilinear interpolation
+        assert!(matches!(
+            nearest.depth(&Point::new(100.0, 5.0)),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    // the bathymetry created in memory is the same as the one read from the
+    // file created with the same function
+    fn test_from_arrays() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        create_netcdf3_bathymetry(&temp_path, 100, 50, 500.0, 500.0, four_depth_fn);
+        let file = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let memory = bathymetry_from_fn(100, 50, 500.0, 500.0, four_depth_fn).unwrap();
+
+        assert_eq!(file.depth_grid(), memory.depth_grid());
+        for (x, y) in [(0.0, 0.0), (24800.0, 12400.0), (30000.0, 20000.0)] {
+            let point = Point::new(x, y);
+            assert_eq!(
+                file.depth_and_gradient(&point).unwrap(),
+                memory.depth_and_gradient(&point).unwrap()
+            );
+        }
+
+        assert!(matches!(
+            CartesianNetcdf3::from_arrays(vec![0.0, 1.0], vec![0.0, 1.0], vec![10.0; 3]),
+            Err(Error::DimensionMismatch {
+                expected: 4,
+                got: 3
+            })
+        ));
+        // uneven and descending axes
+        assert!(matches!(
+            CartesianNetcdf3::from_arrays(vec![0.0, 1.0, 3.0], vec![0.0, 1.0], vec![10.0; 6]),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            CartesianNetcdf3::from_arrays(vec![0.0, 1.0], vec![1.0, 0.0], vec![10.0; 4]),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    /// an empty or single point x axis is an error rather than a panic, since
+    /// there is no cell to interpolate in, while the nearest grid point of a
+    /// single point axis is well defined
+    fn test_degenerate_x_axis() {
+        let point = Point::new(0.0, 0.5);
+        for nx in [0, 1] {
+            let data = || {
+                let x = (0..nx).map(|i| i as f32).collect();
+                CartesianNetcdf3::from_arrays(x, vec![0.0, 1.0, 2.0], vec![10.0; 3 * nx]).unwrap()
+            };
+            for gradient_method in [
+                GradientMethod::OneSided,
+                GradientMethod::CentralDifference,
+                GradientMethod::InterpolatedCentralDifference,
+            ] {
+                let data = data().with_gradient_method(gradient_method);
+                assert!(matches!(data.depth(&point), Err(Error::IndexOutOfBounds)));
+                assert!(matches!(
+                    data.depth_and_gradient(&point),
+                    Err(Error::IndexOutOfBounds)
+                ));
+                assert!(matches!(
+                    data.depth_and_gradient_batch(std::slice::from_ref(&point))[..],
+                    [Err(Error::IndexOutOfBounds)]
+                ));
+            }
+
+            let nearest = data().with_interpolation_mode(InterpolationMode::Nearest);
+            match nx {
+                0 => assert!(nearest.depth(&point).is_err()),
+                _ => assert_eq!(nearest.depth(&point).unwrap(), 10.0),
+            }
+        }
+    }
+
+    #[test]
+    /// the depth from ndarray arrays is the same as from the file, and the
+    /// shape and spacing are validated
+    fn test_from_ndarray() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        create_netcdf3_bathymetry(&temp_path, 100, 50, 500.0, 500.0, four_depth_fn);
+        let file = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        let (x, y, depth) = file.depth_grid();
+        let (x, y) = (Array1::from(x.to_vec()), Array1::from(y.to_vec()));
+        let memory = CartesianNetcdf3::from_ndarray(x.clone(), y.clone(), depth.clone()).unwrap();
+
+        assert_eq!(file.depth_grid(), memory.depth_grid());
+        for (x, y) in [(0.0, 0.0), (24800.0, 12400.0), (30000.0, 20000.0)] {
+            let point = Point::new(x, y);
+            assert_eq!(
+                file.depth_and_gradient(&point).unwrap(),
+                memory.depth_and_gradient(&point).unwrap()
+            );
+        }
+
+        // a transposed view has the wrong shape
+        assert!(matches!(
+            CartesianNetcdf3::from_ndarray(x.clone(), y.clone(), depth.clone().reversed_axes()),
+            Err(Error::DimensionMismatch {
+                expected: 50,
+                got: 100
+            })
+        ));
+        // descending or not equally spaced coordinates
+        let descending = x.iter().rev().copied().collect::<Array1<f32>>();
+        assert!(matches!(
+            CartesianNetcdf3::from_ndarray(descending, y.clone(), depth.clone()),
+            Err(Error::InvalidArgument)
+        ));
+        let mut uneven = y.clone();
+        uneven[10] += 100.0;
+        assert!(matches!(
+            CartesianNetcdf3::from_ndarray(x, uneven, depth.clone()),
+            Err(Error::InvalidArgument)
+        ));
+
+        // UTM coordinates with a spacing that is not a whole number, which
+        // are rounded by a few centimeters in single precision
+        let utm_x: Array1<f32> = (0..100)
+            .map(|i| (500_000.0 + 10.1 * i as f64) as f32)
+            .collect();
+        let utm_y: Array1<f32> = (0..50)
+            .map(|j| (4_000_000.0 + 10.1 * j as f64) as f32)
+            .collect();
+        let utm =
+            CartesianNetcdf3::from_ndarray(utm_x.clone(), utm_y.clone(), depth.clone()).unwrap();
+        let point = Point::new(utm_x[10], utm_y[5]);
+        assert_eq!(utm.depth(&point).unwrap(), depth[[5, 10]]);
+    }
+
+    #[test]
+    /// the batch results are the same as the results point by point, in the
+    /// order of the points, including the errors
+    fn test_depth_and_gradient_batch() {
+        // land at x < 10000, and a slope elsewhere
+        let data = || {
+            bathymetry_from_fn(100, 50, 500.0, 500.0, |x, y| {
+                if x < 10_000.0 {
+                    f64::NAN
+                } else {
+                    50.0 - 0.001 * x as f64 + 0.0005 * y as f64
+                }
+            })
+            .unwrap()
+        };
+        // scattered points, with several in the same cells, out of the
+        // domain, and NaN
+        let mut points: Vec<Point<f32>> = (0..500)
+            .map(|i| {
+                let i = i as f32;
+                Point::new((i * 7919.0) % 52_000.0 - 1000.0, (i * 104.729) % 26_000.0)
+            })
+            .collect();
+        points.push(Point::new(f32::NAN, 100.0));
+        points.push(Point::new(20_000.0, 12_000.0));
+
+        type Results = Vec<crate::error::Result<(f32, Gradient<f32>)>>;
+        let as_string = |results: Results| -> Vec<String> {
+            results.iter().map(|r| format!("{:?}", r)).collect()
+        };
+        for data in [
+            data(),
+            data().with_gradient_method(GradientMethod::CentralDifference),
+            data().with_interpolation_mode(InterpolationMode::Nearest),
+        ] {
+            let expected: Vec<_> = points.iter().map(|p| data.depth_and_gradient(p)).collect();
+            assert!(expected.iter().any(|r| matches!(r, Err(Error::Land))));
+            assert!(expected
+                .iter()
+                .any(|r| matches!(r, Err(Error::IndexOutOfBounds))));
+            assert!(expected.iter().filter(|r| r.is_ok()).count() > 100);
+            assert_eq!(
+                as_string(data.depth_and_gradient_batch(&points)),
+                as_string(expected)
+            );
+        }
+    }
+
+    /// total change of direction \[rad\] of a ray from (100, 500) going
+    /// toward the shore at 0.3 rad
+    fn total_curvature(data: &CartesianNetcdf3) -> f64 {
+        use crate::current::DEFAULT_CURRENT;
+        use crate::datatype::{RayState, WaveNumber};
+        use crate::ray::SingleRay;
+
+        let initial_ray = RayState::new(
+            Point::new(100.0, 500.0),
+            WaveNumber::new(0.05 * 0.3_f64.cos(), 0.05 * 0.3_f64.sin()),
+        );
+        let result = SingleRay::new(data, &DEFAULT_CURRENT, &initial_ray)
+            .trace(0.0, 200.0, 1.0)
+            .unwrap();
+        let direction = result.direction_unwrapped();
+        direction.windows(2).map(|w| (w[1] - w[0]).abs()).sum()
+    }
+
+    #[test]
+    /// the smoothing removes the grid-scale noise that makes a ray wiggle, and
+    /// keeps a linear slope unchanged away from the edges
+    fn test_smoothed() {
+        // 50 m at x = 0 decreasing by 2 cm per m, with a checkerboard of
+        // +/- 0.5 m
+        let noisy = bathymetry_from_fn(200, 100, 10.0, 10.0, |x, y| {
+            let cells = (x / 10.0).round() as i64 + (y / 10.0).round() as i64;
+            50.0 - 0.02 * x as f64 + if cells % 2 == 0 { 0.5 } else { -0.5 }
+        })
+        .unwrap();
+        let smoothed = noisy.smoothed(2.0);
+        assert!(
+            total_curvature(&smoothed) < 0.5 * total_curvature(&noisy),
+            "{} >= 0.5 * {}",
+            total_curvature(&smoothed),
+            total_curvature(&noisy)
+        );
+
+        let slope =
+            bathymetry_from_fn(200, 100, 10.0, 10.0, |x, _| 50.0 - 0.02 * x as f64).unwrap();
+        let smoothed = slope.smoothed(2.0);
+        assert!((total_curvature(&smoothed) - total_curvature(&slope)).abs() < 1e-6);
+        let point = Point::new(1005.0, 505.0);
+        assert!((smoothed.depth(&point).unwrap() - slope.depth(&point).unwrap()).abs() < 1e-4);
+
+        // no smoothing
+        assert_eq!(noisy.smoothed(0.0).depth_grid(), noisy.depth_grid());
+    }
+
+    #[test]
+    /// land cells stay land, and do not leak into the smoothed water depth
+    fn test_smoothed_land() {
+        let land_fn = |x: f32, _| if x < 50.0 { f64::NAN } else { 20.0 };
+        let data = bathymetry_from_fn(20, 10, 10.0, 10.0, land_fn).unwrap();
+        let (_, _, grid) = data.smoothed(1.5).depth_grid();
+        for ((_, i), h) in grid.indexed_iter() {
+            if i < 5 {
+                assert!(h.is_nan());
+            } else {
+                assert_eq!(*h, 20.0);
+            }
+        }
+    }
+
+    #[test]
+    /// with UTM-like coordinates, rounding the point to f32 moves it to the
+    /// next cell, while the f64 path finds the correct cell
+    fn test_depth_f64_large_coordinates() {
+        // the depth steps from 10 m to 20 m between the first two columns
+        let data = CartesianNetcdf3::from_arrays(
+            vec![500_000.0, 500_001.0, 500_002.0],
+            vec![0.0, 1.0],
+            vec![10.0, 20.0, 20.0, 10.0, 20.0, 20.0],
+        )
+        .unwrap();
+
+        // 500000.99 is rounded to 500001.0 in f32
+        let x = 500_000.99_f64;
+        assert_eq!(x as f32, 500_001.0);
+        assert_eq!(
+            data.four_corners(&(x as f32 as f64), &0.5).unwrap(),
+            vec![(1, 0), (1, 1), (2, 1), (2, 0)]
+        );
+        assert_eq!(
+            data.four_corners(&x, &0.5).unwrap(),
+            vec![(0, 0), (0, 1), (1, 1), (1, 0)]
+        );
+
+        assert_eq!(data.depth(&Point::new(x as f32, 0.5)).unwrap(), 20.0);
+        let depth = data.depth_f64(&Point::new(x, 0.5)).unwrap();
+        assert!((depth - 19.9).abs() < 1e-4, "depth: {}", depth);
+        let (depth, gradient) = data.depth_and_gradient_f64(&Point::new(x, 0.5)).unwrap();
+        assert!((depth - 19.9).abs() < 1e-4, "depth: {}", depth);
+        assert_eq!(*gradient.dx(), 10.0);
+    }
+
+    /// create a 11 x 6 file with 1 m spacing and a linear depth 10 + x,
+    /// where the coordinates are named "easting" and "northing", and the
+    /// depth variable is named `depth_name` and is multiplied by `sign`.
+    /// Each attribute is given as (variable, name, value).
+    fn create_cf_file(
+        path: &std::path::Path,
+        depth_name: &str,
+        sign: f64,
+        attrs: &[(&str, &str, &str)],
+    ) {
+        use netcdf3::{DataSet, FileWriter, Version};
+
+        let x: Vec<f32> = (0..11).map(|i| i as f32).collect();
+        let y: Vec<f32> = (0..6).map(|j| j as f32).collect();
+        let depth: Vec<f64> = y
+            .iter()
+            .flat_map(|_| x.iter().map(|x| sign * (10.0 + *x as f64)))
+            .collect();
+
+        let mut data_set = DataSet::new();
+        data_set.add_fixed_dim("northing", y.len()).unwrap();
+        data_set.add_fixed_dim("easting", x.len()).unwrap();
+        data_set.add_var_f32("northing", &["northing"]).unwrap();
+        data_set.add_var_f32("easting", &["easting"]).unwrap();
+        data_set
+            .add_var_f64(depth_name, &["northing", "easting"])
+            .unwrap();
+        for (var, name, value) in attrs {
+            data_set.add_var_attr_string(var, name, value).unwrap();
+        }
+
+        let mut file_writer = FileWriter::open(path).unwrap();
+        file_writer.set_def(&data_set, Version::Classic, 0).unwrap();
+        file_writer.write_var_f32("northing", &y).unwrap();
+        file_writer.write_var_f32("easting", &x).unwrap();
+        file_writer.write_var_f64(depth_name, &depth).unwrap();
+        file_writer.close().unwrap();
+    }
+
+    #[test]
+    // the elevation is detected by its standard name and converted to depth
+    fn test_open_cf_elevation() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        create_cf_file(
+            &temp_path,
+            "elevation",
+            -1.0,
+            &[
+                ("easting", "axis", "X"),
+                ("northing", "axis", "Y"),
+                ("elevation", "standard_name", "height_above_mean_sea_level"),
+            ],
+        );
+
+        let data = CartesianNetcdf3::open_cf(&temp_path).unwrap();
+        assert_eq!(data.x.len(), 11);
+        assert_eq!(data.y.len(), 6);
+        assert!((data.depth(&Point::new(2.5, 3.0)).unwrap() - 12.5).abs() < 1e-5);
+    }
+
+    #[test]
+    // without a standard name, the depth is detected by its units and
+    // positive direction, and the axes by their standard name
+    fn test_open_cf_positive_down() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        create_cf_file(
+            &temp_path,
+            "Band1",
+            1.0,
+            &[
+                ("easting", "standard_name", "projection_x_coordinate"),
+                ("northing", "standard_name", "projection_y_coordinate"),
+                ("Band1", "units", "m"),
+                ("Band1", "positive", "down"),
+            ],
+        );
+
+        let data = CartesianNetcdf3::open_cf(&temp_path).unwrap();
+        assert!((data.depth(&Point::new(2.5, 3.0)).unwrap() - 12.5).abs() < 1e-5);
+    }
+
+    #[test]
+    // the error lists the available variables
+    fn test_open_cf_not_detected() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        create_cf_file(
+            &temp_path,
+            "z",
+            1.0,
+            &[("easting", "axis", "X"), ("northing", "axis", "Y")],
+        );
+
+        match CartesianNetcdf3::open_cf(&temp_path) {
+            Err(Error::VariableNotDetected(role, names)) => {
+                assert_eq!(role, "depth");
+                assert_eq!(names, vec!["northing", "easting", "z"]);
+            }
+            _ => panic!("expected VariableNotDetected"),
+        }
+    }
+
+    #[test]
+    // an elevation file, negative in water, is read as positive depth, either
+    // detected by the `positive` attribute or set explicitly
+    fn test_elevation() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+        create_cf_file(&temp_path, "z", -1.0, &[("z", "positive", "up")]);
+
+        let data = CartesianNetcdf3::open(&temp_path, "easting", "northing", "z").unwrap();
+        assert!((data.depth(&Point::new(2.5, 3.0)).unwrap() - 12.5).abs() < 1e-5);
+        let (h, dh) = data.depth_and_gradient(&Point::new(2.5, 3.0)).unwrap();
+        assert!((h - 12.5).abs() < 1e-5);
+        assert!((dh.dx() - 1.0).abs() < 1e-5 && dh.dy().abs() < 1e-5);
+        assert_eq!(data.depth_grid().2[[0, 0]], 10.0);
+
+        let data = CartesianNetcdf3::open_single_precision(&temp_path, "easting", "northing", "z")
+            .unwrap();
+        assert!((data.depth(&Point::new(2.5, 3.0)).unwrap() - 12.5).abs() < 1e-5);
+
+        // without attributes, the values are assumed to be depth
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+        create_cf_file(&temp_path, "z", -1.0, &[]);
+
+        let data = CartesianNetcdf3::open(&temp_path, "easting", "northing", "z").unwrap();
+        assert!((data.depth(&Point::new(2.5, 3.0)).unwrap() + 12.5).abs() < 1e-5);
+        let data = data.with_sign_convention(SignConvention::Elevation);
+        assert!((data.depth(&Point::new(2.5, 3.0)).unwrap() - 12.5).abs() < 1e-5);
+    }
+
+    #[test]
+    // the contour of a linear depth is a straight line, and land cells are
+    // skipped
+    fn test_depth_contour() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        // depth = 10 + x, so the 12.5 m contour is the line x = 2.5
+        create_cf_file(&temp_path, "depth", 1.0, &[]);
+        let data = CartesianNetcdf3::open(&temp_path, "easting", "northing", "depth").unwrap();
+
+        let contour = data.depth_contour(12.5);
+        // one segment in each of the 5 cells between x = 2 and x = 3
+        assert_eq!(contour.len(), 10);
+        for (x, y) in &contour {
+            assert!((x - 2.5).abs() < 1e-5, "x: {}", x);
+            assert!((0.0..=5.0).contains(y));
+        }
+        assert!(data.depth_contour(100.0).is_empty());
+
+        fn depth_fn(x: f32, y: f32) -> f64 {
+            if x == 2.0 && y == 2.0 {
+                f64::NAN
+            } else {
+                10.0 + x as f64
+            }
+        }
+        create_netcdf3_bathymetry(&temp_path, 11, 6, 1.0, 1.0, depth_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        assert_eq!(data.depth_contour(12.5).len(), 6);
+    }
+
+    #[test]
+    // in a saddle cell, the segments are chosen by the mean depth of the cell
+    fn test_depth_contour_saddle() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        // deep at (0, 0) and (1, 1), shallow at (1, 0) and (0, 1)
+        fn depth_fn(x: f32, y: f32) -> f64 {
+            if x == y {
+                20.0
+            } else {
+                0.0
+            }
+        }
+        create_netcdf3_bathymetry(&temp_path, 2, 2, 1.0, 1.0, depth_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+
+        // the mean depth 10 is deeper than 5, so the deep corners are
+        // connected and the segments cut the shallow corners (1, 0) and (0, 1)
+        let contour = data.depth_contour(5.0);
+        assert_eq!(
+            contour,
+            vec![(0.75, 0.0), (1.0, 0.25), (0.25, 1.0), (0.0, 0.75)]
+        );
+
+        // the mean depth 10 is shallower than 15, so the segments cut the deep
+        // corners (0, 0) and (1, 1)
+        let contour = data.depth_contour(15.0);
+        assert_eq!(
+            contour,
+            vec![(0.0, 0.25), (0.25, 0.0), (1.0, 0.75), (0.75, 1.0)]
+        );
+    }
+
+    #[test]
+    // with a periodic x axis, the depth and gradient are continuous across the
+    // seam between the last and the first x points
+    fn test_periodic_x() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.into_temp_path();
+
+        // x from 0 to 90 m, so the period is 100 m
+        fn depth_fn(x: f32, _y: f32) -> f64 {
+            50.0 + 10.0 * (2.0 * std::f64::consts::PI * x as f64 / 100.0).cos()
+        }
+        create_netcdf3_bathymetry(&temp_path, 10, 11, 10.0, 10.0, depth_fn);
+        let data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+        assert!(data.depth(&Point::new(95.0, 50.0)).is_err());
+
+        let data = data.with_boundary_x(BoundaryX::Periodic);
+        assert_eq!(data.wrap_x(195.0), 95.0);
+        assert_eq!(data.wrap_x(-5.0), 95.0);
+
+        // the cell closing the seam interpolates between x = 90 and x = 100
+        let h90 = depth_fn(90.0, 0.0);
+        let h0 = depth_fn(0.0, 0.0);
+        let h = data.depth(&Point::new(95.0, 50.0)).unwrap();
+        assert!((h as f64 - (h90 + h0) / 2.0).abs() < 1e-4);
+        let (_, dh) = data.depth_and_gradient(&Point::new(95.0, 50.0)).unwrap();
+        assert!((*dh.dx() as f64 - (h0 - h90) / 10.0).abs() < 1e-5);
+
+        // the same point one period away
+        for x in [-5.0, 195.0, -105.0] {
+            let (hx, dhx) = data.depth_and_gradient(&Point::new(x, 50.0)).unwrap();
+            assert!((hx - h).abs() < 1e-4, "x: {}", x);
+            assert!((dhx.dx() - dh.dx()).abs() < 1e-5, "x: {}", x);
+        }
+
+        // continuous on both sides of the seam
+        let before = data.depth(&Point::new(99.999, 50.0)).unwrap();
+        let after = data.depth(&Point::new(100.001, 50.0)).unwrap();
+        assert!((before - after).abs() < 1e-3);
+        assert!((data.depth(&Point::new(100.0, 50.0)).unwrap() as f64 - h0).abs() < 1e-4);
+
+        // the central difference at x = 90 uses the point at x = 0
+        let data = data.with_gradient_method(GradientMethod::CentralDifference);
+        let (_, dh) = data.depth_and_gradient(&Point::new(90.0, 50.0)).unwrap();
+        let expected = (h0 - depth_fn(80.0, 0.0)) / 20.0;
+        assert!((*dh.dx() as f64 - expected).abs() < 1e-5);
+    }
 }
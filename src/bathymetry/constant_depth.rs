@@ -3,6 +3,7 @@
 use super::BathymetryData;
 use crate::{datatype::{Gradient, Point}, error::Result};
 use derive_builder::Builder;
+use std::f64::consts::PI;
 
 #[allow(dead_code)]
 /// The default depth is 2000 m
@@ -59,11 +60,36 @@ impl ConstantDepth {
     pub(crate) fn new(h: f32) -> ConstantDepth {
         ConstantDepth { h }
     }
+
+    #[allow(dead_code)]
+    /// construct a constant depth that is deep water for the wavenumber
+    ///
+    /// The depth is 10 pi / k, i.e. five wavelengths, thus k h = 10 pi is
+    /// well above the deep water limit of pi.
+    ///
+    /// # Arguments
+    /// `k`: `f64` the magnitude of the wavenumber [m^-1]
+    pub(crate) fn deep_for(k: f64) -> ConstantDepth {
+        ConstantDepth::new((10.0 * PI / k) as f32)
+    }
+
+    #[allow(dead_code)]
+    /// construct a constant depth that is shallow water for the wavenumber
+    ///
+    /// The depth is pi / (20 k), i.e. a fortieth of the wavelength, thus
+    /// k h = pi / 20 is well below the shallow water limit of pi / 10.
+    ///
+    /// # Arguments
+    /// `k`: `f64` the magnitude of the wavenumber [m^-1]
+    pub(crate) fn shallow_for(k: f64) -> ConstantDepth {
+        ConstantDepth::new((PI / (20.0 * k)) as f32)
+    }
 }
 
 #[cfg(test)]
 mod test_constant_depth {
     use crate::datatype::Point;
+    use crate::dispersion::{water_regime, WaterRegime};
 
     use super::{BathymetryData, ConstantDepth};
 
@@ -79,6 +105,17 @@ mod test_constant_depth {
         assert!(c.depth(&Point::new(0.0, f32::NAN)).unwrap().is_nan());
         assert!(c.depth(&Point::new(f32::NAN, f32::NAN)).unwrap().is_nan());
     }
+
+    #[test]
+    /// the depths of the presets are in the intended regime
+    fn test_regime_presets() {
+        for k in [0.001, 0.05, 1.0, 10.0] {
+            let deep = ConstantDepth::deep_for(k);
+            let shallow = ConstantDepth::shallow_for(k);
+            assert_eq!(water_regime(k, deep.h as f64), WaterRegime::Deep);
+            assert_eq!(water_regime(k, shallow.h as f64), WaterRegime::Shallow);
+        }
+    }
 }
 
 #[cfg(test)]
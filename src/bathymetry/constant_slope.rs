@@ -64,6 +64,9 @@ impl BathymetryData for ConstantSlope {
     /// Returns NaN when any input is NaN. Since it is a constant slope,
     /// there is no concept of boundaries, thus it can't fail as out of
     /// bounds.
+    ///
+    /// The gradient is the analytic slope (`dhdx`, `dhdy`), not a finite
+    /// difference, so it is exact everywhere.
     fn depth_and_gradient(&self, point: &Point<f32>) -> Result<(f32, Gradient<f32>)> {
         let x = point.x();
         let y = point.y();
@@ -87,11 +90,45 @@ impl ConstantSlope {
     pub(crate) fn builder() -> ConstantSlopeBuilder {
         ConstantSlopeBuilder::default()
     }
+
+    #[allow(dead_code)]
+    /// Depth \[m\] at the origin (`x0`, `y0`)
+    pub(crate) fn h0(&self) -> &f32 {
+        &self.h0
+    }
+
+    #[allow(dead_code)]
+    /// x coordinate \[m\] of the origin
+    pub(crate) fn x0(&self) -> &f32 {
+        &self.x0
+    }
+
+    #[allow(dead_code)]
+    /// y coordinate \[m\] of the origin
+    pub(crate) fn y0(&self) -> &f32 {
+        &self.y0
+    }
+
+    #[allow(dead_code)]
+    /// Rate of change in depth with respect to x
+    pub(crate) fn dhdx(&self) -> &f32 {
+        &self.dhdx
+    }
+
+    #[allow(dead_code)]
+    /// Rate of change in depth with respect to y
+    pub(crate) fn dhdy(&self) -> &f32 {
+        &self.dhdy
+    }
 }
 
 #[cfg(test)]
 mod test_constant_slope {
-    use crate::datatype::Point;
+    use crate::current::DEFAULT_CURRENT;
+    use crate::datatype::{Point, RayState, WaveNumber};
+    use crate::dispersion::{intrinsic_frequency, wavenumber};
+    use crate::ray::SingleRay;
+    use crate::wave_ray_path::StateView;
 
     use super::{BathymetryData, ConstantSlope};
 
@@ -113,6 +150,68 @@ mod test_constant_slope {
         assert!(c.depth(&Point::new(0.0, f32::NAN)).unwrap().is_nan());
         assert!(c.depth(&Point::new(f32::NAN, f32::NAN)).unwrap().is_nan());
     }
+
+    #[test]
+    // the gradient of a 1:100 slope is exactly the slope everywhere, and the
+    // parameters are available through the getters
+    fn exact_gradient() {
+        let c = ConstantSlope::builder()
+            .h0(100.0)
+            .x0(10.0)
+            .y0(20.0)
+            .dhdx(-1e-2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            (*c.h0(), *c.x0(), *c.y0(), *c.dhdx(), *c.dhdy()),
+            (100.0, 10.0, 20.0, -1e-2, 0.0)
+        );
+
+        for x in (-5000..15000).step_by(731) {
+            for y in (-5000..5000).step_by(917) {
+                let point = Point::new(x as f32, y as f32);
+                let (h, gradient) = c.depth_and_gradient(&point).unwrap();
+                assert_eq!(h, c.depth(&point).unwrap());
+                assert_eq!(*gradient.dx(), -0.01);
+                assert_eq!(*gradient.dy(), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    // over a plane beach, the alongshore wavenumber is conserved and the
+    // wavenumber magnitude follows the dispersion relation at the local
+    // depth, i.e. the ray turns as given by Snell's law
+    fn plane_beach_refraction() {
+        let c = ConstantSlope::builder()
+            .h0(10.0)
+            .dhdx(-1e-2)
+            .build()
+            .unwrap();
+        let (kx, ky) = (0.04, 0.03);
+        let sigma = intrinsic_frequency(0.05, 10.0);
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(kx, ky));
+
+        let res = SingleRay::new(&c, &DEFAULT_CURRENT, &initial_ray)
+            .trace(0.0, 60.0, 0.5)
+            .unwrap();
+        let states: Vec<_> = res.valid_states().collect();
+        assert_eq!(states.len(), 121);
+        for (_, state) in states {
+            let h = c
+                .depth(&Point::new(state.x() as f32, state.y() as f32))
+                .unwrap();
+            let k = wavenumber(sigma, h as f64).unwrap();
+            assert!((state.ky() - ky).abs() < 1e-9);
+            let expected_kx = (k * k - ky * ky).sqrt();
+            assert!(
+                (state.kx() - expected_kx).abs() < 1e-5 * k,
+                "Expected {}, but got {}",
+                expected_kx,
+                state.kx()
+            );
+        }
+    }
 }
 
 #[cfg(test)]
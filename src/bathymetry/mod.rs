@@ -8,28 +8,42 @@
 //!   constraints on the input since the depth is defined by a constant value.
 //! - `ConstantSlope` - constant slope bathymetry. There are no domain
 //!   constraints on the input since the depth is defined by a function.
+//! - `VariableGridBathymetry` - bathymetry on a grid with variable spacing,
+//!   e.g. telescoping towards the coast.
 //!
 //! The following are used primarily for testing purposes:
 //! - `ArrayDepth` - used to create bathymetry data from an array. Useful for
-//!   creating purposefully out of bounds points.
+//!   creating purposefully out of bounds points, or a known gradient.
 
 mod array_depth;
 mod cartesian_netcdf3;
 mod constant_depth;
 mod constant_slope;
+mod variable_grid;
 
 use crate::datatype::{Gradient, Point};
-use crate::error::Result;
+use crate::dispersion;
+use crate::error::{Error, Result};
 #[allow(unused_imports)]
 pub(super) use array_depth::ArrayDepth;
 #[allow(unused_imports)]
+pub(super) use cartesian_netcdf3::BoundaryX;
+#[allow(unused_imports)]
 pub(super) use cartesian_netcdf3::CartesianNetcdf3;
 #[allow(unused_imports)]
+pub(super) use cartesian_netcdf3::GradientMethod;
+#[allow(unused_imports)]
+pub(super) use cartesian_netcdf3::InterpolationMode;
+#[allow(unused_imports)]
+pub(super) use cartesian_netcdf3::SignConvention;
+#[allow(unused_imports)]
 pub(super) use constant_depth::ConstantDepth;
 #[allow(unused_imports)]
 pub(super) use constant_depth::DEFAULT_BATHYMETRY;
 #[allow(unused_imports)]
 pub(super) use constant_slope::ConstantSlope;
+#[allow(unused_imports)]
+pub(super) use variable_grid::VariableGridBathymetry;
 
 /// A trait defining ability to return depth and gradient
 pub(crate) trait BathymetryData: Sync {
@@ -38,4 +52,280 @@ pub(crate) trait BathymetryData: Sync {
     fn depth(&self, point: &Point<f32>) -> Result<f32>;
     /// Returns the nearest depth and depth gradient for the given (x, y) coordinates
     fn depth_and_gradient(&self, point: &Point<f32>) -> Result<(f32, Gradient<f32>)>;
+
+    /// Depth at the given (x, y) point in double precision
+    ///
+    /// The default rounds the point to f32 and calls `depth`, which loses
+    /// precision with large coordinates, e.g. a UTM easting of 500 km is
+    /// rounded to about 0.03 m. Gridded data override it to find the cell in
+    /// double precision.
+    fn depth_f64(&self, point: &Point<f64>) -> Result<f32> {
+        self.depth(&Point::new(*point.x() as f32, *point.y() as f32))
+    }
+
+    /// Depth and depth gradient at the given (x, y) point in double precision
+    ///
+    /// The default rounds the point to f32 and calls `depth_and_gradient`,
+    /// see `depth_f64`.
+    fn depth_and_gradient_f64(&self, point: &Point<f64>) -> Result<(f32, Gradient<f32>)> {
+        self.depth_and_gradient(&Point::new(*point.x() as f32, *point.y() as f32))
+    }
+
+    /// Depth and depth gradient at many (x, y) points
+    ///
+    /// The default calls `depth_and_gradient` for each point. Gridded data
+    /// override it to reuse the computations shared by the points in the same
+    /// cell, e.g. to build a diagnostic field on a fine grid.
+    ///
+    /// # Returns
+    /// `Vec<Result<(f32, Gradient<f32>)>>` : the same as `depth_and_gradient`
+    /// for each point, in the same order as `points`.
+    fn depth_and_gradient_batch(&self, points: &[Point<f32>]) -> Vec<Result<(f32, Gradient<f32>)>> {
+        points
+            .iter()
+            .map(|point| self.depth_and_gradient(point))
+            .collect()
+    }
+
+    /// Grid spacing (dx, dy) \[m\] of gridded data
+    ///
+    /// The default is `None`, for bathymetry defined everywhere by a function.
+    fn grid_spacing(&self) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+#[allow(dead_code)]
+/// Group velocity on a rectilinear grid for waves of a given period
+///
+/// At each grid point, the dispersion relation is solved for the wavenumber
+/// from the period and the local depth, then the group velocity is
+/// calculated. Useful to see the refraction pattern of the bathymetry before
+/// tracing rays, since the rays bend toward lower group velocity.
+///
+/// # Arguments
+/// `bathymetry` : `&dyn BathymetryData`
+/// - the bathymetry
+///
+/// `period` : `f64`
+/// - the wave period \[s\]
+///
+/// `x` : `&[f64]`
+/// - x coordinates of the grid columns
+///
+/// `y` : `&[f64]`
+/// - y coordinates of the grid rows
+///
+/// # Returns
+/// `Result<Vec<f64>>` : the group velocity \[m/s\] flattened in row-major
+/// order, so that the value at (x\[i\], y\[j\]) is at index
+/// `j * x.len() + i`. Points outside of the domain or on land are NaN.
+///
+/// # Errors
+/// - `Error::ArgumentOutOfBounds` : the period is not positive.
+/// - any error from `depth` other than `Error::IndexOutOfBounds` and
+///   `Error::Land`.
+pub(crate) fn group_velocity_field(
+    bathymetry: &dyn BathymetryData,
+    period: f64,
+    x: &[f64],
+    y: &[f64],
+) -> Result<Vec<f64>> {
+    period_field(
+        period,
+        x,
+        y,
+        |points| {
+            points
+                .iter()
+                .map(|point| bathymetry.depth(point).map(|h| (h, ())))
+                .collect()
+        },
+        |k, h, _| dispersion::group_velocity(k, h),
+    )
+}
+
+#[allow(dead_code)]
+/// Refraction strength on a rectilinear grid for waves of a given period
+///
+/// At each grid point, the rate of change of the wavenumber due to the
+/// bathymetry, $|\partial \sigma / \partial h| |\nabla h|$ (see
+/// `dispersion::dsigma_dh`), is divided by the wavenumber magnitude and the
+/// group velocity, i.e. it is the turning rate per distance travelled
+/// \[rad/m\] of a ray. This is a cheap screening of where the bathymetry
+/// refracts the waves strongly, e.g. to decide which areas need a finer grid,
+/// before tracing rays.
+///
+/// # Arguments
+/// `bathymetry` : `&dyn BathymetryData`
+/// - the bathymetry
+///
+/// `period` : `f64`
+/// - the wave period \[s\]
+///
+/// `x` : `&[f64]`
+/// - x coordinates of the grid columns
+///
+/// `y` : `&[f64]`
+/// - y coordinates of the grid rows
+///
+/// # Returns
+/// `Result<Vec<f64>>` : the refraction strength \[1/m\] flattened in
+/// row-major order, as in `group_velocity_field`. It is zero over a flat
+/// bottom. Points outside of the domain or on land are NaN.
+///
+/// # Errors
+/// - `Error::ArgumentOutOfBounds` : the period is not positive.
+/// - any error from `depth_and_gradient` other than
+///   `Error::IndexOutOfBounds` and `Error::Land`.
+pub(crate) fn refraction_strength(
+    bathymetry: &dyn BathymetryData,
+    period: f64,
+    x: &[f64],
+    y: &[f64],
+) -> Result<Vec<f64>> {
+    period_field(
+        period,
+        x,
+        y,
+        |points| bathymetry.depth_and_gradient_batch(points),
+        |k, h, gradient| {
+            let slope = (*gradient.dx() as f64).hypot(*gradient.dy() as f64);
+            Ok(dispersion::dsigma_dh(k, h) * slope / k / dispersion::group_velocity(k, h)?)
+        },
+    )
+}
+
+/// Evaluate a quantity of waves of a given period on a rectilinear grid
+///
+/// The bathymetry is sampled at the grid points in row-major order with
+/// `sample`, then the dispersion relation is solved for the wavenumber from
+/// the period and the local depth, and `value` is called with the
+/// wavenumber, the depth, and the rest of the sample, e.g. the gradient.
+///
+/// # Returns
+/// `Result<Vec<f64>>` : the values flattened in row-major order. Points
+/// outside of the domain or on land, i.e. where the sample is
+/// `Error::IndexOutOfBounds` or `Error::Land` or the depth is not positive,
+/// are NaN.
+///
+/// # Errors
+/// - `Error::ArgumentOutOfBounds` : the period is not positive.
+/// - any other error from `sample` or `value`.
+fn period_field<T>(
+    period: f64,
+    x: &[f64],
+    y: &[f64],
+    sample: impl Fn(&[Point<f32>]) -> Vec<Result<(f32, T)>>,
+    value: impl Fn(f64, f64, T) -> Result<f64>,
+) -> Result<Vec<f64>> {
+    if period.is_nan() || period <= 0.0 {
+        return Err(Error::ArgumentOutOfBounds);
+    }
+    let sigma = 2.0 * std::f64::consts::PI / period;
+    let points: Vec<Point<f32>> = y
+        .iter()
+        .flat_map(|y| x.iter().map(|x| Point::new(*x as f32, *y as f32)))
+        .collect();
+    sample(&points)
+        .into_iter()
+        .map(|result| {
+            let (h, rest) = match result {
+                Ok((h, rest)) => (h as f64, rest),
+                Err(Error::IndexOutOfBounds | Error::Land) => return Ok(f64::NAN),
+                Err(e) => return Err(e),
+            };
+            // NaN or non-positive depth, i.e. land
+            match dispersion::wavenumber(sigma, h) {
+                Ok(k) => value(k, h, rest),
+                Err(_) => Ok(f64::NAN),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_group_velocity_field {
+    use super::{group_velocity_field, ConstantSlope};
+    use crate::{dispersion, error::Error, io::utility::bathymetry_from_fn};
+
+    #[test]
+    /// the group velocity matches the dispersion relation at each depth, and
+    /// is NaN out of the domain and on land
+    fn test_group_velocity_field() {
+        // depth of 50 m at x = 0 decreasing by 5 cm per m
+        let slope = ConstantSlope::builder().build().unwrap();
+        let x = [0.0, 200.0, 800.0, 1200.0];
+        let y = [0.0, 10.0];
+        let cg = group_velocity_field(&slope, 20.0, &x, &y).unwrap();
+        assert_eq!(cg.len(), 8);
+
+        let sigma = 2.0 * std::f64::consts::PI / 20.0;
+        for (i, x) in x.iter().enumerate().take(3) {
+            let h = 50.0 - 0.05 * x;
+            let k = dispersion::wavenumber(sigma, h).unwrap();
+            let expected = dispersion::group_velocity(k, h).unwrap();
+            for j in 0..2 {
+                assert!((cg[j * 4 + i] - expected).abs() < 1e-5 * expected);
+            }
+        }
+        // long waves are slower in shallower water
+        assert!(cg[0] > cg[1] && cg[1] > cg[2]);
+        // past the shoreline
+        assert!(cg[3].is_nan() && cg[7].is_nan());
+
+        // out of the domain of gridded data
+        let grid = bathymetry_from_fn(11, 11, 10.0, 10.0, |_, _| 20.0).unwrap();
+        let cg = group_velocity_field(&grid, 8.0, &[50.0, 500.0], &[50.0]).unwrap();
+        assert!(cg[0] > 0.0);
+        assert!(cg[1].is_nan());
+
+        assert!(matches!(
+            group_velocity_field(&slope, 0.0, &x, &y),
+            Err(Error::ArgumentOutOfBounds)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test_refraction_strength {
+    use super::{refraction_strength, ConstantDepth, ConstantSlope};
+    use crate::error::Error;
+
+    #[test]
+    /// the refraction is stronger over a steeper slope, and zero over a flat
+    /// bottom
+    fn test_refraction_strength() {
+        let x = [0.0, 100.0];
+        let y = [0.0];
+        let flat = ConstantDepth::new(20.0);
+        let gentle = ConstantSlope::builder()
+            .h0(20.0)
+            .dhdx(-0.01)
+            .build()
+            .unwrap();
+        let steep = ConstantSlope::builder()
+            .h0(20.0)
+            .dhdx(-0.05)
+            .build()
+            .unwrap();
+
+        let strength = |bathymetry| refraction_strength(bathymetry, 10.0, &x, &y).unwrap();
+        let (flat_strength, gentle_strength, steep_strength) =
+            (strength(&flat), strength(&gentle), strength(&steep));
+        assert_eq!(flat_strength, vec![0.0, 0.0]);
+        for (gentle, steep) in gentle_strength.iter().zip(&steep_strength) {
+            assert!(*gentle > 0.0);
+            assert!(steep > gentle, "{} <= {}", steep, gentle);
+        }
+        // at the same depth, the strength is proportional to the slope
+        assert!((steep_strength[0] / gentle_strength[0] - 5.0).abs() < 1e-5);
+        // stronger in shallower water
+        assert!(steep_strength[1] > steep_strength[0]);
+
+        assert!(matches!(
+            refraction_strength(&steep, -1.0, &x, &y),
+            Err(Error::ArgumentOutOfBounds)
+        ));
+    }
 }
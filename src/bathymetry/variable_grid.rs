@@ -0,0 +1,275 @@
+//! Struct used to access bathymetry data on a grid with variable spacing.
+//!
+//! Unlike `CartesianNetcdf3`, the x and y coordinates do not need to be
+//! equally spaced, e.g. a grid telescoping towards the coast. They only have
+//! to be strictly ascending.
+
+use std::path::Path;
+
+use netcdf3::FileReader;
+
+use super::{cartesian_netcdf3::is_land, BathymetryData};
+use crate::{
+    datatype::{Gradient, Point},
+    error::{Error, Result},
+    interpolator,
+    io::{read_var_as_f32, read_var_as_f64},
+};
+
+/// Bathymetry on a rectilinear grid with arbitrary, ascending, x and y
+/// coordinates
+///
+/// The cell enclosing a point is found with a binary search of each axis, the
+/// depth is bilinearly interpolated within the cell, and the gradient is the
+/// one-sided difference across the cell using its actual size.
+///
+/// # Note
+/// The grid spacing is not constant, so `grid_spacing` is `None` and the grid
+/// is not compared with the current grid by `ManyRays`.
+pub(crate) struct VariableGridBathymetry {
+    /// the x coordinates in ascending order
+    x: Vec<f32>,
+    /// the y coordinates in ascending order
+    y: Vec<f32>,
+    /// the flattened depth, where the row is y and the column is x
+    depth: Vec<f64>,
+}
+
+impl BathymetryData for VariableGridBathymetry {
+    /// Depth at the given (x, y) point
+    ///
+    /// # Errors
+    /// - `Error::IndexOutOfBounds` : the point is outside of the coordinate
+    ///   range.
+    /// - `Error::Land` : one of the corners of the cell is a land cell.
+    fn depth(&self, point: &Point<f32>) -> Result<f32> {
+        let (x, y) = (*point.x(), *point.y());
+        if x.is_nan() || y.is_nan() {
+            return Ok(f32::NAN);
+        }
+        let (i, j) = self.cell(x, y)?;
+        self.interpolate(i, j, x, y)
+    }
+
+    /// Depth and gradient at the given (x, y) point
+    ///
+    /// # Errors
+    /// - `Error::IndexOutOfBounds` : the point is outside of the coordinate
+    ///   range.
+    /// - `Error::Land` : one of the corners of the cell is a land cell.
+    fn depth_and_gradient(&self, point: &Point<f32>) -> Result<(f32, Gradient<f32>)> {
+        let (x, y) = (*point.x(), *point.y());
+        if x.is_nan() || y.is_nan() {
+            return Ok((f32::NAN, Gradient::new(f32::NAN, f32::NAN)));
+        }
+        let (i, j) = self.cell(x, y)?;
+        let depth = self.interpolate(i, j, x, y)?;
+
+        let sw = self.water_depth_at_indexes(i, j)?;
+        let dhdx = (self.water_depth_at_indexes(i + 1, j)? - sw)
+            / (self.x[i + 1] as f64 - self.x[i] as f64);
+        let dhdy = (self.water_depth_at_indexes(i, j + 1)? - sw)
+            / (self.y[j + 1] as f64 - self.y[j] as f64);
+
+        Ok((depth, Gradient::new(dhdx as f32, dhdy as f32)))
+    }
+}
+
+#[allow(dead_code)]
+impl VariableGridBathymetry {
+    /// Construct a new `VariableGridBathymetry`
+    ///
+    /// # Arguments
+    /// `x` : `Vec<f32>`
+    /// - the x coordinates, strictly ascending
+    ///
+    /// `y` : `Vec<f32>`
+    /// - the y coordinates, strictly ascending
+    ///
+    /// `depth` : `Vec<f64>`
+    /// - the flattened depth, positive in water, where the row is y and the
+    ///   column is x
+    ///
+    /// # Returns
+    /// `Result<Self>` : the bathymetry or an error.
+    ///
+    /// # Errors
+    /// - `Error::InvalidArgument` : an axis has less than two points or is
+    ///   not strictly ascending, or the length of `depth` is not the length
+    ///   of `x` times the length of `y`.
+    pub(crate) fn new(x: Vec<f32>, y: Vec<f32>, depth: Vec<f64>) -> Result<Self> {
+        let ascending = |v: &[f32]| v.len() >= 2 && v.windows(2).all(|w| w[0] < w[1]);
+        if !ascending(&x) || !ascending(&y) || depth.len() != x.len() * y.len() {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(VariableGridBathymetry { x, y, depth })
+    }
+
+    /// Open a NetCDF3 file with variable grid spacing
+    ///
+    /// # Arguments
+    /// `path` : `&Path`
+    /// - path to the netcdf3 file
+    ///
+    /// `xname` : `&str`
+    /// - the name of the x variable in the netcdf3 file
+    ///
+    /// `yname` : `&str`
+    /// - the name of the y variable in the netcdf3 file
+    ///
+    /// `depth_name` : `&str`
+    /// - the name of the depth variable in the netcdf3 file
+    ///
+    /// # Returns
+    /// `Result<Self>` : the bathymetry or an error.
+    ///
+    /// # Errors
+    /// - `Error::IOError` : the file could not be opened.
+    /// - `Error::VariableNotFound` : one of the variables is not in the file.
+    /// - `Error::InvalidArgument` : the coordinates are not valid, see `new`.
+    pub(crate) fn open(path: &Path, xname: &str, yname: &str, depth_name: &str) -> Result<Self> {
+        let mut data = FileReader::open(path)?;
+        let x = read_var_as_f32(&mut data, xname)?;
+        let y = read_var_as_f32(&mut data, yname)?;
+        let depth = read_var_as_f64(&mut data, depth_name)?;
+        VariableGridBathymetry::new(x, y, depth)
+    }
+
+    /// Indexes (i, j) of the south west corner of the cell enclosing the
+    /// point
+    ///
+    /// A point on the last coordinate belongs to the last cell.
+    ///
+    /// # Errors
+    /// `Error::IndexOutOfBounds` : the point is outside of the coordinate
+    /// range.
+    fn cell(&self, x: f32, y: f32) -> Result<(usize, usize)> {
+        Ok((cell_index(&self.x, x)?, cell_index(&self.y, y)?))
+    }
+
+    /// Bilinear interpolation of the depth within the cell (i, j)
+    fn interpolate(&self, i: usize, j: usize, x: f32, y: f32) -> Result<f32> {
+        let corners = [(i, j), (i, j + 1), (i + 1, j + 1), (i + 1, j)];
+        let mut points = Vec::with_capacity(4);
+        for (ci, cj) in corners {
+            let depth = self.water_depth_at_indexes(ci, cj)?;
+            points.push((self.x[ci], self.y[cj], depth as f32));
+        }
+        interpolator::bilinear(&points, &(x, y))
+    }
+
+    /// Depth at the indexes, which must be water
+    ///
+    /// # Errors
+    /// - `Error::IndexOutOfBounds` : the indexes are outside of the grid.
+    /// - `Error::Land` : the depth is NaN or the NetCDF fill value, which
+    ///   mark land cells.
+    fn water_depth_at_indexes(&self, i: usize, j: usize) -> Result<f64> {
+        let depth = *self
+            .depth
+            .get(j * self.x.len() + i)
+            .ok_or(Error::IndexOutOfBounds)?;
+        if is_land(depth) {
+            return Err(Error::Land);
+        }
+        Ok(depth)
+    }
+}
+
+/// Index of the first coordinate of the interval of `axis` containing
+/// `value`, found with a binary search
+///
+/// # Errors
+/// `Error::IndexOutOfBounds` : `value` is outside of the range of `axis`.
+fn cell_index(axis: &[f32], value: f32) -> Result<usize> {
+    if value < axis[0] || value > axis[axis.len() - 1] {
+        return Err(Error::IndexOutOfBounds);
+    }
+    let index = axis.partition_point(|a| *a <= value);
+    Ok(index.saturating_sub(1).min(axis.len() - 2))
+}
+
+#[cfg(test)]
+mod test_variable_grid {
+    use super::*;
+
+    /// geometrically stretched axis 0, 1, 3, 7, 15, ... where each cell is
+    /// twice as large as the previous one
+    fn stretched_axis(n: usize) -> Vec<f32> {
+        (0..n).map(|i| (2_i32.pow(i as u32) - 1) as f32).collect()
+    }
+
+    #[test]
+    /// the cell of a value is the interval of the axis containing it, the
+    /// last cell includes the last coordinate, and values outside of the
+    /// axis are out of bounds
+    fn test_cell_index() {
+        let axis = stretched_axis(6);
+        assert_eq!(cell_index(&axis, 0.0).unwrap(), 0);
+        assert_eq!(cell_index(&axis, 0.5).unwrap(), 0);
+        assert_eq!(cell_index(&axis, 1.0).unwrap(), 1);
+        assert_eq!(cell_index(&axis, 6.9).unwrap(), 2);
+        assert_eq!(cell_index(&axis, 20.0).unwrap(), 4);
+        assert_eq!(cell_index(&axis, 31.0).unwrap(), 4);
+        assert!(matches!(
+            cell_index(&axis, 31.1),
+            Err(Error::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            cell_index(&axis, -0.1),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    /// a depth linear in x and quadratic in y is interpolated exactly in x,
+    /// and the gradient uses the size of the enclosing cell
+    fn test_stretched_grid() {
+        let x = stretched_axis(8);
+        let y = stretched_axis(6);
+        let depth: Vec<f64> = y
+            .iter()
+            .flat_map(|y| {
+                x.iter()
+                    .map(move |x| 10.0 + 0.5 * *x as f64 + (*y as f64).powi(2))
+            })
+            .collect();
+        let bathymetry = VariableGridBathymetry::new(x, y, depth).unwrap();
+
+        // on the grid points
+        assert_eq!(bathymetry.depth(&Point::new(63.0, 15.0)).unwrap(), 266.5);
+        // linear in x within the cell x in [31, 63]
+        assert!((bathymetry.depth(&Point::new(40.0, 0.0)).unwrap() - 30.0).abs() < 1e-4);
+
+        // the cell y in [7, 15] has a slope of (225 - 49) / 8 = 22 in y,
+        // while the cell y in [15, 31] has a slope of (961 - 225) / 16 = 46
+        let (_, gradient) = bathymetry
+            .depth_and_gradient(&Point::new(40.0, 10.0))
+            .unwrap();
+        assert!((gradient.dx() - 0.5).abs() < 1e-6);
+        assert!((gradient.dy() - 22.0).abs() < 1e-4);
+        let (_, gradient) = bathymetry
+            .depth_and_gradient(&Point::new(40.0, 20.0))
+            .unwrap();
+        assert!((gradient.dy() - 46.0).abs() < 1e-4);
+
+        assert!(matches!(
+            bathymetry.depth(&Point::new(128.0, 0.0)),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    /// an axis that is not increasing, or a depth whose length doesn't match
+    /// the axes, is rejected
+    fn test_invalid_axes() {
+        assert!(matches!(
+            VariableGridBathymetry::new(vec![0.0, 2.0, 1.0], vec![0.0, 1.0], vec![1.0; 6]),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            VariableGridBathymetry::new(vec![0.0, 1.0], vec![0.0, 1.0], vec![1.0; 3]),
+            Err(Error::InvalidArgument)
+        ));
+    }
+}
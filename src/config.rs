@@ -0,0 +1,313 @@
+//! Trace rays described by a JSON configuration file
+//!
+//! The configuration gives the bathymetry file, an optional current file,
+//! the initial rays, and the integration parameters, so that rays can be
+//! traced without writing Rust, e.g. from a thin command line wrapper:
+//!
+//! ```json
+//! {
+//!   "bathymetry": {"path": "bathymetry.nc", "depth": "depth"},
+//!   "current": {"path": "current.nc"},
+//!   "rays": [
+//!     {"x": 10.0, "y": 500.0, "kx": 0.05, "ky": 0.0},
+//!     {"x": 10.0, "y": 500.0, "period": 10.0, "direction": 0.5}
+//!   ],
+//!   "end_time": 100.0,
+//!   "step_size": 1.0
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::bathymetry::{BathymetryData, CartesianNetcdf3};
+use crate::current::CartesianCurrent;
+use crate::datatype::{Point, RayState, WaveNumber};
+use crate::error::Result;
+use crate::ray_result::RayResult;
+use crate::scene::Scene;
+
+/// Default name of the x coordinate variable
+fn default_x() -> String {
+    "x".to_string()
+}
+
+/// Default name of the y coordinate variable
+fn default_y() -> String {
+    "y".to_string()
+}
+
+/// Default name of the depth variable
+fn default_depth() -> String {
+    "depth".to_string()
+}
+
+/// Default name of the x component of the current
+fn default_u() -> String {
+    "u".to_string()
+}
+
+/// Default name of the y component of the current
+fn default_v() -> String {
+    "v".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+/// The bathymetry NetCDF3 file and the names of its variables
+struct BathymetryConfig {
+    /// path of the file, relative to the configuration file
+    path: PathBuf,
+    /// name of the x coordinate variable, "x" by default
+    #[serde(default = "default_x")]
+    x: String,
+    /// name of the y coordinate variable, "y" by default
+    #[serde(default = "default_y")]
+    y: String,
+    /// name of the depth variable, "depth" by default
+    #[serde(default = "default_depth")]
+    depth: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+/// The current NetCDF3 file and the names of its variables
+struct CurrentConfig {
+    /// path of the file, relative to the configuration file
+    path: PathBuf,
+    /// name of the x coordinate variable, "x" by default
+    #[serde(default = "default_x")]
+    x: String,
+    /// name of the y coordinate variable, "y" by default
+    #[serde(default = "default_y")]
+    y: String,
+    /// name of the x component of the current, "u" by default
+    #[serde(default = "default_u")]
+    u: String,
+    /// name of the y component of the current, "v" by default
+    #[serde(default = "default_v")]
+    v: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+/// An initial ray, given by its wavenumber or by its period and direction
+enum InitialRayConfig {
+    /// the initial position \[m\] and wavenumber \[1/m\]
+    WaveNumber {
+        /// x coordinate \[m\]
+        x: f64,
+        /// y coordinate \[m\]
+        y: f64,
+        /// x component of the wavenumber \[1/m\]
+        kx: f64,
+        /// y component of the wavenumber \[1/m\]
+        ky: f64,
+    },
+    /// the initial position \[m\], the period \[s\] and the direction \[rad\],
+    /// see `RayState::with_period`
+    Period {
+        /// x coordinate \[m\]
+        x: f64,
+        /// y coordinate \[m\]
+        y: f64,
+        /// wave period \[s\]
+        period: f64,
+        /// direction of propagation \[rad\], counterclockwise from the x axis
+        direction: f64,
+    },
+}
+
+impl InitialRayConfig {
+    /// The initial state of the ray on the given bathymetry
+    fn ray_state(&self, bathymetry: &dyn BathymetryData) -> Result<RayState<f64>> {
+        match *self {
+            InitialRayConfig::WaveNumber { x, y, kx, ky } => {
+                Ok(RayState::new(Point::new(x, y), WaveNumber::new(kx, ky)))
+            }
+            InitialRayConfig::Period {
+                x,
+                y,
+                period,
+                direction,
+            } => RayState::with_period(Point::new(x, y), period, direction, bathymetry),
+        }
+    }
+}
+
+/// Default start time of the integration \[s\]
+fn default_start_time() -> f64 {
+    0.0
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+/// The content of a configuration file
+struct TraceConfig {
+    /// the bathymetry file
+    bathymetry: BathymetryConfig,
+    /// the current file, or none without current
+    #[serde(default)]
+    current: Option<CurrentConfig>,
+    /// the initial rays
+    rays: Vec<InitialRayConfig>,
+    /// the time \[s\] the ray tracing begins, 0 by default
+    #[serde(default = "default_start_time")]
+    start_time: f64,
+    /// the time \[s\] the ray tracing is stopped
+    end_time: f64,
+    /// the change in time \[s\] between integration steps
+    step_size: f64,
+}
+
+#[allow(dead_code)]
+/// Trace the rays described by a JSON configuration file
+///
+/// The paths of the bathymetry and current files are relative to the
+/// directory of the configuration file, unless they are absolute.
+///
+/// # Arguments
+/// `path` : `&Path`
+/// - the path of the JSON configuration file
+///
+/// # Returns
+/// `Result<Vec<Option<RayResult>>>` : the rays in the same order as in the
+/// configuration, where a ray is `None` if there was an error during its
+/// integration, see `ManyRays::trace_rays`.
+///
+/// # Errors
+/// - `Error::IOError` : the configuration file can't be read.
+/// - `Error::JSONError` : the configuration is not valid JSON or misses a
+///   required field.
+/// - errors from `CartesianNetcdf3::open` and `CartesianCurrent::open` if a
+///   file can't be opened.
+/// - errors from `RayState::with_period` if the period of a ray is not
+///   valid at its initial position.
+pub(crate) fn run_from_config(path: &Path) -> Result<Vec<Option<RayResult>>> {
+    let config: TraceConfig = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let bathymetry = &config.bathymetry;
+    let bathymetry_data = CartesianNetcdf3::open(
+        &directory.join(&bathymetry.path),
+        &bathymetry.x,
+        &bathymetry.y,
+        &bathymetry.depth,
+    )?;
+    let mut scene = Scene::new(Arc::new(bathymetry_data));
+    if let Some(current) = &config.current {
+        let current_data = CartesianCurrent::open(
+            &directory.join(&current.path),
+            &current.x,
+            &current.y,
+            &current.u,
+            &current.v,
+        )?;
+        scene = scene.with_current(Arc::new(current_data));
+    }
+
+    let initial_rays = config
+        .rays
+        .iter()
+        .map(|ray| ray.ray_state(scene.bathymetry()))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(scene.trace_many(
+        &initial_rays,
+        config.start_time,
+        config.end_time,
+        config.step_size,
+    ))
+}
+
+#[cfg(test)]
+mod test_config {
+
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use crate::error::Error;
+    use crate::io::utility::{create_netcdf3_bathymetry, create_netcdf3_current};
+    use crate::wave_ray_path::{StateView, TerminationReason};
+
+    use super::run_from_config;
+
+    #[test]
+    /// trace a ray given by its wavenumber and one given by its period, with
+    /// a current, from a sample configuration
+    fn test_run_from_config() {
+        let directory = tempdir().unwrap();
+        create_netcdf3_bathymetry(
+            &directory.path().join("bathymetry.nc"),
+            100,
+            100,
+            10.0,
+            10.0,
+            |_, _| 50.0,
+        );
+        create_netcdf3_current(
+            &directory.path().join("current.nc"),
+            100,
+            100,
+            10.0,
+            10.0,
+            |_, _| (0.0, 0.5),
+        );
+        let config_path = directory.path().join("config.json");
+        fs::write(
+            &config_path,
+            r#"{
+                "bathymetry": {"path": "bathymetry.nc"},
+                "current": {"path": "current.nc"},
+                "rays": [
+                    {"x": 10.0, "y": 500.0, "kx": 0.05, "ky": 0.0},
+                    {"x": 10.0, "y": 500.0, "period": 10.0, "direction": 0.0}
+                ],
+                "end_time": 50.0,
+                "step_size": 1.0
+            }"#,
+        )
+        .unwrap();
+
+        let results = run_from_config(&config_path).unwrap();
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            let result = result.as_ref().unwrap();
+            assert_eq!(
+                result.termination_reason(),
+                Some(TerminationReason::ReachedEndTime)
+            );
+            let (_, last) = result.last_valid().unwrap();
+            assert!(last.x() > 10.0);
+            // the current pushes the ray toward +y
+            assert!(last.y() > 500.0);
+        }
+
+        // the first ray starts from the given wavenumber
+        let first = results[0].as_ref().unwrap();
+        let (_, state) = first.valid_states().next().unwrap();
+        assert_eq!((state.x(), state.y()), (10.0, 500.0));
+        assert_eq!((state.kx(), state.ky()), (0.05, 0.0));
+    }
+
+    #[test]
+    /// a missing file or required field is an error
+    fn test_run_from_config_errors() {
+        let directory = tempdir().unwrap();
+        let config_path = directory.path().join("config.json");
+        assert!(matches!(
+            run_from_config(&config_path),
+            Err(Error::IOError(_))
+        ));
+
+        fs::write(
+            &config_path,
+            r#"{"bathymetry": {"path": "bathymetry.nc"}, "rays": []}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            run_from_config(&config_path),
+            Err(Error::JSONError(_))
+        ));
+    }
+}
@@ -3,13 +3,33 @@
 
 use std::path::Path;
 
-use netcdf3::{DataType, FileReader};
+use netcdf3::FileReader;
 
 use super::CurrentData;
-use crate::datatype::{Current, Gradient, Point};
+use crate::datatype::{Current, CurrentGradient, Point};
 use crate::error::Error;
 use crate::error::Result;
 use crate::interpolator;
+use crate::io::read_var_as_f64;
+
+/// Relative tolerance, as a fraction of the spacing, of the coordinates of
+/// the u and v grids to be considered the same, or staggered by half a cell
+const GRID_TOLERANCE: f64 = 1e-4;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// Location of the u and v components on the grid
+pub(crate) enum Staggering {
+    /// Both components are at the same points. This is the default.
+    #[default]
+    Collocated,
+    /// Arakawa C-grid: u is on the faces of the cells in the x direction and
+    /// v on the faces in the y direction. The x coordinates of u are the
+    /// faces, one more than the cell centers, and its y coordinates are the
+    /// centers, and vice-versa for v. Both components are averaged onto the
+    /// cell centers.
+    ArakawaC,
+}
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -46,14 +66,19 @@ impl CartesianCurrent {
     ///   contains the v data.
     ///
     /// # Returns
-    /// `Self` : `CurrentCartesianFile` the new constructed struct.
+    /// `Result<Self>` : `CurrentCartesianFile` the new constructed struct.
     ///
-    /// # Panics
-    /// Panics if the NetCDF file does not contain the variables `x`, `y`, `u`,
-    /// `v`.
+    /// # Errors
+    /// - `Error::IOError` : the file could not be opened, e.g. it does not
+    ///   exist.
+    /// - `Error::VariableNotFound` : the NetCDF file does not contain one of
+    ///   the variables `x`, `y`, `u`, `v`.
+    /// - `Error::UnsupportedDataType` : one of the variables can't be
+    ///   converted to f64.
+    /// - `Error::ReadError` : the file is not a valid NetCDF3 file.
     ///
     /// # Note
-    /// The variables `x`, `y`, `u`, `v` can be of any type that is in
+    /// The variables `x`, `y`, `u`, `v` can be of any numeric type that is in
     /// `netcdf3::DataType`.
     pub(crate) fn open(
         path: &Path,
@@ -61,154 +86,103 @@ impl CartesianCurrent {
         y_name: &str,
         u_name: &str,
         v_name: &str,
-    ) -> Self {
-        let mut data = FileReader::open(path).unwrap();
-
-        let x_data = data.read_var(x_name).unwrap();
-        let x_data = match x_data.data_type() {
-            DataType::I16 => x_data
-                .get_i16_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I8 => x_data
-                .get_i8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::U8 => x_data
-                .get_u8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I32 => x_data
-                .get_i32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F32 => x_data
-                .get_f32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F64 => x_data.get_f64_into().unwrap(),
-        };
+    ) -> Result<Self> {
+        let mut data = FileReader::open(path)?;
 
-        let y_data = data.read_var(y_name).unwrap();
-        let y_data = match y_data.data_type() {
-            DataType::I16 => y_data
-                .get_i16_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I8 => y_data
-                .get_i8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::U8 => y_data
-                .get_u8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I32 => y_data
-                .get_i32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F32 => y_data
-                .get_f32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F64 => y_data.get_f64_into().unwrap(),
-        };
+        let x_data = read_var_as_f64(&mut data, x_name)?;
+        let y_data = read_var_as_f64(&mut data, y_name)?;
+        let u_data = read_var_as_f64(&mut data, u_name)?;
+        let v_data = read_var_as_f64(&mut data, v_name)?;
 
-        let u_data = data.read_var(u_name).unwrap();
-        let u_data = match u_data.data_type() {
-            DataType::I16 => u_data
-                .get_i16_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I8 => u_data
-                .get_i8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::U8 => u_data
-                .get_u8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I32 => u_data
-                .get_i32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F32 => u_data
-                .get_f32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F64 => u_data.get_f64_into().unwrap(),
-        };
-
-        let v_data = data.read_var(v_name).unwrap();
-        let v_data = match v_data.data_type() {
-            DataType::I16 => v_data
-                .get_i16_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I8 => v_data
-                .get_i8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::U8 => v_data
-                .get_u8_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::I32 => v_data
-                .get_i32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F32 => v_data
-                .get_f32_into()
-                .unwrap()
-                .iter()
-                .map(|x| *x as f64)
-                .collect(),
-            DataType::F64 => v_data.get_f64_into().unwrap(),
-        };
-
-        CartesianCurrent {
+        Ok(CartesianCurrent {
             x_vec: x_data,
             y_vec: y_data,
             u_vec: u_data,
             v_vec: v_data,
+        })
+    }
+
+    /// Create a new `CartesianCurrent` from separate u and v NetCDF files.
+    ///
+    /// # Arguments
+    /// - `u_path` : `&Path` Path to the NetCDF file with the u component.
+    ///
+    /// - `v_path` : `&Path` Path to the NetCDF file with the v component.
+    ///
+    /// - `x_name` : `&str` Name of the x variable in both files.
+    ///
+    /// - `y_name` : `&str` Name of the y variable in both files.
+    ///
+    /// - `u_name` : `&str` Name of the u variable in the u file.
+    ///
+    /// - `v_name` : `&str` Name of the v variable in the v file.
+    ///
+    /// - `staggering` : `Staggering` Location of u and v on the grid. With
+    ///   `Staggering::ArakawaC`, they are averaged onto the cell centers.
+    ///
+    /// # Returns
+    /// `Result<Self>` : the current on the common grid, or on the cell
+    /// centers of a staggered grid.
+    ///
+    /// # Errors
+    /// - same as `open` for each file.
+    /// - `Error::DimensionMismatch` : the number of coordinates of the u and
+    ///   v grids don't match the staggering, or a variable is not defined at
+    ///   every point of its grid.
+    /// - `Error::InvalidArgument` : the coordinates of the u and v grids are
+    ///   not the same or, on a staggered grid, the cell centers are not
+    ///   halfway between the faces.
+    pub(crate) fn open_separate(
+        u_path: &Path,
+        v_path: &Path,
+        x_name: &str,
+        y_name: &str,
+        u_name: &str,
+        v_name: &str,
+        staggering: Staggering,
+    ) -> Result<Self> {
+        let mut u_file = FileReader::open(u_path)?;
+        let u_x = read_var_as_f64(&mut u_file, x_name)?;
+        let u_y = read_var_as_f64(&mut u_file, y_name)?;
+        let u_data = read_var_as_f64(&mut u_file, u_name)?;
+        check_grid_len(&u_x, &u_y, u_data.len())?;
+
+        let mut v_file = FileReader::open(v_path)?;
+        let v_x = read_var_as_f64(&mut v_file, x_name)?;
+        let v_y = read_var_as_f64(&mut v_file, y_name)?;
+        let v_data = read_var_as_f64(&mut v_file, v_name)?;
+        check_grid_len(&v_x, &v_y, v_data.len())?;
+
+        match staggering {
+            Staggering::Collocated => {
+                check_same_axis(&u_x, &v_x)?;
+                check_same_axis(&u_y, &v_y)?;
+                Ok(CartesianCurrent {
+                    x_vec: u_x,
+                    y_vec: u_y,
+                    u_vec: u_data,
+                    v_vec: v_data,
+                })
+            }
+            Staggering::ArakawaC => {
+                // the centers are the x of v and the y of u
+                check_faces(&u_x, &v_x)?;
+                check_faces(&v_y, &u_y)?;
+                let (nx, ny) = (v_x.len(), u_y.len());
+                let u_vec = u_data
+                    .chunks_exact(nx + 1)
+                    .flat_map(|row| row.windows(2).map(|w| 0.5 * (w[0] + w[1])))
+                    .collect();
+                let v_vec = (0..nx * ny)
+                    .map(|index| 0.5 * (v_data[index] + v_data[index + nx]))
+                    .collect();
+                Ok(CartesianCurrent {
+                    x_vec: v_x,
+                    y_vec: u_y,
+                    u_vec,
+                    v_vec,
+                })
+            }
         }
     }
 
@@ -243,13 +217,16 @@ impl CartesianCurrent {
         // will never panic
         let spacing = (array[1] - array[0]).abs();
 
+        // only a point outside of the coordinate range is out of bounds. the
+        // index is clamped, so that a point on the last coordinate is not
+        // rejected due to the rounding of the spacing.
+        if *target < array[0] || *target > array[array.len() - 1] {
+            return Err(Error::IndexOutOfBounds);
+        }
+
         let index = (target - array[0]) / spacing;
 
-        if index < 0.0 || index > (array.len() - 1) as f64 {
-            Err(Error::IndexOutOfBounds)
-        } else {
-            Ok(index)
-        }
+        Ok(index.clamp(0.0, (array.len() - 1) as f64))
     }
 
     /// Returns the nearest (xindex, yindex) point to given (x ,y) point
@@ -280,7 +257,14 @@ impl CartesianCurrent {
     /// `Result<Vec<(usize, usize)>>`: returns a vector of the 4 points
     /// surrounding the target point. The points are in clockwise order starting
     /// with the bottom left point. Or it will return an out of bounds error.
+    ///
+    /// # Errors
+    /// `Error::IndexOutOfBounds` : the point is out of bounds, or an axis has
+    /// less than two points, so there is no cell around the point.
     fn four_corners(&self, point: &Point<f64>) -> Result<Vec<(usize, usize)>> {
+        if self.x_vec.len() < 2 || self.y_vec.len() < 2 {
+            return Err(Error::IndexOutOfBounds);
+        }
         let (xindex, yindex) = self.nearest_point(point)?;
 
         // determine the edges
@@ -427,6 +411,73 @@ impl CartesianCurrent {
     }
 }
 
+/// Check that a variable is defined at every point of its grid
+///
+/// # Errors
+/// `Error::DimensionMismatch` : the variable doesn't have one value per
+/// (x, y) point.
+fn check_grid_len(x: &[f64], y: &[f64], len: usize) -> Result<()> {
+    if len != x.len() * y.len() {
+        return Err(Error::DimensionMismatch {
+            expected: x.len() * y.len(),
+            got: len,
+        });
+    }
+    Ok(())
+}
+
+/// Spacing of the coordinates, used as the scale of `GRID_TOLERANCE`
+fn spacing(v: &[f64]) -> f64 {
+    match v {
+        [first, .., last] => (last - first).abs() / (v.len() - 1) as f64,
+        _ => 1.0,
+    }
+}
+
+/// Check that the coordinates of the u and v grids are the same
+///
+/// # Errors
+/// - `Error::DimensionMismatch` : the axes have different lengths.
+/// - `Error::InvalidArgument` : the coordinates differ by more than
+///   `GRID_TOLERANCE` of the spacing.
+fn check_same_axis(a: &[f64], b: &[f64]) -> Result<()> {
+    if a.len() != b.len() {
+        return Err(Error::DimensionMismatch {
+            expected: a.len(),
+            got: b.len(),
+        });
+    }
+    let tolerance = GRID_TOLERANCE * spacing(a);
+    if a.iter().zip(b).any(|(a, b)| (a - b).abs() > tolerance) {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Check that the centers of a staggered axis are halfway between its faces
+///
+/// # Errors
+/// - `Error::DimensionMismatch` : there isn't one more face than centers.
+/// - `Error::InvalidArgument` : a center is not halfway between the faces
+///   around it, within `GRID_TOLERANCE` of the spacing.
+fn check_faces(faces: &[f64], centers: &[f64]) -> Result<()> {
+    if faces.len() != centers.len() + 1 {
+        return Err(Error::DimensionMismatch {
+            expected: centers.len() + 1,
+            got: faces.len(),
+        });
+    }
+    let tolerance = GRID_TOLERANCE * spacing(faces);
+    if faces
+        .windows(2)
+        .zip(centers)
+        .any(|(w, c)| (0.5 * (w[0] + w[1]) - c).abs() > tolerance)
+    {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
 impl CurrentData for CartesianCurrent {
     /// return the current at the point (x, y)
     ///
@@ -477,8 +528,8 @@ impl CurrentData for CartesianCurrent {
     ///
     /// # Returns
     ///
-    /// `Result<((f64, f64), (f64, f64, f64, f64)), Error>` : the current at the
-    /// point (x, y) and the gradient at the point (x, y) or an error.
+    /// `Result<(Current<f64>, CurrentGradient<f64>), Error>` : the current at
+    /// the point (x, y) and the gradient at the point (x, y) or an error.
     ///
     /// # Errors
     ///
@@ -487,7 +538,7 @@ impl CurrentData for CartesianCurrent {
     fn current_and_gradient(
         &self,
         point: &Point<f64>,
-    ) -> Result<(Current<f64>, (Gradient<f64>, Gradient<f64>))> {
+    ) -> Result<(Current<f64>, CurrentGradient<f64>)> {
         // get the four corners
         let corners = match self.four_corners(point) {
             Ok(corners) => corners,
@@ -537,18 +588,31 @@ impl CurrentData for CartesianCurrent {
 
         Ok((
             Current::new(u as f64, v as f64),
-            (Gradient::new(dudx, dudy), Gradient::new(dvdx, dvdy)),
+            CurrentGradient::new(dudx, dudy, dvdx, dvdy),
         ))
     }
+
+    /// Mean spacing of the x and y coordinates, or `None` if there is a
+    /// single point in either direction.
+    fn grid_spacing(&self) -> Option<(f64, f64)> {
+        let spacing = |v: &[f64]| match v {
+            [first, .., last] => Some((last - first) / (v.len() - 1) as f64),
+            _ => None,
+        };
+        Some((spacing(&self.x_vec)?, spacing(&self.y_vec)?))
+    }
 }
 
 #[cfg(test)]
 mod test_cartesian_file_current {
     use tempfile::NamedTempFile;
 
-    use super::{Current, Gradient, Point};
+    use super::{Current, CurrentGradient, Point};
     use crate::{
-        current::{cartesian_current::CartesianCurrent, CurrentData},
+        current::{
+            cartesian_current::{CartesianCurrent, Staggering},
+            CurrentData,
+        },
         error::Error,
         io::utility::create_netcdf3_current,
     };
@@ -649,11 +713,30 @@ mod test_cartesian_file_current {
 
         // test with f32 and f64
         create_netcdf3_current(&path, 1, 1, 1.0, 1.0, simple_current);
-        let _: CartesianCurrent = CartesianCurrent::open(&path, "x", "y", "u", "v");
+        let _: CartesianCurrent = CartesianCurrent::open(&path, "x", "y", "u", "v").unwrap();
 
         // test with i16, i8, u8, i32
         create_netcdf3_current_iu(&path, 1, 1, 1.0, 1.0);
-        let _: CartesianCurrent = CartesianCurrent::open(&path, "x", "y", "u", "v");
+        let _: CartesianCurrent = CartesianCurrent::open(&path, "x", "y", "u", "v").unwrap();
+    }
+
+    #[test]
+    // a missing file or variable is an error instead of a panic
+    fn test_open_errors() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.into_temp_path();
+        create_netcdf3_current(&path, 3, 3, 1.0, 1.0, simple_current);
+
+        match CartesianCurrent::open(&path, "x", "y", "u", "w") {
+            Err(Error::VariableNotFound { name }) => assert_eq!(name, "w"),
+            _ => panic!("expected VariableNotFound"),
+        }
+
+        let missing = path.with_extension("missing");
+        match CartesianCurrent::open(&missing, "x", "y", "u", "v") {
+            Err(Error::IOError(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+            _ => panic!("expected IOError"),
+        }
     }
 
     #[test]
@@ -665,7 +748,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&temp_path, 101, 51, 500.0, 500.0, simple_current);
 
-        let data = CartesianCurrent::open(&temp_path, "x", "y", "u", "v");
+        let data = CartesianCurrent::open(&temp_path, "x", "y", "u", "v").unwrap();
 
         // in bounds
         assert!(data.nearest(&5499.0, &data.x_vec).unwrap().round() == 11.0);
@@ -678,6 +761,37 @@ mod test_cartesian_file_current {
         assert!((data.nearest(&5500.0, &data.x_vec).unwrap() - 11.0).abs() <= f64::EPSILON);
     }
 
+    #[test]
+    // an empty or single point x axis is an error rather than a panic, since
+    // there is no cell to interpolate in
+    fn test_degenerate_x_axis() {
+        for nx in [0, 1] {
+            let data = CartesianCurrent {
+                x_vec: (0..nx).map(|i| i as f64).collect(),
+                y_vec: vec![0.0, 1.0, 2.0],
+                u_vec: vec![1.0; 3 * nx],
+                v_vec: vec![0.0; 3 * nx],
+            };
+            let point = Point::new(0.0, 0.5);
+            assert!(matches!(data.current(&point), Err(Error::IndexOutOfBounds)));
+            assert!(matches!(
+                data.current_and_gradient(&point),
+                Err(Error::IndexOutOfBounds)
+            ));
+        }
+
+        let empty = CartesianCurrent {
+            x_vec: vec![],
+            y_vec: vec![0.0, 1.0],
+            u_vec: vec![],
+            v_vec: vec![],
+        };
+        assert!(matches!(
+            empty.nearest(&0.0, &empty.x_vec),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
     #[test]
     // test the nearest point function (which returns floating point indexes)
     fn test_nearest_point() {
@@ -687,7 +801,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&temp_path, 101, 51, 500.0, 500.0, simple_current);
 
-        let data = CartesianCurrent::open(&temp_path, "x", "y", "u", "v");
+        let data = CartesianCurrent::open(&temp_path, "x", "y", "u", "v").unwrap();
 
         // in bounds
         assert!(
@@ -728,7 +842,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&temp_path, 101, 51, 500.0, 500.0, simple_current);
 
-        let data = CartesianCurrent::open(&temp_path, "x", "y", "u", "v");
+        let data = CartesianCurrent::open(&temp_path, "x", "y", "u", "v").unwrap();
 
         // check edge cases
 
@@ -832,7 +946,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&path, 101, 51, 500.0, 500.0, simple_current);
 
-        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v");
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
         let corners = data.four_corners(&Point::new(10.0, 10.0)).unwrap();
         let interpolated = data.interpolate(&corners, &(5499.0, 499.0), &data.u_vec);
         assert!(interpolated.unwrap() == 5.0);
@@ -850,7 +964,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&path, 101, 51, 500.0, 500.0, simple_current);
 
-        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v");
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
         let val = data.val_from_arr(&10, &10, &data.u_vec);
         assert!(val.unwrap() == 5.0);
 
@@ -871,7 +985,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&path, 100, 50, 1.0, 1.0, simple_current);
 
-        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v");
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
 
         // check full domain is accurate
         for i in 0..100 {
@@ -893,6 +1007,161 @@ mod test_cartesian_file_current {
         assert!(current.is_err());
     }
 
+    /// create a file with the x and y coordinates and a single variable on
+    /// that grid
+    fn create_netcdf3_var(path: &Path, x: &[f32], y: &[f32], name: &str, values: &[f64]) {
+        use netcdf3::{DataSet, FileWriter, Version};
+        let mut data_set = DataSet::new();
+        data_set.add_fixed_dim("y", y.len()).unwrap();
+        data_set.add_fixed_dim("x", x.len()).unwrap();
+        data_set.add_var_f32("y", &["y"]).unwrap();
+        data_set.add_var_f32("x", &["x"]).unwrap();
+        data_set.add_var_f64(name, &["y", "x"]).unwrap();
+
+        let mut file_writer = FileWriter::open(path).unwrap();
+        file_writer.set_def(&data_set, Version::Classic, 0).unwrap();
+        file_writer.write_var_f32("y", y).unwrap();
+        file_writer.write_var_f32("x", x).unwrap();
+        file_writer.write_var_f64(name, values).unwrap();
+    }
+
+    #[test]
+    // u and v from separate files on the same grid are the same as from a
+    // single file
+    fn test_open_separate() {
+        let combined_path = NamedTempFile::new().unwrap().into_temp_path();
+        let u_path = NamedTempFile::new().unwrap().into_temp_path();
+        let v_path = NamedTempFile::new().unwrap().into_temp_path();
+        create_netcdf3_current(&combined_path, 20, 10, 5.0, 5.0, |x, y| {
+            (0.01 * x as f64, -0.02 * y as f64)
+        });
+        // the other component of each file is not read
+        create_netcdf3_current(&u_path, 20, 10, 5.0, 5.0, |x, _| (0.01 * x as f64, 99.0));
+        create_netcdf3_current(&v_path, 20, 10, 5.0, 5.0, |_, y| (99.0, -0.02 * y as f64));
+
+        let combined = CartesianCurrent::open(&combined_path, "x", "y", "u", "v").unwrap();
+        let separate = CartesianCurrent::open_separate(
+            &u_path,
+            &v_path,
+            "x",
+            "y",
+            "u",
+            "v",
+            Staggering::Collocated,
+        )
+        .unwrap();
+        for (x, y) in [(0.0, 0.0), (12.3, 7.1), (60.0, 22.5), (95.0, 45.0)] {
+            let point = Point::new(x, y);
+            assert_eq!(
+                separate.current_and_gradient(&point).unwrap(),
+                combined.current_and_gradient(&point).unwrap()
+            );
+        }
+
+        // the grids don't match
+        let v_path = NamedTempFile::new().unwrap().into_temp_path();
+        create_netcdf3_current(&v_path, 20, 11, 5.0, 5.0, simple_current);
+        assert!(matches!(
+            CartesianCurrent::open_separate(
+                &u_path,
+                &v_path,
+                "x",
+                "y",
+                "u",
+                "v",
+                Staggering::Collocated
+            ),
+            Err(Error::DimensionMismatch {
+                expected: 10,
+                got: 11
+            })
+        ));
+        create_netcdf3_current(&v_path, 20, 10, 5.0, 6.0, simple_current);
+        assert!(matches!(
+            CartesianCurrent::open_separate(
+                &u_path,
+                &v_path,
+                "x",
+                "y",
+                "u",
+                "v",
+                Staggering::Collocated
+            ),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    // on an Arakawa C-grid, u and v are averaged onto the cell centers, which
+    // is exact for a linear current
+    fn test_open_separate_arakawa_c() {
+        let (nx, ny) = (8, 5);
+        let centers = |n: usize| (0..n).map(|i| 10.0 * i as f32 + 5.0).collect::<Vec<_>>();
+        let faces = |n: usize| (0..=n).map(|i| 10.0 * i as f32).collect::<Vec<_>>();
+
+        // u = 0.1 x on the x faces, v = 0.2 y on the y faces
+        let u_path = NamedTempFile::new().unwrap().into_temp_path();
+        let (x, y) = (faces(nx), centers(ny));
+        let u: Vec<f64> = y
+            .iter()
+            .flat_map(|_| x.iter().map(|x| 0.1 * *x as f64))
+            .collect();
+        create_netcdf3_var(&u_path, &x, &y, "u", &u);
+
+        let v_path = NamedTempFile::new().unwrap().into_temp_path();
+        let (x, y) = (centers(nx), faces(ny));
+        let v: Vec<f64> = y
+            .iter()
+            .flat_map(|y| x.iter().map(move |_| 0.2 * *y as f64))
+            .collect();
+        create_netcdf3_var(&v_path, &x, &y, "v", &v);
+
+        let open = |staggering| {
+            CartesianCurrent::open_separate(&u_path, &v_path, "x", "y", "u", "v", staggering)
+        };
+        let data = open(Staggering::ArakawaC).unwrap();
+        assert_eq!((data.x_vec.len(), data.y_vec.len()), (nx, ny));
+        for (x, y) in [(5.0, 5.0), (23.0, 17.5), (75.0, 45.0)] {
+            let current = data.current(&Point::new(x, y)).unwrap();
+            assert!((current.u() - 0.1 * x).abs() < 1e-5);
+            assert!((current.v() - 0.2 * y).abs() < 1e-5);
+        }
+        // outside of the cell centers
+        assert!(data.current(&Point::new(2.0, 20.0)).is_err());
+
+        // the staggered grids are not the same
+        assert!(matches!(
+            open(Staggering::Collocated),
+            Err(Error::DimensionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    // the current sampled on a grid is in row-major order, and NaN outside
+    // of the domain
+    fn test_sample_grid() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.into_temp_path();
+
+        create_netcdf3_current(&path, 10, 5, 1.0, 1.0, simple_x_gradient);
+
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
+
+        let x = [0.5, 2.5, 100.0];
+        let y = [1.0, 3.0];
+        let (u, v) = data.sample_grid(&x, &y).unwrap();
+        assert_eq!(u.len(), 6);
+        assert_eq!(v.len(), 6);
+
+        // row-major: y is the row and x is the column
+        for (j, _) in y.iter().enumerate() {
+            assert!((u[j * 3] - 0.5).abs() < 1e-12);
+            assert!((v[j * 3 + 1] - 2.5).abs() < 1e-12);
+            assert!(u[j * 3 + 2].is_nan());
+            assert!(v[j * 3 + 2].is_nan());
+        }
+    }
+
     #[test]
     // test the current_and_gradient function
     fn test_current_and_zero_grad() {
@@ -902,7 +1171,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&path, 100, 50, 1.0, 1.0, simple_current);
 
-        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v");
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
 
         // check full domain is accurate
         for i in 0..100 {
@@ -916,7 +1185,7 @@ mod test_cartesian_file_current {
                     current_and_gradient,
                     (
                         Current::new(5.0, 0.0),
-                        (Gradient::new(0.0, 0.0), Gradient::new(0.0, 0.0))
+                        CurrentGradient::new(0.0, 0.0, 0.0, 0.0)
                     )
                 )
             }
@@ -939,7 +1208,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&path, 100, 100, 1.0, 1.0, simple_x_gradient);
 
-        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v");
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
 
         // check full domain is accurate
         for i in 0..100 {
@@ -951,10 +1220,7 @@ mod test_cartesian_file_current {
 
                 assert_eq!(
                     current_and_gradient,
-                    (
-                        Current::new(i, i),
-                        (Gradient::new(1.0, 0.0), Gradient::new(1.0, 0.0))
-                    )
+                    (Current::new(i, i), CurrentGradient::new(1.0, 0.0, 1.0, 0.0))
                 )
             }
         }
@@ -969,7 +1235,7 @@ mod test_cartesian_file_current {
 
         create_netcdf3_current(&path, 100, 100, 1.0, 1.0, simple_y_gradient);
 
-        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v");
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
 
         // check full domain is accurate
         for i in 0..100 {
@@ -981,12 +1247,41 @@ mod test_cartesian_file_current {
 
                 assert_eq!(
                     current_and_gradient,
-                    (
-                        Current::new(j, j),
-                        (Gradient::new(0.0, 1.0), Gradient::new(0.0, 1.0))
-                    )
+                    (Current::new(j, j), CurrentGradient::new(0.0, 1.0, 0.0, 1.0))
                 )
             }
         }
     }
+
+    #[test]
+    // points in the first and last half cells, and on the last coordinate, are
+    // within the domain, while points just outside are not
+    fn test_edge_half_cells() {
+        // create temporary file
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.into_temp_path();
+
+        // the spacing is not exactly representable
+        create_netcdf3_current(&path, 11, 11, 0.1, 0.3, simple_x_gradient);
+
+        let data = CartesianCurrent::open(Path::new(&path), "x", "y", "u", "v").unwrap();
+        let x_last = data.x_vec[10];
+        let y_last = data.y_vec[10];
+
+        for (x, y) in [
+            (0.01, 0.01),
+            (0.04, 1.5),
+            (x_last - 0.04, 1.5),
+            (0.5, y_last - 0.1),
+            (x_last, y_last),
+        ] {
+            let (current, gradient) = data.current_and_gradient(&Point::new(x, y)).unwrap();
+            assert!((current.u() - x).abs() < 1e-5);
+            assert!((gradient.dudx() - 1.0).abs() < 1e-5);
+        }
+
+        assert!(data.current(&Point::new(-0.001, 0.5)).is_err());
+        assert!(data.current(&Point::new(x_last + 0.001, 0.5)).is_err());
+        assert!(data.current(&Point::new(0.5, y_last + 0.001)).is_err());
+    }
 }
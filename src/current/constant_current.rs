@@ -1,6 +1,6 @@
 //! todo
 
-use crate::datatype::{Current, Gradient, Point};
+use crate::datatype::{Current, CurrentGradient, Point};
 use crate::error::Result;
 
 use super::CurrentData;
@@ -9,6 +9,19 @@ use super::CurrentData;
 /// the default current is (u, v) = (0, 0)
 pub(crate) const DEFAULT_CURRENT: ConstantCurrent = ConstantCurrent { u: 0.0, v: 0.0 };
 
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Convention of the direction of a current
+///
+/// The direction is in degrees clockwise from north, i.e. 0 is north and 90
+/// is east.
+pub(crate) enum DirectionConvention {
+    /// oceanographic convention: the direction the current flows toward
+    Toward,
+    /// meteorological convention: the direction the current comes from
+    From,
+}
+
 /// struct representing a constant current field.
 ///
 /// # Properties:
@@ -32,6 +45,36 @@ impl ConstantCurrent {
     pub(crate) fn new(u: f64, v: f64) -> Self {
         ConstantCurrent { u, v }
     }
+
+    /// Construct from the speed and direction of the current
+    ///
+    /// # Arguments
+    /// `speed` : `f64`
+    /// - the speed of the current \[m/s\]
+    ///
+    /// `direction_deg` : `f64`
+    /// - the direction of the current in degrees clockwise from north
+    ///
+    /// `convention` : `DirectionConvention`
+    /// - whether the direction is where the current flows toward or comes from
+    ///
+    /// # Returns
+    /// returns the constructed ConstantCurrent
+    pub(crate) fn from_speed_and_direction(
+        speed: f64,
+        direction_deg: f64,
+        convention: DirectionConvention,
+    ) -> Self {
+        let toward = match convention {
+            DirectionConvention::Toward => direction_deg,
+            DirectionConvention::From => direction_deg + 180.0,
+        }
+        .to_radians();
+        ConstantCurrent {
+            u: speed * toward.sin(),
+            v: speed * toward.cos(),
+        }
+    }
 }
 
 impl CurrentData for ConstantCurrent {
@@ -60,8 +103,8 @@ impl CurrentData for ConstantCurrent {
     /// - `y` : `f64` the y location
     ///
     /// # Returns
-    /// `Result<((f64, f64), (f64, f64, f64, f64)), Error>` : returns the values
-    /// (u, v) and (du/dx, du/dy, dv/dx, dv/dy) or an Error.
+    /// `Result<(Current<f64>, CurrentGradient<f64>), Error>` : returns the
+    /// current (u, v) and its gradient, which is zero, or an Error.
     ///
     /// # Error
     /// The trait definition includes the chance for error. However, the
@@ -69,10 +112,54 @@ impl CurrentData for ConstantCurrent {
     fn current_and_gradient(
         &self,
         _point: &Point<f64>,
-    ) -> Result<(Current<f64>, (Gradient<f64>, Gradient<f64>))> {
+    ) -> Result<(Current<f64>, CurrentGradient<f64>)> {
         Ok((
             Current::new(self.u, self.v),
-            (Gradient::new(0.0, 0.0), Gradient::new(0.0, 0.0)),
+            CurrentGradient::new(0.0, 0.0, 0.0, 0.0),
         ))
     }
+
+    /// the constant current has zero gradient everywhere
+    fn is_uniform(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_constant_current {
+    use super::{ConstantCurrent, DirectionConvention};
+
+    /// compare the components of the current
+    fn assert_components(current: ConstantCurrent, u: f64, v: f64) {
+        assert!(
+            (current.u - u).abs() < 1e-12 && (current.v - v).abs() < 1e-12,
+            "Expected ({}, {}), but got ({}, {})",
+            u,
+            v,
+            current.u,
+            current.v
+        );
+    }
+
+    #[test]
+    /// the direction is where the current flows toward
+    fn test_toward() {
+        let c = |d| ConstantCurrent::from_speed_and_direction(2.0, d, DirectionConvention::Toward);
+        assert_components(c(0.0), 0.0, 2.0);
+        assert_components(c(90.0), 2.0, 0.0);
+        assert_components(c(180.0), 0.0, -2.0);
+        assert_components(c(270.0), -2.0, 0.0);
+        assert_components(c(45.0), 2.0_f64.sqrt(), 2.0_f64.sqrt());
+    }
+
+    #[test]
+    /// the direction is where the current comes from
+    fn test_from() {
+        let c = |d| ConstantCurrent::from_speed_and_direction(2.0, d, DirectionConvention::From);
+        assert_components(c(0.0), 0.0, -2.0);
+        assert_components(c(90.0), -2.0, 0.0);
+        assert_components(c(180.0), 0.0, 2.0);
+        assert_components(c(270.0), 2.0, 0.0);
+        assert_components(c(225.0), 2.0_f64.sqrt(), 2.0_f64.sqrt());
+    }
 }
@@ -0,0 +1,131 @@
+//! Struct used to create and access a current with a constant rate of change.
+
+use derive_builder::Builder;
+
+use super::CurrentData;
+use crate::datatype::{Current, CurrentGradient, Point};
+use crate::error::Result;
+
+#[derive(Builder, Debug, PartialEq)]
+/// A current pseudo-database with a constant rate of change
+///
+/// The current changes linearly in space, so it models a sheared current
+/// analytically, without creating a gridded dataset.
+///
+/// # Arguments
+///
+/// * `u0`: x component of the current [m/s] at ($x_0$, $y_0$).
+/// * `v0`: y component of the current [m/s] at ($x_0$, $y_0$).
+/// * `x0`: `x` coordinate [m] where the current is (`u0`, `v0`).
+/// * `y0`: `y` coordinate [m] where the current is (`u0`, `v0`).
+/// * `dudx`, `dudy`: rate of change of `u` in the x and y directions [1/s].
+/// * `dvdx`, `dvdy`: rate of change of `v` in the x and y directions [1/s].
+///
+/// All of the values default to zero. For example, a shear where `u`
+/// increases 1 cm/s per meter in the y direction:
+///
+/// let current = ConstantChange::builder().dudy(0.01).build().unwrap();
+pub(crate) struct ConstantChange {
+    /// x component of the current at (x0, y0)
+    #[builder(default = "0.0")]
+    u0: f64,
+    /// y component of the current at (x0, y0)
+    #[builder(default = "0.0")]
+    v0: f64,
+    /// reference x
+    #[builder(default = "0.0")]
+    x0: f64,
+    /// reference y
+    #[builder(default = "0.0")]
+    y0: f64,
+    /// rate of change of u with respect to x
+    #[builder(default = "0.0")]
+    dudx: f64,
+    /// rate of change of u with respect to y
+    #[builder(default = "0.0")]
+    dudy: f64,
+    /// rate of change of v with respect to x
+    #[builder(default = "0.0")]
+    dvdx: f64,
+    /// rate of change of v with respect to y
+    #[builder(default = "0.0")]
+    dvdy: f64,
+}
+
+impl CurrentData for ConstantChange {
+    /// Current for a given position (x, y)
+    ///
+    /// Returns NaN when any input is NaN. Since the rate of change is
+    /// constant, there is no concept of boundaries, thus it can't fail as out
+    /// of bounds.
+    fn current(&self, point: &Point<f64>) -> Result<Current<f64>> {
+        let dx = point.x() - self.x0;
+        let dy = point.y() - self.y0;
+        Ok(Current::new(
+            self.u0 + self.dudx * dx + self.dudy * dy,
+            self.v0 + self.dvdx * dx + self.dvdy * dy,
+        ))
+    }
+
+    /// Current and gradient for a given position (x, y)
+    ///
+    /// The gradient is (du/dx, du/dy) and (dv/dx, dv/dy), the same everywhere.
+    fn current_and_gradient(
+        &self,
+        point: &Point<f64>,
+    ) -> Result<(Current<f64>, CurrentGradient<f64>)> {
+        Ok((
+            self.current(point)?,
+            CurrentGradient::new(self.dudx, self.dudy, self.dvdx, self.dvdy),
+        ))
+    }
+}
+
+impl ConstantChange {
+    #[allow(dead_code)]
+    /// create the default `ConstantChangeBuilder` object
+    ///
+    /// For example, `ConstantChange::builder().u0(0.5).dudy(0.01).build().unwrap()`
+    /// builds a current of 0.5 m/s in the x direction at the origin that
+    /// increases 0.01 m/s per meter in the y direction.
+    pub(crate) fn builder() -> ConstantChangeBuilder {
+        ConstantChangeBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod test_constant_change {
+    use super::*;
+
+    #[test]
+    /// the current changes linearly from the reference point
+    fn test_current_and_gradient() {
+        let c = ConstantChange::builder()
+            .u0(1.0)
+            .v0(-1.0)
+            .x0(10.0)
+            .y0(20.0)
+            .dudx(0.1)
+            .dudy(0.2)
+            .dvdx(0.3)
+            .dvdy(0.4)
+            .build()
+            .unwrap();
+
+        let (current, gradient) = c.current_and_gradient(&Point::new(11.0, 22.0)).unwrap();
+        assert!((current.u() - (1.0 + 0.1 + 0.4)).abs() < 1e-12);
+        assert!((current.v() - (-1.0 + 0.3 + 0.8)).abs() < 1e-12);
+        assert_eq!(gradient, CurrentGradient::new(0.1, 0.2, 0.3, 0.4));
+
+        assert!(c.current(&Point::new(f64::NAN, 0.0)).unwrap().u().is_nan());
+    }
+
+    #[test]
+    /// all values default to zero
+    fn build_default() {
+        let c = ConstantChange::builder().build().unwrap();
+        let (current, gradient) = c.current_and_gradient(&Point::new(3.0, 4.0)).unwrap();
+        assert_eq!((*current.u(), *current.v()), (0.0, 0.0));
+        assert_eq!(gradient, CurrentGradient::new(0.0, 0.0, 0.0, 0.0));
+    }
+}
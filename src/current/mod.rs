@@ -2,20 +2,30 @@
 //!
 //! This module contains the following structs that implement the `CurrentData`
 //! trait:
-//! - `ConstantCurrent`
+//! - `CartesianCurrent` - read and access the data stored in a NetCDF3 file.
+//! - `ConstantCurrent` - the same current everywhere.
+//! - `ConstantChange` - a current with a constant rate of change, such as a
+//!   linear shear.
 
-use crate::datatype::{Current, Gradient, Point};
-use crate::error::Result;
+use crate::datatype::{Current, CurrentGradient, Point};
+use crate::error::{Error, Result};
 
 mod cartesian_current;
 mod constant_current;
+mod constant_rate_change_current;
 
 #[allow(unused_imports)]
 pub(super) use cartesian_current::CartesianCurrent;
 #[allow(unused_imports)]
+pub(super) use cartesian_current::Staggering;
+#[allow(unused_imports)]
 pub(super) use constant_current::ConstantCurrent;
 #[allow(unused_imports)]
+pub(super) use constant_current::DirectionConvention;
+#[allow(unused_imports)]
 pub(super) use constant_current::DEFAULT_CURRENT;
+#[allow(unused_imports)]
+pub(super) use constant_rate_change_current::ConstantChange;
 
 /// A trait implementing methods to get current and gradient
 pub(crate) trait CurrentData: Sync {
@@ -23,9 +33,62 @@ pub(crate) trait CurrentData: Sync {
     /// Current (u, v) at the given (x, y)
     fn current(&self, point: &Point<f64>) -> Result<Current<f64>>;
 
-    /// Current (u, v) and the gradient (du/dx, du/dy, dv/dx, dv/dy)
+    /// Current (u, v) and its gradient
     fn current_and_gradient(
         &self,
         point: &Point<f64>,
-    ) -> Result<(Current<f64>, (Gradient<f64>, Gradient<f64>))>;
+    ) -> Result<(Current<f64>, CurrentGradient<f64>)>;
+
+    /// Whether the gradient of the current is zero everywhere
+    ///
+    /// When this is true, the ray equations skip the gradient of the current
+    /// and only call `current`. The default is false, which is always correct.
+    fn is_uniform(&self) -> bool {
+        false
+    }
+
+    /// Grid spacing (dx, dy) \[m\] of gridded data
+    ///
+    /// The default is `None`, for currents defined everywhere by a function.
+    fn grid_spacing(&self) -> Option<(f64, f64)> {
+        None
+    }
+
+    #[allow(dead_code)]
+    /// Sample the current (u, v) on a rectilinear grid
+    ///
+    /// # Arguments
+    /// `x` : `&[f64]`
+    /// - x coordinates of the grid columns
+    ///
+    /// `y` : `&[f64]`
+    /// - y coordinates of the grid rows
+    ///
+    /// # Returns
+    /// `Result<(Vec<f64>, Vec<f64>)>` : u and v flattened in row-major
+    /// order, so that the value at (x\[i\], y\[j\]) is at index
+    /// `j * x.len() + i`. Points outside of the domain are NaN.
+    ///
+    /// # Errors
+    /// Any error from `current` other than `Error::IndexOutOfBounds`.
+    fn sample_grid(&self, x: &[f64], y: &[f64]) -> Result<(Vec<f64>, Vec<f64>)> {
+        let mut u = Vec::with_capacity(x.len() * y.len());
+        let mut v = Vec::with_capacity(x.len() * y.len());
+        for &y in y {
+            for &x in x {
+                match self.current(&Point::new(x, y)) {
+                    Ok(current) => {
+                        u.push(*current.u());
+                        v.push(*current.v());
+                    }
+                    Err(Error::IndexOutOfBounds) => {
+                        u.push(f64::NAN);
+                        v.push(f64::NAN);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok((u, v))
+    }
 }
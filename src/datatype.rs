@@ -1,8 +1,13 @@
 //! # Data types
 
+use serde::{Deserialize, Serialize};
+
+use crate::bathymetry::BathymetryData;
+use crate::dispersion;
+use crate::error::{Error, Result};
 use crate::wave_ray_path::State;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// A point in 2D cartesian space
 ///
 /// A `Point` is composed by `x` and `y`, expected to be in meters.
@@ -37,6 +42,7 @@ impl<T> Point<T> {
 /// A `Coordinate` is composed by `lat` and `lon`, expected to be in decimal
 /// degrees. For instance, the latitude of the North Pole is 90, and a
 /// latitude of -10.5 is equivalent to 10 degrees and 30 minutes South.
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Coordinate<T> {
     lat: T,
     lon: T,
@@ -46,19 +52,61 @@ pub(crate) struct Coordinate<T> {
 impl<T> Coordinate<T> {
     /// Create a new `Coordinate` with the given `lat` and `lon` coordinates.
     ///
-    fn new(lat: T, lon: T) -> Self {
+    pub(crate) fn new(lat: T, lon: T) -> Self {
         Coordinate { lat, lon }
     }
 
-    fn lat(&self) -> &T {
+    pub(crate) fn lat(&self) -> &T {
         &self.lat
     }
 
-    fn lon(&self) -> &T {
+    pub(crate) fn lon(&self) -> &T {
         &self.lon
     }
 }
 
+/// Mean radius of the Earth \[m\]
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq)]
+/// A local tangent plane projection around an origin
+///
+/// The x axis points East and the y axis points North, both in meters from
+/// the origin, on a spherical Earth. The distortion grows with the distance
+/// from the origin, thus it is meant for coastal domains of a few tens of
+/// kilometers.
+pub(crate) struct LocalTangentPlane {
+    /// the geolocation of x = 0, y = 0
+    origin: Coordinate<f64>,
+}
+
+#[allow(dead_code)]
+impl LocalTangentPlane {
+    /// Create a new projection around the given origin
+    pub(crate) fn new(origin: Coordinate<f64>) -> Self {
+        LocalTangentPlane { origin }
+    }
+
+    /// The (x, y) position \[m\] of a geolocation
+    pub(crate) fn to_point(&self, coordinate: &Coordinate<f64>) -> Point<f64> {
+        let lat0 = self.origin.lat.to_radians();
+        Point::new(
+            EARTH_RADIUS * lat0.cos() * (coordinate.lon - self.origin.lon).to_radians(),
+            EARTH_RADIUS * (coordinate.lat - self.origin.lat).to_radians(),
+        )
+    }
+
+    /// The geolocation of an (x, y) position \[m\]
+    pub(crate) fn to_coordinate(&self, point: &Point<f64>) -> Coordinate<f64> {
+        let lat0 = self.origin.lat.to_radians();
+        Coordinate::new(
+            self.origin.lat + (point.y() / EARTH_RADIUS).to_degrees(),
+            self.origin.lon + (point.x() / (EARTH_RADIUS * lat0.cos())).to_degrees(),
+        )
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq)]
 /// The current in a 2D cartesian point
@@ -86,7 +134,7 @@ impl<T> Current<T> {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// A wave number in 2D cartesian space
 pub(crate) struct WaveNumber<T> {
     kx: T,
@@ -112,7 +160,7 @@ impl<T> WaveNumber<T> {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// a ray state is the point and wave number of the ray
 pub(crate) struct RayState<T> {
     // Position in 2D cartesian space.
@@ -127,7 +175,8 @@ impl<T> RayState<T> {
         RayState { point, wave_number }
     }
 
-    fn point(&self) -> &Point<T> {
+    /// get the point of the ray state
+    pub(crate) fn point(&self) -> &Point<T> {
         &self.point
     }
 
@@ -137,6 +186,182 @@ impl<T> RayState<T> {
     }
 }
 
+#[allow(dead_code)]
+impl RayState<f64> {
+    /// create a new `RayState` from the wave period and direction
+    ///
+    /// The wavenumber magnitude is obtained from the dispersion relation at
+    /// the depth of the initial point, so the initial condition is always
+    /// consistent with a real wave.
+    ///
+    /// # Arguments
+    /// `point` : `Point<f64>`
+    /// - the initial position \[m\]
+    ///
+    /// `period` : `f64`
+    /// - the wave period \[s\]
+    ///
+    /// `direction` : `f64`
+    /// - the direction of propagation \[rad\], counterclockwise from the x
+    ///   axis
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry at the initial position
+    ///
+    /// # Returns
+    /// `Result<Self>` : the new `RayState`
+    ///
+    /// # Errors
+    /// - `Error::InvalidInitialCondition` : the period or the depth at the
+    ///   initial point is not positive.
+    /// - errors from `depth_f64` if the point is out of the bathymetry domain.
+    pub(crate) fn with_period(
+        point: Point<f64>,
+        period: f64,
+        direction: f64,
+        bathymetry: &dyn BathymetryData,
+    ) -> Result<Self> {
+        let h = bathymetry.depth_f64(&point)? as f64;
+        let k = wavenumber_from_period(period, h)?;
+        Ok(RayState::new(
+            point,
+            WaveNumber::new(k * direction.cos(), k * direction.sin()),
+        ))
+    }
+
+    /// create a fan of rays launched from the same point
+    ///
+    /// The rays have the same period, so the same wavenumber magnitude, and
+    /// their directions are evenly spaced from `theta_start` to `theta_end`,
+    /// both included.
+    ///
+    /// # Arguments
+    /// `x` : `f64`
+    /// - the x coordinate of the origin of the fan \[m\]
+    ///
+    /// `y` : `f64`
+    /// - the y coordinate of the origin of the fan \[m\]
+    ///
+    /// `period` : `f64`
+    /// - the wave period \[s\]
+    ///
+    /// `depth` : `f64`
+    /// - the depth at the origin of the fan \[m\]
+    ///
+    /// `theta_start` : `f64`
+    /// - the direction of the first ray \[rad\], counterclockwise from the x
+    ///   axis
+    ///
+    /// `theta_end` : `f64`
+    /// - the direction of the last ray \[rad\], counterclockwise from the x
+    ///   axis
+    ///
+    /// `n` : `usize`
+    /// - the number of rays. If `n` is 1, the only ray has the direction
+    ///   `theta_start`.
+    ///
+    /// # Returns
+    /// `Result<Vec<Self>>` : the initial rays, ready to use with `ManyRays`
+    ///
+    /// # Errors
+    /// `Error::InvalidInitialCondition` : the period or the depth is not
+    /// positive.
+    pub(crate) fn fan(
+        x: f64,
+        y: f64,
+        period: f64,
+        depth: f64,
+        theta_start: f64,
+        theta_end: f64,
+        n: usize,
+    ) -> Result<Vec<Self>> {
+        let k = wavenumber_from_period(period, depth)?;
+        let dtheta = if n > 1 {
+            (theta_end - theta_start) / (n - 1) as f64
+        } else {
+            0.0
+        };
+        Ok((0..n)
+            .map(|i| {
+                let theta = theta_start + i as f64 * dtheta;
+                RayState::new(
+                    Point::new(x, y),
+                    WaveNumber::new(k * theta.cos(), k * theta.sin()),
+                )
+            })
+            .collect())
+    }
+
+    /// create rays launched along a wave crest
+    ///
+    /// The rays have the same period, so the same wavenumber magnitude, and
+    /// the same direction, i.e. a plane wave. Their origins are evenly spaced
+    /// along the segment from `start` to `end`, both included. This is the
+    /// usual initial condition for a wave approaching a beach.
+    ///
+    /// # Arguments
+    /// `start` : `(f64, f64)`
+    /// - the (x, y) coordinates of the first ray \[m\]
+    ///
+    /// `end` : `(f64, f64)`
+    /// - the (x, y) coordinates of the last ray \[m\]
+    ///
+    /// `n` : `usize`
+    /// - the number of rays. If `n` is 1, the only ray starts at `start`.
+    ///
+    /// `period` : `f64`
+    /// - the wave period \[s\]
+    ///
+    /// `depth` : `f64`
+    /// - the depth along the crest \[m\]
+    ///
+    /// `incidence_dir` : `f64`
+    /// - the direction of propagation \[rad\], counterclockwise from the x
+    ///   axis. It is usually perpendicular to the segment, but that is not
+    ///   required.
+    ///
+    /// # Returns
+    /// `Result<Vec<Self>>` : the initial rays, ready to use with `ManyRays`
+    ///
+    /// # Errors
+    /// `Error::InvalidInitialCondition` : the period or the depth is not
+    /// positive.
+    pub(crate) fn crest_line(
+        start: (f64, f64),
+        end: (f64, f64),
+        n: usize,
+        period: f64,
+        depth: f64,
+        incidence_dir: f64,
+    ) -> Result<Vec<Self>> {
+        let k = wavenumber_from_period(period, depth)?;
+        let (dx, dy) = if n > 1 {
+            (
+                (end.0 - start.0) / (n - 1) as f64,
+                (end.1 - start.1) / (n - 1) as f64,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        Ok((0..n)
+            .map(|i| {
+                RayState::new(
+                    Point::new(start.0 + i as f64 * dx, start.1 + i as f64 * dy),
+                    WaveNumber::new(k * incidence_dir.cos(), k * incidence_dir.sin()),
+                )
+            })
+            .collect())
+    }
+}
+
+/// Wavenumber magnitude of a wave with the given period at depth `h`
+fn wavenumber_from_period(period: f64, h: f64) -> Result<f64> {
+    let sigma = 2.0 * std::f64::consts::PI / period;
+    dispersion::wavenumber(sigma, h).map_err(|_| {
+        Error::InvalidInitialCondition(format!("no wave with period {} s at depth {} m", period, h))
+    })
+}
+
 impl From<RayState<f64>> for State {
     /// convert mantaray's `RayState` into `State` object used by `ode_solvers`.
     fn from(value: RayState<f64>) -> Self {
@@ -149,6 +374,39 @@ impl From<RayState<f64>> for State {
     }
 }
 
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+/// A `State` with named fields used for serialization
+///
+/// `State` is a nalgebra vector, which is serialized as a nested array. This
+/// struct is serialized as `{"x": .., "y": .., "kx": .., "ky": ..}`, and can
+/// be converted to and from `State`.
+pub(crate) struct StateRecord {
+    x: f64,
+    y: f64,
+    kx: f64,
+    ky: f64,
+}
+
+impl From<State> for StateRecord {
+    /// convert the `State` used by `ode_solvers` into a `StateRecord`
+    fn from(value: State) -> Self {
+        StateRecord {
+            x: value[0],
+            y: value[1],
+            kx: value[2],
+            ky: value[3],
+        }
+    }
+}
+
+impl From<StateRecord> for State {
+    /// convert a `StateRecord` into the `State` used by `ode_solvers`
+    fn from(value: StateRecord) -> Self {
+        State::new(value.x, value.y, value.kx, value.ky)
+    }
+}
+
 // Possible names:
 // - RayPath
 // - RayTrajectory
@@ -220,6 +478,163 @@ mod test_ray {
     }
 }
 
+#[cfg(test)]
+mod test_local_tangent_plane {
+    use super::*;
+
+    #[test]
+    // one degree of latitude is about 111 km, and one degree of longitude is
+    // shorter away from the equator
+    fn test_projection() {
+        let projection = LocalTangentPlane::new(Coordinate::new(60.0, -30.0));
+        assert_eq!(
+            projection.to_point(&Coordinate::new(60.0, -30.0)),
+            Point::new(0.0, 0.0)
+        );
+
+        let point = projection.to_point(&Coordinate::new(61.0, -29.0));
+        assert!((point.y() - 111_195.0).abs() < 1.0);
+        assert!((point.x() - 0.5 * 111_195.0).abs() < 1.0);
+
+        let coordinate = projection.to_coordinate(&Point::new(-1500.0, 2500.0));
+        let round_trip = projection.to_point(&coordinate);
+        assert!((round_trip.x() + 1500.0).abs() < 1e-6);
+        assert!((round_trip.y() - 2500.0).abs() < 1e-6);
+    }
+}
+
+#[cfg(test)]
+mod test_ray_state {
+    use super::*;
+    use crate::bathymetry::{ConstantDepth, ConstantSlope};
+
+    #[test]
+    /// the wavenumber from the period satisfies the dispersion relation
+    fn test_with_period() {
+        let bathymetry = ConstantDepth::new(10.0);
+        let direction = std::f64::consts::FRAC_PI_6;
+        let ray_state =
+            RayState::with_period(Point::new(1.0, 2.0), 8.0, direction, &bathymetry).unwrap();
+
+        assert_eq!(ray_state.point(), &Point::new(1.0, 2.0));
+        let kx = *ray_state.wave_number().kx();
+        let ky = *ray_state.wave_number().ky();
+        let sigma = dispersion::intrinsic_frequency(kx.hypot(ky), 10.0);
+        assert!((sigma - 2.0 * std::f64::consts::PI / 8.0).abs() < 1e-12);
+        assert!((ky.atan2(kx) - direction).abs() < 1e-12);
+    }
+
+    #[test]
+    /// there is no wave on land or with a negative period
+    fn test_with_period_invalid() {
+        let bathymetry = ConstantSlope::builder().build().unwrap();
+        assert!(matches!(
+            RayState::with_period(Point::new(2000.0, 0.0), 8.0, 0.0, &bathymetry),
+            Err(Error::InvalidInitialCondition(_))
+        ));
+        assert!(matches!(
+            RayState::with_period(Point::new(0.0, 0.0), -8.0, 0.0, &bathymetry),
+            Err(Error::InvalidInitialCondition(_))
+        ));
+    }
+
+    #[test]
+    /// the rays of a fan share the origin and wavenumber magnitude, and their
+    /// directions are evenly spaced including both ends
+    fn test_fan() {
+        let (start, end) = (-std::f64::consts::FRAC_PI_4, std::f64::consts::FRAC_PI_4);
+        let fan = RayState::fan(1.0, 2.0, 8.0, 10.0, start, end, 5).unwrap();
+        assert_eq!(fan.len(), 5);
+
+        let expected =
+            RayState::with_period(Point::new(1.0, 2.0), 8.0, start, &ConstantDepth::new(10.0))
+                .unwrap();
+        assert_eq!(fan[0], expected);
+        for (i, ray_state) in fan.iter().enumerate() {
+            assert_eq!(ray_state.point(), &Point::new(1.0, 2.0));
+            let kx = *ray_state.wave_number().kx();
+            let ky = *ray_state.wave_number().ky();
+            let theta = start + i as f64 * std::f64::consts::FRAC_PI_8;
+            assert!((ky.atan2(kx) - theta).abs() < 1e-12);
+        }
+
+        // a single ray points in the start direction
+        let fan = RayState::fan(1.0, 2.0, 8.0, 10.0, start, end, 1).unwrap();
+        assert_eq!(fan, vec![expected]);
+        assert!(RayState::fan(1.0, 2.0, 8.0, 10.0, start, end, 0)
+            .unwrap()
+            .is_empty());
+
+        assert!(matches!(
+            RayState::fan(1.0, 2.0, 8.0, -10.0, start, end, 5),
+            Err(Error::InvalidInitialCondition(_))
+        ));
+    }
+
+    #[test]
+    /// the rays along a crest share the wavenumber and direction, and their
+    /// origins are evenly spaced including both ends
+    fn test_crest_line() {
+        let direction = std::f64::consts::FRAC_PI_2;
+        let rays =
+            RayState::crest_line((-100.0, 0.0), (100.0, 0.0), 5, 8.0, 10.0, direction).unwrap();
+        assert_eq!(rays.len(), 5);
+
+        let expected = RayState::with_period(
+            Point::new(-100.0, 0.0),
+            8.0,
+            direction,
+            &ConstantDepth::new(10.0),
+        )
+        .unwrap();
+        assert_eq!(rays[0], expected);
+        for (i, ray_state) in rays.iter().enumerate() {
+            assert_eq!(ray_state.wave_number(), expected.wave_number());
+            assert_eq!(
+                ray_state.point(),
+                &Point::new(-100.0 + 50.0 * i as f64, 0.0)
+            );
+        }
+
+        let rays = RayState::crest_line((1.0, 2.0), (3.0, 4.0), 1, 8.0, 10.0, 0.0).unwrap();
+        assert_eq!(rays.len(), 1);
+        assert_eq!(rays[0].point(), &Point::new(1.0, 2.0));
+
+        assert!(matches!(
+            RayState::crest_line((1.0, 2.0), (3.0, 4.0), 5, -8.0, 10.0, 0.0),
+            Err(Error::InvalidInitialCondition(_))
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test_serde {
+    use super::*;
+
+    #[test]
+    /// serialize and deserialize the initial conditions of a ray
+    fn test_ray_state_round_trip() {
+        let ray_state = RayState::new(Point::new(1.0, 2.0), WaveNumber::new(0.1, -0.2));
+        let json = serde_json::to_string(&ray_state).unwrap();
+        assert_eq!(
+            json,
+            "{\"point\":{\"x\":1.0,\"y\":2.0},\"wave_number\":{\"kx\":0.1,\"ky\":-0.2}}"
+        );
+        let converted: RayState<f64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(converted, ray_state);
+    }
+
+    #[test]
+    /// serialize and deserialize a `State` with named fields
+    fn test_state_round_trip() {
+        let state = State::new(1.0, 2.0, 0.1, -0.2);
+        let json = serde_json::to_string(&StateRecord::from(state)).unwrap();
+        assert_eq!(json, "{\"x\":1.0,\"y\":2.0,\"kx\":0.1,\"ky\":-0.2}");
+        let converted: StateRecord = serde_json::from_str(&json).unwrap();
+        assert_eq!(State::from(converted), state);
+    }
+}
+
 pub(crate) struct Bundle<T> {
     rays: Vec<Ray<T>>,
 }
@@ -255,3 +670,48 @@ impl<T> Gradient<T> {
         &self.dy
     }
 }
+
+#[derive(Debug, PartialEq)]
+/// The gradient of a current in 2D space
+///
+/// The partial derivatives of both components of the current, named to avoid
+/// confusing, for example, du/dy with dv/dx.
+pub(crate) struct CurrentGradient<T> {
+    dudx: T,
+    dudy: T,
+    dvdx: T,
+    dvdy: T,
+}
+
+impl<T> CurrentGradient<T> {
+    /// create a new `CurrentGradient` from the partial derivatives of the x
+    /// (u) and y (v) components of the current
+    pub(crate) fn new(dudx: T, dudy: T, dvdx: T, dvdy: T) -> Self {
+        CurrentGradient {
+            dudx,
+            dudy,
+            dvdx,
+            dvdy,
+        }
+    }
+
+    /// du/dx, the change of the x component of the current in the x direction
+    pub(crate) fn dudx(&self) -> &T {
+        &self.dudx
+    }
+
+    /// du/dy, the change of the x component of the current in the y direction
+    pub(crate) fn dudy(&self) -> &T {
+        &self.dudy
+    }
+
+    /// dv/dx, the change of the y component of the current in the x direction
+    pub(crate) fn dvdx(&self) -> &T {
+        &self.dvdx
+    }
+
+    /// dv/dy, the change of the y component of the current in the y direction
+    pub(crate) fn dvdy(&self) -> &T {
+        &self.dvdy
+    }
+}
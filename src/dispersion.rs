@@ -0,0 +1,318 @@
+//! Linear wave theory dispersion relation and related quantities.
+//!
+//! These functions do not depend on the bathymetry or current data, and can be
+//! used anywhere the wave properties at a given wavenumber and depth are
+//! needed.
+
+use std::f64::consts::PI;
+
+use crate::error::{Error, Result};
+
+/// constant for gravity
+pub(crate) const G: f64 = 9.8;
+
+/// density of sea water \[kg/m^3\]
+pub(crate) const RHO: f64 = 1025.0;
+
+/// ratio of the wave height to the depth where depth-limited waves break,
+/// from the solitary wave limit of McCowan (1894)
+pub(crate) const BREAKER_INDEX: f64 = 0.78;
+
+/// Intrinsic (relative) angular frequency from the dispersion relation
+///
+/// $\sigma = \sqrt{g k \tanh(k h)}$
+///
+/// # Arguments
+/// `k` : `f64`
+/// - the magnitude of the wavenumber \[m^-1\]
+///
+/// `h` : `f64`
+/// - the depth \[m\]
+///
+/// # Returns
+/// `f64` : the intrinsic angular frequency \[rad/s\]. Note: if `h` or `k` is
+/// negative, this will return `f64::NAN`.
+pub(crate) fn intrinsic_frequency(k: f64, h: f64) -> f64 {
+    (G * k * (k * h).tanh()).sqrt()
+}
+
+/// Magnitude of the group velocity relative to the water
+///
+/// # Arguments
+/// `k` : `f64`
+/// - the magnitude of the wavenumber \[m^-1\] should always be positive.
+///
+/// `h` : `f64`
+/// - the depth \[m\] in this case should be positive.
+///
+/// # Returns
+/// `Result<f64>`
+///
+/// - `Ok(f64)` : the group velocity \[m/s\]. Note: if `h` is less then 0, it
+///   will return `f64::NAN`. This represents the wave have no water to
+///   propagate in.
+///
+/// - `Err(Error::ArgumentOutOfBounds)` : returns this error if k <= 0.
+pub(crate) fn group_velocity(k: f64, h: f64) -> Result<f64> {
+    if h <= 0.0 {
+        return Ok(f64::NAN);
+    }
+    if k <= 0.0 {
+        return Err(Error::ArgumentOutOfBounds);
+    }
+    let cg = (G / 2.0)
+        * (((k * h).tanh() + (k * h) / (k * h).cosh().powi(2)) / (k * G * (k * h).tanh()).sqrt());
+    Ok(cg)
+}
+
+/// Derivative of the intrinsic frequency with respect to the depth
+///
+/// $\frac{\partial \sigma}{\partial h} = \frac{\sigma k}{\sinh(2 k h)}$
+///
+/// at a fixed wavenumber. The bathymetry refracts the waves through this
+/// derivative, since $d\vec{k}/dt = -\frac{\partial \sigma}{\partial h} \nabla h$.
+///
+/// # Arguments
+/// `k` : `f64`
+/// - the magnitude of the wavenumber \[m^-1\]
+///
+/// `h` : `f64`
+/// - the depth \[m\]
+///
+/// # Returns
+/// `f64` : the derivative \[rad/s/m\], which vanishes in deep water. It is
+/// NaN if `h` or `k` is negative.
+pub(crate) fn dsigma_dh(k: f64, h: f64) -> f64 {
+    intrinsic_frequency(k, h) * k / (2.0 * k * h).sinh()
+}
+
+/// Wavenumber magnitude from the intrinsic frequency and depth
+///
+/// Solves the dispersion relation $\sigma^2 = g k \tanh(k h)$ for $k$ with
+/// the Newton-Raphson method. The initial guess is the largest of the deep and
+/// shallow water approximations, both of which are smaller than the solution.
+/// The first iteration may overshoot the root, but the method converges in a
+/// few iterations from any positive guess.
+///
+/// # Arguments
+/// `sigma` : `f64`
+/// - the intrinsic angular frequency \[rad/s\]
+///
+/// `h` : `f64`
+/// - the depth \[m\]
+///
+/// # Returns
+/// `Result<f64>` : the magnitude of the wavenumber \[m^-1\]
+///
+/// # Errors
+/// `Error::ArgumentOutOfBounds` : `sigma` or `h` is not positive.
+pub(crate) fn wavenumber(sigma: f64, h: f64) -> Result<f64> {
+    if sigma.is_nan() || sigma <= 0.0 || h.is_nan() || h <= 0.0 {
+        return Err(Error::ArgumentOutOfBounds);
+    }
+    let mut k = (sigma * sigma / G).max(sigma / (G * h).sqrt());
+    for _ in 0..100 {
+        let tanh = (k * h).tanh();
+        let f = G * k * tanh - sigma * sigma;
+        let dfdk = G * tanh + G * k * h * (1.0 - tanh * tanh);
+        let dk = f / dfdk;
+        k -= dk;
+        if dk.abs() <= k * 1e-14 {
+            break;
+        }
+    }
+    Ok(k)
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Dispersion relation used for the group velocity and the refraction by the
+/// bathymetry
+pub(crate) enum DispersionModel {
+    /// Full linear wave theory, $\sigma = \sqrt{g k \tanh(k h)}$. This is the
+    /// default.
+    #[default]
+    FullLinear,
+    /// Non dispersive shallow water limit, $\sigma = k \sqrt{g h}$, e.g. to
+    /// compare with tsunami ray tracers.
+    ShallowWater,
+    /// Deep water limit, $\sigma = \sqrt{g k}$, where the waves do not feel
+    /// the bottom and are not refracted by the bathymetry.
+    DeepWater,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Water depth regime relative to the wavelength
+pub(crate) enum WaterRegime {
+    /// $h > L / 2$, i.e. $k h > \pi$. The waves do not feel the bottom.
+    Deep,
+    /// $L / 20 \le h \le L / 2$
+    Intermediate,
+    /// $h < L / 20$, i.e. $k h < \pi / 10$. The waves are non dispersive.
+    Shallow,
+}
+
+#[allow(dead_code)]
+/// Classify the water depth regime
+///
+/// # Arguments
+/// `k` : `f64`
+/// - the magnitude of the wavenumber \[m^-1\]
+///
+/// `h` : `f64`
+/// - the depth \[m\]
+///
+/// # Returns
+/// `WaterRegime` : deep if the depth is more than half of the wavelength,
+/// shallow if it is less than a twentieth, intermediate otherwise.
+pub(crate) fn water_regime(k: f64, h: f64) -> WaterRegime {
+    let kh = k * h;
+    if kh > PI {
+        WaterRegime::Deep
+    } else if kh < PI / 10.0 {
+        WaterRegime::Shallow
+    } else {
+        WaterRegime::Intermediate
+    }
+}
+
+#[allow(dead_code)]
+/// Depth of the transition between deep and intermediate water for a period
+///
+/// The depth where $k h = \pi$, i.e. the boundary of `WaterRegime::Deep`.
+/// Since $k h$ is fixed, the dispersion relation gives
+/// $k = \sigma^2 / (g \tanh(\pi))$ directly.
+///
+/// # Arguments
+/// `period` : `f64`
+/// - the wave period \[s\]
+///
+/// # Returns
+/// `f64` : the depth \[m\], slightly less than half of the deep water
+/// wavelength. NaN if the period is not positive.
+pub(crate) fn transition_depth(period: f64) -> f64 {
+    depth_at_kh(period, PI)
+}
+
+#[allow(dead_code)]
+/// Depth of the transition between intermediate and shallow water for a
+/// period
+///
+/// The depth where $k h = \pi / 10$, i.e. the boundary of
+/// `WaterRegime::Shallow`. See `transition_depth`.
+///
+/// # Arguments
+/// `period` : `f64`
+/// - the wave period \[s\]
+///
+/// # Returns
+/// `f64` : the depth \[m\], or NaN if the period is not positive.
+pub(crate) fn shallow_transition_depth(period: f64) -> f64 {
+    depth_at_kh(period, PI / 10.0)
+}
+
+/// Depth where a wave of the given period has the given k h
+fn depth_at_kh(period: f64, kh: f64) -> f64 {
+    if period.is_nan() || period <= 0.0 {
+        return f64::NAN;
+    }
+    let sigma = 2.0 * PI / period;
+    let k = sigma * sigma / (G * kh.tanh());
+    kh / k
+}
+
+#[cfg(test)]
+mod test_dispersion {
+    use super::*;
+
+    #[test]
+    /// in deep water, the intrinsic frequency is sqrt(g k) and the group
+    /// velocity is half of the phase speed
+    fn test_deep_water() {
+        let k = 1.0;
+        let h = 1000.0;
+        assert!((intrinsic_frequency(k, h) - G.sqrt()).abs() < 1e-12);
+        assert!((group_velocity(k, h).unwrap() - G.sqrt() / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    /// in shallow water, the waves are non dispersive and the group velocity
+    /// approaches sqrt(g h)
+    fn test_shallow_water() {
+        let k = 1e-4;
+        let h = 1.0;
+        assert!((intrinsic_frequency(k, h) - k * (G * h).sqrt()).abs() < 1e-10);
+        assert!((group_velocity(k, h).unwrap() - (G * h).sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    /// the derivative with respect to the depth matches a finite difference
+    /// of the intrinsic frequency
+    fn test_dsigma_dh() {
+        for (k, h) in [(0.01, 5.0), (0.05, 20.0), (0.02, 50.0)] {
+            let dh = 1e-4;
+            let expected =
+                (intrinsic_frequency(k, h + dh) - intrinsic_frequency(k, h - dh)) / (2.0 * dh);
+            assert!((dsigma_dh(k, h) - expected).abs() < 1e-6 * expected);
+        }
+        assert!(dsigma_dh(1.0, 1000.0).abs() < 1e-300);
+    }
+
+    #[test]
+    /// no water and invalid wavenumbers
+    fn test_invalid() {
+        assert!(group_velocity(1.0, 0.0).unwrap().is_nan());
+        assert!(group_velocity(0.0, 10.0).is_err());
+        assert!(intrinsic_frequency(1.0, -1.0).is_nan());
+    }
+
+    #[test]
+    /// the wavenumber satisfies the dispersion relation from deep to shallow
+    /// water
+    fn test_wavenumber() {
+        for h in [0.5, 5.0, 50.0, 5000.0] {
+            for period in [2.0, 8.0, 20.0] {
+                let sigma = 2.0 * PI / period;
+                let k = wavenumber(sigma, h).unwrap();
+                assert!(
+                    (intrinsic_frequency(k, h) - sigma).abs() < 1e-12,
+                    "h: {}, period: {}, k: {}",
+                    h,
+                    period,
+                    k
+                );
+            }
+        }
+        assert!(wavenumber(0.0, 10.0).is_err());
+        assert!(wavenumber(1.0, 0.0).is_err());
+        assert!(wavenumber(f64::NAN, 10.0).is_err());
+    }
+
+    #[test]
+    /// the regimes are bounded by h = L / 2 and h = L / 20
+    fn test_water_regime() {
+        let k = 2.0 * PI / 100.0;
+        assert_eq!(water_regime(k, 51.0), WaterRegime::Deep);
+        assert_eq!(water_regime(k, 49.0), WaterRegime::Intermediate);
+        assert_eq!(water_regime(k, 5.1), WaterRegime::Intermediate);
+        assert_eq!(water_regime(k, 4.9), WaterRegime::Shallow);
+    }
+
+    #[test]
+    /// the transition depths of a 10 s wave, from k = sigma^2 / (g tanh(kh))
+    fn test_transition_depth() {
+        let h = transition_depth(10.0);
+        assert!((h - 77.695).abs() < 1e-3, "h: {}", h);
+        let k = wavenumber(2.0 * PI / 10.0, h).unwrap();
+        assert!((k * h - PI).abs() < 1e-10);
+
+        let h = shallow_transition_depth(10.0);
+        assert!((h - 2.3725).abs() < 1e-4, "h: {}", h);
+        let k = wavenumber(2.0 * PI / 10.0, h).unwrap();
+        assert!((k * h - PI / 10.0).abs() < 1e-10);
+
+        assert!(transition_depth(0.0).is_nan());
+        assert!(shallow_transition_depth(-10.0).is_nan());
+    }
+}
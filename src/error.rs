@@ -13,11 +13,60 @@ pub(crate) enum Error {
     /// The argument passed was not a valid option
     InvalidArgument,
 
+    #[error("Dimension mismatch: expected {expected} values, got {got}")]
+    /// The length of a data array does not match the lengths of its
+    /// coordinates, e.g. the depth is not defined at every grid point.
+    DimensionMismatch {
+        /// the expected number of values
+        expected: usize,
+        /// the number of values in the array
+        got: usize,
+    },
+
     #[error("Index passed was out of bounds")]
     /// The index is out of bounds of the array and would panic if attempted to
     /// access array.
     IndexOutOfBounds,
 
+    #[error("Wave blocked by an opposing current")]
+    /// The absolute group velocity in the direction of the wavenumber vector is
+    /// zero or negative, i.e. the wave can no longer propagate against the
+    /// current.
+    Blocked,
+
+    #[error("Reached land")]
+    /// The depth at the point is undefined, because it is on land. Land is
+    /// encoded in the bathymetry data as NaN or as the NetCDF fill value.
+    Land,
+
+    #[error("Invalid initial condition: {0}")]
+    /// The initial condition of a ray does not correspond to a real wave, for
+    /// instance, the depth or the wavenumber is not positive.
+    InvalidInitialCondition(String),
+
+    #[error("Variable not found: {name}")]
+    /// The variable is not defined in the dataset.
+    VariableNotFound {
+        /// the name of the variable
+        name: String,
+    },
+
+    #[error("Could not detect the {0} variable, available variables: {1:?}")]
+    /// The variable could not be detected from the attributes of a dataset.
+    /// Holds the role of the variable (e.g. "depth") and the names of the
+    /// variables available in the dataset.
+    VariableNotDetected(String, Vec<String>),
+
+    #[error("Unsupported data type of variable {name}: {dtype}")]
+    /// The variable could not be converted to a floating point array, e.g.
+    /// its data type is not one of the numeric types supported by netcdf3.
+    UnsupportedDataType {
+        /// the name of the variable
+        name: String,
+        /// the data type of the variable in the file
+        dtype: String,
+    },
+
     #[error("Generic error: {0}")]
     /// Temporary error type. Any undefined error should be eventually
     /// replaced by a permanent type.
@@ -32,8 +81,37 @@ pub(crate) enum Error {
     IntegrationError(#[from] ode_solvers::dop_shared::IntegrationError),
 
     #[error(transparent)]
-    // ReadError from netcdf3
-    ReadError(#[from] netcdf3::error::ReadError),
+    // ReadError from netcdf3, except for a missing variable or an IO error,
+    // which are converted to `VariableNotFound` and `IOError`.
+    ReadError(netcdf3::error::ReadError),
+
+    #[error(transparent)]
+    // Error from netcdf
+    NetCDFError(#[from] netcdf::Error),
+
+    #[error(transparent)]
+    // Error from serde_json while reading a configuration
+    JSONError(#[from] serde_json::Error),
+
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    // Error from arrow while building a table
+    ArrowError(#[from] arrow_schema::ArrowError),
+
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    // Error from parquet while writing a table
+    ParquetError(#[from] parquet::errors::ParquetError),
+}
+
+impl From<netcdf3::error::ReadError> for Error {
+    fn from(error: netcdf3::error::ReadError) -> Self {
+        match error {
+            netcdf3::error::ReadError::VariableNotDefined(name) => Error::VariableNotFound { name },
+            netcdf3::error::ReadError::IOErrorKind(kind) => Error::IOError(kind.into()),
+            error => Error::ReadError(error),
+        }
+    }
 }
 
 pub(crate) type Result<T> = core::result::Result<T, Error>;
@@ -7,6 +7,7 @@ use std::path::Path;
 use std::str;
 
 use ode_solvers::dop_shared::SolverResult;
+use pyo3::exceptions::PyIOError;
 use pyo3::prelude::*;
 
 use crate::bathymetry::CartesianNetcdf3;
@@ -34,8 +35,9 @@ fn single_ray(
     current_filename: String,
 ) -> PyResult<(Vec<(f64, f64, f64, f64, f64)>)> {
     let bathymetry = CartesianNetcdf3::open(Path::new(&bathymetry_filename), "x", "y", "depth")
-        .expect("could not open bathymetry file");
-    let current = CartesianCurrent::open(Path::new(&current_filename), "x", "y", "u", "v");
+        .map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let current = CartesianCurrent::open(Path::new(&current_filename), "x", "y", "u", "v")
+        .map_err(|e| PyIOError::new_err(e.to_string()))?;
     let initial_state = RayState::new(Point::new(x0, y0), WaveNumber::new(kx0, ky0));
     let wave = SingleRay::new(&bathymetry, &current, &initial_state);
     let res = wave.trace_individual(0.0, duration, step_size).unwrap();
@@ -60,8 +62,9 @@ fn ray_tracing(
     current_filename: String,
 ) -> PyResult<(Vec<Vec<(f64, f64, f64, f64, f64)>>)> {
     let bathymetry = CartesianNetcdf3::open(Path::new(&bathymetry_filename), "x", "y", "depth")
-        .expect("could not open bathymetry file");
-    let current = CartesianCurrent::open(Path::new(&current_filename), "x", "y", "u", "v");
+        .map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let current = CartesianCurrent::open(Path::new(&current_filename), "x", "y", "u", "v")
+        .map_err(|e| PyIOError::new_err(e.to_string()))?;
     let init_cond = x0
         .iter()
         .zip(y0.iter())
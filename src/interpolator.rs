@@ -6,7 +6,8 @@ use std::collections::HashMap;
 
 use tracing::trace;
 
-use crate::datatype::Point;
+use crate::bathymetry::BathymetryData;
+use crate::datatype::{Gradient, Point};
 use crate::error::{Error, Result};
 use crate::io::Dataset;
 
@@ -245,7 +246,7 @@ impl LinearFit<f64> {
             ));
         }
         Ok(LinearFit {
-            slope,
+            slope: 1.0 / slope,
             intercept: x[0],
         })
     }
@@ -253,7 +254,7 @@ impl LinearFit<f64> {
 
 struct RegularGrid<'a> {
     // dataset: &'a dyn Dataset,
-    dataset: Box<dyn Dataset + 'a>,
+    dataset: Box<dyn Dataset + Sync + 'a>,
     x_size: usize,
     x_map: LinearFit<f64>,
     y_size: usize,
@@ -271,7 +272,7 @@ impl<'a> RegularGrid<'a> {
         }
     */
     #[allow(dead_code)]
-    fn open(dataset: impl Dataset + 'a, varname_x: &str, varname_y: &str) -> Result<Self> {
+    fn open(dataset: impl Dataset + Sync + 'a, varname_x: &str, varname_y: &str) -> Result<Self> {
         // confirm it is linear
         // Define A & B coefficients
         // get i_size and j_size
@@ -283,12 +284,12 @@ impl<'a> RegularGrid<'a> {
 
         let dimension_order = dataset.dimensions_order(varname_x, varname_y);
 
-        let x_size = dataset.dimension_len(varname_x).unwrap();
+        let x_size = dataset.dimension_len(varname_x)?;
 
-        let x_map = LinearFit::from_fit(dataset.values(varname_x).unwrap())?;
+        let x_map = LinearFit::from_fit(dataset.values(varname_x)?)?;
 
-        let y_size = dataset.dimension_len(varname_y).unwrap();
-        let y_map = LinearFit::from_fit(dataset.values(varname_x).unwrap())?;
+        let y_size = dataset.dimension_len(varname_y)?;
+        let y_map = LinearFit::from_fit(dataset.values(varname_y)?)?;
 
         Ok(Self {
             // dataset: dataset,
@@ -304,16 +305,28 @@ impl<'a> RegularGrid<'a> {
     #[allow(dead_code)]
     /// Get the nearest `varname` value to the given `x` and `y` coordinates
     fn nearest(&self, varname: &str, point: Point<f64>) -> Result<f32> {
-        // Error message if less than 0
-        let i = self.x_map.predict(*point.x()).round() as usize;
-        if i >= self.x_size {
+        let (i, j) = self.index(&point)?;
+        self.value(varname, i, j)
+    }
+
+    /// Index of the grid point nearest to the given `x` and `y` coordinates
+    ///
+    /// # Errors
+    /// `Error::IndexOutOfBounds` : the point is outside of the grid.
+    fn index(&self, point: &Point<f64>) -> Result<(usize, usize)> {
+        let i = self.x_map.predict(*point.x()).round();
+        if !(0.0..self.x_size as f64).contains(&i) {
             return Err(Error::IndexOutOfBounds);
         }
-        let j = self.y_map.predict(*point.y()).round() as usize;
-        if j >= self.y_size {
+        let j = self.y_map.predict(*point.y()).round();
+        if !(0.0..self.y_size as f64).contains(&j) {
             return Err(Error::IndexOutOfBounds);
         }
+        Ok((i as usize, j as usize))
+    }
 
+    /// Get the `varname` value at the `i` (x) and `j` (y) indexes
+    fn value(&self, varname: &str, i: usize, j: usize) -> Result<f32> {
         match self.dimension_order.get(varname) {
             Some(v) => match v.as_str() {
                 "xy" => {
@@ -324,13 +337,139 @@ impl<'a> RegularGrid<'a> {
                     trace!("Assuming dimension order is 'yx'");
                     self.dataset.get_variable(varname, j, i)
                 }
-                _ => return Err(Error::Undefined("Dimension order not found".to_string())),
+                _ => Err(Error::Undefined("Dimension order not found".to_string())),
             },
-            _ => return Err(Error::Undefined("Variable not found".to_string())),
+            _ => Err(Error::Undefined("Variable not found".to_string())),
         }
     }
 }
 
+/// A regular grid rotated with respect to the physical frame
+///
+/// Some swath bathymetry products are regular in a frame rotated with respect
+/// to the cartesian coordinates used for the ray tracing. The `x` and `y`
+/// variables of the dataset are the coordinates in the grid frame, whose
+/// origin is at `origin` in the physical frame and whose x axis is rotated
+/// counterclockwise by `angle` from the physical x axis.
+pub(crate) struct RotatedRegularGrid<'a> {
+    /// the grid in its own frame
+    grid: RegularGrid<'a>,
+    /// name of the depth variable
+    depth_name: String,
+    /// location of the origin of the grid frame in the physical frame
+    origin: Point<f64>,
+    /// cosine of the rotation angle
+    cos: f64,
+    /// sine of the rotation angle
+    sin: f64,
+}
+
+#[allow(dead_code)]
+impl<'a> RotatedRegularGrid<'a> {
+    /// Open a rotated regular grid
+    ///
+    /// # Arguments
+    /// `dataset` : `impl Dataset`
+    /// - the dataset with the grid coordinates and the depth
+    ///
+    /// `varname_x` : `&str`
+    /// - name of the x coordinate in the grid frame
+    ///
+    /// `varname_y` : `&str`
+    /// - name of the y coordinate in the grid frame
+    ///
+    /// `depth_name` : `&str`
+    /// - name of the depth variable
+    ///
+    /// `origin` : `Point<f64>`
+    /// - location of the origin of the grid frame in the physical frame \[m\]
+    ///
+    /// `angle` : `f64`
+    /// - counterclockwise rotation of the grid x axis from the physical x axis
+    ///   \[rad\]
+    ///
+    /// # Returns
+    /// `Result<Self>` : the rotated grid
+    ///
+    /// # Errors
+    /// `Error::Undefined` : the coordinates are not regularly spaced.
+    pub(crate) fn open(
+        dataset: impl Dataset + Sync + 'a,
+        varname_x: &str,
+        varname_y: &str,
+        depth_name: &str,
+        origin: Point<f64>,
+        angle: f64,
+    ) -> Result<Self> {
+        Ok(RotatedRegularGrid {
+            grid: RegularGrid::open(dataset, varname_x, varname_y)?,
+            depth_name: depth_name.to_string(),
+            origin,
+            cos: angle.cos(),
+            sin: angle.sin(),
+        })
+    }
+
+    /// Convert a point in the physical frame to the grid frame
+    fn to_grid_frame(&self, point: &Point<f64>) -> Point<f64> {
+        let dx = point.x() - self.origin.x();
+        let dy = point.y() - self.origin.y();
+        Point::new(
+            self.cos * dx + self.sin * dy,
+            -self.sin * dx + self.cos * dy,
+        )
+    }
+
+    /// Index of the grid point nearest to the given point in the physical
+    /// frame
+    ///
+    /// # Errors
+    /// `Error::IndexOutOfBounds` : the point is outside of the grid.
+    pub(crate) fn index(&self, point: &Point<f64>) -> Result<(usize, usize)> {
+        self.grid.index(&self.to_grid_frame(point))
+    }
+
+    /// Derivative of the depth between the grid points `(i0, j0)` and
+    /// `(i1, j1)`, which are `distance` apart in the grid frame
+    fn derivative(
+        &self,
+        (i0, j0): (usize, usize),
+        (i1, j1): (usize, usize),
+        distance: f64,
+    ) -> Result<f64> {
+        if distance == 0.0 {
+            return Ok(0.0);
+        }
+        let h0 = self.grid.value(&self.depth_name, i0, j0)?;
+        let h1 = self.grid.value(&self.depth_name, i1, j1)?;
+        Ok((h1 - h0) as f64 / distance)
+    }
+}
+
+impl BathymetryData for RotatedRegularGrid<'_> {
+    fn depth(&self, point: &Point<f32>) -> Result<f32> {
+        let (i, j) = self.index(&Point::new(*point.x() as f64, *point.y() as f64))?;
+        self.grid.value(&self.depth_name, i, j)
+    }
+
+    fn depth_and_gradient(&self, point: &Point<f32>) -> Result<(f32, Gradient<f32>)> {
+        let (i, j) = self.index(&Point::new(*point.x() as f64, *point.y() as f64))?;
+        let depth = self.grid.value(&self.depth_name, i, j)?;
+
+        // central differences in the interior and one sided at the edges
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.grid.x_size - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.grid.y_size - 1));
+        let dhdxr = self.derivative((i0, j), (i1, j), (i1 - i0) as f64 / self.grid.x_map.slope)?;
+        let dhdyr = self.derivative((i, j0), (i, j1), (j1 - j0) as f64 / self.grid.y_map.slope)?;
+
+        // rotate the gradient from the grid frame back to the physical frame
+        let dhdx = self.cos * dhdxr - self.sin * dhdyr;
+        let dhdy = self.sin * dhdxr + self.cos * dhdyr;
+
+        Ok((depth, Gradient::new(dhdx as f32, dhdy as f32)))
+    }
+}
+
 /*
 
 impl<T: num_traits::float::Float> RegularGrid<T> {
@@ -430,3 +569,112 @@ mod test_regulargrid {
     }
 }
 */
+
+#[cfg(test)]
+mod test_rotated_regular_grid {
+    use super::*;
+
+    /// a grid with x = 0, 10, ..., 100 and y = 0, 20, ..., 100 in the grid
+    /// frame, where the depth is 10 * i + j for the indexes i and j.
+    struct MockGrid;
+
+    impl Dataset for MockGrid {
+        fn dimension_len(&self, name: &str) -> Result<usize> {
+            Ok(if name == "x" { 11 } else { 6 })
+        }
+
+        fn varnames(&self) -> Vec<String> {
+            vec!["x".to_string(), "y".to_string(), "depth".to_string()]
+        }
+
+        fn values(&self, name: &str) -> Result<ndarray::ArrayD<f64>> {
+            let step = if name == "x" { 10.0 } else { 20.0 };
+            let n = self.dimension_len(name)?;
+            Ok(ndarray::Array1::from_iter((0..n).map(|i| i as f64 * step)).into_dyn())
+        }
+
+        fn get_variable(&self, _name: &str, row: usize, column: usize) -> Result<f32> {
+            // stored with the dimension order (y, x)
+            Ok(10.0 * column as f32 + row as f32)
+        }
+
+        fn dimensions_order(&self, _varname_x: &str, _varname_y: &str) -> HashMap<String, String> {
+            HashMap::from([("depth".to_string(), "yx".to_string())])
+        }
+    }
+
+    /// physical coordinates of the grid frame point (xr, yr)
+    fn physical(xr: f64, yr: f64) -> Point<f32> {
+        let (sin, cos) = 30_f64.to_radians().sin_cos();
+        Point::new(
+            (100.0 + cos * xr - sin * yr) as f32,
+            (50.0 + sin * xr + cos * yr) as f32,
+        )
+    }
+
+    fn open() -> RotatedRegularGrid<'static> {
+        RotatedRegularGrid::open(
+            MockGrid,
+            "x",
+            "y",
+            "depth",
+            Point::new(100.0, 50.0),
+            30_f64.to_radians(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    /// the unrotated grid maps x and y to their own indexes, with the
+    /// inverse of the spacing, and rejects points before the first node
+    fn test_regular_grid_index() {
+        let grid = RegularGrid::open(MockGrid, "x", "y").unwrap();
+        assert_eq!(grid.index(&Point::new(30.0, 40.0)).unwrap(), (3, 2));
+        assert_eq!(grid.index(&Point::new(34.0, 49.0)).unwrap(), (3, 2));
+        assert_eq!(grid.index(&Point::new(100.0, 100.0)).unwrap(), (10, 5));
+        assert_eq!(grid.nearest("depth", Point::new(30.0, 40.0)).unwrap(), 32.0);
+
+        assert!(matches!(
+            grid.index(&Point::new(-10.0, 40.0)),
+            Err(Error::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            grid.index(&Point::new(30.0, 120.0)),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    /// a physical point maps to the expected cell of a grid rotated 30
+    /// degrees
+    fn test_depth() {
+        let grid = open();
+        assert_eq!(grid.depth(&physical(30.0, 40.0)).unwrap(), 32.0);
+        assert_eq!(grid.depth(&physical(34.0, 36.0)).unwrap(), 32.0);
+        assert_eq!(grid.depth(&physical(100.0, 100.0)).unwrap(), 105.0);
+
+        // inside the bounding box of the coordinates, but outside the grid
+        assert!(matches!(
+            grid.depth(&Point::new(80.0, 70.0)),
+            Err(Error::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            grid.depth(&physical(120.0, 40.0)),
+            Err(Error::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    /// the depth increases 10 m per 10 m along the grid x axis and 1 m per 20
+    /// m along the grid y axis, which is rotated to the physical frame
+    fn test_gradient() {
+        let grid = open();
+        let (sin, cos) = 30_f64.to_radians().sin_cos();
+        let (dhdxr, dhdyr) = (1.0, 0.05);
+        for (xr, yr) in [(30.0, 40.0), (0.0, 0.0), (100.0, 100.0)] {
+            let (_, gradient) = grid.depth_and_gradient(&physical(xr, yr)).unwrap();
+            assert!((*gradient.dx() as f64 - (cos * dhdxr - sin * dhdyr)).abs() < 1e-6);
+            assert!((*gradient.dy() as f64 - (sin * dhdxr + cos * dhdyr)).abs() < 1e-6);
+        }
+    }
+}
@@ -2,14 +2,16 @@
 //!
 //! Data types supported:
 //! - netcdf4: reading bathymetry file
-//! - netcdf3: creating files
+//! - netcdf3: reading variables of any numeric type, creating files
 
 mod netcdf;
 pub(crate) mod utility;
 
 use std::collections::HashMap;
 
-use crate::error::Result;
+use netcdf3::{DataType, DataVector, FileReader};
+
+use crate::error::{Error, Result};
 
 pub(crate) trait Dataset {
     /// Get the length of a dimension
@@ -29,3 +31,152 @@ pub(crate) trait Dataset {
     /// Get the order of the dimensions for a variable
     fn dimensions_order(&self, varname_x: &str, varname_y: &str) -> HashMap<String, String>;
 }
+
+/// Read a variable from a netcdf3 file converting it to f64
+///
+/// # Arguments
+/// `reader` : `&mut FileReader`
+/// - the opened netcdf3 file
+///
+/// `name` : `&str`
+/// - the name of the variable
+///
+/// # Returns
+/// `Result<Vec<f64>>` : the flattened values of the variable
+///
+/// # Errors
+/// - `Error::VariableNotFound` : the variable is not in the file.
+/// - `Error::UnsupportedDataType` : the values can't be converted to f64.
+/// - `Error::ReadError` : any other error reading the file.
+pub(crate) fn read_var_as_f64(reader: &mut FileReader, name: &str) -> Result<Vec<f64>> {
+    let var = reader.read_var(name)?;
+    vector_as_f64(var, name)
+}
+
+/// Read a variable from a netcdf3 file converting it to f32
+///
+/// Same as `read_var_as_f64`, but f32 variables are not promoted, which
+/// avoids a copy of large grids stored in single precision.
+///
+/// # Arguments
+/// `reader` : `&mut FileReader`
+/// - the opened netcdf3 file
+///
+/// `name` : `&str`
+/// - the name of the variable
+///
+/// # Returns
+/// `Result<Vec<f32>>` : the flattened values of the variable
+///
+/// # Errors
+/// Same as `read_var_as_f64`.
+pub(crate) fn read_var_as_f32(reader: &mut FileReader, name: &str) -> Result<Vec<f32>> {
+    let var = reader.read_var(name)?;
+    if var.data_type() == DataType::F32 {
+        return var
+            .get_f32_into()
+            .map_err(|var| unsupported_data_type(name, &var));
+    }
+    Ok(vector_as_f64(var, name)?
+        .into_iter()
+        .map(|x| x as f32)
+        .collect())
+}
+
+/// Convert the values of a variable to f64
+fn vector_as_f64(var: DataVector, name: &str) -> Result<Vec<f64>> {
+    let values = match var.data_type() {
+        DataType::I8 => var
+            .get_i8_into()
+            .map(|v| v.into_iter().map(f64::from).collect()),
+        DataType::U8 => var
+            .get_u8_into()
+            .map(|v| v.into_iter().map(f64::from).collect()),
+        DataType::I16 => var
+            .get_i16_into()
+            .map(|v| v.into_iter().map(f64::from).collect()),
+        DataType::I32 => var
+            .get_i32_into()
+            .map(|v| v.into_iter().map(f64::from).collect()),
+        DataType::F32 => var
+            .get_f32_into()
+            .map(|v| v.into_iter().map(f64::from).collect()),
+        DataType::F64 => var.get_f64_into(),
+    };
+    values.map_err(|var| unsupported_data_type(name, &var))
+}
+
+/// Error of a variable that can't be converted
+fn unsupported_data_type(name: &str, var: &DataVector) -> Error {
+    Error::UnsupportedDataType {
+        name: name.to_string(),
+        dtype: format!("{:?}", var.data_type()),
+    }
+}
+
+#[cfg(test)]
+mod test_read_var {
+    use netcdf3::{DataSet, DataVector, FileReader, FileWriter, Version};
+    use tempfile::NamedTempFile;
+
+    use super::{read_var_as_f32, read_var_as_f64, unsupported_data_type};
+    use crate::error::Error;
+
+    #[test]
+    /// every numeric type is converted, and a missing variable is an error
+    fn test_read_var_types() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let path = temp_file.into_temp_path();
+
+        let mut data_set = DataSet::new();
+        data_set.add_fixed_dim("n", 3).unwrap();
+        data_set.add_var_i8("a", &["n"]).unwrap();
+        data_set.add_var_i32("b", &["n"]).unwrap();
+        data_set.add_var_f32("c", &["n"]).unwrap();
+        let mut writer = FileWriter::open(&path).unwrap();
+        writer.set_def(&data_set, Version::Classic, 0).unwrap();
+        writer.write_var_i8("a", &[-1, 0, 1]).unwrap();
+        writer.write_var_i32("b", &[-100_000, 0, 100_000]).unwrap();
+        writer.write_var_f32("c", &[0.5, 1.5, 2.5]).unwrap();
+        writer.close().unwrap();
+
+        let mut reader = FileReader::open(&path).unwrap();
+        assert_eq!(
+            read_var_as_f64(&mut reader, "a").unwrap(),
+            vec![-1.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            read_var_as_f64(&mut reader, "b").unwrap(),
+            vec![-100_000.0, 0.0, 100_000.0]
+        );
+        assert_eq!(
+            read_var_as_f64(&mut reader, "c").unwrap(),
+            vec![0.5, 1.5, 2.5]
+        );
+        assert_eq!(
+            read_var_as_f32(&mut reader, "a").unwrap(),
+            vec![-1.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            read_var_as_f32(&mut reader, "c").unwrap(),
+            vec![0.5, 1.5, 2.5]
+        );
+
+        match read_var_as_f64(&mut reader, "d") {
+            Err(Error::VariableNotFound { name }) => assert_eq!(name, "d"),
+            _ => panic!("expected VariableNotFound"),
+        }
+    }
+
+    #[test]
+    /// the error of an unsupported variable holds its name and data type
+    fn test_unsupported_data_type() {
+        match unsupported_data_type("depth", &DataVector::I8(vec![])) {
+            Error::UnsupportedDataType { name, dtype } => {
+                assert_eq!(name, "depth");
+                assert_eq!(dtype, "I8");
+            }
+            _ => panic!("expected UnsupportedDataType"),
+        }
+    }
+}
@@ -8,12 +8,19 @@ use std::collections::HashMap;
 use super::Dataset;
 use crate::bathymetry::BathymetryData;
 use crate::datatype::{Gradient, Point};
-use crate::error::Result;
+use crate::error::{Error, Result};
+
+/// Error of a variable missing from the file
+fn not_found(name: &str) -> Error {
+    Error::VariableNotFound {
+        name: name.to_string(),
+    }
+}
 
 /// Implement the Dataset trait for the netcdf::File
 impl Dataset for netcdf::File {
     fn dimension_len(&self, name: &str) -> Result<usize> {
-        Ok(self.dimension_len(name).unwrap())
+        self.dimension_len(name).ok_or_else(|| not_found(name))
     }
 
     #[allow(unreachable_patterns, unused_variables)]
@@ -55,16 +62,18 @@ impl Dataset for netcdf::File {
     }
 
     fn values(&self, name: &str) -> Result<ndarray::ArrayD<f64>> {
-        Ok(self.variable(name).unwrap().get::<f64, _>(..).unwrap())
+        Ok(self
+            .variable(name)
+            .ok_or_else(|| not_found(name))?
+            .get::<f64, _>(..)?)
     }
 
     // Missing get full variable (such as all x values), and get size.
     fn get_variable(&self, name: &str, i: usize, j: usize) -> Result<f32> {
         Ok(self
             .variable(name)
-            .unwrap()
-            .get_value::<f32, _>([i, j])
-            .unwrap())
+            .ok_or_else(|| not_found(name))?
+            .get_value::<f32, _>([i, j])?)
     }
 }
 
@@ -77,32 +86,30 @@ pub(crate) struct BathymetryFromNetCDF {
 
 #[allow(dead_code, deprecated)]
 impl BathymetryFromNetCDF {
-    pub(crate) fn new<P>(file: P, x_name: &str, y_name: &str, depth_name: String) -> Self
+    pub(crate) fn new<P>(file: P, x_name: &str, y_name: &str, depth_name: String) -> Result<Self>
     where
         P: AsRef<std::path::Path>,
     {
-        let file = netcdf::open(&file).unwrap();
+        let file = netcdf::open(&file)?;
 
         let x: Vec<f32> = file
             .variable(x_name)
-            .expect("Could not find variable 'x'")
-            .get::<f32, _>(..)
-            .expect("Could not get value of variable 'x'")
+            .ok_or_else(|| not_found(x_name))?
+            .get::<f32, _>(..)?
             .into_raw_vec();
 
         let y: Vec<f32> = file
             .variable(y_name)
-            .expect("Could not find variable 'y'")
-            .get::<f32, _>(..)
-            .expect("Could not get value of variable 'y'")
+            .ok_or_else(|| not_found(y_name))?
+            .get::<f32, _>(..)?
             .into_raw_vec();
 
-        Self {
+        Ok(Self {
             file,
             x,
             y,
             depth_name,
-        }
+        })
     }
 }
 
@@ -5,6 +5,9 @@
 
 use std::path::Path;
 
+use crate::bathymetry::CartesianNetcdf3;
+use crate::error::Result;
+
 #[allow(dead_code)]
 /// Create a NetCDF3 Bathymetry File
 ///
@@ -34,15 +37,7 @@ pub(crate) fn create_netcdf3_bathymetry(
     y_step: f32,
     depth_fn: fn(f32, f32) -> f64,
 ) {
-    let x_data: Vec<f32> = (0..x_num).map(|x| x as f32 * x_step).collect();
-    let y_data: Vec<f32> = (0..y_num).map(|y| y as f32 * y_step).collect();
-
-    let mut depth_data: Vec<f64> = Vec::new();
-    for y in &y_data {
-        for x in &x_data {
-            depth_data.push(depth_fn(*x, *y));
-        }
-    }
+    let (x_data, y_data, depth_data) = grid_from_fn(x_num, y_num, x_step, y_step, depth_fn);
 
     // most below copied from the docs
     use netcdf3::{DataSet, FileWriter, Version};
@@ -89,6 +84,63 @@ pub(crate) fn create_netcdf3_bathymetry(
     // end of copied from docs
 }
 
+#[allow(dead_code)]
+/// Create a bathymetry from an analytic depth function, in memory
+///
+/// The grid is the same as the file created by `create_netcdf3_bathymetry`,
+/// but no file is needed.
+///
+/// # Arguments
+/// `x_num` : `usize` the number of points in the x direction
+///
+/// `y_num` : `usize` the number of points in the y direction
+///
+/// `x_step` : `f32` the step size distance between points in the x direction
+///
+/// `y_step` : `f32` the step size distance between points in the y direction
+///
+/// `depth_fn` : `fn(f32,f32) -> f64` a function that maps each (x,y) input to
+/// the depth, h, at that point.
+///
+/// # Returns
+/// `Result<CartesianNetcdf3>` : the bathymetry or an error.
+///
+/// # Example
+/// Create a bathymetry with a constant depth of 100 m.
+///
+/// let bathymetry = bathymetry_from_fn(10, 10, 100.0, 100.0, |_, _| 100.0).unwrap();
+pub(crate) fn bathymetry_from_fn(
+    x_num: usize,
+    y_num: usize,
+    x_step: f32,
+    y_step: f32,
+    depth_fn: fn(f32, f32) -> f64,
+) -> Result<CartesianNetcdf3> {
+    let (x, y, depth) = grid_from_fn(x_num, y_num, x_step, y_step, depth_fn);
+    CartesianNetcdf3::from_arrays(x, y, depth)
+}
+
+/// The x and y coordinates starting at 0, and the flattened depth of
+/// `depth_fn` at each (x, y), where the row is y and the column is x.
+fn grid_from_fn(
+    x_num: usize,
+    y_num: usize,
+    x_step: f32,
+    y_step: f32,
+    depth_fn: fn(f32, f32) -> f64,
+) -> (Vec<f32>, Vec<f32>, Vec<f64>) {
+    let x_data: Vec<f32> = (0..x_num).map(|x| x as f32 * x_step).collect();
+    let y_data: Vec<f32> = (0..y_num).map(|y| y as f32 * y_step).collect();
+
+    let mut depth_data = Vec::with_capacity(x_num * y_num);
+    for y in &y_data {
+        for x in &x_data {
+            depth_data.push(depth_fn(*x, *y));
+        }
+    }
+    (x_data, y_data, depth_data)
+}
+
 #[allow(dead_code)]
 /// Create a NetCDF3 current snapshot (no time)
 ///
@@ -24,14 +24,20 @@
 #![deny(missing_docs)]
 
 mod bathymetry; 
+mod config;
 mod current;
 mod datatype;
+mod dispersion;
 mod error;
 mod ffi;
 mod interpolator;
 mod io;
+mod presets;
 mod ray;
+mod ray_bundle;
 mod ray_result;
+mod scene;
+mod spectral;
 #[cfg(test)]
 mod tests;
 /// cbindgen:ignore
@@ -40,4 +46,8 @@ mod wave_ray_path;
 #[allow(unused_imports)]
 use datatype::{Coordinate, Current, Point};
 #[allow(unused_imports)]
+pub(crate) use ray::trace_ray;
+#[allow(unused_imports)]
 pub(crate) use wave_ray_path::State;
+#[allow(unused_imports)]
+pub(crate) use wave_ray_path::{StateView, KX_INDEX, KY_INDEX, X_INDEX, Y_INDEX};
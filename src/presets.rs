@@ -0,0 +1,105 @@
+//! Ready-made bathymetry and current for common setups
+//!
+//! Most of the tests, and many of the use cases, trace rays in deep water,
+//! i.e. without any effect of the bathymetry, with or without a uniform
+//! current. A `Preset` owns that bathymetry and current, so that it only takes
+//! one line to create the `WaveRayPath` or to pass the data to `SingleRay` or
+//! `ManyRays`.
+//!
+//! # Example
+//! let preset = Preset::deep_water_with_current(0.5, 0.0);
+//! let system = preset.wave_ray_path();
+//! let ray = SingleRay::new(preset.bathymetry(), preset.current(), &initial_ray);
+
+use crate::bathymetry::ConstantDepth;
+use crate::current::ConstantCurrent;
+use crate::wave_ray_path::WaveRayPath;
+
+/// Depth \[m\] of the deep water presets
+///
+/// Any wave shorter than about 2 km, i.e. k > 0.003 m^-1, is in deep water,
+/// since kh > pi.
+pub(crate) const DEEP_WATER_DEPTH: f32 = 1000.0;
+
+/// A constant depth and a uniform current
+pub(crate) struct Preset {
+    /// the constant depth
+    bathymetry: ConstantDepth,
+    /// the uniform current
+    current: ConstantCurrent,
+}
+
+#[allow(dead_code)]
+impl Preset {
+    /// Deep water without current, the canonical "no bathymetry" baseline
+    ///
+    /// # Returns
+    /// `Self` : a constant depth of `DEEP_WATER_DEPTH` and zero current
+    pub(crate) fn deep_water() -> Self {
+        Self::deep_water_with_current(0.0, 0.0)
+    }
+
+    /// Deep water with a uniform current
+    ///
+    /// # Arguments
+    /// `u` : `f64`
+    /// - x component of the current \[m/s\]
+    ///
+    /// `v` : `f64`
+    /// - y component of the current \[m/s\]
+    ///
+    /// # Returns
+    /// `Self` : a constant depth of `DEEP_WATER_DEPTH` and the current (u, v)
+    pub(crate) fn deep_water_with_current(u: f64, v: f64) -> Self {
+        Preset {
+            bathymetry: ConstantDepth::new(DEEP_WATER_DEPTH),
+            current: ConstantCurrent::new(u, v),
+        }
+    }
+
+    /// The bathymetry of the preset
+    pub(crate) fn bathymetry(&self) -> &ConstantDepth {
+        &self.bathymetry
+    }
+
+    /// The current of the preset
+    pub(crate) fn current(&self) -> &ConstantCurrent {
+        &self.current
+    }
+
+    /// The `WaveRayPath` with the bathymetry and current of the preset
+    pub(crate) fn wave_ray_path(&self) -> WaveRayPath<'_> {
+        WaveRayPath::new(&self.bathymetry, &self.current)
+    }
+}
+
+#[cfg(test)]
+mod test_presets {
+    use super::Preset;
+    use crate::bathymetry::BathymetryData;
+    use crate::current::CurrentData;
+    use crate::datatype::Point;
+
+    #[test]
+    /// the deep water group velocity is half of the phase speed, plus the
+    /// current
+    fn test_deep_water() {
+        let preset = Preset::deep_water();
+        assert_eq!(
+            preset.bathymetry().depth(&Point::new(0.0, 0.0)).unwrap(),
+            1000.0
+        );
+        let (dxdt, dydt, dkxdt, dkydt) =
+            preset.wave_ray_path().odes(&0.0, &0.0, &0.1, &0.0).unwrap();
+        let cg = 0.5 * (9.8_f64 / 0.1).sqrt();
+        assert!((dxdt - cg).abs() < 1e-6);
+        assert_eq!((dydt, dkxdt, dkydt), (0.0, 0.0, 0.0));
+
+        let preset = Preset::deep_water_with_current(0.5, -0.2);
+        let current = preset.current().current(&Point::new(0.0, 0.0)).unwrap();
+        assert_eq!((*current.u(), *current.v()), (0.5, -0.2));
+        let (dxdt, dydt, _, _) = preset.wave_ray_path().odes(&0.0, &0.0, &0.1, &0.0).unwrap();
+        assert!((dxdt - (cg + 0.5)).abs() < 1e-6);
+        assert!((dydt + 0.2).abs() < 1e-12);
+    }
+}
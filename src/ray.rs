@@ -1,24 +1,169 @@
 //! This module makes it easier to use the Rk4 ray tracing by encapsulating it
 //! with the SingleRay struct
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs::OpenOptions;
 use std::io::{BufWriter, Write};
 use std::path::Path;
+use std::sync::Arc;
 
 use derive_builder::Builder;
 use ode_solvers::dop_shared::SolverResult;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use ode_solvers::Rk4;
 
 use crate::bathymetry::DEFAULT_BATHYMETRY;
 use crate::current::{CurrentData, DEFAULT_CURRENT};
-use crate::datatype::RayState;
+use crate::datatype::{Point, RayState, WaveNumber};
+use crate::ray_result::RayResult;
 use crate::{
-    bathymetry::BathymetryData, error::Result, wave_ray_path::State, wave_ray_path::Time,
+    bathymetry::BathymetryData,
+    dispersion::{G, RHO},
+    error::{Error, Result},
+    wave_ray_path::EnergyRayPath,
+    wave_ray_path::EnergyState,
+    wave_ray_path::FrequencyConservingRayPath,
+    wave_ray_path::ObservedRayPath,
+    wave_ray_path::SpreadingRayPath,
+    wave_ray_path::SpreadingState,
+    wave_ray_path::State,
+    wave_ray_path::StateView,
+    wave_ray_path::StopCondition,
+    wave_ray_path::TerminationReason,
+    wave_ray_path::Time,
     wave_ray_path::WaveRayPath,
 };
 
+/// Largest number of steps of `SingleRay::trace_until`, in case the stop
+/// condition is never met
+const STOP_CONDITION_MAX_STEPS: f64 = 1e6;
+
+/// Largest number of times a step is halved with `IntegrationMode::AutoRefine`
+const MAX_REFINEMENTS: usize = 10;
+
+/// Largest ratio between the bathymetry and current grid spacings considered
+/// compatible by `ManyRays::check_grid_compatibility`
+const GRID_SPACING_RATIO_LIMIT: f64 = 4.0;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// The bathymetry and current grids have incompatible resolutions
+pub(crate) struct GridMismatch {
+    /// the (dx, dy) spacing \[m\] of the bathymetry grid
+    bathymetry_spacing: (f64, f64),
+    /// the (dx, dy) spacing \[m\] of the current grid
+    current_spacing: (f64, f64),
+    /// the largest ratio between the spacings in the x or y direction
+    ratio: f64,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+/// How the wavenumber is integrated along the ray
+pub(crate) enum IntegrationMode {
+    /// Integrate (kx, ky) directly with the ray equations. This is the
+    /// default.
+    #[default]
+    Standard,
+    /// Integrate only the direction of the wavenumber, and solve the
+    /// dispersion relation for its magnitude at each step with the initial
+    /// frequency, so that the frequency is conserved exactly. Only valid
+    /// without a current, see `FrequencyConservingRayPath`.
+    FrequencyConserving,
+    /// Integrate (kx, ky) directly, but halve a step, up to
+    /// `MAX_REFINEMENTS` times, while the bathymetry it crosses is not
+    /// resolved, i.e. while the mean slope of the bottom between the ends of
+    /// the step differs from the slope given by `depth_and_gradient` at
+    /// either end by more than `threshold` \[m/m\]. Otherwise, a step can
+    /// jump over a sharp change of depth without refracting the ray.
+    AutoRefine {
+        /// the largest accepted difference of slope \[m/m\]
+        threshold: f64,
+    },
+}
+
+/// Name of the integrator recorded in `TraceMetadata`
+const INTEGRATOR: &str = "Rk4";
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The parameters of a `ManyRays` run, to describe how its results were
+/// produced, e.g. as global attributes of the saved output
+pub(crate) struct TraceMetadata {
+    /// the version of mantaray
+    version: String,
+    /// the name of the integrator
+    integrator: String,
+    /// the time \[s\] the ray tracing began
+    start_time: f64,
+    /// the time \[s\] the ray tracing was stopped
+    end_time: f64,
+    /// the change in time \[s\] between integration steps
+    step_size: f64,
+    /// the gravitational acceleration \[m/s^2\]
+    gravity: f64,
+    /// the output stride of each ray, see `SingleRay::output_stride`
+    output_stride: usize,
+    /// the number of rays
+    n_rays: usize,
+    /// other parameters known by the caller but not by `ManyRays`, such as
+    /// the paths of the files and the interpolation mode of the datasets
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<String, String>,
+}
+
+#[allow(dead_code)]
+impl TraceMetadata {
+    /// Add a parameter to the metadata
+    ///
+    /// The datasets are only seen through the `BathymetryData` and
+    /// `CurrentData` traits, thus, for instance, the path of the bathymetry
+    /// file has to be added by the caller who opened it.
+    ///
+    /// # Arguments
+    /// `name` : `&str`
+    /// - the name of the parameter, e.g. "bathymetry_path". A previous value
+    ///   with the same name is replaced.
+    ///
+    /// `value` : `impl ToString`
+    /// - the value of the parameter
+    ///
+    /// # Returns
+    /// `Self` : the metadata with the parameter
+    pub(crate) fn with_attribute(mut self, name: &str, value: impl ToString) -> Self {
+        self.attributes.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// The parameters added with `with_attribute`, ordered by name
+    pub(crate) fn attributes(&self) -> &BTreeMap<String, String> {
+        &self.attributes
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The results of a `ManyRays` run with the parameters that produced them
+pub(crate) struct TraceOutput {
+    /// the parameters of the run
+    pub(crate) metadata: TraceMetadata,
+    /// the rays in the same order as the initial rays, where a ray is `None`
+    /// if there was an error during its integration
+    pub(crate) results: Vec<Option<RayResult>>,
+}
+
+#[allow(dead_code)]
+impl TraceOutput {
+    /// Convert the `TraceOutput` to a JSON string
+    pub(crate) fn as_json(&self) -> String {
+        serde_json::to_string(&self).unwrap()
+    }
+}
+
 #[derive(Builder)]
 /// a struct that creates many rays
 pub(crate) struct ManyRays<'a> {
@@ -28,8 +173,14 @@ pub(crate) struct ManyRays<'a> {
     #[builder(default = "&DEFAULT_CURRENT")]
     /// a reference to the current dataset. Default is (u, v) = (0, 0) m/s
     current_data: &'a dyn CurrentData,
-    /// a vector of initial x, y, kx, and ky values for the many waves
-    initial_rays: &'a Vec<RayState<f64>>,
+    #[builder(setter(into))]
+    /// a vector of initial x, y, kx, and ky values for the many waves,
+    /// borrowed or, if created with `from_periods`, owned
+    initial_rays: Cow<'a, [RayState<f64>]>,
+    #[builder(default = "1")]
+    /// keep only every Nth step of the output of each ray. See
+    /// `SingleRay::output_stride`. Default is 1, i.e. every step.
+    output_stride: usize,
 }
 
 #[allow(dead_code)]
@@ -46,23 +197,68 @@ impl<'a> ManyRays<'a> {
     ///  `get_current_gradient` methods. If `None`, then the current is assumed
     /// to be zero.
     ///
-    /// `init_rays`: `&'a Vec<(f64, f64, f64, f64)>`
-    /// - a vector of initial x, y, kx, and ky values for the many waves
+    /// `initial_rays`: `&'a [RayState<f64>]`
+    /// - the initial x, y, kx, and ky values for the many waves
     ///
     /// # Returns
     /// `Self`: a constructed `ManyRays` struct
     pub(crate) fn new(
         bathymetry_data: &'a dyn BathymetryData,
         current_data: &'a dyn CurrentData,
-        initial_rays: &'a Vec<RayState<f64>>,
+        initial_rays: &'a [RayState<f64>],
     ) -> Self {
         ManyRays {
             bathymetry_data,
             current_data,
-            initial_rays,
+            initial_rays: Cow::Borrowed(initial_rays),
+            output_stride: 1,
         }
     }
 
+    /// construct a new `ManyRays` from the period and direction of the waves
+    ///
+    /// The initial wavenumber of each ray is solved from the dispersion
+    /// relation at the depth of its initial point, see
+    /// `RayState::with_period`.
+    ///
+    /// # Arguments
+    /// `bathymetry_data`: `&'a dyn BathymetryData`
+    /// - the data on depth that implements the `depth` and
+    ///   `depth_and_gradient` methods.
+    ///
+    /// `current_data`: `&'a dyn CurrentData`
+    /// - the data on current that implements the `current` and
+    ///   `current_and_gradient` methods.
+    ///
+    /// `inits_with_period`: `&[(f64, f64, f64, f64)]`
+    /// - the initial x \[m\], y \[m\], period \[s\], and direction \[rad\],
+    ///   counterclockwise from the x axis, of the many waves
+    ///
+    /// # Returns
+    /// `Result<Self>`: a constructed `ManyRays` struct
+    ///
+    /// # Errors
+    /// - errors from `RayState::with_period` for the first initial condition
+    ///   that is not valid, e.g. with a period or depth that is not positive.
+    pub(crate) fn from_periods(
+        bathymetry_data: &'a dyn BathymetryData,
+        current_data: &'a dyn CurrentData,
+        inits_with_period: &[(f64, f64, f64, f64)],
+    ) -> Result<Self> {
+        let initial_rays = inits_with_period
+            .iter()
+            .map(|&(x, y, period, direction)| {
+                RayState::with_period(Point::new(x, y), period, direction, bathymetry_data)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ManyRays {
+            bathymetry_data,
+            current_data,
+            initial_rays: Cow::Owned(initial_rays),
+            output_stride: 1,
+        })
+    }
+
     /// create a new `ManyRays` using the builder method
     ///
     /// Used to create a builder object and then set each argument individually.
@@ -75,10 +271,90 @@ impl<'a> ManyRays<'a> {
         ManyRaysBuilder::default()
     }
 
+    /// Check the initial rays correspond to real waves
+    ///
+    /// For each initial ray, the wavenumber magnitude and the depth at the
+    /// initial point have to be positive, so that the intrinsic frequency
+    /// $\sigma = \sqrt{g k \tanh(k h)}$ is defined. This allows failing fast
+    /// before tracing rays that would produce invalid paths.
+    ///
+    /// # Returns
+    /// `Vec<Result<()>>` : for each initial ray, `Ok(())` if it is valid or
+    /// the reason it is not.
+    ///
+    /// # Errors
+    /// - `Error::InvalidInitialCondition` : the wavenumber or the depth is not
+    ///   positive.
+    /// - errors from `depth` if the point is out of the bathymetry domain.
+    pub(crate) fn validate_inits(&self) -> Vec<Result<()>> {
+        self.initial_rays
+            .iter()
+            .map(|ray_state| {
+                let state = State::from(ray_state.clone());
+                let k = state.kx().hypot(state.ky());
+                if k.is_nan() || k <= 0.0 {
+                    return Err(Error::InvalidInitialCondition(format!(
+                        "wavenumber ({}, {}) at ({}, {}) is not positive",
+                        state.kx(),
+                        state.ky(),
+                        state.x(),
+                        state.y()
+                    )));
+                }
+                let h = self
+                    .bathymetry_data
+                    .depth_f64(&Point::new(state.x(), state.y()))? as f64;
+                if h.is_nan() || h <= 0.0 {
+                    return Err(Error::InvalidInitialCondition(format!(
+                        "depth {} m at ({}, {}) is not positive",
+                        h,
+                        state.x(),
+                        state.y()
+                    )));
+                }
+                Ok(())
+            })
+            .collect()
+    }
+
+    /// Check the bathymetry and current grids have compatible resolutions
+    ///
+    /// When the grid spacing of the bathymetry and current differ by more than
+    /// `GRID_SPACING_RATIO_LIMIT` in either direction, the rays sample them at
+    /// incompatible scales, which can produce spurious refraction. This is a
+    /// diagnostic, not a failure: the rays can still be traced. Data defined
+    /// by a function, such as `ConstantDepth`, has no grid and is always
+    /// compatible.
+    ///
+    /// # Returns
+    /// `Option<GridMismatch>` : the spacings of both grids if they differ by
+    /// more than the limit, in which case a warning is also logged, or `None`
+    /// if they are compatible.
+    pub(crate) fn check_grid_compatibility(&self) -> Option<GridMismatch> {
+        let bathymetry_spacing = self.bathymetry_data.grid_spacing()?;
+        let current_spacing = self.current_data.grid_spacing()?;
+        let ratio = |a: f64, b: f64| (a / b).max(b / a).abs();
+        let ratio = ratio(bathymetry_spacing.0, current_spacing.0)
+            .max(ratio(bathymetry_spacing.1, current_spacing.1));
+        if ratio <= GRID_SPACING_RATIO_LIMIT {
+            return None;
+        }
+        warn!(
+            "bathymetry grid spacing {:?} and current grid spacing {:?} differ by a factor of {}",
+            bathymetry_spacing, current_spacing, ratio
+        );
+        Some(GridMismatch {
+            bathymetry_spacing,
+            current_spacing,
+            ratio,
+        })
+    }
+
     /// Trace many rays given start time, stop time, and step size (delta t)
     ///
-    /// Given the arguments, `trace_many` creates a vector of SingleRays,
-    /// integrates each ray, and returns the results.
+    /// Given the arguments, `trace_many` creates a SingleRay for each initial
+    /// ray and integrates it in the same parallel pass, without collecting
+    /// the intermediate SingleRays, and returns the results.
     ///
     /// Arguments:
     ///
@@ -95,43 +371,324 @@ impl<'a> ManyRays<'a> {
     /// Returns: `Vec<Option<(XOut, YOut)>>`: A vector of optional values. Each
     /// value in the vector is either `None`, which represents an error during
     /// that ray's integration, or they are a tuple of (XOut, YOut).
+    ///
+    /// The output is in the same order as the initial rays, i.e. the value at
+    /// index `i` is always the result of `initial_rays[i]`, regardless of the
+    /// order in which the rays are traced in parallel.
     pub(crate) fn trace_many(
         &self,
         start_time: f64,
         end_time: f64,
         step_size: f64,
     ) -> Vec<Option<SolverResult<Time, State>>> {
-        // create a vector of SingleRays
-        let rays: Vec<SingleRay> = self
-            .initial_rays
-            .par_iter()
-            .map(|ray_state| SingleRay::new(self.bathymetry_data, self.current_data, ray_state))
-            .collect();
+        self.trace_many_with_progress(start_time, end_time, step_size, |_| {})
+    }
+
+    /// Trace many rays, calling `on_complete` as each ray finishes
+    ///
+    /// Tracing a large number of rays can take minutes, thus the callback can
+    /// be used to report the progress, e.g. with a progress bar.
+    ///
+    /// Arguments:
+    ///
+    /// `start_time`: `f64`
+    /// - the time the ray tracing begins.
+    ///
+    /// `end_time`: `f64`
+    /// - the time the ray tracing is stopped.
+    ///
+    /// `step_size`: `f64`
+    /// - the change in time between integration steps.
+    ///
+    /// `on_complete`: `impl Fn(usize) + Sync`
+    /// - called with the index of each ray, in the initial rays, when its
+    ///   integration finishes, including when it fails. The rays are traced in
+    ///   parallel, so the callback is called from several threads and the
+    ///   order of completion is nondeterministic.
+    ///
+    /// Returns: `Vec<Option<(XOut, YOut)>>`: the same as `trace_many`, in the
+    /// same order as the initial rays.
+    pub(crate) fn trace_many_with_progress(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+        on_complete: impl Fn(usize) + Sync,
+    ) -> Vec<Option<SolverResult<Time, State>>> {
+        self.fan_out(on_complete, |ray| {
+            ray.trace_individual(start_time, end_time, step_size)
+        })
+    }
+
+    /// Trace many rays with the reason each of them was stopped
+    ///
+    /// Same as `trace_many`, but each ray is converted to a `RayResult`
+    /// with its termination reason, e.g. to tell the rays that reached the
+    /// shore from the rays that left the domain.
+    ///
+    /// Arguments:
+    ///
+    /// `start_time`: `f64`
+    /// - the time the ray tracing begins.
+    ///
+    /// `end_time`: `f64`
+    /// - the time the ray tracing is stopped.
+    ///
+    /// `step_size`: `f64`
+    /// - the change in time between integration steps.
+    ///
+    /// Returns: `Vec<Option<RayResult>>`: the rays in the same order as the
+    /// initial rays, where a ray is `None` if there was an error during its
+    /// integration.
+    pub(crate) fn trace_rays(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Vec<Option<RayResult>> {
+        self.fan_out(|_| {}, |ray| ray.trace(start_time, end_time, step_size))
+    }
+
+    /// Trace many rays and record the parameters of the run
+    ///
+    /// Same as `trace_rays`, with the results returned together with a
+    /// `TraceMetadata`. Parameters not known by
+    /// `ManyRays`, such as the file paths, can be added to the metadata with
+    /// `TraceMetadata::with_attribute`.
+    ///
+    /// Arguments:
+    ///
+    /// `start_time`: `f64`
+    /// - the time the ray tracing begins.
+    ///
+    /// `end_time`: `f64`
+    /// - the time the ray tracing is stopped.
+    ///
+    /// `step_size`: `f64`
+    /// - the change in time between integration steps.
+    ///
+    /// Returns: `TraceOutput`: the metadata of the run and the rays in the
+    /// same order as the initial rays.
+    pub(crate) fn trace_with_metadata(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> TraceOutput {
+        let results = self.trace_rays(start_time, end_time, step_size);
+        let metadata = TraceMetadata {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            integrator: INTEGRATOR.to_string(),
+            start_time,
+            end_time,
+            step_size,
+            gravity: G,
+            output_stride: self.output_stride,
+            n_rays: self.initial_rays.len(),
+            attributes: BTreeMap::new(),
+        };
+        TraceOutput { metadata, results }
+    }
 
-        // integrate each. I think here is where I would use `par_iter` from rayon in the future.
-        let results: Vec<Option<SolverResult<Time, State>>> = rays
+    /// trace each of the initial rays in parallel
+    ///
+    /// `trace` is called with the `SingleRay` of each initial ray, and
+    /// `on_complete` with the index of the ray once it is traced. An error
+    /// during the integration of a ray is logged and its result is `None`.
+    /// The results are in the same order as the initial rays.
+    fn fan_out<T: Send>(
+        &self,
+        on_complete: impl Fn(usize) + Sync,
+        trace: impl Fn(SingleRay<'a>) -> Result<T> + Sync,
+    ) -> Vec<Option<T>> {
+        self.initial_rays
             .par_iter()
-            .map(
-                |r| match r.trace_individual(start_time, end_time, step_size) {
-                    Ok(v) => Some(v),
-                    Err(e) => {
-                        println!("ERROR {} during integration", e);
-                        None
-                    }
-                },
-            )
-            .collect();
+            .enumerate()
+            .map(|(i, ray_state)| {
+                let result = trace(self.single_ray(ray_state))
+                    .inspect_err(|e| warn!("error during the integration of ray {}: {}", i, e))
+                    .ok();
+                on_complete(i);
+                result
+            })
+            .collect()
+    }
 
-        // return the results
-        results
+    /// the `SingleRay` of one of the initial rays, with the same data and
+    /// output stride
+    fn single_ray(&self, ray_state: &RayState<f64>) -> SingleRay<'a> {
+        SingleRay {
+            output_stride: self.output_stride,
+            ..SingleRay::new(self.bathymetry_data, self.current_data, ray_state)
+        }
+    }
+
+    /// Trace many rays and return only the last valid position of each
+    ///
+    /// Useful to map where the waves arrive, e.g. at the coast, for a large
+    /// number of rays. The full path of each ray is dropped as soon as its
+    /// last valid position is found, so only the rays being traced by the
+    /// threads are kept in memory.
+    ///
+    /// Arguments:
+    ///
+    /// `start_time`: `f64`
+    /// - the time the ray tracing begins.
+    ///
+    /// `end_time`: `f64`
+    /// - the time the ray tracing is stopped.
+    ///
+    /// `step_size`: `f64`
+    /// - the change in time between integration steps.
+    ///
+    /// Returns: `Vec<Option<(f64, f64)>>`: the last valid (x, y) of each ray,
+    /// in the same order as the initial rays. It is `None` if there was an
+    /// error during that ray's integration or the ray has no valid steps.
+    pub(crate) fn trace_endpoints(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Vec<Option<(f64, f64)>> {
+        let last_position = |ray: SingleRay<'a>| {
+            let result = ray.trace(start_time, end_time, step_size)?;
+            Ok(result.last_valid_position())
+        };
+        self.fan_out(|_| {}, last_position)
+            .into_iter()
+            .map(Option::flatten)
+            .collect()
     }
 }
 
-// A struct with methods for tracing an individual wave and returning the result.
+/// a struct that creates many rays, sharing the ownership of the datasets
+///
+/// Unlike `ManyRays`, which borrows the datasets, this struct owns its
+/// initial rays and holds the datasets with `Arc`, so it can be moved to, or
+/// shared with, threads that outlive the stack frame where the datasets were
+/// loaded. For example, a large bathymetry grid can be loaded once and shared
+/// across a thread pool owned by the caller.
+pub(crate) struct SharedManyRays {
+    /// the shared bathymetry dataset
+    bathymetry_data: Arc<dyn BathymetryData + Send + Sync>,
+    /// the shared current dataset
+    current_data: Arc<dyn CurrentData + Send + Sync>,
+    /// a vector of initial x, y, kx, and ky values for the many waves
+    initial_rays: Vec<RayState<f64>>,
+}
+
+#[allow(dead_code)]
+impl SharedManyRays {
+    /// construct a new `SharedManyRays`
+    ///
+    /// # Arguments
+    /// `bathymetry_data`: `Arc<dyn BathymetryData + Send + Sync>`
+    /// - the shared bathymetry dataset
+    ///
+    /// `current_data`: `Arc<dyn CurrentData + Send + Sync>`
+    /// - the shared current dataset
+    ///
+    /// `initial_rays`: `Vec<RayState<f64>>`
+    /// - a vector of initial x, y, kx, and ky values for the many waves
+    ///
+    /// # Returns
+    /// `Self`: a constructed `SharedManyRays` struct
+    pub(crate) fn new(
+        bathymetry_data: Arc<dyn BathymetryData + Send + Sync>,
+        current_data: Arc<dyn CurrentData + Send + Sync>,
+        initial_rays: Vec<RayState<f64>>,
+    ) -> Self {
+        SharedManyRays {
+            bathymetry_data,
+            current_data,
+            initial_rays,
+        }
+    }
+
+    /// Trace many rays given start time, stop time, and step size (delta t)
+    ///
+    /// See `ManyRays::trace_many`.
+    pub(crate) fn trace_many(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Vec<Option<SolverResult<Time, State>>> {
+        ManyRays::new(
+            self.bathymetry_data.as_ref(),
+            self.current_data.as_ref(),
+            &self.initial_rays,
+        )
+        .trace_many(start_time, end_time, step_size)
+    }
+}
+
+#[derive(Builder)]
+/// A struct with methods for tracing an individual wave and returning the
+/// result.
+///
+/// This struct can be created in two ways:
+/// - using the `new` method, for example, `SingleRay::new(&depth_data,
+///   &current_data, &initial_ray)`
+/// - using the `builder` method, for example,
+///   `SingleRay::builder().bathymetry(&depth_data).current(&current_data).start(x, y).wavenumber(kx, ky).build().unwrap()`
 pub(crate) struct SingleRay<'a> {
+    #[builder(default = "&DEFAULT_BATHYMETRY", setter(name = "bathymetry"))]
+    /// a reference to the bathymetry dataset. Default is 2000 m.
     bathymetry_data: &'a dyn BathymetryData,
+    #[builder(default = "&DEFAULT_CURRENT", setter(name = "current"))]
+    /// a reference to the current dataset. Default is (u, v) = (0, 0) m/s
     current_data: &'a dyn CurrentData,
-    initial_ray: &'a RayState<f64>,
+    #[builder(setter(custom))]
+    /// the initial x and y coordinates of the ray
+    start: Point<f64>,
+    #[builder(setter(custom))]
+    /// the initial kx and ky values of the ray
+    wavenumber: WaveNumber<f64>,
+    #[builder(default = "1")]
+    /// keep only every Nth step of the output, while integrating at every
+    /// step, to reduce the memory used by long integrations. The first step,
+    /// the last valid step and the NaN step that follows it, and the last
+    /// step are always kept. Default is 1, i.e. every step.
+    output_stride: usize,
+    #[builder(default)]
+    /// how the wavenumber is integrated. Default is
+    /// `IntegrationMode::Standard`.
+    integration_mode: IntegrationMode,
+    #[builder(default = "0.0")]
+    /// the bottom friction coefficient used by `trace_with_energy`, see
+    /// `WaveRayPath::with_bottom_friction`. Default is 0, i.e. no
+    /// dissipation.
+    bottom_friction: f64,
+}
+
+#[allow(dead_code)]
+impl<'a> SingleRayBuilder<'a> {
+    /// set the initial position of the ray
+    ///
+    /// # Arguments
+    /// `x` : `f64`
+    /// - the initial x coordinate \[m\]
+    ///
+    /// `y` : `f64`
+    /// - the initial y coordinate \[m\]
+    pub(crate) fn start(&mut self, x: f64, y: f64) -> &mut Self {
+        self.start = Some(Point::new(x, y));
+        self
+    }
+
+    /// set the initial wavenumber of the ray
+    ///
+    /// # Arguments
+    /// `kx` : `f64`
+    /// - the initial x component of the wavenumber \[m^-1\]
+    ///
+    /// `ky` : `f64`
+    /// - the initial y component of the wavenumber \[m^-1\]
+    pub(crate) fn wavenumber(&mut self, kx: f64, ky: f64) -> &mut Self {
+        self.wavenumber = Some(WaveNumber::new(kx, ky));
+        self
+    }
 }
 
 #[allow(dead_code)]
@@ -142,48 +699,62 @@ impl<'a> SingleRay<'a> {
     /// `bathymetry_data` : `&'a dyn BathymetryData`
     /// - a struct that implements the `depth` function
     ///
-    /// `current_data` : `Option<&'a dyn CurrentData>`
-    /// - a struct that implements the `get_current` function. If `None`, then
-    ///  the current is assumed to be zero.
-    ///
-    /// `x0` : `f64`
-    /// - the initial x coordinate
+    /// `current_data` : `&'a dyn CurrentData`
+    /// - a struct that implements the `current` function.
     ///
-    /// `y0` : `f64`
-    /// - the initial y coordinate
-    ///
-    /// `kx0` : `f64`
-    /// - the initial kx value
-    ///
-    /// `ky0` : `f64`
-    /// - the initial ky value
+    /// `initial_ray` : `&RayState<f64>`
+    /// - the initial position and wavenumber of the ray
     ///
     /// # Returns
     /// `Self` : the new `SingleRay` struct
     pub(crate) fn new(
         bathymetry_data: &'a dyn BathymetryData,
         current_data: &'a dyn CurrentData,
-        initial_ray: &'a RayState<f64>,
+        initial_ray: &RayState<f64>,
     ) -> Self {
         SingleRay {
             bathymetry_data,
             current_data,
-            initial_ray,
+            start: initial_ray.point().clone(),
+            wavenumber: initial_ray.wave_number().clone(),
+            output_stride: 1,
+            integration_mode: IntegrationMode::Standard,
+            bottom_friction: 0.0,
         }
     }
 
+    /// create a new `SingleRayBuilder` using the builder method
+    ///
+    /// Used to create a builder object and then set each argument individually.
+    /// For example,
+    /// `SingleRay::builder().start(0.0, 0.0).wavenumber(0.1, 0.0).build().unwrap()`
+    ///
+    /// Note: There are default bathymetry and current, but no default start
+    /// or wavenumber, so `build` returns an error if they are not supplied.
+    pub(crate) fn builder() -> SingleRayBuilder<'a> {
+        SingleRayBuilder::default()
+    }
+
+    /// the initial state of the ray for `ode_solvers`
+    fn initial_state(&self) -> State {
+        State::from(RayState::new(self.start.clone(), self.wavenumber.clone()))
+    }
+
     /// computes ode_solvers Rk4 tracing and returns result
     ///
     /// # Arguments
     ///
     /// `start_time` : `f64`
-    /// - time to start the Rk4
+    /// - time to start the Rk4. This is the physical time \[s\] of the initial
+    ///   state, passed to `System::system` at each step, and the first value
+    ///   of the output time.
     ///
     /// `end_time` : `f64`
-    /// - time to end the Rk4
+    /// - time to end the Rk4. If it is before `start_time`, the ray is traced
+    ///   backward in time.
     ///
     /// `step_size` : `f64`
-    /// - delta t
+    /// - delta t, which is negative when tracing backward in time
     ///
     /// # Returns
     /// `Result<SolverResult<Time, State>, Error>`
@@ -191,26 +762,631 @@ impl<'a> SingleRay<'a> {
     ///   integration.
     /// - `Err(Error::IntegrationError)` : there was an error during Rk4
     ///   integrate method.
+    /// - `Err(Error::ArgumentOutOfBounds)` : the step size is zero or doesn't
+    ///   go from `start_time` toward `end_time`.
     ///
     /// # Note
-    /// This struct still copies the data when it returns, which could be an
-    /// inefficiency, but the arguments are now less.
+    /// `ode_solvers` only lends the results of the stepper, so they are
+    /// copied once out of it. The clipping and the thinning of the output
+    /// then move that copy instead of copying it again.
     pub(crate) fn trace_individual(
         &self,
         start_time: f64,
         end_time: f64,
         step_size: f64,
     ) -> Result<SolverResult<Time, State>> {
+        check_time_span(start_time, end_time, step_size)?;
         // do the calculations
         let system = WaveRayPath::new(self.bathymetry_data, self.current_data);
-        let s0 = State::from(self.initial_ray.clone());
-        let mut stepper = Box::new(Rk4::new(system, start_time, s0, end_time, step_size));
+        self.integrate(&system, start_time, end_time, step_size)
+    }
+
+    /// computes ode_solvers Rk4 tracing and returns a `RayResult`
+    ///
+    /// # Arguments
+    ///
+    /// `start_time` : `f64`
+    /// - time to start the Rk4. This is the physical time \[s\] of the initial
+    ///   state, passed to `System::system` at each step, and the first value
+    ///   of the output time.
+    ///
+    /// `end_time` : `f64`
+    /// - time to end the Rk4. If it is before `start_time`, the ray is traced
+    ///   backward in time.
+    ///
+    /// `step_size` : `f64`
+    /// - delta t, which is negative when tracing backward in time
+    ///
+    /// # Returns
+    /// `Result<RayResult>`
+    /// - `RayResult` : the valid steps of the integration and the reason it was
+    ///   stopped, if any.
+    /// - `Err(Error::IntegrationError)` : there was an error during Rk4
+    ///   integrate method.
+    /// - `Err(Error::ArgumentOutOfBounds)` : the step size is zero or doesn't
+    ///   go from `start_time` toward `end_time`.
+    pub(crate) fn trace(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Result<RayResult> {
+        check_time_span(start_time, end_time, step_size)?;
+        let system = WaveRayPath::new(self.bathymetry_data, self.current_data);
+        let result = self.integrate(&system, start_time, end_time, step_size)?;
+
+        Ok(finish_result(
+            RayResult::from(result),
+            &system,
+            end_time,
+            step_size,
+        ))
+    }
+
+    /// computes ode_solvers Rk4 tracing calling an observer at each step
+    ///
+    /// Same as `trace`, but `observer` is called with the time and state of
+    /// the initial state and of each following valid step, e.g. to log
+    /// quantities derived from the ray such as k*h along the integration.
+    /// The observer is called at every step, even if the output is thinned
+    /// with `output_stride`, and always integrates (kx, ky) directly, i.e.
+    /// with `IntegrationMode::Standard`.
+    ///
+    /// # Arguments
+    ///
+    /// `start_time` : `f64`
+    /// - time to start the Rk4.
+    ///
+    /// `end_time` : `f64`
+    /// - time to end the Rk4.
+    ///
+    /// `step_size` : `f64`
+    /// - delta t, which is negative when tracing backward in time
+    ///
+    /// `observer` : `impl FnMut(Time, &State)`
+    /// - called with the time and state of each valid step
+    ///
+    /// # Returns
+    /// `Result<RayResult>`
+    /// - `RayResult` : the valid steps of the integration and the reason it was
+    ///   stopped, if any.
+    /// - `Err(Error::IntegrationError)` : there was an error during Rk4
+    ///   integrate method.
+    /// - `Err(Error::ArgumentOutOfBounds)` : the step size is zero or doesn't
+    ///   go from `start_time` toward `end_time`.
+    pub(crate) fn trace_with_observer(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+        mut observer: impl FnMut(Time, &State),
+    ) -> Result<RayResult> {
+        check_time_span(start_time, end_time, step_size)?;
+        let system = WaveRayPath::new(self.bathymetry_data, self.current_data);
+        let s0 = self.initial_state();
+        observer(start_time, &s0);
+        let mut stepper = Rk4::new(
+            ObservedRayPath::new(&system, observer),
+            start_time,
+            s0,
+            end_time,
+            step_size,
+        );
+        stepper.integrate()?;
+        let (t_out, s_out) = stepper.results().get();
+        let result = self.finish_output(&system, t_out.clone(), s_out.clone());
+
+        Ok(finish_result(
+            RayResult::from(result),
+            &system,
+            end_time,
+            step_size,
+        ))
+    }
+
+    /// integrate the ray system with the integration mode and thin the output
+    fn integrate(
+        &self,
+        system: &WaveRayPath,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Result<SolverResult<Time, State>> {
+        let s0 = self.initial_state();
+        match self.integration_mode {
+            IntegrationMode::Standard => {
+                let mut stepper = Rk4::new(system, start_time, s0, end_time, step_size);
+                stepper.integrate()?;
+                let (t_out, s_out) = stepper.results().get();
+                Ok(self.finish_output(system, t_out.clone(), s_out.clone()))
+            }
+            IntegrationMode::FrequencyConserving => {
+                let conserving = FrequencyConservingRayPath::new(system, &s0)?;
+                let s0 = conserving.initial_state(&s0);
+                let mut stepper = Rk4::new(conserving, start_time, s0, end_time, step_size);
+                stepper.integrate()?;
+                let (t_out, s_out) = stepper.results().get();
+                let s_out = s_out.iter().map(|s| conserving.full_state(s)).collect();
+                Ok(self.finish_output(system, t_out.clone(), s_out))
+            }
+            IntegrationMode::AutoRefine { threshold } => {
+                let mut t_out = vec![start_time];
+                let mut s_out = vec![s0];
+                let num_steps = ((end_time - start_time) / step_size).ceil() as usize;
+                for _ in 0..num_steps {
+                    let (t, s) = (t_out[t_out.len() - 1], s_out[s_out.len() - 1]);
+                    let stop = self.refined_step(
+                        system,
+                        (t, s),
+                        step_size,
+                        threshold,
+                        MAX_REFINEMENTS,
+                        (&mut t_out, &mut s_out),
+                    )?;
+                    if stop {
+                        break;
+                    }
+                }
+                Ok(self.finish_output(system, t_out, s_out))
+            }
+        }
+    }
+
+    /// one Rk4 step, halved while the bathymetry it crosses is not resolved
+    ///
+    /// The accepted steps are appended to the output. See
+    /// `IntegrationMode::AutoRefine`.
+    ///
+    /// # Returns
+    /// `Result<bool>` : whether the integration should stop, because the ray
+    /// is NaN or the stop condition of `system` is met.
+    fn refined_step(
+        &self,
+        system: &WaveRayPath,
+        (t, s): (Time, State),
+        step_size: f64,
+        threshold: f64,
+        refinements: usize,
+        (t_out, s_out): (&mut Vec<Time>, &mut Vec<State>),
+    ) -> Result<bool> {
+        // Rk4 takes ceil((end - start) / step_size) steps, thus ending half a
+        // step ahead gives exactly one step, whatever the rounding
+        let mut stepper = Rk4::new(system, t, s, t + 0.5 * step_size, step_size);
+        stepper.integrate()?;
+        let next = stepper.y_out()[1];
+
+        let is_nan = next.iter().any(|v| v.is_nan());
+        if refinements > 0 && !is_nan && !self.is_resolved(&s, &next, threshold) {
+            let half = 0.5 * step_size;
+            let io = (&mut *t_out, &mut *s_out);
+            if self.refined_step(system, (t, s), half, threshold, refinements - 1, io)? {
+                return Ok(true);
+            }
+            let middle = (t_out[t_out.len() - 1], s_out[s_out.len() - 1]);
+            return self.refined_step(
+                system,
+                middle,
+                half,
+                threshold,
+                refinements - 1,
+                (t_out, s_out),
+            );
+        }
+
+        t_out.push(t + step_size);
+        s_out.push(next);
+        let condition_met = system.stop_excess(&next).is_some_and(|e| e >= 0.0);
+        Ok(is_nan || condition_met)
+    }
+
+    /// whether the slope of the bottom is resolved by a step from `a` to `b`
+    ///
+    /// The mean slope between the two positions is compared to the slope
+    /// along the step given by the gradient of the depth at each end. The
+    /// step is considered resolved if the depth can't be found, e.g. out of
+    /// the domain, since refining it wouldn't help.
+    fn is_resolved(&self, a: &State, b: &State, threshold: f64) -> bool {
+        let (dx, dy) = (b.x() - a.x(), b.y() - a.y());
+        let length = dx.hypot(dy);
+        let depth_and_slope = |s: &State| {
+            let (h, gradient) = self
+                .bathymetry_data
+                .depth_and_gradient_f64(&Point::new(s.x(), s.y()))
+                .ok()?;
+            let slope = (*gradient.dx() as f64 * dx + *gradient.dy() as f64 * dy) / length;
+            Some((h as f64, slope))
+        };
+        if length == 0.0 {
+            return true;
+        }
+        let (Some((ha, slope_a)), Some((hb, slope_b))) = (depth_and_slope(a), depth_and_slope(b))
+        else {
+            return true;
+        };
+        let mean_slope = (hb - ha) / length;
+        (mean_slope - slope_a).abs() <= threshold && (mean_slope - slope_b).abs() <= threshold
+    }
+
+    /// computes ode_solvers Rk4 tracing together with the wave energy
+    ///
+    /// The ray equations are augmented with the energy flux (see
+    /// `EnergyRayPath`), which is dissipated by the `bottom_friction`. The
+    /// energy density at each step is the energy flux divided by the
+    /// intrinsic group velocity. The integration stops after the step where
+    /// the wave breaks, with the termination reason
+    /// `TerminationReason::Breaking`.
+    ///
+    /// # Arguments
+    ///
+    /// `start_time` : `f64`
+    /// - time to start the Rk4.
+    ///
+    /// `end_time` : `f64`
+    /// - time to end the Rk4.
+    ///
+    /// `step_size` : `f64`
+    /// - delta t, which is negative when tracing backward in time
+    ///
+    /// `amplitude` : `f64`
+    /// - the wave amplitude \[m\] at the start of the ray
+    ///
+    /// # Returns
+    /// `Result<RayResult>`
+    /// - `RayResult` : the valid steps of the integration with the wave
+    ///   energy, see `RayResult::energy`, thinned with the `output_stride`.
+    /// - `Err(Error::IntegrationError)` : there was an error during Rk4
+    ///   integrate method.
+    /// - `Err(Error::ArgumentOutOfBounds)` : the step size is zero or doesn't
+    ///   go from `start_time` toward `end_time`.
+    /// - an error getting the depth or the group velocity at the start or
+    ///   along the ray.
+    pub(crate) fn trace_with_energy(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+        amplitude: f64,
+    ) -> Result<RayResult> {
+        check_time_span(start_time, end_time, step_size)?;
+        let system = WaveRayPath::new(self.bathymetry_data, self.current_data)
+            .with_bottom_friction(self.bottom_friction);
+        let s0 = self.initial_state();
+        let flux = 0.5 * RHO * G * amplitude * amplitude * system.intrinsic_group_velocity(&s0)?;
+        let s0 = EnergyState::from_column_slice(&[s0.x(), s0.y(), s0.kx(), s0.ky(), flux]);
+        let mut stepper = Rk4::new(
+            EnergyRayPath::new(&system),
+            start_time,
+            s0,
+            end_time,
+            step_size,
+        );
+        stepper.integrate()?;
+
+        // keep the valid steps, where the ray is not NaN
+        let steps: Vec<(Time, State, f64)> = stepper
+            .x_out()
+            .iter()
+            .zip(stepper.y_out())
+            .map(|(t, s)| (*t, s.fixed_rows::<4>(0).into_owned(), s[4]))
+            .take_while(|(t, s, _)| !t.is_nan() && !s.iter().any(|v| v.is_nan()))
+            .collect();
+        let steps = thin_steps(steps, self.output_stride);
+        let energy = steps
+            .iter()
+            .map(|(_, s, flux)| Ok(flux / system.intrinsic_group_velocity(s)?))
+            .collect::<Result<Vec<f64>>>()?;
+        let column = |i: usize| steps.iter().map(|(_, s, _)| s[i]).collect();
+
+        let result = RayResult::new(
+            steps.iter().map(|(t, _, _)| *t).collect(),
+            column(0),
+            column(1),
+            column(2),
+            column(3),
+        );
+        Ok(finish_result(result, &system, end_time, step_size).with_energy(energy))
+    }
+
+    /// clip the last step to the stop condition, if any, and thin the output
+    ///
+    /// The output is taken by value so that, without thinning, it is moved
+    /// into the `SolverResult` rather than copied.
+    fn finish_output(
+        &self,
+        system: &WaveRayPath,
+        mut t_out: Vec<Time>,
+        mut s_out: Vec<State>,
+    ) -> SolverResult<Time, State> {
+        clip_to_stop_condition(system, &mut t_out, &mut s_out);
+        thin_output(t_out, s_out, self.output_stride)
+    }
+
+    /// computes ode_solvers Rk4 tracing until the stop condition is met
+    ///
+    /// Instead of guessing an end time, the integration is stopped after the
+    /// step where the condition is met, e.g. where the ray reaches a given x,
+    /// and that step is linearly interpolated back onto the condition. The
+    /// integration is always stopped after `STOP_CONDITION_MAX_STEPS` steps,
+    /// in case the condition is never met.
+    ///
+    /// The termination reason depends on the condition:
+    /// - `AtDepthBelow`, `AtXBelow` and `AtXAbove` :
+    ///   `TerminationReason::ConditionMet` when the condition is met.
+    /// - `AtTime` : `TerminationReason::ReachedEndTime`, as for `trace`.
+    /// - `OnBoundary` : the reason the ray stopped, i.e.
+    ///   `TerminationReason::LeftBathymetry` or `LeftCurrent` when it leaves
+    ///   the domain, and never `ConditionMet`.
+    ///
+    /// Any condition can also end with another reason if the ray stops
+    /// before, e.g. on `Land` or `Blocked`.
+    ///
+    /// # Arguments
+    ///
+    /// `start_time` : `f64`
+    /// - time to start the Rk4.
+    ///
+    /// `step_size` : `f64`
+    /// - delta t, which is negative when tracing backward in time
+    ///
+    /// `condition` : `StopCondition`
+    /// - the condition to stop the integration. `AtTime(t)` is the same as
+    ///   `trace(start_time, t, step_size)`, and `OnBoundary` traces the ray
+    ///   until it leaves the domain of the data.
+    ///
+    /// # Returns
+    /// `Result<RayResult>`
+    /// - `RayResult` : the valid steps of the integration and the reason it was
+    ///   stopped.
+    /// - `Err(Error::IntegrationError)` : there was an error during Rk4
+    ///   integrate method.
+    /// - `Err(Error::ArgumentOutOfBounds)` : the step size is zero, or doesn't
+    ///   go from `start_time` toward the time of `AtTime`.
+    pub(crate) fn trace_until(
+        &self,
+        start_time: f64,
+        step_size: f64,
+        condition: StopCondition,
+    ) -> Result<RayResult> {
+        let end_time = match condition {
+            StopCondition::AtTime(end_time) => end_time,
+            _ => start_time + step_size * STOP_CONDITION_MAX_STEPS,
+        };
+        check_time_span(start_time, end_time, step_size)?;
+        let system = WaveRayPath::new(self.bathymetry_data, self.current_data)
+            .with_stop_condition(condition);
+        let result = self.integrate(&system, start_time, end_time, step_size)?;
+
+        Ok(finish_result(
+            RayResult::from(result),
+            &system,
+            end_time,
+            step_size,
+        ))
+    }
+
+    /// computes ode_solvers Rk4 tracing together with the geometric spreading
+    ///
+    /// The ray equations are augmented with their variational equations (see
+    /// `SpreadingRayPath`), started from the rotation of the initial
+    /// wavenumber by one radian, i.e. a ray launched from the same point in a
+    /// slightly different direction. The spreading factor is the component of
+    /// the variation of the position perpendicular to the ray.
+    ///
+    /// # Arguments
+    ///
+    /// `start_time` : `f64`
+    /// - time to start the Rk4.
+    ///
+    /// `end_time` : `f64`
+    /// - time to end the Rk4.
+    ///
+    /// `step_size` : `f64`
+    /// - delta t, which is negative when tracing backward in time
+    ///
+    /// # Returns
+    /// `Result<RayResult>`
+    /// - `RayResult` : the valid steps of the integration with the spreading
+    ///   factor, see `RayResult::spreading_factor`, thinned with the
+    ///   `output_stride`.
+    /// - `Err(Error::IntegrationError)` : there was an error during Rk4
+    ///   integrate method.
+    /// - `Err(Error::ArgumentOutOfBounds)` : the step size is zero or doesn't
+    ///   go from `start_time` toward `end_time`.
+    pub(crate) fn trace_with_spreading(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Result<RayResult> {
+        check_time_span(start_time, end_time, step_size)?;
+        let system = WaveRayPath::new(self.bathymetry_data, self.current_data);
+        let s0 = self.initial_state();
+        let s0 = SpreadingState::from_column_slice(&[
+            s0.x(),
+            s0.y(),
+            s0.kx(),
+            s0.ky(),
+            0.0,
+            0.0,
+            -s0.ky(),
+            s0.kx(),
+        ]);
+        let mut stepper = Rk4::new(
+            SpreadingRayPath::new(&system),
+            start_time,
+            s0,
+            end_time,
+            step_size,
+        );
         stepper.integrate()?;
-        // return the stepper results
-        let results: &SolverResult<Time, State> = stepper.results();
 
-        Ok(results.clone())
+        // keep the valid steps, where the ray is not NaN
+        let steps: Vec<(Time, &SpreadingState)> = stepper
+            .x_out()
+            .iter()
+            .zip(stepper.y_out())
+            .map(|(t, s)| (*t, s))
+            .take_while(|(t, s)| !t.is_nan() && !s.iter().take(4).any(|v| v.is_nan()))
+            .collect();
+        let steps = thin_steps(steps, self.output_stride);
+        let column = |i: usize| steps.iter().map(|(_, s)| s[i]).collect();
+        // variation of the position perpendicular to the wavenumber
+        let spreading = steps
+            .iter()
+            .map(|(_, s)| (s[2] * s[5] - s[3] * s[4]) / s[2].hypot(s[3]))
+            .collect();
+
+        let result = RayResult::new(
+            steps.iter().map(|(t, _)| *t).collect(),
+            column(0),
+            column(1),
+            column(2),
+            column(3),
+        );
+        Ok(finish_result(result, &system, end_time, step_size).with_spreading(spreading))
+    }
+}
+
+/// Interpolate the last step onto the stop condition of the system
+///
+/// The last step is replaced by the linear interpolation between the last two
+/// steps where the condition is met. The output is left unchanged if there is
+/// no stop condition or it was not crossed by the last step, e.g. it was
+/// already met at the previous step.
+fn clip_to_stop_condition(system: &WaveRayPath, t_out: &mut [Time], s_out: &mut [State]) {
+    let n = t_out.len();
+    if n < 2 {
+        return;
+    }
+    let (ta, sa) = (t_out[n - 2], s_out[n - 2]);
+    let (tb, sb) = (t_out[n - 1], s_out[n - 1]);
+    let (Some(ea), Some(eb)) = (system.stop_excess(&sa), system.stop_excess(&sb)) else {
+        return;
+    };
+    if ea >= 0.0 || eb < 0.0 {
+        return;
     }
+    let fraction = ea / (ea - eb);
+    t_out[n - 1] = ta + (tb - ta) * fraction;
+    s_out[n - 1] = sa + (sb - sa) * fraction;
+}
+
+/// Keep only every `stride`th step of the integration
+///
+/// The first step, the last valid step and the NaN step that follows it, if
+/// any, and the last step are always kept, so that the endpoints and the
+/// termination of the ray are retained.
+fn thin_output(t_out: Vec<Time>, s_out: Vec<State>, stride: usize) -> SolverResult<Time, State> {
+    if stride <= 1 {
+        return SolverResult::new(t_out, s_out);
+    }
+    let last_valid = s_out.iter().rposition(|s| !s.iter().any(|v| v.is_nan()));
+    let mut thinned = SolverResult::with_capacity(t_out.len() / stride + 3);
+    for (i, (t, s)) in t_out.iter().zip(&s_out).enumerate() {
+        let endpoint = last_valid.is_some_and(|j| i == j || i == j + 1);
+        if i % stride == 0 || endpoint || i + 1 == t_out.len() {
+            thinned.push(*t, *s);
+        }
+    }
+    thinned
+}
+
+/// Keep only every `stride`th step of valid steps
+///
+/// Same as `thin_output` for the steps of the augmented integrations, e.g.
+/// with the spreading, which are already cut before the first NaN state. The
+/// first and last steps are always kept.
+fn thin_steps<T>(steps: Vec<T>, stride: usize) -> Vec<T> {
+    if stride <= 1 {
+        return steps;
+    }
+    let n = steps.len();
+    steps
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| i % stride == 0 || i + 1 == n)
+        .map(|(_, step)| step)
+        .collect()
+}
+
+/// Set the termination reason of a traced ray
+///
+/// The reason is the one recorded by `system` during the integration or, if
+/// none was recorded and the last valid step is at `end_time`,
+/// `TerminationReason::ReachedEndTime`.
+fn finish_result(
+    result: RayResult,
+    system: &WaveRayPath,
+    end_time: f64,
+    step_size: f64,
+) -> RayResult {
+    let reason = system.termination_reason().or_else(|| {
+        let (t, _) = result.last_valid()?;
+        ((t - end_time).abs() < 0.5 * step_size.abs()).then_some(TerminationReason::ReachedEndTime)
+    });
+    result.with_termination_reason(reason)
+}
+
+/// Check the integration goes from the start time toward the end time
+///
+/// The ray equations are integrated backward in time with `end_time` before
+/// `start_time` and a negative `step_size`. A step size with the wrong sign
+/// would never reach `end_time`, and `Rk4::new` panics on it.
+///
+/// # Errors
+/// `Error::ArgumentOutOfBounds` : the step size is zero or NaN, or it has the
+/// opposite sign of `end_time - start_time`.
+fn check_time_span(start_time: f64, end_time: f64, step_size: f64) -> Result<()> {
+    let span = end_time - start_time;
+    if step_size.is_nan() || step_size == 0.0 || span * step_size < 0.0 {
+        return Err(Error::ArgumentOutOfBounds);
+    }
+    Ok(())
+}
+
+#[allow(dead_code)]
+/// Trace a single ray
+///
+/// This is the simplest way to trace a ray: it creates the `SingleRay`,
+/// integrates the ray equations with Rk4, and returns the valid steps.
+///
+/// # Arguments
+/// `bathymetry` : `&dyn BathymetryData`
+/// - the bathymetry the ray propagates over
+///
+/// `current` : `Option<&dyn CurrentData>`
+/// - the current the ray propagates through. If `None`, the current is
+///   assumed to be zero.
+///
+/// `init` : `RayState<f64>`
+/// - the initial position and wavenumber of the ray
+///
+/// `t0` : `f64`
+/// - time to start the integration
+///
+/// `tf` : `f64`
+/// - time to end the integration
+///
+/// `step` : `f64`
+/// - delta t
+///
+/// # Returns
+/// `Result<RayResult>` : the traced ray, or an error during the integration.
+///
+/// # Example
+/// let depth = ConstantDepth::new(1000.0);
+/// let init = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.1, 0.0));
+/// let result = trace_ray(&depth, None, init, 0.0, 100.0, 1.0).unwrap();
+pub(crate) fn trace_ray(
+    bathymetry: &dyn BathymetryData,
+    current: Option<&dyn CurrentData>,
+    init: RayState<f64>,
+    t0: f64,
+    tf: f64,
+    step: f64,
+) -> Result<RayResult> {
+    let current = current.unwrap_or(&DEFAULT_CURRENT);
+    SingleRay::new(bathymetry, current, &init).trace(t0, tf, step)
 }
 
 #[allow(dead_code)]
@@ -248,13 +1424,16 @@ mod test_single_wave {
     use tempfile::NamedTempFile;
 
     use crate::{
-        bathymetry::{BathymetryData, CartesianNetcdf3, ConstantDepth, ConstantSlope},
-        current::{CartesianCurrent, ConstantCurrent},
+        bathymetry::{BathymetryData, BoundaryX, CartesianNetcdf3, ConstantDepth, ConstantSlope},
+        current::{CartesianCurrent, ConstantChange, ConstantCurrent, DEFAULT_CURRENT},
         datatype::{Point, RayState, WaveNumber},
-        io::utility::{create_netcdf3_bathymetry, create_netcdf3_current},
+        dispersion::{BREAKER_INDEX, G, RHO},
+        error::Error,
+        io::utility::{bathymetry_from_fn, create_netcdf3_bathymetry, create_netcdf3_current},
+        wave_ray_path::{State, StateView, StopCondition, TerminationReason, WaveRayPath},
     };
 
-    use super::SingleRay;
+    use super::{trace_ray, IntegrationMode, RayResult, SingleRay};
 
     /// Create a test file with depths split down the middle
     fn two_depth_fn(x: f32, _y: f32) -> f64 {
@@ -400,12 +1579,7 @@ mod test_single_wave {
     /// ray tracing on a two-depth (half 50m and half 20m) shallow wave
     /// propagating in the x direction. The kx increases slightly.
     fn test_two_depth_wave_shallow_x() {
-        let tmp_file = NamedTempFile::new().unwrap();
-        let tmp_path = tmp_file.into_temp_path();
-
-        create_netcdf3_bathymetry(&tmp_path, 100, 100, 1.0, 1.0, two_depth_fn);
-
-        let bathymetry_data = &CartesianNetcdf3::open(&tmp_path, "x", "y", "depth").unwrap();
+        let bathymetry_data = &bathymetry_from_fn(100, 100, 1.0, 1.0, two_depth_fn).unwrap();
         let initial_ray = RayState::new(Point::new(10.0, 50.0), WaveNumber::new(0.01, 0.0));
         let current_data = &ConstantCurrent::new(0.0, 0.0);
 
@@ -439,12 +1613,7 @@ mod test_single_wave {
     /// ray tracing on a two-depth shallow wave propagating at an angle in the
     /// x=y direction. This shows a change in the kx and ky.
     fn test_two_depth_wave_shallow_xy() {
-        let tmp_file = NamedTempFile::new().unwrap();
-        let tmp_path = tmp_file.into_temp_path();
-
-        create_netcdf3_bathymetry(&tmp_path, 100, 100, 1.0, 1.0, two_depth_fn);
-
-        let bathymetry_data = &CartesianNetcdf3::open(&tmp_path, "x", "y", "depth").unwrap();
+        let bathymetry_data = &bathymetry_from_fn(100, 100, 1.0, 1.0, two_depth_fn).unwrap();
         let current_data = &ConstantCurrent::new(0.0, 0.0);
 
         let initial_ray = RayState::new(Point::new(10.0, 10.0), WaveNumber::new(0.007, 0.007));
@@ -476,16 +1645,39 @@ mod test_single_wave {
         assert!(data.iter().last().unwrap()[2] > data.iter().next().unwrap()[2]);
     }
 
+    #[test]
+    /// a shallow wave refracting across the step of the two-depth bathymetry
+    /// conserves sin(theta) / c, i.e. it obeys Snell's law. The depth changes
+    /// over a single grid cell, thus the time step must be small enough to
+    /// resolve it.
+    fn test_two_depth_snell_invariant() {
+        let bathymetry_data = &bathymetry_from_fn(100, 100, 1.0, 1.0, two_depth_fn).unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        let initial_ray = RayState::new(Point::new(10.0, 10.0), WaveNumber::new(0.007, 0.007));
+        let wave = SingleRay::new(bathymetry_data, current_data, &initial_ray);
+        let result: RayResult = wave.trace_individual(0.0, 6.8, 0.001).unwrap().into();
+
+        // the ray crosses the step
+        let (x, _) = result.last_valid_position().unwrap();
+        assert!(x > 60.0);
+
+        let invariant = result.snell_invariant(bathymetry_data).unwrap();
+        for value in &invariant {
+            assert!(
+                (value - invariant[0]).abs() < 1e-3 * invariant[0],
+                "Expected {}, but got {}",
+                invariant[0],
+                value
+            );
+        }
+    }
+
     #[test]
     /// ray tracing on a two-depth deep wave propagating in the x direction.
     /// This correctly shows no change in kx or ky.
     fn test_two_depth_wave_deep_x() {
-        let tmp_file = NamedTempFile::new().unwrap();
-        let tmp_path = tmp_file.into_temp_path();
-
-        create_netcdf3_bathymetry(&tmp_path, 100, 100, 1.0, 1.0, two_depth_fn);
-
-        let bathymetry_data = &CartesianNetcdf3::open(&tmp_path, "x", "y", "depth").unwrap();
+        let bathymetry_data = &bathymetry_from_fn(100, 100, 1.0, 1.0, two_depth_fn).unwrap();
         let current_data = &ConstantCurrent::new(0.0, 0.0);
 
         let initial_ray = RayState::new(Point::new(10.0, 50.0), WaveNumber::new(1.0, 0.0));
@@ -513,12 +1705,7 @@ mod test_single_wave {
     /// ray tracing on a two-depth deep wave propagating at an angle in the x=y
     /// direction. This correctly shows no change in kx or ky.
     fn test_two_depth_wave_deep_xy() {
-        let tmp_file = NamedTempFile::new().unwrap();
-        let tmp_path = tmp_file.into_temp_path();
-
-        create_netcdf3_bathymetry(&tmp_path, 100, 100, 1.0, 1.0, two_depth_fn);
-
-        let bathymetry_data = &CartesianNetcdf3::open(&tmp_path, "x", "y", "depth").unwrap();
+        let bathymetry_data = &bathymetry_from_fn(100, 100, 1.0, 1.0, two_depth_fn).unwrap();
         let current_data = &ConstantCurrent::new(0.0, 0.0);
 
         let initial_ray = RayState::new(Point::new(10.0, 10.0), WaveNumber::new(0.7, 0.7));
@@ -562,25 +1749,24 @@ mod test_single_wave {
         assert_eq!(bathymetry_data.depth(&Point::new(10.0, 1000.0)).unwrap(), 49.5);
         assert_eq!(bathymetry_data.depth(&Point::new(300.0, 1000.0)).unwrap(), 35.0);
 
-        let (_, data) = &res.get();
+        let res = RayResult::from(res);
 
         // verify each y and ky value stays the same
         //data.iter().for_each(|r| assert_eq!(r[1], 1000.0));
         //data.iter().for_each(|r| assert_eq!(r[3], 0.0));
 
         // verify the x values are increasing
-        let mut last_x = data[0][0];
-        for r in data.iter().filter(|v| !v[0].is_nan()) {
+        let (_, first) = res.valid_states().next().unwrap();
+        let mut last_x = first[0];
+        for (_, r) in res.valid_states() {
             assert!(r[0] >= last_x);
             last_x = r[0];
         }
 
         // verify that the last kx value is greater than the first. this is because
         // the wave is getting more and more shallow.
-        assert!(
-            data.iter().filter(|v| !v[0].is_nan()).last().unwrap()[2]
-                > data.iter().filter(|v| !v[0].is_nan()).next().unwrap()[2]
-        );
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last[2] > first[2]);
     }
 
     #[test]
@@ -889,7 +2075,7 @@ mod test_single_wave {
         create_netcdf3_current(&tmp_path, 100, 100, 1.0, 1.0, u_gradient_fn);
 
         // open the current data
-        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v");
+        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v").unwrap();
 
         // deep water
         let bathymetry_data = &ConstantDepth::new(1000.0);
@@ -978,7 +2164,7 @@ mod test_single_wave {
         create_netcdf3_current(&tmp_path, 100, 100, 1.0, 1.0, u_gradient_fn);
 
         // open the current data
-        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v");
+        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v").unwrap();
 
         // deep water
         let bathymetry_data = &ConstantDepth::new(1000.0);
@@ -1050,6 +2236,40 @@ mod test_single_wave {
         assert!(data.iter().last().unwrap()[1] > data.iter().next().unwrap()[1]);
     }
 
+    #[test]
+    /// A ray through the analytical shear u = y / 100 follows the same path as
+    /// through the current file with the same du/dy in
+    /// `test_simple_dudy_gradient`.
+    fn test_constant_change_dudy() {
+        fn u_gradient_fn(_x: f32, y: f32) -> (f64, f64) {
+            ((y / 100.0) as f64, 0.0)
+        }
+
+        let tmp_file = NamedTempFile::new().unwrap();
+        let tmp_path = tmp_file.into_temp_path();
+        create_netcdf3_current(&tmp_path, 100, 100, 1.0, 1.0, u_gradient_fn);
+        let gridded = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v").unwrap();
+        let analytical = &ConstantChange::builder().dudy(0.01).build().unwrap();
+
+        let bathymetry_data = &ConstantDepth::new(1000.0);
+        let initial_ray = RayState::new(Point::new(1.0, 50.0), WaveNumber::new(0.1, 0.0));
+        let expected = SingleRay::new(bathymetry_data, gridded, &initial_ray)
+            .trace(1.0, 10.0, 1.0)
+            .unwrap();
+        let res = SingleRay::new(bathymetry_data, analytical, &initial_ray)
+            .trace(1.0, 10.0, 1.0)
+            .unwrap();
+
+        // the current file is stored in single precision
+        assert_eq!(res.valid_states().count(), 10);
+        for ((_, a), (_, b)) in res.valid_states().zip(expected.valid_states()) {
+            assert!((a - b).norm() < 1e-5, "expected {}, got {}", b, a);
+        }
+        // the shear turns the ray toward -y
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last[1] < 50.0 && last[3] < 0.0);
+    }
+
     #[test]
     /// This test will create a current file with a gradient in the v direction
     /// where v = (x / 100.0) and u = 0.0. This will create a gradient of dv/dy
@@ -1073,7 +2293,7 @@ mod test_single_wave {
         create_netcdf3_current(&tmp_path, 100, 100, 1.0, 1.0, v_gradient_fn);
 
         // open the current data
-        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v");
+        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v").unwrap();
 
         // deep water
         let bathymetry_data = &ConstantDepth::new(1000.0);
@@ -1117,10 +2337,9 @@ mod test_single_wave {
 
         // verify that the x and kx values are the same
         data.iter().for_each(|r| assert_eq!(r[2], 0.0)); // kx
-                                                         // FIXME: why is the x value slowing increasing by almost f64::EPSILON each iteration?
         data.iter().for_each(|r| {
             assert!(
-                (r[0] - 1.0).abs() < 10.0 * f64::EPSILON,
+                (r[0] - 1.0).abs() <= f64::EPSILON,
                 "expected: 1.0, got: {}",
                 r[0]
             )
@@ -1150,6 +2369,27 @@ mod test_single_wave {
         assert!(data.iter().last().unwrap()[3] < data.iter().next().unwrap()[3]);
     }
 
+    #[test]
+    /// a ray propagating along y in a current with only a dv/dy gradient
+    /// stays exactly on its x, since the direction of the group velocity is
+    /// not computed from the angle of the wavenumber
+    fn test_no_x_drift_along_y() {
+        let bathymetry_data = &ConstantDepth::new(1000.0);
+        let current_data = &ConstantChange::builder().dvdy(0.01).build().unwrap();
+        let initial_ray = RayState::new(Point::new(1.0, 1.0), WaveNumber::new(0.0, 0.1));
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+
+        assert_eq!(res.valid_states().count(), 101);
+        for (_, s) in res.valid_states() {
+            assert!((s.x() - 1.0).abs() <= f64::EPSILON, "x = {}", s.x());
+            assert_eq!(s.kx(), 0.0);
+        }
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last.y() > 100.0);
+    }
+
     #[test]
     /// This test will create a current file with a gradient in the v direction
     /// where v = (x / 100.0) and u = 0.0. This will create a gradient of dv/dx
@@ -1173,7 +2413,7 @@ mod test_single_wave {
         create_netcdf3_current(&tmp_path, 100, 100, 1.0, 1.0, v_gradient_fn);
 
         // open the current data
-        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v");
+        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v").unwrap();
 
         // deep water
         let bathymetry_data = &ConstantDepth::new(1000.0);
@@ -1266,7 +2506,7 @@ mod test_single_wave {
         create_netcdf3_current(&tmp_path, 100, 100, 1.0, 1.0, all_gradient_fn);
 
         // open the current data
-        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v");
+        let current_data = &CartesianCurrent::open(&tmp_path, "x", "y", "u", "v").unwrap();
 
         // deep water
         let bathymetry_data = &ConstantDepth::new(1000.0);
@@ -1313,6 +2553,759 @@ mod test_single_wave {
         assert!(data.iter().last().unwrap()[2] < data.iter().next().unwrap()[2]);
         assert!(data.iter().last().unwrap()[3] < data.iter().next().unwrap()[3]);
     }
+
+    #[test]
+    /// the convenience function gives the same result as `SingleRay`
+    fn test_trace_ray() {
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        let initial_ray = RayState::new(Point::new(10.0, 1000.0), WaveNumber::new(0.1, 0.02));
+        let expected = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+
+        let res = trace_ray(bathymetry_data, None, initial_ray.clone(), 0.0, 100.0, 1.0).unwrap();
+        assert_eq!(res, expected);
+
+        let res = trace_ray(
+            bathymetry_data,
+            Some(current_data),
+            initial_ray,
+            0.0,
+            100.0,
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(res.valid_states().count(), 101);
+    }
+
+    #[test]
+    /// a short wave propagating against a strong current is blocked and the
+    /// reason is reported in the result
+    fn test_blocked_by_opposing_current() {
+        let bathymetry_data = &ConstantDepth::new(1000.0);
+        let current_data = &ConstantCurrent::new(-2.0, 0.0);
+
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(1.0, 0.0));
+        let wave = SingleRay::new(bathymetry_data, current_data, &initial_ray);
+        let res = wave.trace(0.0, 10.0, 1.0).unwrap();
+
+        assert_eq!(res.termination_reason(), Some(TerminationReason::Blocked));
+        assert_eq!(res.valid_states().count(), 1);
+    }
+
+    #[test]
+    /// the output time begins at the start time and increments by the step
+    /// size
+    fn test_start_time() {
+        let bathymetry_data = &ConstantDepth::new(1000.0);
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.1, 0.0));
+        let wave = SingleRay::new(bathymetry_data, current_data, &initial_ray);
+
+        let res = wave.trace_individual(5.0, 10.0, 0.5).unwrap();
+        let (t, _) = res.get();
+        assert_eq!(t.len(), 11);
+        for (i, t) in t.iter().enumerate() {
+            assert!((t - (5.0 + 0.5 * i as f64)).abs() < 1e-12, "t: {}", t);
+        }
+        assert_eq!(t[0], 5.0);
+
+        let res = wave.trace(5.0, 10.0, 0.5).unwrap();
+        let times: Vec<f64> = res.valid_states().map(|(t, _)| t).collect();
+        assert_eq!(times.len(), 11);
+        assert_eq!(times[0], 5.0);
+        assert!((times[10] - 10.0).abs() < 1e-12);
+
+        // the path does not depend on the start time
+        let (_, state) = res.last_valid().unwrap();
+        let (_, expected) = wave.trace(0.0, 5.0, 0.5).unwrap().last_valid().unwrap();
+        assert!((state - expected).norm() < 1e-12);
+    }
+
+    #[test]
+    /// a ray heading to an island of NaN cells stops before the island and
+    /// reports that it reached land
+    fn test_land() {
+        fn island_fn(x: f32, y: f32) -> f64 {
+            if (x - 60.0).hypot(y - 50.0) < 5.0 {
+                f64::NAN
+            } else {
+                1000.0
+            }
+        }
+
+        let bathymetry_data = &bathymetry_from_fn(100, 100, 1.0, 1.0, island_fn).unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        let initial_ray = RayState::new(Point::new(10.0, 50.0), WaveNumber::new(0.1, 0.0));
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        assert_eq!(res.termination_reason(), Some(TerminationReason::Land));
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last[0] > 45.0 && last[0] < 55.0, "x: {}", last[0]);
+
+        // a ray passing by the island leaves the domain
+        let initial_ray = RayState::new(Point::new(10.0, 20.0), WaveNumber::new(0.1, 0.0));
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::LeftBathymetry)
+        );
+    }
+
+    #[test]
+    /// the thinned output keeps every Nth step and the endpoints, including
+    /// the last valid step of a ray stopped at the shore
+    fn test_output_stride() {
+        // shoreline at x = 1000 m
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let full = SingleRay::builder()
+            .bathymetry(bathymetry_data)
+            .start(10.0, 500.0)
+            .wavenumber(0.05, 0.0)
+            .build()
+            .unwrap();
+        let thinned = SingleRay::builder()
+            .bathymetry(bathymetry_data)
+            .start(10.0, 500.0)
+            .wavenumber(0.05, 0.0)
+            .output_stride(7)
+            .build()
+            .unwrap();
+
+        // 101 steps: 0, 7, ..., 98 and the last step
+        let res = thinned.trace_individual(0.0, 100.0, 1.0).unwrap();
+        let (t, _) = res.get();
+        assert_eq!(t.len(), 16);
+        assert_eq!((t[0], t[1], t[14], t[15]), (0.0, 7.0, 98.0, 100.0));
+        let expected = full.trace_individual(0.0, 100.0, 1.0).unwrap();
+        assert_eq!(res.get().1.last(), expected.get().1.last());
+
+        // stopped at the shore
+        let res = thinned.trace(0.0, 1000.0, 1.0).unwrap();
+        let expected = full.trace(0.0, 1000.0, 1.0).unwrap();
+        assert_eq!(res.last_valid(), expected.last_valid());
+        assert_eq!(res.termination_reason(), expected.termination_reason());
+        assert!(res.valid_states().count() < expected.valid_states().count() / 5);
+
+        // the spreading factor is thinned with the states
+        let res = thinned.trace_with_spreading(0.0, 100.0, 1.0).unwrap();
+        let expected = full.trace_with_spreading(0.0, 100.0, 1.0).unwrap();
+        let spreading = res.spreading_factor().unwrap();
+        assert_eq!(res.valid_states().count(), 16);
+        assert_eq!(spreading.len(), 16);
+        for ((t, s), b) in res.valid_states().zip(spreading) {
+            let i = t as usize;
+            assert_eq!(s, expected.valid_states().nth(i).unwrap().1);
+            assert_eq!(*b, expected.spreading_factor().unwrap()[i]);
+        }
+
+        // and so is the energy
+        let res = thinned.trace_with_energy(0.0, 100.0, 1.0, 1.0).unwrap();
+        let expected = full.trace_with_energy(0.0, 100.0, 1.0, 1.0).unwrap();
+        let energy = res.energy().unwrap();
+        assert_eq!(res.valid_states().count(), 16);
+        assert_eq!(energy.len(), 16);
+        for ((t, s), e) in res.valid_states().zip(energy) {
+            let i = t as usize;
+            assert_eq!(s, expected.valid_states().nth(i).unwrap().1);
+            assert_eq!(*e, expected.energy().unwrap()[i]);
+        }
+    }
+
+    #[test]
+    /// in constant depth the rays from a point source are straight lines, so
+    /// the width of the ray tube per radian is the distance traveled
+    fn test_spreading_constant_depth() {
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+        for depth in [1000.0, 10.0] {
+            let bathymetry_data = &ConstantDepth::new(depth);
+            let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.05, 0.02));
+            let ray = SingleRay::new(bathymetry_data, current_data, &initial_ray);
+
+            let res = ray.trace_with_spreading(0.0, 100.0, 1.0).unwrap();
+            let spreading = res.spreading_factor().unwrap();
+            let distance = res.arclength();
+            assert_eq!(spreading.len(), 101);
+            for (j, d) in spreading.iter().zip(distance.iter()) {
+                assert!(
+                    (j - d).abs() < 1e-6 * d.max(1.0),
+                    "Expected {}, but got {}",
+                    d,
+                    j
+                );
+            }
+
+            // the ray itself is not changed
+            let expected = ray.trace(0.0, 100.0, 1.0).unwrap();
+            assert_eq!(
+                res.valid_states().collect::<Vec<_>>(),
+                expected.valid_states().collect::<Vec<_>>()
+            );
+            assert_eq!(expected.spreading_factor(), None);
+        }
+    }
+
+    #[test]
+    /// on a slope, the spreading matches the perpendicular separation of two
+    /// neighboring rays launched at slightly different angles
+    fn test_spreading_slope() {
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+        let launch = |angle: f64| {
+            let k = 0.05;
+            let initial_ray = RayState::new(
+                Point::new(10.0, 500.0),
+                WaveNumber::new(k * angle.cos(), k * angle.sin()),
+            );
+            SingleRay::new(bathymetry_data, current_data, &initial_ray)
+        };
+
+        let angle = 0.5;
+        let dangle = 1e-4;
+        let res = launch(angle).trace_with_spreading(0.0, 60.0, 0.1).unwrap();
+        let spreading = res.spreading_factor().unwrap();
+        let left = launch(angle + dangle).trace(0.0, 60.0, 0.1).unwrap();
+        let right = launch(angle - dangle).trace(0.0, 60.0, 0.1).unwrap();
+
+        for ((i, (_, a)), (_, b)) in left.valid_states().enumerate().zip(right.valid_states()) {
+            let (_, s) = res.valid_states().nth(i).unwrap();
+            let k = s.kx().hypot(s.ky());
+            let separation = (s.kx() * (a.y() - b.y()) - s.ky() * (a.x() - b.x())) / k;
+            let expected = separation / (2.0 * dangle);
+            assert!(
+                (spreading[i] - expected).abs() < 1e-3 * expected.abs().max(1.0),
+                "Expected {}, but got {} at step {}",
+                expected,
+                spreading[i],
+                i
+            );
+        }
+    }
+
+    #[test]
+    /// shoaling on a slope, the frequency conserving mode keeps the frequency
+    /// to machine precision, while it drifts in the standard mode
+    fn test_frequency_conserving() {
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let path = WaveRayPath::new(bathymetry_data, &DEFAULT_CURRENT);
+        let trace = |mode: IntegrationMode| {
+            SingleRay::builder()
+                .bathymetry(bathymetry_data)
+                .start(10.0, 500.0)
+                .wavenumber(0.04, 0.03)
+                .integration_mode(mode)
+                .build()
+                .unwrap()
+                .trace(0.0, 60.0, 1.0)
+                .unwrap()
+        };
+        let drift = |res: &RayResult| {
+            let omega: Vec<f64> = res
+                .valid_states()
+                .map(|(_, s)| path.intrinsic_frequency(&s).unwrap())
+                .collect();
+            omega
+                .iter()
+                .map(|w| ((w - omega[0]) / omega[0]).abs())
+                .fold(0.0, f64::max)
+        };
+
+        let standard = trace(IntegrationMode::Standard);
+        let conserving = trace(IntegrationMode::FrequencyConserving);
+        assert_eq!(standard.valid_states().count(), 61);
+        assert_eq!(conserving.valid_states().count(), 61);
+
+        assert!(drift(&conserving) < 1e-14);
+        assert!(drift(&standard) > 1e3 * drift(&conserving));
+
+        // the paths agree, up to the integration error of the standard mode
+        let (_, a) = standard.valid_states().last().unwrap();
+        let (_, b) = conserving.valid_states().last().unwrap();
+        assert!((a.x() - b.x()).abs() < 1e-2);
+        assert!((a.y() - b.y()).abs() < 1e-2);
+        assert!(b.x() > 10.0 + 100.0);
+    }
+
+    #[test]
+    /// a coarse step jumps over the step of the two-depth bathymetry, while
+    /// the auto-refine mode halves the steps across it and refracts the ray
+    /// as the fine fixed step does
+    fn test_auto_refine() {
+        let bathymetry_data = &bathymetry_from_fn(100, 100, 1.0, 1.0, two_depth_fn).unwrap();
+        let trace = |mode: IntegrationMode, step_size: f64| {
+            SingleRay::builder()
+                .bathymetry(bathymetry_data)
+                .start(10.0, 10.0)
+                .wavenumber(0.007, 0.007)
+                .integration_mode(mode)
+                .build()
+                .unwrap()
+                .trace(0.0, 4.0, step_size)
+                .unwrap()
+        };
+        let final_state = |res: &RayResult| res.last_valid().unwrap().1;
+
+        let coarse = trace(IntegrationMode::Standard, 2.0);
+        let refined = trace(IntegrationMode::AutoRefine { threshold: 0.01 }, 2.0);
+        let fine = trace(IntegrationMode::Standard, 0.001);
+
+        // the coarse ray doesn't see the step
+        assert_eq!(final_state(&coarse).kx(), 0.007);
+        assert!(final_state(&coarse).x() > 50.0);
+
+        let (a, b) = (final_state(&refined), final_state(&fine));
+        assert!((a.kx() - b.kx()).abs() < 1e-2 * b.kx(), "kx: {}", a.kx());
+        assert_eq!(a.ky(), 0.007);
+        assert!((a.x() - b.x()).abs() < 0.1);
+        assert!((a.y() - b.y()).abs() < 0.1);
+        // the steps are only refined across the step of the bathymetry
+        assert!(refined.valid_states().count() < 100);
+        assert_eq!(refined.last_valid().unwrap().0, 4.0);
+        assert_eq!(
+            refined.termination_reason(),
+            Some(TerminationReason::ReachedEndTime)
+        );
+    }
+
+    #[test]
+    /// the frequency conserving mode is not valid with a current
+    fn test_frequency_conserving_current() {
+        let current_data = &ConstantCurrent::new(0.5, 0.0);
+        let res = SingleRay::builder()
+            .current(current_data)
+            .start(0.0, 0.0)
+            .wavenumber(0.05, 0.0)
+            .integration_mode(IntegrationMode::FrequencyConserving)
+            .build()
+            .unwrap()
+            .trace(0.0, 10.0, 1.0);
+        assert!(matches!(res, Err(Error::InvalidArgument)));
+    }
+
+    #[test]
+    /// on a linear beach, the bottom friction dissipates the energy, and
+    /// faster in shallower water
+    fn test_bottom_friction() {
+        // depth of 50 m at x = 0 decreasing by 5 cm per m
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let trace = |friction: f64| {
+            SingleRay::builder()
+                .bathymetry(bathymetry_data)
+                .start(10.0, 500.0)
+                .wavenumber(0.05, 0.0)
+                .bottom_friction(friction)
+                .build()
+                .unwrap()
+                .trace_with_energy(0.0, 120.0, 0.5, 1.0)
+                .unwrap()
+        };
+        let conservative = trace(0.0);
+        let dissipative = trace(0.01);
+        assert!(conservative.energy().unwrap()[0] > 0.0);
+
+        // without friction the energy flux is conserved
+        let path = WaveRayPath::new(bathymetry_data, &DEFAULT_CURRENT);
+        let flux: Vec<f64> = conservative
+            .valid_states()
+            .zip(conservative.energy().unwrap())
+            .map(|((_, s), e)| e * path.intrinsic_group_velocity(&s).unwrap())
+            .collect();
+        assert!(flux.iter().all(|f| (f - flux[0]).abs() < 1e-9 * flux[0]));
+
+        // the dissipated fraction of the energy, which only depends on the
+        // path since both rays follow the same path
+        let remaining: Vec<f64> = dissipative
+            .energy()
+            .unwrap()
+            .iter()
+            .zip(conservative.energy().unwrap())
+            .map(|(e, e0)| e / e0)
+            .collect();
+        let x: Vec<f64> = dissipative.valid_states().map(|(_, s)| s.x()).collect();
+        assert!(x.last().unwrap() > &600.0, "x = {}", x.last().unwrap());
+        assert!(remaining.windows(2).all(|r| r[1] < r[0]));
+        assert!(*remaining.last().unwrap() < 0.99);
+
+        // decay rate per meter over consecutive segments of 50 steps
+        let rates: Vec<f64> = (50..remaining.len())
+            .step_by(50)
+            .map(|i| (remaining[i - 50] / remaining[i]).ln() / (x[i] - x[i - 50]))
+            .collect();
+        assert!(rates.windows(2).all(|r| r[1] > r[0]), "{:?}", rates);
+    }
+
+    #[test]
+    /// a wave shoaling on a linear beach stops where its height reaches
+    /// BREAKER_INDEX times the depth, before the shoreline
+    fn test_breaking() {
+        // depth of 50 m at x = 0 decreasing by 5 cm per m
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let ray = SingleRay::builder()
+            .bathymetry(bathymetry_data)
+            .start(10.0, 500.0)
+            .wavenumber(0.05, 0.0)
+            .build()
+            .unwrap();
+        let res = ray.trace_with_energy(0.0, 1000.0, 0.5, 1.0).unwrap();
+        assert_eq!(res.termination_reason(), Some(TerminationReason::Breaking));
+
+        // the wave height from the energy, H = 2 a with E = rho g a^2 / 2
+        let ratio: Vec<f64> = res
+            .valid_states()
+            .zip(res.energy().unwrap())
+            .map(|((_, s), e)| {
+                let h = bathymetry_data
+                    .depth_f64(&Point::new(s.x(), s.y()))
+                    .unwrap() as f64;
+                2.0 * (2.0 * e / (RHO * G)).sqrt() / h
+            })
+            .collect();
+        let (last, before) = ratio.split_last().unwrap();
+        assert!(*last > BREAKER_INDEX, "H / h: {}", last);
+        assert!(before.iter().all(|r| *r <= BREAKER_INDEX));
+        // a 2 m high wave breaks in about 2.6 m of water
+        let (_, s) = res.last_valid().unwrap();
+        assert!(s.x() > 900.0 && s.x() < 960.0, "x: {}", s.x());
+
+        // the ray without energy is traced up to the shoreline
+        assert_eq!(
+            ray.trace(0.0, 1000.0, 0.5).unwrap().termination_reason(),
+            Some(TerminationReason::ShorelineReached)
+        );
+    }
+
+    #[test]
+    /// tracing until a time is the same as tracing with that end time
+    fn test_trace_until_time() {
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let ray = SingleRay::builder()
+            .bathymetry(bathymetry_data)
+            .start(10.0, 500.0)
+            .wavenumber(0.04, 0.03)
+            .build()
+            .unwrap();
+        let res = ray
+            .trace_until(0.0, 1.0, StopCondition::AtTime(50.0))
+            .unwrap();
+        assert_eq!(res, ray.trace(0.0, 50.0, 1.0).unwrap());
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::ReachedEndTime)
+        );
+        assert!(ray
+            .trace_until(0.0, 1.0, StopCondition::AtTime(-50.0))
+            .is_err());
+    }
+
+    #[test]
+    /// the ray stops exactly where it reaches x, in either direction
+    fn test_trace_until_x() {
+        let bathymetry_data = &ConstantDepth::new(100.0);
+        let trace = |kx: f64, condition: StopCondition| {
+            SingleRay::builder()
+                .bathymetry(bathymetry_data)
+                .start(0.0, 0.0)
+                .wavenumber(kx, 0.01)
+                .build()
+                .unwrap()
+                .trace_until(0.0, 1.0, condition)
+                .unwrap()
+        };
+
+        let res = trace(0.05, StopCondition::AtXAbove(1234.5));
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::ConditionMet)
+        );
+        let (t, last) = res.last_valid().unwrap();
+        assert!((last.x() - 1234.5).abs() < 1e-9, "x = {}", last.x());
+        assert!(t.fract() != 0.0);
+        assert!(res.valid_states().all(|(_, s)| s.x() <= 1234.5 + 1e-9));
+
+        let res = trace(-0.05, StopCondition::AtXBelow(-321.0));
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::ConditionMet)
+        );
+        let (_, last) = res.last_valid().unwrap();
+        assert!((last.x() + 321.0).abs() < 1e-9, "x = {}", last.x());
+    }
+
+    #[test]
+    /// on a slope, the ray stops where it reaches the depth
+    fn test_trace_until_depth() {
+        // depth of 50 m at x = 0 decreasing by 5 cm per m
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let res = SingleRay::builder()
+            .bathymetry(bathymetry_data)
+            .start(10.0, 500.0)
+            .wavenumber(0.04, 0.01)
+            .build()
+            .unwrap()
+            .trace_until(0.0, 1.0, StopCondition::AtDepthBelow(20.0))
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::ConditionMet)
+        );
+        let (_, last) = res.last_valid().unwrap();
+        let depth = bathymetry_data
+            .depth(&Point::new(last.x() as f32, last.y() as f32))
+            .unwrap();
+        assert!((depth - 20.0).abs() < 1e-3, "depth = {}", depth);
+    }
+
+    #[test]
+    /// the ray is traced until it leaves the domain of the bathymetry
+    fn test_trace_until_boundary() {
+        let bathymetry_data = &bathymetry_from_fn(11, 11, 100.0, 100.0, |_, _| 50.0).unwrap();
+        let res = SingleRay::builder()
+            .bathymetry(bathymetry_data)
+            .start(500.0, 500.0)
+            .wavenumber(0.03, 0.04)
+            .build()
+            .unwrap()
+            .trace_until(0.0, 1.0, StopCondition::OnBoundary)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::LeftBathymetry)
+        );
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last.y() > 900.0, "y = {}", last.y());
+    }
+
+    #[test]
+    /// with a periodic x axis, a ray crosses the seam of a zonally uniform
+    /// depth without noticing it, instead of leaving the domain
+    fn test_periodic_x() {
+        let tmp_file = NamedTempFile::new().unwrap();
+        let tmp_path = tmp_file.into_temp_path();
+        // x from 0 to 90 m, so the period is 100 m
+        create_netcdf3_bathymetry(&tmp_path, 10, 101, 10.0, 10.0, |_x, y| {
+            20.0 + 0.01 * y as f64
+        });
+        let bathymetry_data = CartesianNetcdf3::open(&tmp_path, "x", "y", "depth").unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(50.0, 500.0), WaveNumber::new(0.1, 0.0));
+
+        let res = SingleRay::new(&bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::LeftBathymetry)
+        );
+
+        let bathymetry_data = bathymetry_data.with_boundary_x(BoundaryX::Periodic);
+        let res = SingleRay::new(&bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::ReachedEndTime)
+        );
+        assert_eq!(res.valid_states().count(), 101);
+        // crossed the seam several times, without any change of kx, since the
+        // depth doesn't depend on x
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last[0] > 300.0, "x: {}", last[0]);
+        assert!(res.valid_states().all(|(_, s)| s[2] == 0.1));
+        let wrapped = bathymetry_data.wrap_x(last[0] as f32);
+        assert!((0.0..100.0).contains(&wrapped));
+    }
+
+    #[test]
+    /// tracing a ray backward in time from the end of a forward trace
+    /// recovers the initial state
+    fn test_backward() {
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantChange::builder()
+            .v0(0.2)
+            .dvdx(-0.001)
+            .build()
+            .unwrap();
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.02, 0.02));
+        let forward = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        let (_, end) = forward.last_valid().unwrap();
+        assert!(end[0] > 100.0);
+
+        let final_ray = RayState::new(Point::new(end[0], end[1]), WaveNumber::new(end[2], end[3]));
+        let backward = SingleRay::new(bathymetry_data, current_data, &final_ray)
+            .trace(100.0, 0.0, -1.0)
+            .unwrap();
+        let times: Vec<f64> = backward.valid_states().map(|(t, _)| t).collect();
+        assert_eq!(times.len(), 101);
+        assert_eq!(times[0], 100.0);
+        assert!(times[100].abs() < 1e-9);
+
+        // the depth of `ConstantSlope` is single precision
+        let (_, start) = backward.last_valid().unwrap();
+        let expected = State::from(initial_ray);
+        assert!((start - expected).norm() < 1e-4, "start: {}", start);
+    }
+
+    #[test]
+    /// a step size that doesn't go toward the end time is an error
+    fn test_invalid_time_span() {
+        let bathymetry_data = &ConstantDepth::new(1000.0);
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.1, 0.0));
+        let wave = SingleRay::new(bathymetry_data, current_data, &initial_ray);
+        for (start, end, step) in [(0.0, 10.0, -1.0), (10.0, 0.0, 1.0), (0.0, 10.0, 0.0)] {
+            assert!(matches!(
+                wave.trace(start, end, step),
+                Err(Error::ArgumentOutOfBounds)
+            ));
+            assert!(matches!(
+                wave.trace_individual(start, end, step),
+                Err(Error::ArgumentOutOfBounds)
+            ));
+        }
+    }
+
+    #[test]
+    /// the builder creates the same ray as `new`, with default bathymetry and
+    /// current, and fails without the initial conditions.
+    fn test_single_ray_builder() {
+        let bathymetry_data = &ConstantDepth::new(2000.0);
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(10.0, 20.0), WaveNumber::new(0.1, 0.05));
+        let expected = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 10.0, 1.0)
+            .unwrap();
+
+        let ray = SingleRay::builder()
+            .bathymetry(bathymetry_data)
+            .current(current_data)
+            .start(10.0, 20.0)
+            .wavenumber(0.1, 0.05)
+            .build()
+            .unwrap();
+        assert_eq!(ray.trace(0.0, 10.0, 1.0).unwrap(), expected);
+
+        // the default depth is 2000 m and the default current is zero
+        let ray = SingleRay::builder()
+            .start(10.0, 20.0)
+            .wavenumber(0.1, 0.05)
+            .build()
+            .unwrap();
+        assert_eq!(ray.trace(0.0, 10.0, 1.0).unwrap(), expected);
+
+        assert!(SingleRay::builder().start(10.0, 20.0).build().is_err());
+        assert!(SingleRay::builder().wavenumber(0.1, 0.05).build().is_err());
+    }
+
+    #[test]
+    /// a ray propagating in the x direction stops at the edge of whichever of
+    /// the bathymetry or current grids is smaller, and reports which one.
+    fn test_left_domain() {
+        let bathymetry_file = NamedTempFile::new().unwrap().into_temp_path();
+        let current_file = NamedTempFile::new().unwrap().into_temp_path();
+        let initial_ray = RayState::new(Point::new(10.0, 50.0), WaveNumber::new(0.1, 0.0));
+
+        // the current grid is smaller than the bathymetry grid
+        create_netcdf3_bathymetry(&bathymetry_file, 100, 100, 1.0, 1.0, |_, _| 1000.0);
+        create_netcdf3_current(&current_file, 50, 100, 1.0, 1.0, |_, _| (0.0, 0.0));
+        let bathymetry_data = &CartesianNetcdf3::open(&bathymetry_file, "x", "y", "depth").unwrap();
+        let current_data = &CartesianCurrent::open(&current_file, "x", "y", "u", "v").unwrap();
+
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::LeftCurrent)
+        );
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last[0] < 50.0 && last[0] > 40.0, "x: {}", last[0]);
+
+        // the bathymetry grid is smaller than the current grid
+        create_netcdf3_bathymetry(&bathymetry_file, 50, 100, 1.0, 1.0, |_, _| 1000.0);
+        create_netcdf3_current(&current_file, 100, 100, 1.0, 1.0, |_, _| (0.0, 0.0));
+        let bathymetry_data = &CartesianNetcdf3::open(&bathymetry_file, "x", "y", "depth").unwrap();
+        let current_data = &CartesianCurrent::open(&current_file, "x", "y", "u", "v").unwrap();
+
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::LeftBathymetry)
+        );
+        let (_, last) = res.last_valid().unwrap();
+        assert!(last[0] < 50.0 && last[0] > 40.0, "x: {}", last[0]);
+    }
+
+    #[test]
+    /// the observer is called once for each step of the result, but not for
+    /// the NaN steps after the ray left the domain
+    fn test_trace_with_observer() {
+        let bathymetry_file = NamedTempFile::new().unwrap().into_temp_path();
+        create_netcdf3_bathymetry(&bathymetry_file, 50, 100, 1.0, 1.0, |_, _| 10.0);
+        let bathymetry_data = &CartesianNetcdf3::open(&bathymetry_file, "x", "y", "depth").unwrap();
+        let initial_ray = RayState::new(Point::new(10.0, 50.0), WaveNumber::new(0.1, 0.0));
+
+        let mut steps = Vec::new();
+        let res = SingleRay::new(bathymetry_data, &DEFAULT_CURRENT, &initial_ray)
+            .trace_with_observer(0.0, 100.0, 1.0, |t, s| steps.push((t, s.kx() * 10.0)))
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::LeftBathymetry)
+        );
+
+        let valid: Vec<(f64, State)> = res.valid_states().collect();
+        assert_eq!(steps.len(), valid.len());
+        for ((t, kh), (t_res, s)) in steps.iter().zip(&valid) {
+            assert_eq!(t, t_res);
+            assert_eq!(*kh, s.kx() * 10.0);
+        }
+    }
+
+    #[test]
+    /// the reason a ray stopped is recorded for the end time, the shoreline
+    /// and a NaN in the initial state
+    fn test_termination_reasons() {
+        let bathymetry_data = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        // stops offshore at the end time
+        let initial_ray = RayState::new(Point::new(10.0, 500.0), WaveNumber::new(0.05, 0.0));
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 10.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::ReachedEndTime)
+        );
+
+        // runs up the slope until the depth vanishes
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 1000.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::ShorelineReached)
+        );
+
+        let initial_ray = RayState::new(Point::new(10.0, 500.0), WaveNumber::new(f64::NAN, 0.0));
+        let res = SingleRay::new(bathymetry_data, current_data, &initial_ray)
+            .trace(0.0, 10.0, 1.0)
+            .unwrap();
+        assert_eq!(
+            res.termination_reason(),
+            Some(TerminationReason::NaNInInput)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -1320,13 +3313,46 @@ mod test_many_waves {
 
     use crate::{
         bathymetry::{
-            BathymetryData, ConstantSlope,
+            BathymetryData, CartesianNetcdf3, ConstantSlope,
         },
-        current::ConstantCurrent,
+        current::{CartesianCurrent, ConstantCurrent},
+        io::utility::{create_netcdf3_bathymetry, create_netcdf3_current},
+        wave_ray_path::{StateView, TerminationReason},
     };
     use crate::datatype::{Point, RayState, WaveNumber};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::NamedTempFile;
+
+    use super::{GridMismatch, ManyRays, RayResult, SharedManyRays, SingleRay, TraceOutput};
+    use crate::dispersion::G;
+    use crate::error::Error;
+
+    #[test]
+    /// each ray from `trace_rays` carries its own termination reason
+    fn test_trace_rays_termination_reasons() {
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+        let initial_waves = vec![
+            RayState::new(Point::new(10.0, 500.0), WaveNumber::new(0.05, 0.0)),
+            RayState::new(Point::new(10.0, 500.0), WaveNumber::new(-0.05, 0.0)),
+        ];
 
-    use super::ManyRays;
+        let results = ManyRays::new(bathymetry_data, current_data, &initial_waves)
+            .trace_rays(0.0, 1000.0, 1.0);
+        let reasons: Vec<_> = results
+            .iter()
+            .map(|r| r.as_ref().unwrap().termination_reason())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                Some(TerminationReason::ShorelineReached),
+                Some(TerminationReason::ReachedEndTime),
+            ]
+        );
+    }
 
     #[test]
     /// check that output with test values from single wave works
@@ -1359,4 +3385,332 @@ mod test_many_waves {
         // TODO: test to verify each instance of many ray against single ray
         //
     }
+
+    #[test]
+    /// the endpoints are the last valid positions of the rays traced
+    /// individually, including rays that reach the shore before the end time
+    fn test_trace_endpoints() {
+        // shoreline at x = 1000 m
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        let initial_waves = vec![
+            RayState::new(Point::new(10.0, 10.0), WaveNumber::new(0.05, 0.0)),
+            RayState::new(Point::new(10.0, 20.0), WaveNumber::new(-0.05, 0.01)),
+            RayState::new(Point::new(900.0, 30.0), WaveNumber::new(0.05, 0.0)),
+        ];
+
+        let waves = ManyRays::new(bathymetry_data, current_data, &initial_waves);
+        let endpoints = waves.trace_endpoints(0.0, 500.0, 1.0);
+        assert_eq!(endpoints.len(), initial_waves.len());
+
+        for (endpoint, init) in endpoints.iter().zip(initial_waves.iter()) {
+            let expected = SingleRay::new(bathymetry_data, current_data, init)
+                .trace(0.0, 500.0, 1.0)
+                .unwrap()
+                .last_valid_position();
+            assert_eq!(*endpoint, expected);
+        }
+        // the last ray reached the shore
+        let (x, _) = endpoints[2].unwrap();
+        assert!(x > 990.0 && x < 1000.0, "x: {}", x);
+    }
+
+    #[test]
+    /// each ray traced by `trace_many` is identical to the same ray traced
+    /// individually, in the same order as the initial rays
+    fn test_many_waves_match_single() {
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.1, -0.1);
+
+        let initial_waves: Vec<RayState<f64>> = (0..100)
+            .map(|i| {
+                RayState::new(
+                    Point::new(10.0, i as f64),
+                    WaveNumber::new(0.05, 0.001 * i as f64),
+                )
+            })
+            .collect();
+
+        let waves = ManyRays::new(bathymetry_data, current_data, &initial_waves);
+        let results = waves.trace_many(0.0, 50.0, 1.0);
+        assert_eq!(results.len(), initial_waves.len());
+
+        for (res, init) in results.iter().zip(initial_waves.iter()) {
+            let single = SingleRay::new(bathymetry_data, current_data, init)
+                .trace_individual(0.0, 50.0, 1.0)
+                .unwrap();
+            let (t, s) = res.as_ref().unwrap().get();
+            assert_eq!(t, single.get().0);
+            assert_eq!(s, single.get().1);
+        }
+    }
+
+    #[test]
+    /// rays with the same period launched at different depths have different
+    /// initial wavenumbers, each consistent with the dispersion relation
+    fn test_from_periods() {
+        let bathymetry_data = ConstantSlope::builder()
+            .h0(200.0)
+            .dhdx(-0.05)
+            .build()
+            .unwrap();
+        let current_data = ConstantCurrent::new(0.0, 0.0);
+        // h = 200 m and h = 10 m
+        let inits = [(0.0, 0.0, 10.0, 0.0), (3800.0, 0.0, 10.0, 0.0)];
+        let waves = ManyRays::from_periods(&bathymetry_data, &current_data, &inits).unwrap();
+
+        let k: Vec<f64> = waves
+            .initial_rays
+            .iter()
+            .map(|ray| ray.wave_number().kx().hypot(*ray.wave_number().ky()))
+            .collect();
+        // the wavelength is shorter in shallow water
+        assert!(k[1] > k[0], "k: {:?}", k);
+        for (k, h) in k.iter().zip([200.0, 10.0]) {
+            let sigma = (G * k * (k * h).tanh()).sqrt();
+            assert!((sigma - 2.0 * std::f64::consts::PI / 10.0).abs() < 1e-6);
+        }
+
+        let results = waves.trace_many(0.0, 10.0, 1.0);
+        assert!(results.iter().all(|res| res.is_some()));
+
+        // the depth at the initial point has to be positive
+        let dry = [(5000.0, 0.0, 10.0, 0.0)];
+        assert!(ManyRays::from_periods(&bathymetry_data, &current_data, &dry).is_err());
+    }
+
+    #[test]
+    /// the output stride is applied to each ray
+    fn test_many_waves_output_stride() {
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let initial_waves: Vec<RayState<f64>> = (0..5)
+            .map(|i| RayState::new(Point::new(10.0, i as f64), WaveNumber::new(0.05, 0.0)))
+            .collect();
+
+        let waves = ManyRays::builder()
+            .bathymetry_data(bathymetry_data)
+            .initial_rays(&initial_waves)
+            .output_stride(10)
+            .build()
+            .unwrap();
+        for res in waves.trace_many(0.0, 100.0, 1.0) {
+            let (t, _) = res.as_ref().unwrap().get();
+            assert_eq!(t.len(), 11);
+            assert_eq!(t.last(), Some(&100.0));
+        }
+    }
+
+    #[test]
+    /// the metadata records the parameters of the run, and survives a round
+    /// trip through JSON with the results
+    fn test_trace_with_metadata() {
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let initial_waves: Vec<RayState<f64>> = (0..3)
+            .map(|i| RayState::new(Point::new(10.0, i as f64), WaveNumber::new(0.05, 0.0)))
+            .collect();
+
+        let waves = ManyRays::builder()
+            .bathymetry_data(bathymetry_data)
+            .initial_rays(&initial_waves)
+            .output_stride(5)
+            .build()
+            .unwrap();
+        let mut output = waves.trace_with_metadata(0.0, 100.0, 0.5);
+        output.metadata = output
+            .metadata
+            .with_attribute("bathymetry_path", "bathymetry.nc")
+            .with_attribute("interpolation_mode", "Bilinear");
+
+        let metadata = &output.metadata;
+        assert_eq!(metadata.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(metadata.integrator, "Rk4");
+        assert_eq!(
+            (metadata.start_time, metadata.end_time, metadata.step_size),
+            (0.0, 100.0, 0.5)
+        );
+        assert_eq!(metadata.gravity, G);
+        assert_eq!(metadata.output_stride, 5);
+        assert_eq!(metadata.n_rays, 3);
+        assert_eq!(
+            metadata.attributes().get("bathymetry_path").unwrap(),
+            "bathymetry.nc"
+        );
+
+        assert_eq!(output.results, waves.trace_rays(0.0, 100.0, 0.5));
+
+        let converted: TraceOutput = serde_json::from_str(&output.as_json()).unwrap();
+        assert_eq!(converted.metadata, output.metadata);
+        assert_eq!(converted.results.len(), 3);
+    }
+
+    #[test]
+    /// the result at each index is the ray launched from the initial state at
+    /// the same index
+    fn test_trace_many_order() {
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        // distinguishable rays, with a different start and direction each
+        let initial_waves: Vec<RayState<f64>> = (0..200)
+            .map(|i| {
+                let angle = (i as f64 / 200.0 - 0.5) * 1.5;
+                RayState::new(
+                    Point::new(10.0 + (i % 7) as f64, i as f64 * 10.0),
+                    WaveNumber::new(0.05 * angle.cos(), 0.05 * angle.sin()),
+                )
+            })
+            .collect();
+
+        let waves = ManyRays::new(bathymetry_data, current_data, &initial_waves);
+        let results = waves.trace_many(0.0, 50.0, 1.0);
+        assert_eq!(results.len(), initial_waves.len());
+
+        for (init, res) in initial_waves.iter().zip(results.iter()) {
+            let (_, y_out) = res.as_ref().unwrap().get();
+            assert_eq!(y_out[0].x(), *init.point().x());
+            assert_eq!(y_out[0].y(), *init.point().y());
+            assert_eq!(y_out[0].kx(), *init.wave_number().kx());
+            assert_eq!(y_out[0].ky(), *init.wave_number().ky());
+
+            let single = SingleRay::new(bathymetry_data, current_data, init)
+                .trace_individual(0.0, 50.0, 1.0)
+                .unwrap();
+            assert_eq!(res.as_ref().unwrap().get(), single.get());
+        }
+    }
+
+    #[test]
+    /// the progress callback is called once for each ray, and the results are
+    /// the same as `trace_many`
+    fn test_trace_many_with_progress() {
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        let initial_waves: Vec<RayState<f64>> = (0..20)
+            .map(|i| RayState::new(Point::new(10.0, i as f64), WaveNumber::new(0.05, 0.0)))
+            .collect();
+
+        let waves = ManyRays::new(bathymetry_data, current_data, &initial_waves);
+        let completed: Vec<AtomicUsize> =
+            initial_waves.iter().map(|_| AtomicUsize::new(0)).collect();
+        let results = waves.trace_many_with_progress(0.0, 50.0, 1.0, |i| {
+            completed[i].fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert!(completed.iter().all(|c| c.load(Ordering::Relaxed) == 1));
+
+        let expected = waves.trace_many(0.0, 50.0, 1.0);
+        for (res, exp) in results.iter().zip(expected.iter()) {
+            assert_eq!(res.as_ref().map(|r| r.get()), exp.as_ref().map(|r| r.get()));
+        }
+    }
+
+    #[test]
+    /// rays on land or without a wavenumber are invalid
+    fn test_validate_inits() {
+        // land for x > 1000 m
+        let bathymetry_data: &dyn BathymetryData = &ConstantSlope::builder().build().unwrap();
+        let current_data = &ConstantCurrent::new(0.0, 0.0);
+
+        let initial_waves = vec![
+            RayState::new(Point::new(10.0, 10.0), WaveNumber::new(0.1, 0.0)),
+            RayState::new(Point::new(2000.0, 10.0), WaveNumber::new(0.1, 0.0)),
+            RayState::new(Point::new(10.0, 10.0), WaveNumber::new(0.0, 0.0)),
+            RayState::with_period(Point::new(500.0, 10.0), 10.0, 0.0, bathymetry_data).unwrap(),
+        ];
+
+        let waves = ManyRays::new(bathymetry_data, current_data, &initial_waves);
+        let results = waves.validate_inits();
+
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::InvalidInitialCondition(_))));
+        assert!(matches!(results[2], Err(Error::InvalidInitialCondition(_))));
+        assert!(results[3].is_ok());
+    }
+
+    #[test]
+    /// the shared datasets can be used from a thread owned by the caller and
+    /// give the same results as the borrowed ones
+    fn test_shared_many_rays() {
+        let bathymetry_data = Arc::new(ConstantSlope::builder().build().unwrap());
+        let current_data = Arc::new(ConstantCurrent::new(0.1, 0.0));
+
+        let initial_waves: Vec<RayState<f64>> = (1..5)
+            .map(|i| {
+                RayState::new(
+                    Point::new(10.0, i as f64 * 10.0),
+                    WaveNumber::new(0.1, 0.01),
+                )
+            })
+            .collect();
+
+        let expected: Vec<Option<RayResult>> = ManyRays::new(
+            bathymetry_data.as_ref(),
+            current_data.as_ref(),
+            &initial_waves,
+        )
+        .trace_many(0.0, 100.0, 1.0)
+        .into_iter()
+        .map(|r| r.map(RayResult::from))
+        .collect();
+
+        let waves = Arc::new(SharedManyRays::new(
+            bathymetry_data,
+            current_data,
+            initial_waves,
+        ));
+        let handle = {
+            let waves = Arc::clone(&waves);
+            thread::spawn(move || waves.trace_many(0.0, 100.0, 1.0))
+        };
+        let results: Vec<Option<RayResult>> = handle
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.map(RayResult::from))
+            .collect();
+
+        assert_eq!(results, expected);
+    }
+
+    #[test]
+    /// grids with spacings more than 4 times apart are reported, while
+    /// similar grids and data without a grid are compatible
+    fn test_check_grid_compatibility() {
+        let bathymetry_file = NamedTempFile::new().unwrap().into_temp_path();
+        create_netcdf3_bathymetry(&bathymetry_file, 101, 101, 1.0, 1.0, |_, _| 50.0);
+        let bathymetry_data = &CartesianNetcdf3::open(&bathymetry_file, "x", "y", "depth").unwrap();
+        let initial_rays = vec![RayState::new(
+            Point::new(10.0, 10.0),
+            WaveNumber::new(0.1, 0.0),
+        )];
+
+        // 10 m current grid over a 1 m bathymetry grid
+        let coarse_file = NamedTempFile::new().unwrap().into_temp_path();
+        create_netcdf3_current(&coarse_file, 11, 21, 10.0, 5.0, |_, _| (0.0, 0.0));
+        let coarse = &CartesianCurrent::open(&coarse_file, "x", "y", "u", "v").unwrap();
+        let rays = ManyRays::new(bathymetry_data, coarse, &initial_rays);
+        assert_eq!(
+            rays.check_grid_compatibility(),
+            Some(GridMismatch {
+                bathymetry_spacing: (1.0, 1.0),
+                current_spacing: (10.0, 5.0),
+                ratio: 10.0,
+            })
+        );
+
+        // 2 m current grid
+        let fine_file = NamedTempFile::new().unwrap().into_temp_path();
+        create_netcdf3_current(&fine_file, 51, 51, 2.0, 2.0, |_, _| (0.0, 0.0));
+        let fine = &CartesianCurrent::open(&fine_file, "x", "y", "u", "v").unwrap();
+        let rays = ManyRays::new(bathymetry_data, fine, &initial_rays);
+        assert_eq!(rays.check_grid_compatibility(), None);
+
+        // the constant current has no grid
+        let constant = &ConstantCurrent::new(0.0, 0.0);
+        let rays = ManyRays::new(bathymetry_data, constant, &initial_rays);
+        assert_eq!(rays.check_grid_compatibility(), None);
+    }
 }
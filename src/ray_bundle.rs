@@ -0,0 +1,461 @@
+//! A bundle of rays launched from adjacent initial conditions.
+//!
+//! Neighboring rays of a bundle are used to detect caustics, where the rays
+//! cross each other, indicating the focusing of the wave energy.
+
+use crate::bathymetry::BathymetryData;
+use crate::error::{Error, Result};
+use crate::ray_result::RayResult;
+use crate::wave_ray_path::{State, Time};
+
+/// A set of rays launched from adjacent initial conditions
+///
+/// The rays are expected to be ordered, so that the rays `i` and `i + 1` are
+/// neighbors, and traced with the same start time and step size, so that the
+/// steps of every ray correspond to the same times.
+pub(crate) struct RayBundle {
+    /// the traced rays in order
+    rays: Vec<RayResult>,
+}
+
+#[allow(dead_code)]
+impl RayBundle {
+    /// Construct a new `RayBundle`
+    ///
+    /// # Arguments
+    /// `rays` : `Vec<RayResult>`
+    /// - the traced rays, ordered so that consecutive rays are neighbors
+    ///
+    /// # Returns
+    /// `Self` : the new `RayBundle`
+    pub(crate) fn new(rays: Vec<RayResult>) -> Self {
+        RayBundle { rays }
+    }
+
+    /// The rays of the bundle
+    pub(crate) fn rays(&self) -> &[RayResult] {
+        &self.rays
+    }
+
+    /// Time of the first caustic between each pair of neighboring rays
+    ///
+    /// The separation between neighboring rays is projected on the direction
+    /// perpendicular to the first ray, given by the cross product of the unit
+    /// wavenumber vector and the separation vector. When the rays cross, the
+    /// perpendicular spacing collapses to zero and the sign of the cross
+    /// product changes.
+    ///
+    /// # Returns
+    /// `Vec<Option<Time>>` : for each pair of neighboring rays (`i`, `i + 1`),
+    /// the first time the rays cross, or `None` if they don't cross while both
+    /// are valid. The length is one less than the number of rays.
+    pub(crate) fn caustic_times(&self) -> Vec<Option<Time>> {
+        self.rays
+            .windows(2)
+            .map(|pair| {
+                let mut previous_sign = None;
+                for ((t, a), (_, b)) in pair[0].valid_states().zip(pair[1].valid_states()) {
+                    let sign = perpendicular_separation(&a, &b).signum();
+                    match previous_sign {
+                        None => previous_sign = Some(sign),
+                        Some(s) if s != sign => return Some(t),
+                        Some(_) => (),
+                    }
+                }
+                None
+            })
+            .collect()
+    }
+
+    /// Wavefront, i.e. isochrone, of the bundle at the given time
+    ///
+    /// The rays are launched simultaneously, thus the points reached by every
+    /// ray at the same time form the wavefront. The position of each ray is
+    /// linearly interpolated between the valid steps around `t`.
+    ///
+    /// # Arguments
+    /// `t` : `Time`
+    /// - the time \[s\] of the wavefront
+    ///
+    /// # Returns
+    /// `Vec<(f64, f64)>` : the (x, y) of the rays at `t`, in the order of the
+    /// rays. The rays that terminated before `t`, or that don't reach it, are
+    /// excluded.
+    pub(crate) fn wavefront_at(&self, t: Time) -> Vec<(f64, f64)> {
+        self.rays
+            .iter()
+            .filter_map(|ray| ray.position_at(t).map(|(x, y, ..)| (x, y)))
+            .collect()
+    }
+
+    /// Position and direction of each ray where it first crosses a contour
+    ///
+    /// For example, with a depth contour near the shore, the crossings give
+    /// the distribution of the arrival positions and directions of a fan of
+    /// rays along the coast. The time, position, and wavenumber are linearly
+    /// interpolated between the valid steps on each side of the contour.
+    ///
+    /// # Arguments
+    /// `contour` : `&[(f32, f32)]`
+    /// - the contour as consecutive pairs of points, each pair being a
+    ///   segment, as returned by `CartesianNetcdf3::depth_contour`
+    ///
+    /// # Returns
+    /// `Vec<Option<(Time, f64, f64, f64)>>` : for each ray, the time \[s\],
+    /// the (x, y) \[m\], and the direction of the wavenumber \[rad\],
+    /// counterclockwise from the x axis, where the ray first crosses the
+    /// contour, or `None` if it doesn't cross it while valid.
+    pub(crate) fn contour_crossings(
+        &self,
+        contour: &[(f32, f32)],
+    ) -> Vec<Option<(Time, f64, f64, f64)>> {
+        let segments: Vec<[(f64, f64); 2]> = contour
+            .chunks_exact(2)
+            .map(|pair| {
+                [
+                    (pair[0].0 as f64, pair[0].1 as f64),
+                    (pair[1].0 as f64, pair[1].1 as f64),
+                ]
+            })
+            .collect();
+        self.rays
+            .iter()
+            .map(|ray| first_crossing(ray, &segments))
+            .collect()
+    }
+
+    /// Wave height along each ray from the shoaling and the refraction
+    ///
+    /// The wave height is
+    ///
+    /// $H = H_0 K_s K_r$
+    ///
+    /// where $K_s$ is the `RayResult::shoaling_coefficient` and
+    /// $K_r = \sqrt{b_0 / b}$ is the refraction coefficient from the width
+    /// $b$ of the ray tube, i.e. the separation between neighboring rays
+    /// perpendicular to the ray. The width is the mean of the separations to
+    /// both neighbors, or to the only neighbor for the first and last rays.
+    ///
+    /// # Arguments
+    /// `h0` : `f64`
+    /// - the wave height \[m\] at the start of the rays
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the rays
+    ///
+    /// # Returns
+    /// `Result<Vec<Vec<f64>>>` : for each ray, the wave height \[m\] at each
+    /// step while the ray and its neighbors are valid.
+    ///
+    /// # Errors
+    /// - `Error::InvalidArgument` : the bundle has less than two rays, so the
+    ///   width of the ray tube is undefined.
+    /// - errors from `RayResult::shoaling_coefficient`.
+    ///
+    /// # Note
+    /// Where neighboring rays cross, at a caustic (see `caustic_times`), the
+    /// width vanishes and the linear theory predicts an infinite height.
+    pub(crate) fn wave_height_transformation(
+        &self,
+        h0: f64,
+        bathymetry: &dyn BathymetryData,
+    ) -> Result<Vec<Vec<f64>>> {
+        if self.rays.len() < 2 {
+            return Err(Error::InvalidArgument);
+        }
+        // perpendicular separation between the rays i and i + 1 at each step
+        let separations: Vec<Vec<f64>> = self
+            .rays
+            .windows(2)
+            .map(|pair| {
+                pair[0]
+                    .valid_states()
+                    .zip(pair[1].valid_states())
+                    .map(|((_, a), (_, b))| perpendicular_separation(&a, &b).abs())
+                    .collect()
+            })
+            .collect();
+
+        (0..self.rays.len())
+            .map(|i| {
+                let neighbors: Vec<&Vec<f64>> = [i.checked_sub(1), Some(i)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|j| separations.get(j))
+                    .collect();
+                let n = neighbors.iter().map(|s| s.len()).min().unwrap_or(0);
+                let width: Vec<f64> = (0..n)
+                    .map(|step| {
+                        neighbors.iter().map(|s| s[step]).sum::<f64>() / neighbors.len() as f64
+                    })
+                    .collect();
+                let ks = self.rays[i].shoaling_coefficient(bathymetry)?;
+                Ok(ks
+                    .iter()
+                    .zip(&width)
+                    .map(|(ks, b)| h0 * ks * (width[0] / b).sqrt())
+                    .collect())
+            })
+            .collect()
+    }
+}
+
+/// Time, position, and direction where the ray first crosses one of the
+/// segments, see `RayBundle::contour_crossings`
+fn first_crossing(ray: &RayResult, segments: &[[(f64, f64); 2]]) -> Option<(Time, f64, f64, f64)> {
+    let states: Vec<(Time, State)> = ray.valid_states().collect();
+    states.windows(2).find_map(|pair| {
+        let (ta, sa) = pair[0];
+        let (tb, sb) = pair[1];
+        // the first crossing along the step, as a fraction of the step
+        let fraction = segments
+            .iter()
+            .filter_map(|segment| intersection((sa[0], sa[1]), (sb[0], sb[1]), segment))
+            .min_by(|a, b| a.total_cmp(b))?;
+        let state = sa + (sb - sa) * fraction;
+        Some((
+            ta + (tb - ta) * fraction,
+            state[0],
+            state[1],
+            state[3].atan2(state[2]),
+        ))
+    })
+}
+
+/// Intersection of the segment from `a` to `b` with `segment`, as the
+/// fraction of the distance from `a` to `b`, or `None` if they don't
+/// intersect or are parallel
+fn intersection(a: (f64, f64), b: (f64, f64), segment: &[(f64, f64); 2]) -> Option<f64> {
+    let [c, d] = *segment;
+    let r = (b.0 - a.0, b.1 - a.1);
+    let q = (d.0 - c.0, d.1 - c.1);
+    let denominator = r.0 * q.1 - r.1 * q.0;
+    if denominator == 0.0 {
+        return None;
+    }
+    let ac = (c.0 - a.0, c.1 - a.1);
+    // fractions along the step and along the segment
+    let t = (ac.0 * q.1 - ac.1 * q.0) / denominator;
+    let u = (ac.0 * r.1 - ac.1 * r.0) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
+
+/// Separation between the rays `a` and `b` perpendicular to the direction of
+/// propagation of `a`
+fn perpendicular_separation(a: &State, b: &State) -> f64 {
+    let k = a[2].hypot(a[3]);
+    (a[2] * (b[1] - a[1]) - a[3] * (b[0] - a[0])) / k
+}
+
+#[cfg(test)]
+mod test_ray_bundle {
+    use super::*;
+    use crate::{
+        bathymetry::{BathymetryData, ConstantDepth, ConstantSlope},
+        datatype::{Gradient, Point, RayState, WaveNumber},
+        dispersion,
+        error::Result,
+        io::utility::bathymetry_from_fn,
+        ray::trace_ray,
+    };
+
+    /// a circular shoal, shallower at the center, acting as a lens
+    struct Shoal;
+
+    impl Shoal {
+        const X0: f32 = 1000.0;
+        const RADIUS: f32 = 300.0;
+
+        fn bump(&self, point: &Point<f32>) -> f32 {
+            let r2 = (point.x() - Self::X0).powi(2) + point.y().powi(2);
+            (-r2 / Self::RADIUS.powi(2)).exp()
+        }
+    }
+
+    impl BathymetryData for Shoal {
+        fn depth(&self, point: &Point<f32>) -> Result<f32> {
+            Ok(50.0 - 40.0 * self.bump(point))
+        }
+
+        fn depth_and_gradient(&self, point: &Point<f32>) -> Result<(f32, Gradient<f32>)> {
+            let bump = self.bump(point);
+            let factor = 80.0 * bump / Self::RADIUS.powi(2);
+            Ok((
+                50.0 - 40.0 * bump,
+                Gradient::new(factor * (point.x() - Self::X0), factor * point.y()),
+            ))
+        }
+    }
+
+    /// trace parallel rays in the x direction
+    fn trace_bundle(bathymetry: &dyn BathymetryData) -> RayBundle {
+        let rays = (-4..=4)
+            .map(|i| {
+                let init =
+                    RayState::new(Point::new(0.0, i as f64 * 25.0), WaveNumber::new(0.05, 0.0));
+                trace_ray(bathymetry, None, init, 0.0, 600.0, 1.0).unwrap()
+            })
+            .collect();
+        RayBundle::new(rays)
+    }
+
+    #[test]
+    /// parallel rays over a flat bottom never cross
+    fn test_no_caustic() {
+        let bundle = trace_bundle(&ConstantDepth::new(50.0));
+        let times = bundle.caustic_times();
+        assert_eq!(times.len(), 8);
+        assert!(times.iter().all(|t| t.is_none()));
+    }
+
+    #[test]
+    /// over a flat bottom, the wavefront of parallel rays is a straight line
+    /// perpendicular to the rays
+    fn test_flat_wavefront() {
+        let bundle = trace_bundle(&ConstantDepth::new(50.0));
+        let cg = dispersion::group_velocity(0.05, 50.0).unwrap();
+
+        // between two steps
+        let wavefront = bundle.wavefront_at(100.5);
+        assert_eq!(wavefront.len(), 9);
+        for (i, (x, y)) in wavefront.iter().enumerate() {
+            assert!((x - cg * 100.5).abs() < 1e-6, "x = {}", x);
+            assert!((y - (i as f64 - 4.0) * 25.0).abs() < 1e-9);
+        }
+
+        // out of the time span of the rays
+        assert!(bundle.wavefront_at(-1.0).is_empty());
+        assert!(bundle.wavefront_at(601.0).is_empty());
+    }
+
+    #[test]
+    /// rays that terminated before the time are excluded from the wavefront
+    fn test_wavefront_terminated() {
+        let ray = |x0: f64, n: usize| {
+            RayResult::new(
+                (0..n).map(|i| i as f64).collect(),
+                (0..n).map(|i| x0 + i as f64).collect(),
+                vec![0.0; n],
+                vec![0.1; n],
+                vec![0.0; n],
+            )
+        };
+        let bundle = RayBundle::new(vec![ray(0.0, 11), ray(5.0, 3), ray(10.0, 11)]);
+
+        assert_eq!(
+            bundle.wavefront_at(2.0),
+            vec![(2.0, 0.0), (7.0, 0.0), (12.0, 0.0)]
+        );
+        assert_eq!(bundle.wavefront_at(5.0), vec![(5.0, 0.0), (15.0, 0.0)]);
+    }
+
+    #[test]
+    /// rays converge behind the shoal and cross near the focal point
+    fn test_shoal_caustic() {
+        let bundle = trace_bundle(&Shoal);
+        let times = bundle.caustic_times();
+        assert_eq!(times.len(), 8);
+
+        // the rays cross after passing over the shoal
+        for t in times.iter() {
+            let t = t.expect("expected the neighboring rays to cross");
+            let (_, state) = bundle.rays()[0]
+                .valid_states()
+                .find(|(time, _)| *time == t)
+                .unwrap();
+            assert!(state[0] > Shoal::X0 as f64, "crossed at x = {}", state[0]);
+        }
+    }
+
+    #[test]
+    /// on a linear beach, the rays arrive at a depth contour more shore
+    /// normal than they were launched, whatever the launch angle
+    fn test_beach_contour_crossings() {
+        // depth from 50 m at x = 0 to 0 at x = 5000 m, the shore is at +x
+        let beach =
+            bathymetry_from_fn(51, 51, 100.0, 100.0, |x, _| 50.0 - 0.01 * x as f64).unwrap();
+        let contour = beach.depth_contour(10.0);
+
+        let angles: Vec<f64> = (-5..=5).map(|i| i as f64 * 0.1).collect();
+        let rays = angles
+            .iter()
+            .map(|angle| {
+                let init = RayState::new(
+                    Point::new(100.0, 2500.0),
+                    WaveNumber::new(0.02 * angle.cos(), 0.02 * angle.sin()),
+                );
+                trace_ray(&beach, None, init, 0.0, 2000.0, 1.0).unwrap()
+            })
+            .collect();
+        let crossings = RayBundle::new(rays).contour_crossings(&contour);
+        assert_eq!(crossings.len(), angles.len());
+
+        for (angle, crossing) in angles.iter().zip(crossings.iter()) {
+            let (t, x, _, direction) = crossing.expect("expected the ray to cross the contour");
+            assert!(t > 0.0);
+            // the 10 m contour is at x = 4000 m
+            assert!((x - 4000.0).abs() < 1.0, "crossed at x = {}", x);
+            if *angle == 0.0 {
+                assert!(direction.abs() < 1e-12);
+            } else {
+                assert!(direction.abs() < angle.abs(), "{} -> {}", angle, direction);
+                assert_eq!(direction.signum(), angle.signum());
+            }
+        }
+
+        // the contour is never reached by a ray moving offshore
+        let init = RayState::new(Point::new(1000.0, 2500.0), WaveNumber::new(-0.02, 0.0));
+        let offshore = trace_ray(&beach, None, init, 0.0, 50.0, 1.0).unwrap();
+        assert_eq!(
+            RayBundle::new(vec![offshore]).contour_crossings(&contour),
+            vec![None]
+        );
+    }
+
+    #[test]
+    /// on a plane beach, the refraction coefficient from the spacing of
+    /// parallel rays matches the analytic sqrt(cos theta0 / cos theta)
+    fn test_wave_height_transformation() {
+        let beach = ConstantSlope::builder()
+            .h0(50.0)
+            .dhdx(-0.01)
+            .build()
+            .unwrap();
+        let theta0 = std::f64::consts::PI / 6.0;
+        let rays: Vec<RayResult> = (0..5)
+            .map(|i| {
+                let init =
+                    RayState::with_period(Point::new(0.0, i as f64 * 20.0), 10.0, theta0, &beach)
+                        .unwrap();
+                trace_ray(&beach, None, init, 0.0, 400.0, 1.0).unwrap()
+            })
+            .collect();
+        let bundle = RayBundle::new(rays);
+
+        let heights = bundle.wave_height_transformation(2.0, &beach).unwrap();
+        assert_eq!(heights.len(), 5);
+        for (ray, height) in bundle.rays().iter().zip(&heights) {
+            let ks = ray.shoaling_coefficient(&beach).unwrap();
+            assert_eq!(height.len(), ks.len());
+            assert_eq!(height[0], 2.0);
+            for ((_, state), (h, ks)) in ray.valid_states().zip(height.iter().zip(&ks)) {
+                let theta = state[3].atan2(state[2]);
+                let kr = (theta0.cos() / theta.cos()).sqrt();
+                assert!(
+                    (h - 2.0 * ks * kr).abs() < 1e-6,
+                    "Expected {}, but got {}",
+                    2.0 * ks * kr,
+                    h
+                );
+            }
+            // the ray turns toward the shore, thus the height decreases by
+            // refraction
+            assert!(height.last().unwrap() < &(2.0 * ks.last().unwrap()));
+        }
+
+        assert!(matches!(
+            RayBundle::new(bundle.rays()[..1].to_vec()).wave_height_transformation(2.0, &beach),
+            Err(crate::error::Error::InvalidArgument)
+        ));
+    }
+}
@@ -2,6 +2,8 @@
 //! Contains methods to convert from `SolverResult` and to `RayResults` and
 //! write using serde and serde_json.
 
+use std::collections::HashMap;
+use std::f64::consts::PI;
 use std::fs::File;
 use std::io::BufWriter;
 use std::io::Write;
@@ -11,10 +13,14 @@ use ode_solvers::dop_shared::SolverResult;
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::error::Result;
-use crate::wave_ray_path::{State, Time};
+use crate::bathymetry::BathymetryData;
+use crate::current::CurrentData;
+use crate::datatype::{LocalTangentPlane, Point};
+use crate::dispersion::{self, WaterRegime};
+use crate::error::{Error, Result};
+use crate::wave_ray_path::{State, StateView, TerminationReason, Time, WaveRayPath};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 /// struct to hold the results of the ray tracing simulation as vectors. Note
 /// that the vectors are not indexed by time, but by the number of steps of the
 /// simulation.
@@ -24,8 +30,22 @@ pub(crate) struct RayResult {
     y_vec: Vec<f64>,
     kx_vec: Vec<f64>,
     ky_vec: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    termination_reason: Option<TerminationReason>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spreading: Option<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    energy: Option<Vec<f64>>,
 }
 
+/// Relative tolerance for the states at the joins of `RayResult::concat`. For
+/// values smaller than 1, this is an absolute tolerance.
+const JOIN_TOLERANCE: f64 = 1e-6;
+
+/// Distance, in grid cells, a ray has to travel before coming back to a cell
+/// for `RayResult::trapping_time` to consider it a revisit
+const TRAPPING_MIN_DISTANCE_CELLS: f64 = 10.0;
+
 #[allow(dead_code)]
 impl RayResult {
     /// Create a new RayResults struct with the given vectors.
@@ -63,9 +83,101 @@ impl RayResult {
             y_vec,
             kx_vec,
             ky_vec,
+            termination_reason: None,
+            spreading: None,
+            energy: None,
         }
     }
 
+    /// Set the reason the integration was stopped
+    ///
+    /// # Arguments
+    ///
+    /// `reason` : `Option<TerminationReason>`
+    /// - the reason recorded during the integration, if any
+    ///
+    /// # Returns
+    ///
+    /// the `RayResult` with the termination reason
+    pub(crate) fn with_termination_reason(mut self, reason: Option<TerminationReason>) -> Self {
+        self.termination_reason = reason;
+        self
+    }
+
+    /// The reason the integration was stopped
+    ///
+    /// # Returns
+    ///
+    /// `Option<TerminationReason>` : the reason the ray was stopped early, or
+    /// `None` if it was not stopped by a known condition
+    pub(crate) fn termination_reason(&self) -> Option<TerminationReason> {
+        self.termination_reason
+    }
+
+    /// Set the geometric spreading factor at each step
+    ///
+    /// # Arguments
+    ///
+    /// `spreading` : `Vec<f64>`
+    /// - the spreading factor at each step, see `spreading_factor`
+    ///
+    /// # Returns
+    ///
+    /// the `RayResult` with the spreading factor
+    pub(crate) fn with_spreading(mut self, spreading: Vec<f64>) -> Self {
+        self.spreading = Some(spreading);
+        self
+    }
+
+    /// Geometric spreading factor at each step
+    ///
+    /// The separation, perpendicular to the ray, between the ray and an
+    /// infinitesimally close ray launched from the same point with the
+    /// wavenumber rotated by one radian, i.e. the width of the ray tube per
+    /// unit launch angle \[m/rad\]. It is integrated with the variational
+    /// ray equations by `SingleRay::trace_with_spreading`.
+    ///
+    /// # Returns
+    ///
+    /// `Option<&[f64]>` : the spreading factor at each step, or `None` if the
+    /// ray was not traced with the spreading. It is dropped by the methods
+    /// that build a new `RayResult`, such as the resampling.
+    pub(crate) fn spreading_factor(&self) -> Option<&[f64]> {
+        self.spreading.as_deref()
+    }
+
+    /// Set the wave energy at each step
+    ///
+    /// # Arguments
+    ///
+    /// `energy` : `Vec<f64>`
+    /// - the wave energy at each step, see `energy`
+    ///
+    /// # Returns
+    ///
+    /// the `RayResult` with the wave energy
+    pub(crate) fn with_energy(mut self, energy: Vec<f64>) -> Self {
+        self.energy = Some(energy);
+        self
+    }
+
+    /// Wave energy density at each step
+    ///
+    /// The energy $E = \rho g a^2 / 2$ \[J/m^2\] from the conservation of the
+    /// energy flux $E c_g$ along the ray, minus the dissipation by bottom
+    /// friction. It is integrated with the ray equations by
+    /// `SingleRay::trace_with_energy`. As for `bottom_orbital_velocity`, the
+    /// spreading of the rays and the current are not taken into account.
+    ///
+    /// # Returns
+    ///
+    /// `Option<&[f64]>` : the energy at each step, or `None` if the ray was
+    /// not traced with the energy. It is dropped by the methods that build a
+    /// new `RayResult`, such as the resampling.
+    pub(crate) fn energy(&self) -> Option<&[f64]> {
+        self.energy.as_deref()
+    }
+
     /// Convert the `RayResults` struct to a JSON string.
     ///
     /// # Returns
@@ -118,6 +230,758 @@ impl RayResult {
         let mut writer = BufWriter::new(file);
         self.write(&mut writer)
     }
+
+    /// Iterate over the valid states of the ray
+    ///
+    /// The integration is stopped with a NaN sentinel row when the ray leaves
+    /// the domain or reaches land. This iterator yields the time and state of
+    /// each step before the first row containing a NaN value.
+    ///
+    /// # Returns
+    ///
+    /// `impl Iterator<Item = (Time, State)>` : the (time, state) of each valid
+    /// step, where the state is (x, y, kx, ky)
+    pub(crate) fn valid_states(&self) -> impl Iterator<Item = (Time, State)> + '_ {
+        (0..self.t_vec.len())
+            .map(|i| {
+                (
+                    self.t_vec[i],
+                    State::new(self.x_vec[i], self.y_vec[i], self.kx_vec[i], self.ky_vec[i]),
+                )
+            })
+            .take_while(|(t, s)| !t.is_nan() && !s.iter().any(|v| v.is_nan()))
+    }
+
+    /// The last valid state of the ray
+    ///
+    /// # Returns
+    ///
+    /// `Option<(Time, State)>` : the (time, state) of the last step before
+    /// the NaN sentinel, or `None` if there are no valid steps
+    pub(crate) fn last_valid(&self) -> Option<(Time, State)> {
+        self.valid_states().last()
+    }
+
+    /// The last valid position of the ray
+    ///
+    /// For instance, where the ray reached the coast or left the domain.
+    ///
+    /// # Returns
+    ///
+    /// `Option<(f64, f64)>` : the (x, y) \[m\] of the last step before the
+    /// NaN sentinel, or `None` if there are no valid steps
+    pub(crate) fn last_valid_position(&self) -> Option<(f64, f64)> {
+        self.last_valid().map(|(_, state)| (state.x(), state.y()))
+    }
+
+    /// Cumulative distance traveled along the ray
+    ///
+    /// The distance is the sum of the Euclidean distances between consecutive
+    /// valid states, so the NaN sentinel rows are never included. The travel
+    /// time to each point is the integration time, `t_vec`.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<f64>` : the distance \[m\] from the first valid state to each
+    /// valid state, starting at 0
+    pub(crate) fn arclength(&self) -> Vec<f64> {
+        let mut traveled = 0.0;
+        let mut previous: Option<State> = None;
+        self.valid_states()
+            .map(|(_, state)| {
+                if let Some(p) = previous {
+                    traveled += (state.x() - p.x()).hypot(state.y() - p.y());
+                }
+                previous = Some(state);
+                traveled
+            })
+            .collect()
+    }
+
+    /// Total distance traveled along the ray
+    ///
+    /// # Returns
+    ///
+    /// `f64` : the length \[m\] of the ray between the first and last valid
+    /// states, or 0 if there are no valid states
+    pub(crate) fn total_length(&self) -> f64 {
+        self.arclength().last().copied().unwrap_or(0.0)
+    }
+
+    /// Signed curvature of the ray path
+    ///
+    /// The curvature is computed from central first and second differences
+    /// of the valid positions,
+    /// $\kappa = (x' y'' - y' x'') / (x'^2 + y'^2)^{3/2}$, which doesn't
+    /// depend on the parametrization of the path. It is positive when the ray
+    /// turns counterclockwise (to the left) and negative when it turns
+    /// clockwise.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<f64>` : the curvature \[m^-1\] at each valid state. The first and
+    /// last states don't have neighbors on both sides, so their curvature is
+    /// NaN, as well as the curvature where the ray doesn't move.
+    pub(crate) fn curvature(&self) -> Vec<f64> {
+        let states: Vec<State> = self.valid_states().map(|(_, s)| s).collect();
+        let n = states.len();
+        (0..n)
+            .map(|i| {
+                if i == 0 || i + 1 >= n {
+                    return f64::NAN;
+                }
+                let (a, b, c) = (&states[i - 1], &states[i], &states[i + 1]);
+                let dx = (c[0] - a[0]) / 2.0;
+                let dy = (c[1] - a[1]) / 2.0;
+                let ddx = c[0] - 2.0 * b[0] + a[0];
+                let ddy = c[1] - 2.0 * b[1] + a[1];
+                let speed = dx.hypot(dy);
+                if speed == 0.0 {
+                    return f64::NAN;
+                }
+                (dx * ddy - dy * ddx) / speed.powi(3)
+            })
+            .collect()
+    }
+
+    /// Resample the ray onto points equally spaced in distance traveled
+    ///
+    /// The distance along the ray is the cumulative Euclidean distance between
+    /// the valid states. The time, position, and wavenumber are linearly
+    /// interpolated at the distances 0, `ds`, 2 `ds`, ... up to the last valid
+    /// state, thus the NaN sentinel rows are never included.
+    ///
+    /// # Arguments
+    ///
+    /// `ds` : `f64`
+    /// - the spacing \[m\] between the resampled points
+    ///
+    /// # Returns
+    ///
+    /// `Ok(RayResult)` : the resampled ray, with the same termination reason
+    ///
+    /// `Err(Error::ArgumentOutOfBounds)` : `ds` is not positive
+    pub(crate) fn resample_by_arclength(&self, ds: f64) -> Result<RayResult> {
+        if ds.is_nan() || ds <= 0.0 {
+            return Err(Error::ArgumentOutOfBounds);
+        }
+
+        let states: Vec<(Time, State)> = self.valid_states().collect();
+        let mut resampled = RayResult::new(vec![], vec![], vec![], vec![], vec![])
+            .with_termination_reason(self.termination_reason);
+
+        let (t0, s0) = match states.first() {
+            Some(first) => *first,
+            None => return Ok(resampled),
+        };
+        resampled.push(t0, &s0);
+
+        // distance traveled at the start of the current segment
+        let mut traveled = 0.0;
+        // distance of the next resampled point
+        let mut target = ds;
+        for pair in states.windows(2) {
+            let (ta, sa) = pair[0];
+            let (tb, sb) = pair[1];
+            let length = (sb[0] - sa[0]).hypot(sb[1] - sa[1]);
+            while target <= traveled + length {
+                let fraction = (target - traveled) / length;
+                let t = ta + fraction * (tb - ta);
+                resampled.push(t, &(sa + (sb - sa) * fraction));
+                target += ds;
+            }
+            traveled += length;
+        }
+
+        Ok(resampled)
+    }
+
+    /// Resample the ray onto a fixed number of points equally spaced in time
+    ///
+    /// The number of steps of the integration depends on the step size and
+    /// on whether the ray was stopped early. This resamples the valid states
+    /// onto `n` times equally spaced between the first and the last valid
+    /// time, so that many rays can be stored in arrays of the same shape. The
+    /// position and wavenumber are linearly interpolated, and the first and
+    /// last points are the first and last valid states.
+    ///
+    /// # Arguments
+    ///
+    /// `n` : `usize`
+    /// - the number of points of the resampled ray
+    ///
+    /// # Returns
+    ///
+    /// `RayResult` : the resampled ray, with the same termination reason. It
+    /// is empty if `n` is zero or there are no valid states.
+    pub(crate) fn resample_by_time(&self, n: usize) -> RayResult {
+        let states: Vec<(Time, State)> = self.valid_states().collect();
+        let mut resampled = RayResult::new(vec![], vec![], vec![], vec![], vec![])
+            .with_termination_reason(self.termination_reason);
+
+        let (Some(&(t0, s0)), Some(&(t1, s1))) = (states.first(), states.last()) else {
+            return resampled;
+        };
+
+        // index of the first state of the segment containing the current time
+        let mut segment = 0;
+        for i in 0..n {
+            if i > 0 && i == n - 1 {
+                resampled.push(t1, &s1);
+                break;
+            }
+            if n == 1 || states.len() == 1 {
+                resampled.push(t0, &s0);
+                continue;
+            }
+            let t = t0 + (t1 - t0) * i as f64 / (n - 1) as f64;
+            // the time may decrease when the ray was traced backward
+            while segment + 2 < states.len() && (t - states[segment + 1].0) * (t1 - t0) > 0.0 {
+                segment += 1;
+            }
+            let (ta, sa) = states[segment];
+            let (tb, sb) = states[segment + 1];
+            let fraction = (t - ta) / (tb - ta);
+            resampled.push(t, &(sa + (sb - sa) * fraction));
+        }
+
+        resampled
+    }
+
+    /// State of the ray at a given time
+    ///
+    /// The position and wavenumber are linearly interpolated between the
+    /// valid steps before and after `t`, e.g. to build a wavefront from the
+    /// positions of many rays at the same time.
+    ///
+    /// # Arguments
+    ///
+    /// `t` : `Time`
+    /// - the time \[s\], which may decrease from the start when the ray was
+    ///   traced backward
+    ///
+    /// # Returns
+    ///
+    /// `Option<(f64, f64, f64, f64)>` : the x \[m\], y \[m\], kx \[1/m\], and
+    /// ky \[1/m\] of the ray at `t`, or `None` if `t` is before the start of
+    /// the ray or after its last valid step
+    pub(crate) fn position_at(&self, t: Time) -> Option<(f64, f64, f64, f64)> {
+        let states: Vec<(Time, State)> = self.valid_states().collect();
+        let state = match states.as_slice() {
+            [] => return None,
+            [(t0, s0)] => (*t0 == t).then_some(*s0)?,
+            _ => states.windows(2).find_map(|w| {
+                let ((ta, sa), (tb, sb)) = (w[0], w[1]);
+                let fraction = (t - ta) / (tb - ta);
+                (0.0..=1.0)
+                    .contains(&fraction)
+                    .then(|| sa + (sb - sa) * fraction)
+            })?,
+        };
+        Some((state.x(), state.y(), state.kx(), state.ky()))
+    }
+
+    /// Concatenate the segments of a ray traced in parts
+    ///
+    /// A long integration can be split in segments, for example, to change
+    /// the step size near the shore, where each segment starts from the last
+    /// valid state of the previous one. The valid states of the segments are
+    /// joined end to end, dropping the repeated state at each join, and the
+    /// time of each segment is offset so that it continues from the end of
+    /// the previous one. The termination reason is the one of the last
+    /// segment.
+    ///
+    /// # Arguments
+    ///
+    /// `segments` : `&[RayResult]`
+    /// - the segments of the ray in order
+    ///
+    /// # Returns
+    ///
+    /// `Ok(RayResult)` : the stitched ray
+    ///
+    /// `Err(Error::InvalidArgument)` : a segment has no valid states, or the
+    /// last state of a segment does not match the first state of the next one
+    /// within `JOIN_TOLERANCE`
+    pub(crate) fn concat(segments: &[RayResult]) -> Result<RayResult> {
+        let mut result = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        let mut previous: Option<(Time, State)> = None;
+        for segment in segments {
+            let mut states = segment.valid_states().peekable();
+            let (t0, s0) = *states.peek().ok_or(Error::InvalidArgument)?;
+            let offset = match previous {
+                None => 0.0,
+                Some((t, s)) => {
+                    let matches = s
+                        .iter()
+                        .zip(s0.iter())
+                        .all(|(a, b)| (a - b).abs() <= JOIN_TOLERANCE * a.abs().max(1.0));
+                    if !matches {
+                        return Err(Error::InvalidArgument);
+                    }
+                    // skip the repeated state at the join
+                    states.next();
+                    t - t0
+                }
+            };
+            for (t, state) in states {
+                result.push(t + offset, &state);
+            }
+            previous = result.last_valid();
+            result.termination_reason = segment.termination_reason;
+        }
+        Ok(result)
+    }
+
+    /// Append a step to the end of the vectors
+    fn push(&mut self, t: Time, state: &State) {
+        self.t_vec.push(t);
+        self.x_vec.push(state.x());
+        self.y_vec.push(state.y());
+        self.kx_vec.push(state.kx());
+        self.ky_vec.push(state.ky());
+    }
+
+    /// Wavelength at each step of the ray
+    ///
+    /// # Returns
+    ///
+    /// `Vec<f64>` : the wavelength $L = 2 \pi / \sqrt{k_x^2 + k_y^2}$ \[m\] at
+    /// each valid step
+    pub(crate) fn wavelength(&self) -> Vec<f64> {
+        self.valid_states()
+            .map(|(_, state)| 2.0 * PI / state.kx().hypot(state.ky()))
+            .collect()
+    }
+
+    /// Continuous direction of the wavenumber at each step of the ray
+    ///
+    /// The direction from `atan2` is in (-pi, pi], thus it jumps by 2 pi when
+    /// a ray turns across the -x direction. Instead, each direction is
+    /// shifted by a multiple of 2 pi so that it differs by at most pi from the
+    /// previous step, i.e. the standard phase unwrapping.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<f64>` : the direction \[rad\], counterclockwise from the x axis,
+    /// at each valid step. The first direction is in (-pi, pi].
+    pub(crate) fn direction_unwrapped(&self) -> Vec<f64> {
+        let mut directions: Vec<f64> = Vec::with_capacity(self.t_vec.len());
+        for (_, state) in self.valid_states() {
+            let theta = state.ky().atan2(state.kx());
+            let theta = match directions.last() {
+                Some(previous) => theta + 2.0 * PI * ((previous - theta) / (2.0 * PI)).round(),
+                None => theta,
+            };
+            directions.push(theta);
+        }
+        directions
+    }
+
+    /// Intrinsic frequency, relative to the water, at each step of the ray
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<f64>)` : the intrinsic angular frequency \[rad/s\] at each step
+    ///
+    /// `Err(Error)` : an error occurred getting the depth
+    pub(crate) fn intrinsic_frequency(&self, bathymetry: &dyn BathymetryData) -> Result<Vec<f64>> {
+        let system = WaveRayPath::builder()
+            .bathymetry_data(bathymetry)
+            .build()
+            .unwrap();
+        self.valid_states()
+            .map(|(_, state)| system.intrinsic_frequency(&state))
+            .collect()
+    }
+
+    /// Snell's law invariant at each step of the ray
+    ///
+    /// For depth contours parallel to the y axis, the angle $\theta$ between
+    /// the wavenumber and the contour normal (the x axis) follows Snell's law,
+    /// thus
+    ///
+    /// $\frac{\sin(\theta)}{c} = \frac{k_y / k}{\sigma / k}$
+    ///
+    /// is conserved along the ray, where $c$ is the phase speed. This is useful
+    /// to validate the refraction across a depth change that varies only in
+    /// the x direction.
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<f64>)` : $\sin(\theta) / c$ \[s/m\] at each valid step
+    ///
+    /// `Err(Error)` : an error occurred getting the depth
+    ///
+    /// # Note
+    ///
+    /// The current is ignored, i.e. the phase speed is relative to the water.
+    pub(crate) fn snell_invariant(&self, bathymetry: &dyn BathymetryData) -> Result<Vec<f64>> {
+        let system = WaveRayPath::builder()
+            .bathymetry_data(bathymetry)
+            .build()
+            .unwrap();
+        self.valid_states()
+            .map(|(_, state)| {
+                let k = state.kx().hypot(state.ky());
+                let c = system.intrinsic_frequency(&state)? / k;
+                Ok(state.ky() / k / c)
+            })
+            .collect()
+    }
+
+    /// Angle of incidence of the ray on the local depth contour
+    ///
+    /// The angle $\theta$ is between the wavenumber and the contour normal
+    /// pointing toward shallower water, $-\nabla h$, so that it is 0 for a
+    /// wave traveling straight up the slope. Unlike `snell_invariant`, it
+    /// applies to contours in any direction, and $\sin(\theta) / c$ is
+    /// conserved along the ray where the contours are straight and parallel.
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Vec<f64>` : the angle \[rad\] in \[0, pi\] at each valid step, or NaN
+    /// where the bottom is flat or the gradient of the depth can't be found
+    pub(crate) fn incidence_angle(&self, bathymetry: &dyn BathymetryData) -> Vec<f64> {
+        self.valid_states()
+            .map(|(_, state)| {
+                let Ok((_, gradient)) =
+                    bathymetry.depth_and_gradient_f64(&Point::new(state.x(), state.y()))
+                else {
+                    return f64::NAN;
+                };
+                let (nx, ny) = (-*gradient.dx() as f64, -*gradient.dy() as f64);
+                if nx == 0.0 && ny == 0.0 {
+                    return f64::NAN;
+                }
+                let cross = state.kx() * ny - state.ky() * nx;
+                let dot = state.kx() * nx + state.ky() * ny;
+                cross.abs().atan2(dot)
+            })
+            .collect()
+    }
+
+    /// Steps where the water depth regime of the wave changed
+    ///
+    /// For instance, the time when the wave started to feel the bottom
+    /// (deep to intermediate), i.e. where shoaling begins. See
+    /// `dispersion::water_regime`.
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<(Time, WaterRegime, WaterRegime)>)` : the time of the first
+    /// step in the new regime, the previous regime, and the new regime, for
+    /// each change along the valid steps
+    ///
+    /// `Err(Error)` : an error occurred getting the depth
+    pub(crate) fn regime_transitions(
+        &self,
+        bathymetry: &dyn BathymetryData,
+    ) -> Result<Vec<(Time, WaterRegime, WaterRegime)>> {
+        let mut transitions = vec![];
+        let mut previous = None;
+        for (t, state) in self.valid_states() {
+            let h = bathymetry.depth_f64(&Point::new(state.x(), state.y()))? as f64;
+            let regime = dispersion::water_regime(state.kx().hypot(state.ky()), h);
+            if let Some(from) = previous.filter(|from| *from != regime) {
+                transitions.push((t, from, regime));
+            }
+            previous = Some(regime);
+        }
+        Ok(transitions)
+    }
+
+    /// Absolute frequency, as seen by a fixed observer, at each step of the
+    /// ray
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// `current` : `&dyn CurrentData`
+    /// - the current used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<f64>)` : the absolute angular frequency \[rad/s\] at each step
+    ///
+    /// `Err(Error)` : an error occurred getting the depth or current
+    pub(crate) fn absolute_frequency(
+        &self,
+        bathymetry: &dyn BathymetryData,
+        current: &dyn CurrentData,
+    ) -> Result<Vec<f64>> {
+        let system = WaveRayPath::new(bathymetry, current);
+        self.valid_states()
+            .map(|(_, state)| system.absolute_frequency(&state))
+            .collect()
+    }
+
+    /// Group velocity components at each step of the ray
+    ///
+    /// The components are recomputed from the stored states with the ray
+    /// equations, thus they include the advection by the current, i.e. they
+    /// are the rate of change of the position (dx/dt, dy/dt).
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// `current` : `&dyn CurrentData`
+    /// - the current used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<(f64, f64)>)` : the (cgx, cgy) \[m/s\] at each step
+    ///
+    /// `Err(Error)` : an error occurred evaluating the ray equations
+    pub(crate) fn group_velocity_components(
+        &self,
+        bathymetry: &dyn BathymetryData,
+        current: &dyn CurrentData,
+    ) -> Result<Vec<(f64, f64)>> {
+        let system = WaveRayPath::new(bathymetry, current);
+        self.valid_states()
+            .map(|(_, s)| {
+                let (cgx, cgy, _, _) = system.odes(&s.x(), &s.y(), &s.kx(), &s.ky())?;
+                Ok((cgx, cgy))
+            })
+            .collect()
+    }
+
+    /// Wave-induced bottom orbital velocity at each step of the ray
+    ///
+    /// From linear wave theory, the amplitude of the near-bed orbital
+    /// velocity is
+    ///
+    /// $u_b = \frac{a \sigma}{\sinh(k h)}$
+    ///
+    /// where $a$ is the wave amplitude, $\sigma$ the intrinsic frequency from
+    /// the dispersion relation, $k$ the wavenumber magnitude, and $h$ the
+    /// depth. The amplitude along the ray is obtained from the conservation of
+    /// wave action (energy) flux, $a^2 c_g = a_0^2 c_{g0}$, so that
+    /// $a = a_0 \sqrt{c_{g0} / c_g}$.
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// `amplitude` : `f64`
+    /// - the wave amplitude \[m\] at the first step of the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<f64>)` : the bottom orbital velocity \[m/s\] at each valid step
+    ///
+    /// `Err(Error)` : an error occurred getting the depth or calculating the
+    /// group velocity
+    ///
+    /// # Note
+    ///
+    /// The spreading or convergence of neighboring rays is not taken into
+    /// account, thus the amplitude only includes the shoaling effect. The
+    /// current is also ignored, i.e. the absolute and relative frequencies are
+    /// assumed to be the same.
+    pub(crate) fn bottom_orbital_velocity(
+        &self,
+        bathymetry: &dyn BathymetryData,
+        amplitude: f64,
+    ) -> Result<Vec<f64>> {
+        let mut cg0 = None;
+        self.valid_states()
+            .map(|(_, state)| {
+                let h = bathymetry.depth_f64(&Point::new(state.x(), state.y()))? as f64;
+                let k = state.kx().hypot(state.ky());
+                let cg = dispersion::group_velocity(k, h)?;
+                let cg0 = *cg0.get_or_insert(cg);
+                let a = amplitude * (cg0 / cg).sqrt();
+                Ok(a * dispersion::intrinsic_frequency(k, h) / (k * h).sinh())
+            })
+            .collect()
+    }
+
+    /// Shoaling coefficient at each step of the ray
+    ///
+    /// From the conservation of the energy flux, the amplitude changes along
+    /// the ray with the shoaling coefficient
+    ///
+    /// $K_s = \sqrt{\frac{c_{g0}}{c_g}}$
+    ///
+    /// where $c_g$ is the group velocity at the local wavenumber and depth,
+    /// and $c_{g0}$ is the group velocity at the first step of the ray. If the
+    /// ray starts in deep water, this is the usual shoaling coefficient
+    /// relative to deep water.
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<f64>)` : the shoaling coefficient at each valid step, which is
+    /// 1 at the first step
+    ///
+    /// `Err(Error)` : an error occurred getting the depth or calculating the
+    /// group velocity
+    ///
+    /// # Note
+    ///
+    /// As in `bottom_orbital_velocity`, the spreading of neighboring rays and
+    /// the current are not taken into account.
+    pub(crate) fn shoaling_coefficient(&self, bathymetry: &dyn BathymetryData) -> Result<Vec<f64>> {
+        let mut cg0 = None;
+        self.valid_states()
+            .map(|(_, state)| {
+                let h = bathymetry.depth_f64(&Point::new(state.x(), state.y()))? as f64;
+                let cg = dispersion::group_velocity(state.kx().hypot(state.ky()), h)?;
+                let cg0 = *cg0.get_or_insert(cg);
+                Ok((cg0 / cg).sqrt())
+            })
+            .collect()
+    }
+
+    /// Depth of the bottom at each step of the ray
+    ///
+    /// The bathymetry is interpolated at each position, e.g. to plot the
+    /// bottom profile crossed by the wave against the `arclength`.
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<f32>)` : the depth \[m\] at each valid step, or NaN where the
+    /// position is out of the domain of the bathymetry
+    ///
+    /// # Errors
+    ///
+    /// Any other error getting the depth, e.g. `Error::Land` at a land cell.
+    pub(crate) fn depth_profile(&self, bathymetry: &dyn BathymetryData) -> Result<Vec<f32>> {
+        self.valid_states()
+            .map(
+                |(_, state)| match bathymetry.depth_f64(&Point::new(state.x(), state.y())) {
+                    Err(Error::IndexOutOfBounds) => Ok(f32::NAN),
+                    depth => depth,
+                },
+            )
+            .collect()
+    }
+
+    /// Wave energy density at each step from the amplitude along the ray
+    ///
+    /// From linear wave theory, the energy density is
+    ///
+    /// $E = \frac{1}{2} \rho g a^2$
+    ///
+    /// where $a$ is the wave amplitude. The amplitude is given by the caller,
+    /// for instance, the initial amplitude times the `shoaling_coefficient`
+    /// and the refraction coefficient from the width of the ray tube, so
+    /// that the energy flux $E c_g b$ is conserved along the ray.
+    ///
+    /// # Arguments
+    ///
+    /// `amplitude` : `&[f64]`
+    /// - the wave amplitude \[m\] at each valid step of the ray
+    ///
+    /// `rho` : `f64`
+    /// - the water density \[kg/m^3\], usually `dispersion::RHO`
+    ///
+    /// # Returns
+    ///
+    /// `Ok(Vec<f64>)` : the energy density \[J/m^2\] at each valid step
+    ///
+    /// # Errors
+    ///
+    /// `Error::DimensionMismatch` : the number of amplitudes is not the
+    /// number of valid steps.
+    pub(crate) fn energy_density(&self, amplitude: &[f64], rho: f64) -> Result<Vec<f64>> {
+        let expected = self.valid_states().count();
+        if amplitude.len() != expected {
+            return Err(Error::DimensionMismatch {
+                expected,
+                got: amplitude.len(),
+            });
+        }
+        Ok(amplitude
+            .iter()
+            .map(|a| 0.5 * rho * dispersion::G * a * a)
+            .collect())
+    }
+
+    /// Time when the ray comes back to a cell it visited before
+    ///
+    /// A ray trapped over a shoal circles around it, and a numerical blowup
+    /// can make a ray oscillate, thus both revisit the cells of the grid. A
+    /// revisit is counted only after the ray traveled at least
+    /// `TRAPPING_MIN_DISTANCE_CELLS` cells since it left the cell, so that a
+    /// ray crossing back and forth the edge of a cell is not trapped.
+    ///
+    /// # Arguments
+    ///
+    /// `bathymetry` : `&dyn BathymetryData`
+    /// - the bathymetry used to trace the ray. The cells have the size of its
+    ///   grid spacing or, for a bathymetry defined by a function, of the
+    ///   initial wavelength of the ray.
+    ///
+    /// # Returns
+    ///
+    /// `Option<f64>` : the time of the first revisit, i.e. the onset of the
+    /// trapping, or `None` if the ray never revisits a cell.
+    pub(crate) fn trapping_time(&self, bathymetry: &dyn BathymetryData) -> Option<f64> {
+        let (dx, dy) = match bathymetry.grid_spacing() {
+            Some(spacing) => spacing,
+            None => {
+                let wavelength = *self.wavelength().first()?;
+                (wavelength, wavelength)
+            }
+        };
+        let min_distance = TRAPPING_MIN_DISTANCE_CELLS * dx.max(dy);
+
+        // the distance traveled the last time the ray was in each cell
+        let mut visited: HashMap<(i64, i64), f64> = HashMap::new();
+        for ((t, state), traveled) in self.valid_states().zip(self.arclength()) {
+            let cell = (
+                (state.x() / dx).floor() as i64,
+                (state.y() / dy).floor() as i64,
+            );
+            match visited.insert(cell, traveled) {
+                Some(last) if traveled - last > min_distance => return Some(t),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Whether the ray is trapped, see `trapping_time`
+    pub(crate) fn is_trapped(&self, bathymetry: &dyn BathymetryData) -> bool {
+        self.trapping_time(bathymetry).is_some()
+    }
 }
 
 impl From<SolverResult<Time, State>> for RayResult {
@@ -150,10 +1014,144 @@ impl From<SolverResult<Time, State>> for RayResult {
     }
 }
 
+#[cfg(feature = "parquet")]
+#[allow(dead_code)]
+/// Save many rays as a single flat Parquet table
+///
+/// The table has the columns `ray_id, step, t, x, y, kx, ky`, with one row
+/// per valid state, thus it can be loaded directly by columnar tools such as
+/// DuckDB or pandas. The steps after a ray was terminated (NaN) are omitted.
+///
+/// # Arguments
+///
+/// `results` : `&[RayResult]`
+/// - the traced rays. The `ray_id` is the index in this slice.
+///
+/// `path` : `&Path`
+/// - the path of the Parquet file to create
+///
+/// # Returns
+///
+/// `Ok(())` : the table was written
+///
+/// `Err(Error)` : an error occurred while writing
+pub(crate) fn save_rays_parquet(results: &[RayResult], path: &Path) -> Result<()> {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Float64Array, RecordBatch, UInt64Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+
+    let mut ray_id = vec![];
+    let mut step = vec![];
+    let mut columns: [Vec<f64>; 5] = Default::default();
+    for (id, result) in results.iter().enumerate() {
+        for (i, (t, s)) in result.valid_states().enumerate() {
+            ray_id.push(id as u64);
+            step.push(i as u64);
+            for (column, value) in columns.iter_mut().zip([t, s.x(), s.y(), s.kx(), s.ky()]) {
+                column.push(value);
+            }
+        }
+    }
+
+    let names = ["t", "x", "y", "kx", "ky"];
+    let fields = [("ray_id", DataType::UInt64), ("step", DataType::UInt64)]
+        .into_iter()
+        .chain(names.iter().map(|name| (*name, DataType::Float64)))
+        .map(|(name, data_type)| Field::new(name, data_type, false))
+        .collect::<Vec<_>>();
+    let arrays = [
+        Arc::new(UInt64Array::from(ray_id)) as ArrayRef,
+        Arc::new(UInt64Array::from(step)) as ArrayRef,
+    ]
+    .into_iter()
+    .chain(
+        columns
+            .into_iter()
+            .map(|column| Arc::new(Float64Array::from(column)) as ArrayRef),
+    )
+    .collect::<Vec<_>>();
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+    let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+#[allow(dead_code)]
+/// Save many rays as a GeoJSON FeatureCollection
+///
+/// Each ray is a Feature with a LineString of its valid (lon, lat)
+/// positions, converted back from (x, y) with the projection, and the
+/// properties `ray_id` and `termination_reason`, so it can be displayed
+/// directly in web maps. A ray with less than two valid positions has a
+/// `null` geometry, since a LineString needs two positions.
+///
+/// # Arguments
+///
+/// `results` : `&[RayResult]`
+/// - the traced rays. The `ray_id` is the index in this slice.
+///
+/// `projection` : `&LocalTangentPlane`
+/// - the projection used to obtain the (x, y) domain of the rays
+///
+/// `path` : `&Path`
+/// - the path of the GeoJSON file to create
+///
+/// # Returns
+///
+/// `Ok(())` : the collection was written
+///
+/// `Err(Error)` : an error occurred while writing
+pub(crate) fn save_geojson(
+    results: &[RayResult],
+    projection: &LocalTangentPlane,
+    path: &Path,
+) -> Result<()> {
+    let features: Vec<_> = results
+        .iter()
+        .enumerate()
+        .map(|(ray_id, result)| {
+            let coordinates: Vec<_> = result
+                .valid_states()
+                .map(|(_, s)| {
+                    let coordinate = projection.to_coordinate(&Point::new(s.x(), s.y()));
+                    [*coordinate.lon(), *coordinate.lat()]
+                })
+                .collect();
+            let geometry = match coordinates.len() {
+                0 | 1 => serde_json::Value::Null,
+                _ => serde_json::json!({"type": "LineString", "coordinates": coordinates}),
+            };
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": {
+                    "ray_id": ray_id,
+                    "termination_reason": result.termination_reason(),
+                },
+            })
+        })
+        .collect();
+    let collection = serde_json::json!({"type": "FeatureCollection", "features": features});
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, &collection)?;
+    writer.flush()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod test_ray_result {
 
     use super::*;
+    use crate::bathymetry::{ConstantDepth, ConstantSlope};
+    use crate::current::ConstantCurrent;
+    use crate::datatype::{Coordinate, RayState, WaveNumber};
+    use crate::ray::SingleRay;
 
     #[test]
     /// test the converted RayResults struct from a SolverResult with constructor
@@ -180,6 +1178,24 @@ mod test_ray_result {
         );
     }
 
+    #[test]
+    /// test the RayResult can be read back from its JSON string
+    fn test_json_round_trip() {
+        let ray_results = RayResult::new(
+            vec![0.0, 1.0],
+            vec![2.0, 2.5],
+            vec![3.0, 3.5],
+            vec![4.0, 4.0],
+            vec![5.0, 5.0],
+        )
+        .with_termination_reason(Some(TerminationReason::Blocked));
+
+        let json_string = ray_results.as_json();
+        let converted: RayResult = serde_json::from_str(&json_string).unwrap();
+
+        assert_eq!(converted, ray_results);
+    }
+
     #[test]
     /// test NaN. when converting the `SolverResult` to `RayResult`, if an entry
     /// in the `SolverResult` has a NaN value, then that value and all after it
@@ -204,4 +1220,802 @@ mod test_ray_result {
             "{\"t_vec\":[0.0],\"x_vec\":[1.0],\"y_vec\":[1.0],\"kx_vec\":[1.0],\"ky_vec\":[1.0]}"
         );
     }
+
+    #[test]
+    /// the bottom orbital velocity is negligible in deep water and grows as the
+    /// wave shoals
+    fn test_bottom_orbital_velocity() {
+        let bathymetry = ConstantSlope::builder()
+            .h0(2000.0)
+            .dhdx(-1.0)
+            .build()
+            .unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.02, 0.0));
+        let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+        let result: RayResult = ray.trace_individual(0.0, 300.0, 1.0).unwrap().into();
+
+        let velocity = result.bottom_orbital_velocity(&bathymetry, 1.0).unwrap();
+        assert_eq!(velocity.len(), result.valid_states().count());
+
+        // deep water
+        assert!(velocity[0] < 1e-10, "Expected ~0, but got {}", velocity[0]);
+
+        // the ray reaches shallow water
+        let last = velocity.last().unwrap();
+        assert!(*last > 0.1, "Expected > 0.1, but got {}", last);
+        for v in velocity.windows(2) {
+            assert!(v[1] >= v[0], "Expected {} >= {}", v[1], v[0]);
+        }
+
+        // the NaN sentinel row of a ray reaching land is skipped
+        let stopped = RayResult::new(
+            vec![0.0, 1.0, f64::NAN],
+            vec![0.0, 1.0, f64::NAN],
+            vec![0.0, 0.0, f64::NAN],
+            vec![0.02, 0.02, f64::NAN],
+            vec![0.0, 0.0, f64::NAN],
+        );
+        let velocity = stopped.bottom_orbital_velocity(&bathymetry, 1.0).unwrap();
+        assert_eq!(velocity.len(), 2);
+    }
+
+    #[test]
+    /// a ray launched tangentially over a shoal orbits around it and is
+    /// trapped, while rays outside of the shoal are not
+    fn test_trapping() {
+        // flat-topped shoal at (3000, 3000), 1 m deep at the top and 100 m
+        // away from it, where k r has a maximum at r = 500 m for a 30 s wave.
+        // A ray is trapped between the radii where k r is its initial value.
+        let shoal = crate::io::utility::bathymetry_from_fn(121, 121, 50.0, 50.0, |x, y| {
+            let r = (x as f64 - 3000.0).hypot(y as f64 - 3000.0);
+            100.0 - 99.0 * (-(r / 1500.0).powi(4)).exp()
+        })
+        .unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let trace = |x: f64, y: f64, direction: f64| {
+            let initial_ray =
+                RayState::with_period(Point::new(x, y), 30.0, direction, &shoal).unwrap();
+            SingleRay::new(&shoal, &current, &initial_ray)
+                .trace(0.0, 3000.0, 1.0)
+                .unwrap()
+        };
+
+        // tangential at r = 800 m, orbiting between r = 380 m and 800 m
+        let trapped = trace(3800.0, 3000.0, PI / 2.0);
+        let onset = trapped.trapping_time(&shoal).unwrap();
+        assert!(trapped.is_trapped(&shoal));
+        assert!(onset > 100.0, "onset: {}", onset);
+        // still orbiting at the end
+        let (t, last) = trapped.last_valid().unwrap();
+        assert_eq!(t, 3000.0);
+        let r = (last.x() - 3000.0).hypot(last.y() - 3000.0);
+        assert!(r > 300.0 && r < 850.0, "r: {}", r);
+
+        // tangential at r = 2500 m, refracted away by the shoal
+        assert!(!trace(5500.0, 3000.0, PI / 2.0).is_trapped(&shoal));
+        // straight across the deep corner of the domain
+        assert!(!trace(100.0, 5900.0, 0.0).is_trapped(&shoal));
+    }
+
+    #[test]
+    /// the shoaling coefficient of a wave on a beach matches the linear
+    /// theory, e.g. the tables of Dean & Dalrymple (1991)
+    fn test_shoaling_coefficient() {
+        // 8 s wave, with a deep water wavelength L0 = 99.8 m, starting at
+        // h = 200 m with the shoreline at x = 4000 m
+        let bathymetry = ConstantSlope::builder()
+            .h0(200.0)
+            .dhdx(-0.05)
+            .build()
+            .unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray =
+            RayState::with_period(Point::new(0.0, 0.0), 8.0, 0.0, &bathymetry).unwrap();
+        let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+        let result = ray.trace(0.0, 2000.0, 1.0).unwrap();
+
+        let ks = result.shoaling_coefficient(&bathymetry).unwrap();
+        assert_eq!(ks.len(), result.valid_states().count());
+        assert_eq!(ks[0], 1.0);
+
+        // the minimum, at h / L0 = 0.15
+        let min = ks.iter().cloned().fold(f64::INFINITY, f64::min);
+        assert!(
+            (min - 0.913).abs() < 0.002,
+            "Expected 0.913, but got {}",
+            min
+        );
+
+        // Ks = 1.226 at h / L0 = 0.02, i.e. h = 2 m at x = 3960 m
+        let i = result.x_vec.iter().position(|x| *x >= 3960.0).unwrap();
+        let h = bathymetry
+            .depth(&Point::new(result.x_vec[i] as f32, 0.0))
+            .unwrap();
+        assert!((h - 2.0).abs() < 0.1, "h: {}", h);
+        assert!(
+            (ks[i] - 1.226).abs() < 0.02,
+            "Expected 1.226, but got {}",
+            ks[i]
+        );
+        assert!(ks.last().unwrap() > &1.226);
+    }
+
+    #[test]
+    /// the depth decreases along a ray traveling toward the shore of a linear
+    /// beach, and is NaN out of the domain of the bathymetry
+    fn test_depth_profile() {
+        let bathymetry = ConstantSlope::builder()
+            .h0(200.0)
+            .dhdx(-0.05)
+            .build()
+            .unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray =
+            RayState::with_period(Point::new(0.0, 0.0), 8.0, 0.3, &bathymetry).unwrap();
+        let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+        let result = ray.trace(0.0, 1000.0, 1.0).unwrap();
+
+        let depth = result.depth_profile(&bathymetry).unwrap();
+        assert_eq!(depth.len(), result.valid_states().count());
+        assert_eq!(depth[0], 200.0);
+        assert!(depth.windows(2).all(|w| w[1] < w[0]));
+
+        let grid =
+            crate::io::utility::bathymetry_from_fn(10, 10, 10.0, 10.0, |x, _| 100.0 - x as f64)
+                .unwrap();
+        let result = RayResult::new(
+            vec![0.0, 1.0],
+            vec![25.0, 500.0],
+            vec![25.0, 25.0],
+            vec![0.05, 0.05],
+            vec![0.0, 0.0],
+        );
+        let depth = result.depth_profile(&grid).unwrap();
+        assert!((depth[0] - 75.0).abs() < 1e-4, "depth: {}", depth[0]);
+        assert!(depth[1].is_nan());
+
+        // a land cell is an error rather than NaN
+        let land = crate::io::utility::bathymetry_from_fn(10, 10, 10.0, 10.0, |x, _| {
+            if x < 50.0 {
+                f64::NAN
+            } else {
+                100.0 - x as f64
+            }
+        })
+        .unwrap();
+        assert!(matches!(result.depth_profile(&land), Err(Error::Land)));
+    }
+
+    #[test]
+    /// on a plane beach, the incidence angle decreases toward zero as the ray
+    /// shoals, with sin(theta) / c conserved, and is NaN on a flat bottom
+    fn test_incidence_angle() {
+        // the contours are perpendicular to (1, 1), shallower toward +x, +y
+        let bathymetry = ConstantSlope::builder()
+            .h0(10.0)
+            .dhdx(-0.002)
+            .dhdy(-0.002)
+            .build()
+            .unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray =
+            RayState::with_period(Point::new(0.0, 0.0), 10.0, 0.0, &bathymetry).unwrap();
+        let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+        let result = ray.trace(0.0, 300.0, 0.1).unwrap();
+
+        let angle = result.incidence_angle(&bathymetry);
+        assert_eq!(angle.len(), result.valid_states().count());
+        assert!((angle[0] - std::f64::consts::FRAC_PI_4).abs() < 1e-12);
+        assert!(angle.windows(2).all(|w| w[1] < w[0]));
+        assert!(*angle.last().unwrap() < 0.75 * angle[0]);
+
+        let system = WaveRayPath::builder()
+            .bathymetry_data(&bathymetry)
+            .build()
+            .unwrap();
+        let invariant: Vec<f64> = result
+            .valid_states()
+            .zip(&angle)
+            .map(|((_, state), theta)| {
+                let k = state.kx().hypot(state.ky());
+                theta.sin() * k / system.intrinsic_frequency(&state).unwrap()
+            })
+            .collect();
+        for value in &invariant {
+            assert!((value - invariant[0]).abs() < 1e-4 * invariant[0]);
+        }
+
+        let flat = ConstantDepth::new(100.0);
+        assert!(result.incidence_angle(&flat).iter().all(|a| a.is_nan()));
+    }
+
+    #[test]
+    /// the energy density scales as the amplitude squared and, with the
+    /// shoaling and refraction of the amplitude, the energy flux through the
+    /// ray tube is conserved on a plane beach
+    fn test_energy_density() {
+        let bathymetry = ConstantSlope::builder()
+            .h0(50.0)
+            .dhdx(-0.01)
+            .build()
+            .unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        // two rays from a point source, with directions one milliradian apart
+        let ray = |angle: f64| {
+            let initial_ray =
+                RayState::with_period(Point::new(0.0, 0.0), 10.0, angle, &bathymetry).unwrap();
+            SingleRay::new(&bathymetry, &current, &initial_ray)
+        };
+        let dangle = 1e-3;
+        let result = ray(PI / 6.0).trace_with_spreading(0.0, 300.0, 1.0).unwrap();
+        let neighbor = ray(PI / 6.0 + dangle).trace(0.0, 300.0, 1.0).unwrap();
+        let n = result.valid_states().count();
+        assert_eq!(n, 301);
+
+        // amplitude from the separation of the two rays, perpendicular to the ray
+        let separation: Vec<f64> = result
+            .valid_states()
+            .zip(neighbor.valid_states())
+            .map(|((_, s), (_, s_neighbor))| {
+                let (dx, dy) = (s_neighbor.x() - s.x(), s_neighbor.y() - s.y());
+                (dx * s.ky() - dy * s.kx()).abs() / s.kx().hypot(s.ky())
+            })
+            .collect();
+        // the separation is zero at the source, so start from a later step
+        let i0 = 10;
+        let ks = result.shoaling_coefficient(&bathymetry).unwrap();
+        let amplitude: Vec<f64> = ks
+            .iter()
+            .zip(&separation)
+            .map(|(k, b)| 0.5 * k / ks[i0] * (separation[i0] / b).sqrt())
+            .collect();
+
+        let energy = result.energy_density(&amplitude, dispersion::RHO).unwrap();
+        assert_eq!(energy.len(), n);
+        assert_eq!(energy[i0], 0.125 * dispersion::RHO * dispersion::G);
+        let doubled: Vec<f64> = amplitude.iter().map(|a| 2.0 * a).collect();
+        let energy_doubled = result.energy_density(&doubled, dispersion::RHO).unwrap();
+        for (e, e_doubled) in energy[i0..].iter().zip(&energy_doubled[i0..]) {
+            assert!((e_doubled - 4.0 * e).abs() < 1e-9 * e);
+        }
+
+        // the energy flux through the ray tube from the variational ray
+        // equations is conserved
+        let width = result.spreading_factor().unwrap();
+        let flux: Vec<f64> = result
+            .valid_states()
+            .zip(energy.iter().zip(width))
+            .map(|((_, s), (e, b))| {
+                let h = bathymetry
+                    .depth(&Point::new(s.x() as f32, s.y() as f32))
+                    .unwrap() as f64;
+                e * dispersion::group_velocity(s.kx().hypot(s.ky()), h).unwrap() * b.abs()
+            })
+            .collect();
+        for f in &flux[i0..] {
+            assert!((f - flux[i0]).abs() < 1e-3 * flux[i0]);
+        }
+
+        assert!(matches!(
+            result.energy_density(&amplitude[1..], dispersion::RHO),
+            Err(Error::DimensionMismatch {
+                expected: 301,
+                got: 300
+            })
+        ));
+    }
+
+    #[test]
+    /// a ray refracted across the -x direction has a continuous direction,
+    /// although its direction from atan2 wraps from pi to -pi
+    fn test_direction_unwrapped() {
+        // the depth decreases toward -y, where the ray turns
+        let bathymetry = ConstantSlope::builder()
+            .h0(50.0)
+            .dhdx(0.0)
+            .dhdy(0.05)
+            .build()
+            .unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray =
+            RayState::with_period(Point::new(0.0, 0.0), 10.0, PI - 0.05, &bathymetry).unwrap();
+        let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+        let result = ray.trace(0.0, 200.0, 1.0).unwrap();
+
+        let raw: Vec<f64> = result
+            .valid_states()
+            .map(|(_, s)| s.ky().atan2(s.kx()))
+            .collect();
+        assert!(raw.windows(2).any(|w| (w[1] - w[0]).abs() > PI));
+
+        let unwrapped = result.direction_unwrapped();
+        assert_eq!(unwrapped.len(), raw.len());
+        assert_eq!(unwrapped[0], raw[0]);
+        assert!(unwrapped.windows(2).all(|w| (w[1] - w[0]).abs() < 0.1));
+        assert!(*unwrapped.last().unwrap() > PI);
+        for (u, r) in unwrapped.iter().zip(&raw) {
+            assert!((u.sin() - r.sin()).abs() < 1e-12);
+            assert!((u.cos() - r.cos()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    /// the iterator stops at the first row with a NaN value
+    fn test_valid_states() {
+        let rr = RayResult::new(
+            vec![0.0, 1.0, 2.0, 3.0],
+            vec![1.0, 2.0, f64::NAN, 4.0],
+            vec![1.0, 2.0, 3.0, 4.0],
+            vec![1.0, 2.0, 3.0, 4.0],
+            vec![1.0, 2.0, 3.0, 4.0],
+        );
+
+        let states: Vec<(Time, State)> = rr.valid_states().collect();
+        assert_eq!(
+            states,
+            vec![
+                (0.0, State::new(1.0, 1.0, 1.0, 1.0)),
+                (1.0, State::new(2.0, 2.0, 2.0, 2.0))
+            ]
+        );
+        assert_eq!(rr.last_valid(), Some((1.0, State::new(2.0, 2.0, 2.0, 2.0))));
+        assert_eq!(rr.last_valid_position(), Some((2.0, 2.0)));
+
+        let empty = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        assert_eq!(empty.valid_states().count(), 0);
+        assert_eq!(empty.last_valid(), None);
+        assert_eq!(empty.last_valid_position(), None);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    /// one row per valid state, with the ray id and the step
+    fn test_save_rays_parquet() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::{Float64Type, UInt64Type};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let results = vec![
+            RayResult::new(
+                vec![0.0, 1.0, 2.0],
+                vec![0.0, 1.0, f64::NAN],
+                vec![0.0, 0.5, f64::NAN],
+                vec![0.1, 0.1, f64::NAN],
+                vec![0.0, 0.2, f64::NAN],
+            ),
+            RayResult::new(vec![0.0], vec![3.0], vec![4.0], vec![0.5], vec![0.25]),
+        ];
+
+        let tmp_file = tempfile::NamedTempFile::new().unwrap();
+        save_rays_parquet(&results, tmp_file.path()).unwrap();
+
+        let file = File::open(tmp_file.path()).unwrap();
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.next().unwrap().unwrap();
+        assert!(reader.next().is_none());
+
+        let names: Vec<String> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+        assert_eq!(names, ["ray_id", "step", "t", "x", "y", "kx", "ky"]);
+        let integers = |i: usize| {
+            batch
+                .column(i)
+                .as_primitive::<UInt64Type>()
+                .values()
+                .to_vec()
+        };
+        let floats = |i: usize| {
+            batch
+                .column(i)
+                .as_primitive::<Float64Type>()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(integers(0), [0, 0, 1]);
+        assert_eq!(integers(1), [0, 1, 0]);
+        assert_eq!(floats(2), [0.0, 1.0, 0.0]);
+        assert_eq!(floats(3), [0.0, 1.0, 3.0]);
+        assert_eq!(floats(4), [0.0, 0.5, 4.0]);
+        assert_eq!(floats(5), [0.1, 0.1, 0.5]);
+        assert_eq!(floats(6), [0.0, 0.2, 0.25]);
+    }
+
+    #[test]
+    /// each ray is a feature with its id and termination reason, and its valid
+    /// steps projected to longitude and latitude, or no geometry if it has a
+    /// single step
+    fn test_save_geojson() {
+        let results = vec![
+            RayResult::new(
+                vec![0.0, 1.0, 2.0],
+                vec![0.0, 1000.0, f64::NAN],
+                vec![0.0, 500.0, f64::NAN],
+                vec![0.1, 0.1, f64::NAN],
+                vec![0.0, 0.2, f64::NAN],
+            )
+            .with_termination_reason(Some(TerminationReason::LeftBathymetry)),
+            RayResult::new(vec![0.0], vec![3.0], vec![4.0], vec![0.5], vec![0.25]),
+        ];
+        let projection = LocalTangentPlane::new(Coordinate::new(40.0, -105.0));
+
+        let tmp_file = tempfile::NamedTempFile::new().unwrap();
+        save_geojson(&results, &projection, tmp_file.path()).unwrap();
+
+        let content = std::fs::read_to_string(tmp_file.path()).unwrap();
+        let geojson: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 2);
+
+        let feature = &features[0];
+        assert_eq!(feature["properties"]["ray_id"], 0);
+        assert_eq!(
+            feature["properties"]["termination_reason"],
+            "LeftBathymetry"
+        );
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        let coordinates = feature["geometry"]["coordinates"].as_array().unwrap();
+        assert_eq!(coordinates.len(), 2);
+        assert_eq!(coordinates[0], serde_json::json!([-105.0, 40.0]));
+        // the second position is to the North-East of the origin
+        assert!(coordinates[1][0].as_f64().unwrap() > -105.0);
+        assert!(coordinates[1][1].as_f64().unwrap() > 40.0);
+
+        assert_eq!(features[1]["properties"]["ray_id"], 1);
+        assert!(features[1]["properties"]["termination_reason"].is_null());
+        assert!(features[1]["geometry"].is_null());
+    }
+
+    #[test]
+    /// a wave shoaling on a beach goes from deep to intermediate to shallow
+    /// water
+    fn test_regime_transitions() {
+        // shoreline at x = 1000 m
+        let bathymetry = ConstantSlope::builder().build().unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(10.0, 0.0), WaveNumber::new(0.1, 0.0));
+        let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+        let result = ray.trace(0.0, 1000.0, 1.0).unwrap();
+
+        let transitions = result.regime_transitions(&bathymetry).unwrap();
+        assert_eq!(
+            transitions
+                .iter()
+                .map(|(_, from, to)| (*from, *to))
+                .collect::<Vec<_>>(),
+            vec![
+                (WaterRegime::Deep, WaterRegime::Intermediate),
+                (WaterRegime::Intermediate, WaterRegime::Shallow)
+            ]
+        );
+
+        // shoaling begins where the depth is half of the wavelength
+        let (t, _, _) = transitions[0];
+        let i = result.t_vec.iter().position(|ti| *ti == t).unwrap();
+        let h = bathymetry
+            .depth(&Point::new(result.x_vec[i] as f32, 0.0))
+            .unwrap() as f64;
+        let wavelength = 2.0 * PI / result.kx_vec[i];
+        assert!(h <= wavelength / 2.0);
+        let h = bathymetry
+            .depth(&Point::new(result.x_vec[i - 1] as f32, 0.0))
+            .unwrap() as f64;
+        let wavelength = 2.0 * PI / result.kx_vec[i - 1];
+        assert!(h > wavelength / 2.0);
+    }
+
+    #[test]
+    /// the wavelength is 2 pi / k at each valid step
+    fn test_wavelength() {
+        let rr = RayResult::new(
+            vec![0.0, 1.0, 2.0],
+            vec![0.0, 1.0, f64::NAN],
+            vec![0.0, 0.0, f64::NAN],
+            vec![0.06, 0.0, f64::NAN],
+            vec![0.08, 2.0 * PI, f64::NAN],
+        );
+        let wavelength = rr.wavelength();
+        assert_eq!(wavelength.len(), 2);
+        assert!((wavelength[0] - 2.0 * PI / 0.1).abs() < 1e-12);
+        assert!((wavelength[1] - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    /// a straight ray in deep water travels at the group velocity, so the arc
+    /// length is the speed times the time
+    fn test_arclength() {
+        let bathymetry = ConstantDepth::new(1000.0);
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.06, 0.08));
+        let result = SingleRay::new(&bathymetry, &current, &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+
+        let cg = dispersion::group_velocity(0.1, 1000.0).unwrap();
+        let arclength = result.arclength();
+        assert_eq!(arclength.len(), 101);
+        for (s, t) in arclength.iter().zip(result.t_vec.iter()) {
+            assert!((s - cg * t).abs() < 1e-9, "s: {}, t: {}", s, t);
+        }
+        assert_eq!(result.total_length(), arclength[100]);
+
+        // the NaN sentinel rows are not included
+        let rr = RayResult::new(
+            vec![0.0, 1.0, 2.0],
+            vec![0.0, 3.0, f64::NAN],
+            vec![0.0, 4.0, f64::NAN],
+            vec![1.0, 1.0, f64::NAN],
+            vec![1.0, 1.0, f64::NAN],
+        );
+        assert_eq!(rr.arclength(), vec![0.0, 5.0]);
+        assert_eq!(rr.total_length(), 5.0);
+        let empty = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        assert_eq!(empty.total_length(), 0.0);
+    }
+
+    #[test]
+    /// the curvature of a circle is the inverse of the radius, with the sign
+    /// of the direction of rotation, and it is zero on a straight line
+    fn test_curvature() {
+        let radius = 250.0;
+        let circle = |sign: f64| {
+            let angles: Vec<f64> = (0..20).map(|i| sign * i as f64 * 0.01).collect();
+            RayResult::new(
+                (0..20).map(|i| i as f64).collect(),
+                angles.iter().map(|a| radius * a.cos()).collect(),
+                angles.iter().map(|a| radius * a.sin()).collect(),
+                vec![1.0; 20],
+                vec![0.0; 20],
+            )
+        };
+
+        let curvature = circle(1.0).curvature();
+        assert_eq!(curvature.len(), 20);
+        assert!(curvature[0].is_nan() && curvature[19].is_nan());
+        for k in &curvature[1..19] {
+            assert!((k - 1.0 / radius).abs() < 1e-6, "k: {}", k);
+        }
+        for k in &circle(-1.0).curvature()[1..19] {
+            assert!((k + 1.0 / radius).abs() < 1e-6, "k: {}", k);
+        }
+
+        // straight line ending with the NaN sentinel
+        let rr = RayResult::new(
+            vec![0.0, 1.0, 2.0, 3.0, 4.0],
+            vec![0.0, 1.0, 2.0, 3.0, f64::NAN],
+            vec![0.0, 2.0, 4.0, 6.0, f64::NAN],
+            vec![1.0, 1.0, 1.0, 1.0, f64::NAN],
+            vec![2.0, 2.0, 2.0, 2.0, f64::NAN],
+        );
+        let curvature = rr.curvature();
+        assert_eq!(curvature.len(), 4);
+        assert_eq!(curvature[1..3], [0.0, 0.0]);
+        assert!(curvature[3].is_nan());
+
+        let empty = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        assert!(empty.curvature().is_empty());
+    }
+
+    #[test]
+    /// the resampled points are equally spaced along the ray and stop at the
+    /// last valid state
+    fn test_resample_by_arclength() {
+        // the ray slows down, so the steps get closer to each other
+        let rr = RayResult::new(
+            vec![0.0, 1.0, 2.0, 3.0, 4.0],
+            vec![0.0, 4.0, 7.0, 9.0, f64::NAN],
+            vec![0.0, 0.0, 0.0, 0.0, f64::NAN],
+            vec![1.0, 1.0, 1.0, 2.0, f64::NAN],
+            vec![0.0, 0.0, 0.0, 0.0, f64::NAN],
+        );
+
+        let resampled = rr.resample_by_arclength(2.0).unwrap();
+        assert_eq!(resampled.x_vec, vec![0.0, 2.0, 4.0, 6.0, 8.0]);
+        assert_eq!(resampled.t_vec, vec![0.0, 0.5, 1.0, 1.0 + 2.0 / 3.0, 2.5]);
+        assert_eq!(resampled.kx_vec, vec![1.0, 1.0, 1.0, 1.0, 1.5]);
+        assert!(resampled.valid_states().all(|(_, s)| s[1] == 0.0));
+
+        // along a diagonal
+        let rr = RayResult::new(
+            vec![0.0, 1.0],
+            vec![0.0, 3.0],
+            vec![0.0, 4.0],
+            vec![1.0, 1.0],
+            vec![1.0, 1.0],
+        );
+        let resampled = rr.resample_by_arclength(2.5).unwrap();
+        assert_eq!(resampled.t_vec, vec![0.0, 0.5, 1.0]);
+        assert_eq!(resampled.y_vec, vec![0.0, 2.0, 4.0]);
+
+        assert!(rr.resample_by_arclength(0.0).is_err());
+        let empty = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        assert_eq!(empty.resample_by_arclength(1.0).unwrap(), empty);
+    }
+
+    #[test]
+    /// the state at a time between two steps is linearly interpolated, and
+    /// there is none out of the valid steps
+    fn test_position_at() {
+        let rr = RayResult::new(
+            vec![0.0, 1.0, 2.0, 4.0, 5.0],
+            vec![0.0, 4.0, 7.0, 9.0, f64::NAN],
+            vec![1.0, 1.0, 1.0, 3.0, f64::NAN],
+            vec![1.0, 1.0, 1.0, 2.0, f64::NAN],
+            vec![0.0, 0.0, 0.0, 0.5, f64::NAN],
+        );
+
+        // a quarter of the way from t = 2 to t = 4
+        assert_eq!(rr.position_at(2.5), Some((7.5, 1.5, 1.25, 0.125)));
+        // the steps themselves
+        assert_eq!(rr.position_at(0.0), Some((0.0, 1.0, 1.0, 0.0)));
+        assert_eq!(rr.position_at(1.0), Some((4.0, 1.0, 1.0, 0.0)));
+        assert_eq!(rr.position_at(4.0), Some((9.0, 3.0, 2.0, 0.5)));
+
+        // before the start, after the last valid step, or at the NaN step
+        assert_eq!(rr.position_at(-0.5), None);
+        assert_eq!(rr.position_at(4.5), None);
+        assert_eq!(rr.position_at(5.0), None);
+        assert_eq!(rr.position_at(f64::NAN), None);
+
+        // traced backward in time
+        let backward = RayResult::new(
+            vec![0.0, -1.0, -3.0],
+            vec![0.0, -1.0, -5.0],
+            vec![0.0, 0.0, 0.0],
+            vec![1.0, 1.0, 1.0],
+            vec![0.0, 0.0, 0.0],
+        );
+        assert_eq!(backward.position_at(-2.0), Some((-3.0, 0.0, 1.0, 0.0)));
+        assert_eq!(backward.position_at(1.0), None);
+
+        let empty = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        assert_eq!(empty.position_at(0.0), None);
+    }
+
+    #[test]
+    /// the resampled ray has the requested number of points, equally spaced
+    /// in time, with the same endpoints as the valid states
+    fn test_resample_by_time() {
+        let rr = RayResult::new(
+            vec![0.0, 1.0, 2.0, 4.0, 5.0],
+            vec![0.0, 4.0, 7.0, 9.0, f64::NAN],
+            vec![1.0, 1.0, 1.0, 3.0, f64::NAN],
+            vec![1.0, 1.0, 1.0, 2.0, f64::NAN],
+            vec![0.0, 0.0, 0.0, 0.0, f64::NAN],
+        )
+        .with_termination_reason(Some(TerminationReason::LeftBathymetry));
+
+        let resampled = rr.resample_by_time(5);
+        assert_eq!(resampled.t_vec, vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(resampled.x_vec, vec![0.0, 4.0, 7.0, 8.0, 9.0]);
+        assert_eq!(resampled.y_vec, vec![1.0, 1.0, 1.0, 2.0, 3.0]);
+        assert_eq!(resampled.kx_vec, vec![1.0, 1.0, 1.0, 1.5, 2.0]);
+        assert_eq!(
+            resampled.termination_reason(),
+            Some(TerminationReason::LeftBathymetry)
+        );
+
+        // the endpoints match the original valid states
+        let resampled = rr.resample_by_time(7);
+        assert_eq!(resampled.t_vec.len(), 7);
+        assert_eq!(resampled.valid_states().next(), rr.valid_states().next());
+        assert_eq!(resampled.last_valid(), rr.last_valid());
+        assert!((resampled.t_vec[1] - 4.0 / 6.0).abs() < 1e-12);
+        assert!((resampled.x_vec[1] - 8.0 / 3.0).abs() < 1e-12);
+
+        // traced backward in time
+        let backward = RayResult::new(
+            vec![0.0, -1.0, -3.0],
+            vec![0.0, -1.0, -5.0],
+            vec![0.0, 0.0, 0.0],
+            vec![1.0, 1.0, 1.0],
+            vec![0.0, 0.0, 0.0],
+        );
+        let resampled = backward.resample_by_time(4);
+        assert_eq!(resampled.t_vec, vec![0.0, -1.0, -2.0, -3.0]);
+        assert_eq!(resampled.x_vec, vec![0.0, -1.0, -3.0, -5.0]);
+
+        assert_eq!(rr.resample_by_time(1).t_vec, vec![0.0]);
+        assert_eq!(rr.resample_by_time(0).t_vec.len(), 0);
+        let empty = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        assert_eq!(empty.resample_by_time(10), empty);
+    }
+
+    #[test]
+    /// a ray traced in two segments with different step sizes is stitched
+    /// with a continuous time axis
+    fn test_concat() {
+        let bathymetry = ConstantSlope::builder().build().unwrap();
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let start = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.01, 0.01));
+        let first = SingleRay::new(&bathymetry, &current, &start)
+            .trace(0.0, 10.0, 1.0)
+            .unwrap();
+
+        // the second segment starts from the end of the first, with its own
+        // time axis starting at zero
+        let (_, end) = first.last_valid().unwrap();
+        let restart = RayState::new(Point::new(end[0], end[1]), WaveNumber::new(end[2], end[3]));
+        let second = SingleRay::new(&bathymetry, &current, &restart)
+            .trace(0.0, 10.0, 0.5)
+            .unwrap()
+            .with_termination_reason(Some(TerminationReason::Blocked));
+
+        let ray = RayResult::concat(&[first.clone(), second.clone()]).unwrap();
+        assert_eq!(ray.t_vec.len(), 11 + 20);
+        assert_eq!(ray.t_vec[..11], first.t_vec[..]);
+        for (i, t) in ray.t_vec[10..].iter().enumerate() {
+            assert!((t - (10.0 + 0.5 * i as f64)).abs() < 1e-12);
+        }
+        assert_eq!(ray.x_vec[10..], second.x_vec[..]);
+        assert_eq!(ray.termination_reason(), Some(TerminationReason::Blocked));
+
+        // the result is close to tracing the whole ray at once
+        let (_, expected) = SingleRay::new(&bathymetry, &current, &start)
+            .trace(0.0, 20.0, 1.0)
+            .unwrap()
+            .last_valid()
+            .unwrap();
+        let (_, state) = ray.last_valid().unwrap();
+        assert!((state - expected).norm() < 1e-3);
+
+        // the segments don't join
+        assert!(matches!(
+            RayResult::concat(&[second.clone(), first.clone()]),
+            Err(Error::InvalidArgument)
+        ));
+        let empty = RayResult::new(vec![], vec![], vec![], vec![], vec![]);
+        assert!(matches!(
+            RayResult::concat(&[first.clone(), empty]),
+            Err(Error::InvalidArgument)
+        ));
+        assert_eq!(
+            RayResult::concat(std::slice::from_ref(&first)).unwrap(),
+            first
+        );
+    }
+
+    #[test]
+    /// without current, the group velocity components are the rate of change
+    /// of the position
+    fn test_group_velocity_components() {
+        let bathymetry = ConstantDepth::new(10.0);
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.1, 0.05));
+        let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+        let result: RayResult = ray.trace_individual(0.0, 10.0, 1.0).unwrap().into();
+
+        let cg = result
+            .group_velocity_components(&bathymetry, &current)
+            .unwrap();
+        assert_eq!(cg.len(), 11);
+
+        let k = 0.1_f64.hypot(0.05);
+        let cg_magnitude = dispersion::group_velocity(k, 10.0).unwrap();
+        for (i, (cgx, cgy)) in cg.iter().enumerate().take(10) {
+            let dxdt = result.x_vec[i + 1] - result.x_vec[i];
+            let dydt = result.y_vec[i + 1] - result.y_vec[i];
+            assert!(
+                (cgx - dxdt).abs() < 1e-12,
+                "Expected {}, but got {}",
+                dxdt,
+                cgx
+            );
+            assert!(
+                (cgy - dydt).abs() < 1e-12,
+                "Expected {}, but got {}",
+                dydt,
+                cgy
+            );
+            assert!((cgx.hypot(*cgy) - cg_magnitude).abs() < 1e-12);
+        }
+    }
 }
@@ -0,0 +1,225 @@
+//! A loaded bathymetry and current, reused to trace many rays
+//!
+//! Instead of passing the bathymetry and current around separately, with
+//! their lifetimes, a `Scene` holds both with `Arc`, so it can be loaded once
+//! and reused, or shared across threads, for all the rays traced on it.
+
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::bathymetry::BathymetryData;
+use crate::current::{CurrentData, DEFAULT_CURRENT};
+use crate::datatype::RayState;
+use crate::error::Result;
+use crate::ray::{GridMismatch, ManyRays, SingleRay};
+use crate::ray_result::RayResult;
+use crate::wave_ray_path::{StopCondition, TerminationReason};
+
+/// A bathymetry and an optional current
+pub(crate) struct Scene {
+    /// the shared bathymetry dataset
+    bathymetry_data: Arc<dyn BathymetryData + Send + Sync>,
+    /// the shared current dataset, or `None` without current
+    current_data: Option<Arc<dyn CurrentData + Send + Sync>>,
+}
+
+#[allow(dead_code)]
+impl Scene {
+    /// construct a new `Scene` without current
+    ///
+    /// # Arguments
+    /// `bathymetry_data`: `Arc<dyn BathymetryData + Send + Sync>`
+    /// - the shared bathymetry dataset
+    ///
+    /// # Returns
+    /// `Self`: the new `Scene`
+    pub(crate) fn new(bathymetry_data: Arc<dyn BathymetryData + Send + Sync>) -> Self {
+        Scene {
+            bathymetry_data,
+            current_data: None,
+        }
+    }
+
+    /// Add a current to the scene
+    ///
+    /// # Arguments
+    /// `current_data`: `Arc<dyn CurrentData + Send + Sync>`
+    /// - the shared current dataset
+    ///
+    /// # Returns
+    /// `Self`: the scene with the current
+    pub(crate) fn with_current(mut self, current_data: Arc<dyn CurrentData + Send + Sync>) -> Self {
+        self.current_data = Some(current_data);
+        self
+    }
+
+    /// The bathymetry of the scene
+    pub(crate) fn bathymetry(&self) -> &dyn BathymetryData {
+        self.bathymetry_data.as_ref()
+    }
+
+    /// The current of the scene, which is zero if none was given
+    pub(crate) fn current(&self) -> &dyn CurrentData {
+        match &self.current_data {
+            Some(current_data) => current_data.as_ref(),
+            None => &DEFAULT_CURRENT,
+        }
+    }
+
+    /// Trace a single ray on the scene
+    ///
+    /// See `SingleRay::trace`.
+    pub(crate) fn trace(
+        &self,
+        initial_ray: &RayState<f64>,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Result<RayResult> {
+        SingleRay::new(self.bathymetry(), self.current(), initial_ray)
+            .trace(start_time, end_time, step_size)
+    }
+
+    /// Trace many rays on the scene in parallel
+    ///
+    /// See `ManyRays::trace_rays`.
+    ///
+    /// # Returns
+    /// `Vec<Option<RayResult>>` : the rays in the same order as
+    /// `initial_rays`, where a ray is `None` if there was an error during its
+    /// integration.
+    pub(crate) fn trace_many(
+        &self,
+        initial_rays: &[RayState<f64>],
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Vec<Option<RayResult>> {
+        ManyRays::new(self.bathymetry(), self.current(), initial_rays)
+            .trace_rays(start_time, end_time, step_size)
+    }
+
+    /// Where each ray of a fan lands on the shore
+    ///
+    /// Each ray is traced in parallel with `SingleRay::trace_until` until the
+    /// depth is shallower than `shoreline_depth`, and the landing point is
+    /// where the ray crosses that depth, linearly interpolated between the
+    /// steps on each side.
+    ///
+    /// # Arguments
+    /// `fan` : `&[RayState<f64>]`
+    /// - the initial rays, e.g. from `RayState::fan`
+    ///
+    /// `shoreline_depth` : `f64`
+    /// - the depth \[m\] of the contour taken as the shoreline. A small
+    ///   positive depth avoids the singular group velocity at zero depth.
+    ///
+    /// `step_size` : `f64`
+    /// - the change in time \[s\] between integration steps
+    ///
+    /// # Returns
+    /// `Vec<Option<(f64, f64)>>` : the (x, y) \[m\] where each ray lands, in
+    /// the order of `fan`, or `None` if the ray doesn't reach the shoreline,
+    /// e.g. it leaves the domain, or there was an error during its
+    /// integration.
+    pub(crate) fn shoreline_landings(
+        &self,
+        fan: &[RayState<f64>],
+        shoreline_depth: f64,
+        step_size: f64,
+    ) -> Vec<Option<(f64, f64)>> {
+        fan.par_iter()
+            .map(|initial_ray| {
+                let result = SingleRay::new(self.bathymetry(), self.current(), initial_ray)
+                    .trace_until(0.0, step_size, StopCondition::AtDepthBelow(shoreline_depth))
+                    .ok()?;
+                match result.termination_reason() {
+                    Some(TerminationReason::ConditionMet) => result.last_valid_position(),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Check the bathymetry and current grids have compatible resolutions
+    ///
+    /// See `ManyRays::check_grid_compatibility`.
+    pub(crate) fn check_grid_compatibility(&self) -> Option<GridMismatch> {
+        ManyRays::new(self.bathymetry(), self.current(), &[]).check_grid_compatibility()
+    }
+}
+
+#[cfg(test)]
+mod test_scene {
+    use std::sync::Arc;
+
+    use super::Scene;
+    use crate::current::ConstantCurrent;
+    use crate::datatype::{Point, RayState, WaveNumber};
+    use crate::io::utility::bathymetry_from_fn;
+    use crate::ray::SingleRay;
+    use crate::wave_ray_path::StateView;
+
+    #[test]
+    /// the rays traced on the scene are the same as with the borrowed data
+    fn test_scene() {
+        let bathymetry_data = Arc::new(
+            bathymetry_from_fn(100, 100, 10.0, 10.0, |x, _| 50.0 - 0.04 * x as f64).unwrap(),
+        );
+        let initial_ray = RayState::new(Point::new(10.0, 500.0), WaveNumber::new(0.05, 0.02));
+
+        let scene = Scene::new(bathymetry_data.clone());
+        assert!(scene.check_grid_compatibility().is_none());
+        let result = scene.trace(&initial_ray, 0.0, 100.0, 1.0).unwrap();
+        let expected = SingleRay::new(bathymetry_data.as_ref(), scene.current(), &initial_ray)
+            .trace(0.0, 100.0, 1.0)
+            .unwrap();
+        assert_eq!(result, expected);
+        let (_, last) = result.last_valid().unwrap();
+        assert!(last.x() > 10.0);
+
+        let results = scene.trace_many(&vec![initial_ray.clone(); 3], 0.0, 100.0, 1.0);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.as_ref() == Some(&expected)));
+
+        // the current pushes the ray toward +y
+        let scene = scene.with_current(Arc::new(ConstantCurrent::new(0.0, 0.5)));
+        let with_current = scene.trace(&initial_ray, 0.0, 100.0, 1.0).unwrap();
+        let (_, last_with_current) = with_current.last_valid().unwrap();
+        assert!(last_with_current.y() > last.y());
+    }
+
+    #[test]
+    /// on a linear beach, oblique rays turn toward the shore normal, so they
+    /// land closer to their origin alongshore than a straight line
+    fn test_shoreline_landings() {
+        // depth from 50 m at x = 0 to 0 at x = 5000 m, the shore is at +x
+        let beach = Arc::new(
+            bathymetry_from_fn(51, 101, 100.0, 100.0, |x, _| 50.0 - 0.01 * x as f64).unwrap(),
+        );
+        let scene = Scene::new(beach.clone());
+        let fan = RayState::fan(100.0, 5000.0, 10.0, 49.0, -0.6, 0.6, 7).unwrap();
+
+        let landings = scene.shoreline_landings(&fan, 1.0, 1.0);
+        assert_eq!(landings.len(), 7);
+        for (initial_ray, landing) in fan.iter().zip(&landings) {
+            let (x, y) = landing.expect("expected the ray to land");
+            // the 1 m contour is at x = 4900 m
+            assert!((x - 4900.0).abs() < 1.0, "landed at x = {}", x);
+            let wave_number = initial_ray.wave_number();
+            let direction = wave_number.ky().atan2(*wave_number.kx());
+            let straight = 5000.0 + (4900.0 - 100.0) * direction.tan();
+            assert!((y - 5000.0).abs() <= (straight - 5000.0).abs());
+            if direction != 0.0 {
+                assert!((y - 5000.0).abs() < 0.9 * (straight - 5000.0).abs());
+                assert_eq!((y - 5000.0).signum(), direction.signum());
+            }
+        }
+        assert!((landings[3].unwrap().1 - 5000.0).abs() < 1e-6);
+
+        // a ray moving offshore leaves the domain without landing
+        let offshore = RayState::new(Point::new(1000.0, 5000.0), WaveNumber::new(-0.05, 0.0));
+        assert_eq!(scene.shoreline_landings(&[offshore], 1.0, 1.0), vec![None]);
+    }
+}
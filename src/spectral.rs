@@ -0,0 +1,190 @@
+//! Spectral ray tracing, i.e. many frequencies and directions from one point
+//!
+//! A sea state is a spectrum rather than a single wave, thus it is
+//! represented by a set of frequency and direction bins. One ray is launched
+//! for each (frequency, direction) bin from a common origin, and all of them
+//! are traced in parallel with `ManyRays`.
+
+use derive_builder::Builder;
+
+use crate::bathymetry::{BathymetryData, DEFAULT_BATHYMETRY};
+use crate::current::{CurrentData, DEFAULT_CURRENT};
+use crate::datatype::{Point, RayState};
+use crate::error::Result;
+use crate::ray::ManyRays;
+use crate::ray_result::RayResult;
+
+#[derive(Builder)]
+/// Trace one ray per (frequency, direction) bin from a common origin
+pub(crate) struct SpectralRayTracer<'a> {
+    #[builder(default = "&DEFAULT_BATHYMETRY")]
+    /// a reference to the bathymetry dataset. Default is 2000 m.
+    bathymetry_data: &'a dyn BathymetryData,
+    #[builder(default = "&DEFAULT_CURRENT")]
+    /// a reference to the current dataset. Default is (u, v) = (0, 0) m/s
+    current_data: &'a dyn CurrentData,
+    #[builder(default = "(0.0, 0.0)")]
+    /// the (x, y) \[m\] where every ray is launched. Default is (0, 0).
+    origin: (f64, f64),
+    /// the frequency bins \[Hz\]
+    frequencies: Vec<f64>,
+    /// the direction bins \[rad\], counterclockwise from the x axis, e.g.
+    /// evenly spaced over the directional spread of the sea state
+    directions: Vec<f64>,
+}
+
+#[allow(dead_code)]
+impl<'a> SpectralRayTracer<'a> {
+    /// create a new `SpectralRayTracer` using the builder method
+    ///
+    /// For example,
+    /// `SpectralRayTracer::builder().frequencies(f).directions(d).build()`
+    ///
+    /// Note: There are default bathymetry, current, and origin, but no
+    /// default frequencies or directions.
+    pub(crate) fn builder() -> SpectralRayTracerBuilder<'a> {
+        SpectralRayTracerBuilder::default()
+    }
+
+    /// The initial rays of every bin
+    ///
+    /// The wavenumber of each frequency is obtained from the dispersion
+    /// relation at the depth of the origin.
+    ///
+    /// # Returns
+    /// `Result<Vec<RayState<f64>>>` : the initial rays ordered by frequency,
+    /// then by direction, i.e. the ray of the frequency `i` and direction `j`
+    /// is at the index `i * directions.len() + j`.
+    ///
+    /// # Errors
+    /// - `Error::InvalidInitialCondition` : a frequency or the depth at the
+    ///   origin is not positive.
+    /// - errors from `depth` if the origin is out of the bathymetry domain.
+    pub(crate) fn initial_rays(&self) -> Result<Vec<RayState<f64>>> {
+        let mut rays = Vec::with_capacity(self.frequencies.len() * self.directions.len());
+        for frequency in &self.frequencies {
+            for direction in &self.directions {
+                rays.push(RayState::with_period(
+                    Point::new(self.origin.0, self.origin.1),
+                    1.0 / frequency,
+                    *direction,
+                    self.bathymetry_data,
+                )?);
+            }
+        }
+        Ok(rays)
+    }
+
+    /// Trace the rays of every bin
+    ///
+    /// # Arguments
+    /// `start_time` : `f64`
+    /// - the time the ray tracing begins.
+    ///
+    /// `end_time` : `f64`
+    /// - the time the ray tracing is stopped.
+    ///
+    /// `step_size` : `f64`
+    /// - the change in time between integration steps.
+    ///
+    /// # Returns
+    /// `Result<Vec<(f64, Vec<Option<RayResult>>)>>` : for each frequency, in
+    /// the order of the bins, the frequency and the rays in the order of the
+    /// directions. As in `ManyRays::trace_rays`, a ray is `None` if there was
+    /// an error during its integration.
+    ///
+    /// # Errors
+    /// Same as `initial_rays`.
+    pub(crate) fn trace(
+        &self,
+        start_time: f64,
+        end_time: f64,
+        step_size: f64,
+    ) -> Result<Vec<(f64, Vec<Option<RayResult>>)>> {
+        let initial_rays = self.initial_rays()?;
+        let mut results = ManyRays::new(self.bathymetry_data, self.current_data, &initial_rays)
+            .trace_rays(start_time, end_time, step_size)
+            .into_iter();
+
+        Ok(self
+            .frequencies
+            .iter()
+            .map(|frequency| {
+                let rays = results.by_ref().take(self.directions.len()).collect();
+                (*frequency, rays)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test_spectral {
+    use super::SpectralRayTracer;
+    use crate::bathymetry::ConstantSlope;
+    use crate::error::Error;
+    use crate::ray_result::RayResult;
+    use crate::wave_ray_path::StateView;
+
+    /// x where the direction of the ray changed by more than 0.01 rad
+    fn turning_x(ray: &RayResult) -> f64 {
+        let mut states = ray.valid_states();
+        let (_, first) = states.next().unwrap();
+        let theta0 = first.ky().atan2(first.kx());
+        let (_, turning) = states
+            .find(|(_, s)| (s.ky().atan2(s.kx()) - theta0).abs() > 0.01)
+            .unwrap();
+        turning.x()
+    }
+
+    #[test]
+    /// the rays are grouped by frequency, and the longer wave feels the
+    /// bottom, thus turns, further offshore
+    fn test_two_frequencies() {
+        // shoreline at x = 4000 m
+        let bathymetry = ConstantSlope::builder()
+            .h0(200.0)
+            .dhdx(-0.05)
+            .build()
+            .unwrap();
+        let directions = vec![0.3, 0.5, 0.7];
+        let tracer = SpectralRayTracer::builder()
+            .bathymetry_data(&bathymetry)
+            .frequencies(vec![1.0 / 5.0, 1.0 / 15.0])
+            .directions(directions.clone())
+            .build()
+            .unwrap();
+        assert_eq!(tracer.initial_rays().unwrap().len(), 6);
+
+        let results = tracer.trace(0.0, 2000.0, 1.0).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, 1.0 / 5.0);
+        assert_eq!(results[1].0, 1.0 / 15.0);
+
+        for (j, direction) in directions.iter().enumerate() {
+            let short = results[0].1[j].as_ref().unwrap();
+            let long = results[1].1[j].as_ref().unwrap();
+            let (_, s) = short.valid_states().next().unwrap();
+            assert!((s.ky().atan2(s.kx()) - direction).abs() < 1e-12);
+            assert!(
+                turning_x(long) < turning_x(short),
+                "{} >= {}",
+                turning_x(long),
+                turning_x(short)
+            );
+        }
+    }
+
+    #[test]
+    /// there is no wave with a zero frequency
+    fn test_invalid_frequency() {
+        let tracer = SpectralRayTracer::builder()
+            .frequencies(vec![0.1, 0.0])
+            .directions(vec![0.0])
+            .build()
+            .unwrap();
+        assert!(matches!(
+            tracer.trace(0.0, 10.0, 1.0),
+            Err(Error::InvalidInitialCondition(_))
+        ));
+    }
+}
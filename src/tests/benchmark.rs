@@ -0,0 +1,155 @@
+//! Benchmarks
+//!
+//! These tests are ignored by default since they only report timings. Run
+//! them in release mode with
+//! `cargo test --release benchmark -- --ignored --nocapture`.
+
+use std::time::Instant;
+
+use ode_solvers::dop_shared::SolverResult;
+use ode_solvers::Rk4;
+use rayon::prelude::*;
+
+use crate::bathymetry::{BathymetryData, ConstantDepth, ConstantSlope};
+use crate::current::{ConstantCurrent, CurrentData};
+use crate::datatype::{Current, CurrentGradient, Point, RayState, WaveNumber};
+use crate::error::Result;
+use crate::ray::{ManyRays, SingleRay};
+use crate::wave_ray_path::{State, Time, WaveRayPath};
+
+/// number of times each case is repeated
+const REPEAT: usize = 20;
+
+/// Runs `f` `REPEAT` times and prints the mean time
+fn time_it<F: FnMut()>(name: &str, mut f: F) {
+    let start = Instant::now();
+    for _ in 0..REPEAT {
+        f();
+    }
+    println!("{}: {:?} per run", name, start.elapsed() / REPEAT as u32);
+}
+
+/// a constant current that does not report it is uniform, so the gradient is
+/// always evaluated
+struct NotUniform(ConstantCurrent);
+
+impl CurrentData for NotUniform {
+    fn current(&self, point: &Point<f64>) -> Result<Current<f64>> {
+        self.0.current(point)
+    }
+
+    fn current_and_gradient(
+        &self,
+        point: &Point<f64>,
+    ) -> Result<(Current<f64>, CurrentGradient<f64>)> {
+        self.0.current_and_gradient(point)
+    }
+}
+
+#[test]
+#[ignore]
+/// a 10k step deep water ray with and without skipping the gradient of a
+/// constant current
+fn benchmark_uniform_current() {
+    let bathymetry = ConstantDepth::new(2000.0);
+    let uniform = ConstantCurrent::new(0.5, 0.2);
+    let not_uniform = NotUniform(ConstantCurrent::new(0.5, 0.2));
+    let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.05, 0.01));
+
+    time_it("uniform current (short-circuit)", || {
+        let ray = SingleRay::new(&bathymetry, &uniform, &initial_ray);
+        let result = ray.trace_individual(0.0, 10_000.0, 1.0).unwrap();
+        assert_eq!(result.get().0.len(), 10_001);
+    });
+
+    time_it("uniform current (gradient)", || {
+        let ray = SingleRay::new(&bathymetry, &not_uniform, &initial_ray);
+        let result = ray.trace_individual(0.0, 10_000.0, 1.0).unwrap();
+        assert_eq!(result.get().0.len(), 10_001);
+    });
+}
+
+/// `ManyRays::trace_many` before the two parallel passes were fused: first
+/// collect the `SingleRay`s, then integrate them.
+fn trace_many_two_pass(
+    bathymetry: &dyn BathymetryData,
+    current: &dyn CurrentData,
+    initial_rays: &[RayState<f64>],
+    end_time: f64,
+) -> Vec<Option<SolverResult<Time, State>>> {
+    let rays: Vec<SingleRay> = initial_rays
+        .par_iter()
+        .map(|ray_state| SingleRay::new(bathymetry, current, ray_state))
+        .collect();
+    rays.par_iter()
+        .map(|r| r.trace_individual(0.0, end_time, 1.0).ok())
+        .collect()
+}
+
+#[test]
+#[ignore]
+/// 50k short rays on a slope traced with one and two parallel passes
+fn benchmark_trace_many() {
+    let bathymetry = ConstantSlope::builder().build().unwrap();
+    let current = ConstantCurrent::new(0.1, -0.1);
+    let initial_rays: Vec<RayState<f64>> = (0..50_000)
+        .map(|i| {
+            let theta = (i % 90) as f64 * std::f64::consts::PI / 180.0;
+            RayState::new(
+                Point::new(0.0, i as f64),
+                WaveNumber::new(0.05 * theta.cos(), 0.05 * theta.sin()),
+            )
+        })
+        .collect();
+    let many_rays = ManyRays::new(&bathymetry, &current, &initial_rays);
+
+    let fused = many_rays.trace_many(0.0, 100.0, 1.0);
+    let two_pass = trace_many_two_pass(&bathymetry, &current, &initial_rays, 100.0);
+    assert_eq!(fused.len(), two_pass.len());
+    for (a, b) in fused.iter().zip(two_pass.iter()) {
+        let (a, b) = (a.as_ref().unwrap().get(), b.as_ref().unwrap().get());
+        assert_eq!(a.0, b.0);
+        assert_eq!(a.1, b.1);
+    }
+
+    time_it("trace_many (two passes)", || {
+        trace_many_two_pass(&bathymetry, &current, &initial_rays, 100.0);
+    });
+
+    time_it("trace_many (fused)", || {
+        many_rays.trace_many(0.0, 100.0, 1.0);
+    });
+}
+
+#[test]
+#[ignore]
+/// a 1M step deep water ray traced with the stepper alone, which is the one
+/// copy of the results that `ode_solvers` imposes, and with
+/// `SingleRay::trace_individual`, which should not copy them again
+fn benchmark_long_ray() {
+    let bathymetry = ConstantDepth::new(2000.0);
+    let current = ConstantCurrent::new(0.0, 0.0);
+    let initial_ray = RayState::new(Point::new(0.0, 0.0), WaveNumber::new(0.05, 0.01));
+    let ray = SingleRay::new(&bathymetry, &current, &initial_ray);
+
+    let stepper_only = || {
+        let system = WaveRayPath::new(&bathymetry, &current);
+        let s0 = State::from(initial_ray.clone());
+        let mut stepper = Rk4::new(&system, 0.0, s0, 1_000_000.0, 1.0);
+        stepper.integrate().unwrap();
+        let (t_out, s_out) = stepper.results().get();
+        SolverResult::new(t_out.clone(), s_out.clone())
+    };
+    let traced = ray.trace_individual(0.0, 1_000_000.0, 1.0).unwrap();
+    let expected = stepper_only();
+    assert_eq!(traced.get().0, expected.get().0);
+    assert_eq!(traced.get().1, expected.get().1);
+
+    time_it("long ray (stepper and one copy)", || {
+        stepper_only();
+    });
+
+    time_it("long ray (trace_individual)", || {
+        ray.trace_individual(0.0, 1_000_000.0, 1.0).unwrap();
+    });
+}
@@ -1,18 +1,15 @@
 //! helper functions and types for integration testing
 
-use crate::State;
-
-pub(crate) const XINDEX: usize = 0;
-pub(crate) const YINDEX: usize = 1;
-pub(crate) const KX_INDEX: usize = 2;
-pub(crate) const KY_INDEX: usize = 3;
+use crate::{State, StateView};
+#[allow(unused_imports)]
+pub(crate) use crate::{KX_INDEX, KY_INDEX, X_INDEX, Y_INDEX};
 
 #[cfg(test)]
 
 /// true if the value at the given index increases at each time step
 pub(crate) fn increase(data: &Vec<State>, index: usize) -> bool {
     let mut last = data[0][index];
-    for r in data.iter().filter(|v| !v[0].is_nan()).skip(1) {
+    for r in data.iter().filter(|v| !v.x().is_nan()).skip(1) {
         if !(r[index] > last) {
             return false;
         }
@@ -24,7 +21,7 @@ pub(crate) fn increase(data: &Vec<State>, index: usize) -> bool {
 /// true if the value at the given index decreases at each time step
 pub(crate) fn decrease(data: &Vec<State>, index: usize) -> bool {
     let mut last = data[0][index];
-    for r in data.iter().filter(|v| !v[0].is_nan()).skip(1) {
+    for r in data.iter().filter(|v| !v.x().is_nan()).skip(1) {
         if !(r[index] < last) {
             return false;
         }
@@ -33,10 +30,22 @@ pub(crate) fn decrease(data: &Vec<State>, index: usize) -> bool {
     return true;
 }
 
+/// true if the wavelength 2 pi / k decreases at each time step, as it does
+/// when a ray shoals
+pub(crate) fn wavelength_shortens(data: &[State]) -> bool {
+    let wavelength = |s: &State| 2.0 * std::f64::consts::PI / s.kx().hypot(s.ky());
+    let valid: Vec<f64> = data
+        .iter()
+        .filter(|v| !v.x().is_nan())
+        .map(wavelength)
+        .collect();
+    valid.windows(2).all(|w| w[1] < w[0])
+}
+
 /// true if the value at the given index is exactly the same at each time step
 pub(crate) fn same(data: &Vec<State>, index: usize) -> bool {
     let mut last = data[0][index];
-    for r in data.iter().filter(|v| !v[0].is_nan()).skip(1) {
+    for r in data.iter().filter(|v| !v.x().is_nan()).skip(1) {
         if !(r[index] == last) {
             println!("Expected {last} but got {}", r[index]);
             return false;
@@ -2,11 +2,16 @@
 
 use std::f64::consts::PI;
 
+use tempfile::NamedTempFile;
+
 use crate::{
-    bathymetry::ConstantSlope,
+    bathymetry::{CartesianNetcdf3, ConstantSlope},
     current::ConstantCurrent,
     datatype::{Point, RayState, WaveNumber},
-    ray::ManyRays,
+    dispersion::{self, G},
+    io::utility::create_netcdf3_bathymetry,
+    ray::{ManyRays, SingleRay},
+    StateView,
 };
 
 use crate::tests::helper::*;
@@ -92,22 +97,25 @@ fn test_linear_beach_right() {
 
     // verify up ray
     let (_, data) = up_result.get();
-    assert!(increase(data, XINDEX));
-    assert!(increase(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(increase(data, X_INDEX));
+    assert!(increase(data, Y_INDEX));
     assert!(same(data, KY_INDEX));
     assert!(increase(data, KX_INDEX));
 
     // verify the down ray
     let (_, data) = down_result.get();
-    assert!(increase(data, XINDEX));
-    assert!(decrease(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(increase(data, X_INDEX));
+    assert!(decrease(data, Y_INDEX));
     assert!(same(data, KY_INDEX));
     assert!(increase(data, KX_INDEX));
 
     // verify the straight ray
     let (_, data) = straight_result.get();
-    assert!(increase(data, XINDEX));
-    assert!(same(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(increase(data, X_INDEX));
+    assert!(same(data, Y_INDEX));
     assert!(same(data, KY_INDEX));
     assert!(increase(data, KX_INDEX));
 }
@@ -193,22 +201,25 @@ fn test_linear_beach_left() {
 
     // verify up ray
     let (_, data) = up_result.get();
-    assert!(decrease(data, XINDEX));
-    assert!(increase(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(decrease(data, X_INDEX));
+    assert!(increase(data, Y_INDEX));
     assert!(same(data, KY_INDEX));
     assert!(decrease(data, KX_INDEX));
 
     // verify the down ray
     let (_, data) = down_result.get();
-    assert!(decrease(data, XINDEX));
-    assert!(decrease(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(decrease(data, X_INDEX));
+    assert!(decrease(data, Y_INDEX));
     assert!(same(data, KY_INDEX));
     assert!(decrease(data, KX_INDEX));
 
     // verify the straight ray
     let (_, data) = straight_result.get();
-    assert!(decrease(data, XINDEX));
-    assert!(same(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(decrease(data, X_INDEX));
+    assert!(same(data, Y_INDEX));
     assert!(same(data, KY_INDEX));
     assert!(decrease(data, KX_INDEX));
 }
@@ -294,22 +305,25 @@ fn test_linear_beach_top() {
 
     // verify left ray
     let (_, data) = left_result.get();
-    assert!(decrease(data, XINDEX));
-    assert!(increase(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(decrease(data, X_INDEX));
+    assert!(increase(data, Y_INDEX));
     assert!(same(data, KX_INDEX));
     assert!(increase(data, KY_INDEX));
 
     // verify the down ray
     let (_, data) = right_result.get();
-    assert!(increase(data, XINDEX));
-    assert!(increase(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(increase(data, X_INDEX));
+    assert!(increase(data, Y_INDEX));
     assert!(same(data, KX_INDEX));
     assert!(increase(data, KY_INDEX));
 
     // verify the straight ray
     let (_, data) = vertical_result.get();
-    assert!(same(data, XINDEX));
-    assert!(increase(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(same(data, X_INDEX));
+    assert!(increase(data, Y_INDEX));
     assert!(same(data, KX_INDEX));
     assert!(increase(data, KY_INDEX));
 }
@@ -395,22 +409,92 @@ fn test_linear_beach_bottom() {
 
     // verify left ray
     let (_, data) = left_result.get();
-    assert!(decrease(data, XINDEX));
-    assert!(decrease(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(decrease(data, X_INDEX));
+    assert!(decrease(data, Y_INDEX));
     assert!(same(data, KX_INDEX));
     assert!(decrease(data, KY_INDEX));
 
     // verify the down ray
     let (_, data) = right_result.get();
-    assert!(increase(data, XINDEX));
-    assert!(decrease(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(increase(data, X_INDEX));
+    assert!(decrease(data, Y_INDEX));
     assert!(same(data, KX_INDEX));
     assert!(decrease(data, KY_INDEX));
 
     // verify the straight ray
     let (_, data) = vertical_result.get();
-    assert!(same(data, XINDEX));
-    assert!(decrease(data, YINDEX));
+    assert!(wavelength_shortens(data));
+    assert!(same(data, X_INDEX));
+    assert!(decrease(data, Y_INDEX));
     assert!(same(data, KX_INDEX));
     assert!(decrease(data, KY_INDEX));
 }
+
+#[test]
+/// a ray heading offshore at an angle turns back toward the beach on the
+/// depth contour predicted by Snell's law
+///
+/// ## Bathymetry
+/// `CartesianNetcdf3` grid with 10 m by 50 m spacing of the linear depth
+/// `h = 2 + 0.02 x`
+///
+/// ## Initial conditions
+/// - `x = 10 m`
+/// - `y = 0 m`
+/// - `kx = k * cos(PI/6)`
+/// - `ky = k * sin(PI/6)`
+/// - `k = 0.1`
+///
+/// ## Description
+/// The depth doesn't depend on y, so ky and the frequency are conserved
+/// along the ray. The ray turns back, i.e. kx = 0, where the wavenumber is
+/// ky, which happens at the depth $h_t = \tanh^{-1}(\omega^2 / (g k_y)) / k_y$.
+///
+/// ## Expected behavior
+/// The furthest point offshore of the ray is on the contour of depth $h_t$.
+fn test_linear_beach_turning_point() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.into_temp_path();
+    create_netcdf3_bathymetry(&temp_path, 201, 41, 10.0, 50.0, |x, _y| {
+        2.0 + 0.02 * x as f64
+    });
+    let bathymetry_data = CartesianNetcdf3::open(&temp_path, "x", "y", "depth").unwrap();
+    let current_data = ConstantCurrent::new(0.0, 0.0);
+
+    let k = 0.1;
+    let (kx, ky) = (k * (PI / 6.0).cos(), k * (PI / 6.0).sin());
+    let initial_ray = RayState::new(Point::new(10.0, 0.0), WaveNumber::new(kx, ky));
+
+    let omega = dispersion::intrinsic_frequency(k, 2.2);
+    let turning_depth = (omega * omega / (G * ky)).atanh() / ky;
+
+    let result = SingleRay::new(&bathymetry_data, &current_data, &initial_ray)
+        .trace(0.0, 300.0, 0.1)
+        .unwrap();
+    let (x_turn, y_turn) = result
+        .valid_states()
+        .map(|(_, s)| (s.x(), s.y()))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    // the ray turned back before the end of the integration
+    let (x_last, _) = result.last_valid_position().unwrap();
+    assert!(x_last < x_turn - 10.0, "x_last: {}", x_last);
+
+    let contour = bathymetry_data.depth_contour(turning_depth as f32);
+    let (x_contour, _) = contour
+        .iter()
+        .min_by(|a, b| {
+            (a.1 as f64 - y_turn)
+                .abs()
+                .total_cmp(&(b.1 as f64 - y_turn).abs())
+        })
+        .unwrap();
+    assert!(
+        (x_turn - *x_contour as f64).abs() < 1.0,
+        "turning point: {}, contour: {}",
+        x_turn,
+        x_contour
+    );
+}
@@ -2,6 +2,8 @@
 //!
 //! Simple Integration tests in Rust
 
+#[cfg(test)]
+mod benchmark;
 #[cfg(test)]
 mod linear_beach;
 #[cfg(test)]
@@ -65,20 +65,20 @@ fn constant_depth_deep() {
 
         // x
         if (kx - 0.0).abs() < f64::EPSILON {
-            assert!(same(data, XINDEX));
+            assert!(same(data, X_INDEX));
         } else if kx.is_sign_positive() {
-            assert!(increase(data, XINDEX));
+            assert!(increase(data, X_INDEX));
         } else {
-            assert!(decrease(data, XINDEX));
+            assert!(decrease(data, X_INDEX));
         }
 
         // y
         if (ky - 0.0).abs() < f64::EPSILON {
-            assert!(same(data, YINDEX));
+            assert!(same(data, Y_INDEX));
         } else if ky.is_sign_positive() {
-            assert!(increase(data, YINDEX));
+            assert!(increase(data, Y_INDEX));
         } else {
-            assert!(decrease(data, YINDEX));
+            assert!(decrease(data, Y_INDEX));
         }
 
         // kx and ky will be the same
@@ -139,20 +139,20 @@ fn constant_depth_shallow() {
         let ky = target_ray.wave_number().ky();
         // x
         if (kx - 0.0).abs() < f64::EPSILON {
-            assert!(same(data, XINDEX));
+            assert!(same(data, X_INDEX));
         } else if kx.is_sign_positive() {
-            assert!(increase(data, XINDEX));
+            assert!(increase(data, X_INDEX));
         } else {
-            assert!(decrease(data, XINDEX));
+            assert!(decrease(data, X_INDEX));
         }
 
         // y
         if (ky - 0.0).abs() < f64::EPSILON {
-            assert!(same(data, YINDEX));
+            assert!(same(data, Y_INDEX));
         } else if ky.is_sign_positive() {
-            assert!(increase(data, YINDEX));
+            assert!(increase(data, Y_INDEX));
         } else {
-            assert!(decrease(data, YINDEX));
+            assert!(decrease(data, Y_INDEX));
         }
 
         // kx and ky will be the same
@@ -8,28 +8,121 @@
 //! functions to use RK4 to numerically integrate these equations given the
 //! initial conditions.
 
+use std::cell::Cell;
+
 use derive_builder::Builder;
 use ode_solvers::*;
+use serde::{Deserialize, Serialize};
 
 use crate::bathymetry::BathymetryData;
-use crate::bathymetry::DEFAULT_BATHYMETRY;
 use crate::current::CurrentData;
 use crate::current::DEFAULT_CURRENT;
-use crate::datatype::Point;
-use crate::error::Error;
-use crate::error::Result;
-
-/// constant for gravity
-const G: f64 = 9.8;
+use crate::datatype::{Gradient, Point};
+use crate::dispersion;
+use crate::dispersion::{DispersionModel, BREAKER_INDEX, G, RHO};
+use crate::error::{Error, Result};
 
 /// state of the ray system for `ode_solvers`
 /// the values in the state are x, y, kx, ky
 /// for example: `State::new(x, y, kx, ky)`
 pub(crate) type State = Vector4<f64>;
 
+/// index of x \[m\] in the `State`
+pub(crate) const X_INDEX: usize = 0;
+/// index of y \[m\] in the `State`
+pub(crate) const Y_INDEX: usize = 1;
+/// index of kx \[m^-1\] in the `State`
+pub(crate) const KX_INDEX: usize = 2;
+/// index of ky \[m^-1\] in the `State`
+pub(crate) const KY_INDEX: usize = 3;
+
+/// Named access to the components of a `State`
+///
+/// For example, `state.kx()` instead of `state[2]`, so that kx and ky can not
+/// be swapped by mistake.
+pub(crate) trait StateView {
+    /// x \[m\]
+    fn x(&self) -> f64;
+    /// y \[m\]
+    fn y(&self) -> f64;
+    /// x component of the wavenumber \[m^-1\]
+    fn kx(&self) -> f64;
+    /// y component of the wavenumber \[m^-1\]
+    fn ky(&self) -> f64;
+}
+
+impl StateView for State {
+    fn x(&self) -> f64 {
+        self[X_INDEX]
+    }
+
+    fn y(&self) -> f64 {
+        self[Y_INDEX]
+    }
+
+    fn kx(&self) -> f64 {
+        self[KX_INDEX]
+    }
+
+    fn ky(&self) -> f64 {
+        self[KY_INDEX]
+    }
+}
+
 /// time in seconds for `ode_solvers` to use
 pub(crate) type Time = f64;
 
+/// state of the ray system augmented with the energy flux, i.e. (x, y, kx,
+/// ky, F), for `ode_solvers`. See `EnergyRayPath`.
+pub(crate) type EnergyState = SVector<f64, 5>;
+
+/// state of the ray system augmented with its variation, i.e. (x, y, kx, ky,
+/// dx, dy, dkx, dky), for `ode_solvers`. See `SpreadingRayPath`.
+pub(crate) type SpreadingState = SVector<f64, 8>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// The reason the integration of a ray was stopped
+pub(crate) enum TerminationReason {
+    /// The ray was traced until the end time.
+    ReachedEndTime,
+    /// The wave was blocked by an opposing current, i.e. the absolute group
+    /// velocity in the direction of propagation reached zero.
+    Blocked,
+    /// The ray left the domain of the bathymetry data.
+    LeftBathymetry,
+    /// The ray left the domain of the current data.
+    LeftCurrent,
+    /// The ray reached a land cell of the bathymetry data.
+    Land,
+    /// The stop condition of the integration was met, see `StopCondition`.
+    ConditionMet,
+    /// The depth reached zero, e.g. at the shoreline of a bathymetry defined
+    /// by a function, which has no land cells.
+    ShorelineReached,
+    /// The wave height reached the depth-limited breaking height, see
+    /// `BREAKER_INDEX`. It is only known when the energy is traced, see
+    /// `SingleRay::trace_with_energy`.
+    Breaking,
+    /// The state of the ray was NaN, e.g. an invalid initial condition.
+    NaNInInput,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// A condition to stop the integration of a ray, instead of a fixed end time
+pub(crate) enum StopCondition {
+    /// At the given time \[s\], i.e. the usual end time.
+    AtTime(f64),
+    /// Where the depth is shallower than the given depth \[m\].
+    AtDepthBelow(f64),
+    /// Where x is less than the given value \[m\].
+    AtXBelow(f64),
+    /// Where x is more than the given value \[m\].
+    AtXAbove(f64),
+    /// Where the ray leaves the domain of the bathymetry or current data.
+    OnBoundary,
+}
+
 #[derive(Builder)]
 /// Stores the bathymetry and current data and calculates the system of odes
 /// that define the ray tracing.
@@ -40,14 +133,44 @@ pub(crate) type Time = f64;
 /// - using the `builder` method, for example,
 ///   `WaveRayPath::builder().bathymetry_data(&depth_data).current_data(&current_data).build().unwrap()`
 pub(crate) struct WaveRayPath<'a> {
-    #[builder(default = "&DEFAULT_BATHYMETRY")]
-    /// A reference to a BathymetryData trait object. If this is None, the depth
-    /// will be set to 2000 m.
-    bathymetry_data: &'a dyn BathymetryData,
+    #[builder(default, setter(strip_option))]
+    /// Optional reference to a BathymetryData trait object. If this is None,
+    /// the depth will be set to `default_depth` with zero gradient.
+    bathymetry_data: Option<&'a dyn BathymetryData>,
+    #[builder(default = "2000.0")]
+    /// The depth \[m\] used when no bathymetry data is given. Defaults to
+    /// 2000 m.
+    default_depth: f64,
+    #[builder(default = "0.0")]
+    /// The minimum depth \[m\] used by the odes. Shallower depths are
+    /// clamped to this value, with zero gradient, so that the ray can
+    /// continue up to the shoreline instead of stopping where the group
+    /// velocity is NaN. Note that the ray then continues past the shoreline
+    /// as if the depth were `min_depth`. Defaults to 0 m, which disables the
+    /// clamp.
+    min_depth: f64,
+    #[builder(default)]
+    /// The dispersion relation used for the group velocity and the
+    /// refraction by the bathymetry. Defaults to
+    /// `DispersionModel::FullLinear`. Note that the frequency, e.g.
+    /// `intrinsic_frequency`, always uses the full linear dispersion relation.
+    dispersion_model: DispersionModel,
     #[builder(default = "&DEFAULT_CURRENT")]
     /// Optional reference to a CurrentData trait object. If this is None, the
     /// current will be set to 0 m/s.
     current_data: &'a dyn CurrentData,
+    #[builder(default = "0.0")]
+    /// The bottom friction coefficient $C_f$ of the dissipation of the wave
+    /// energy, see `energy_flux_odes`. Defaults to 0, i.e. no dissipation.
+    bottom_friction: f64,
+    #[builder(default, setter(strip_option))]
+    /// Optional condition to stop the integration, checked after each step.
+    /// When it is met, the termination reason is set to `ConditionMet`.
+    stop_condition: Option<StopCondition>,
+    #[builder(setter(skip))]
+    /// The reason the integration was stopped, recorded while evaluating the
+    /// odes. This is `None` until a termination condition is found.
+    termination: Cell<Option<TerminationReason>>,
 }
 
 #[allow(dead_code)]
@@ -69,8 +192,14 @@ impl<'a> WaveRayPath<'a> {
     /// Returns: `Self` : the newly created `WaveRayPath`
     pub(crate) fn new(bathymetry_data: &'a dyn BathymetryData, current_data: &'a dyn CurrentData) -> Self {
         WaveRayPath {
-            bathymetry_data,
+            bathymetry_data: Some(bathymetry_data),
+            default_depth: 2000.0,
+            min_depth: 0.0,
+            dispersion_model: DispersionModel::FullLinear,
             current_data,
+            bottom_friction: 0.0,
+            stop_condition: None,
+            termination: Cell::new(None),
         }
     }
 
@@ -111,31 +240,66 @@ impl<'a> WaveRayPath<'a> {
     /// # Errors
     /// - `Error::IndexOutOfBounds` : this error is returned when the `x` or `y`
     /// input give an out of bounds output.
+    /// - The termination reason is set to `LeftBathymetry` or `LeftCurrent`
+    ///   depending on which data is out of bounds.
+    /// - `Error::Land` : the point is on a land cell of the bathymetry. The
+    ///   termination reason is set to `Land`.
     /// - `Error::InvalidArgument` : this error is returned from
     ///   `interpolator::bilinear` due to incorrect argument passed.
     /// `Error::ArgumentOutOfBounds`
     /// - If k is negative, group velocity will return this error.
+    ///
+    /// - `Error::Blocked` : the wave can not propagate against the current.
+    ///
+    /// A depth that is not positive makes the derivatives NaN, and the
+    /// termination reason is set to `ShorelineReached`. A NaN state sets it
+    /// to `NaNInInput`, unless another reason was already recorded.
     pub(crate) fn odes(&self, x: &f64, y: &f64, kx: &f64, ky: &f64) -> Result<(f64, f64, f64, f64)> {
+        if [x, y, kx, ky].iter().any(|v| v.is_nan()) {
+            self.record_first(TerminationReason::NaNInInput);
+        }
         let point = crate::Point::new(*x, *y);
         let (h, dh) = self
-            .bathymetry_data
-            .depth_and_gradient(&Point::new(*x as f32, *y as f32))?;
+            .depth_and_gradient(x, y)
+            .inspect_err(|e| self.record_data_error(e, TerminationReason::LeftBathymetry))?;
 
         let h = h as f64;
+        if h <= 0.0 {
+            // the group velocity is NaN, which stops the integration
+            self.record_first(TerminationReason::ShorelineReached);
+        }
         let dhdx = *dh.dx() as f64;
         let dhdy = *dh.dy() as f64;
 
         // get the current and gradient from the current data or use default.
-        let (current, (du, dv)) = self.current_data.current_and_gradient(&point)?;
+        // the gradient is skipped when it is zero everywhere.
+        let (current, gradient) = if self.current_data.is_uniform() {
+            self.current_data.current(&point).map(|c| (c, None))
+        } else {
+            self.current_data
+                .current_and_gradient(&point)
+                .map(|(c, g)| (c, Some(g)))
+        }
+        .inspect_err(|e| self.record_data_error(e, TerminationReason::LeftCurrent))?;
 
-        // magnitude and direction of the wavenumber
+        // magnitude of the wavenumber. the intrinsic group velocity is
+        // parallel to the wavenumber, but the ray direction includes the
+        // current, see `energy_direction`.
         let k = (kx * kx + ky * ky).sqrt();
-        let theta = ky.atan2(*kx);
 
         // calculate the group velocity
         let cg = self.group_velocity(&k, &h)?;
-        let cgx = cg * theta.cos() + current.u();
-        let cgy = cg * theta.sin() + current.v();
+
+        // the wave is blocked when it can't propagate against the current
+        if cg + (kx * current.u() + ky * current.v()) / k <= 0.0 {
+            return Err(Error::Blocked);
+        }
+
+        // the direction is kx / k and ky / k rather than the cos and sin of
+        // atan2(ky, kx), which are not exactly zero along the axes, e.g.
+        // cos(pi / 2) ~ 6e-17, and would make a ray along y drift in x.
+        let cgx = cg * kx / k + current.u();
+        let cgy = cg * ky / k + current.v();
 
         let dxdt = cgx;
         let dydt = cgy;
@@ -143,12 +307,323 @@ impl<'a> WaveRayPath<'a> {
         // calculate dk/dt
         let (dkxdt_bathy, dkydt_bathy) = self.dkdt_bathy(&k, &h, &dhdx, &dhdy);
 
-        let dkxdt = dkxdt_bathy - kx * du.dx() - ky * dv.dx();
-        let dkydt = dkydt_bathy - kx * du.dy() - ky * dv.dy();
+        let (dkxdt, dkydt) = match gradient {
+            None => (dkxdt_bathy, dkydt_bathy),
+            Some(gradient) => (
+                dkxdt_bathy - kx * gradient.dudx() - ky * gradient.dvdx(),
+                dkydt_bathy - kx * gradient.dudy() - ky * gradient.dvdy(),
+            ),
+        };
 
         Ok((dxdt, dydt, dkxdt, dkydt))
     }
 
+    /// Depth and gradient at the given point from the bathymetry data, or
+    /// `default_depth` with zero gradient if there is no bathymetry data.
+    ///
+    /// Depths shallower than `min_depth` are clamped to it with zero
+    /// gradient. NaN depths are not clamped.
+    fn depth_and_gradient(&self, x: &f64, y: &f64) -> Result<(f32, Gradient<f32>)> {
+        let (h, dh) = match self.bathymetry_data {
+            Some(bathymetry_data) => bathymetry_data.depth_and_gradient_f64(&Point::new(*x, *y))?,
+            None => (self.default_depth as f32, Gradient::new(0.0, 0.0)),
+        };
+        let min_depth = self.min_depth as f32;
+        if min_depth > 0.0 && h < min_depth {
+            return Ok((min_depth, Gradient::new(0.0, 0.0)));
+        }
+        Ok((h, dh))
+    }
+
+    /// Intrinsic frequency, relative to the water, at the given state
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state (x, y, kx, ky) of the ray
+    ///
+    /// # Returns
+    /// `Result<f64>` : the intrinsic angular frequency \[rad/s\] or an error
+    /// getting the depth.
+    pub(crate) fn intrinsic_frequency(&self, state: &State) -> Result<f64> {
+        let (h, _) = self.depth_and_gradient(&state.x(), &state.y())?;
+        let k = state.kx().hypot(state.ky());
+        Ok(dispersion::intrinsic_frequency(k, h as f64))
+    }
+
+    /// Intrinsic group velocity, relative to the water, at the given state
+    ///
+    /// Unlike the velocity of the ray given by `odes`, the current is not
+    /// added. The wave is blocked where this velocity plus the component of
+    /// the current in the direction of the wavenumber reaches zero.
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state (x, y, kx, ky) of the ray
+    ///
+    /// # Returns
+    /// `Result<f64>` : the magnitude of the intrinsic group velocity \[m/s\]
+    /// or an error getting the depth or if the wavenumber is zero.
+    pub(crate) fn intrinsic_group_velocity(&self, state: &State) -> Result<f64> {
+        let (h, _) = self.depth_and_gradient(&state.x(), &state.y())?;
+        let k = state.kx().hypot(state.ky());
+        self.group_velocity(&k, &(h as f64))
+    }
+
+    /// Direction of the energy propagation, i.e. of the ray, at the given
+    /// state
+    ///
+    /// The group velocity relative to the water is parallel to the
+    /// wavenumber, but the current advects the energy, thus with a current
+    /// the ray direction, $\arctan(c_{gy} / c_{gx})$ with the current
+    /// included, differs from the wavenumber direction,
+    /// $\arctan(k_y / k_x)$, which is the direction normal to the crests.
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state (x, y, kx, ky) of the ray
+    ///
+    /// # Returns
+    /// `Result<f64>` : the direction \[rad\], counterclockwise from the x
+    /// axis, or an error getting the depth or the current. Unlike `odes`, no
+    /// termination reason is recorded.
+    pub(crate) fn energy_direction(&self, state: &State) -> Result<f64> {
+        let cg = self.intrinsic_group_velocity(state)?;
+        let current = self
+            .current_data
+            .current(&crate::Point::new(state.x(), state.y()))?;
+        let k = state.kx().hypot(state.ky());
+        let cgx = cg * state.kx() / k + current.u();
+        let cgy = cg * state.ky() / k + current.v();
+        Ok(cgy.atan2(cgx))
+    }
+
+    /// Rate of change of a variation of the state
+    ///
+    /// This is the linearized ray equations, i.e. the Jacobian of `odes` at
+    /// `state` times `variation`. It is the directional derivative of `odes`
+    /// along `variation`, computed with a central difference. The
+    /// perturbation is small relative to the position and wavenumber.
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state (x, y, kx, ky) of the ray
+    ///
+    /// `variation` : `&State`
+    /// - the variation (dx, dy, dkx, dky) of the state, e.g. the difference
+    ///   to an infinitesimally close ray
+    ///
+    /// # Returns
+    /// `Result<State>` : the rate of change of the variation or an error
+    /// evaluating the odes.
+    ///
+    /// # Note
+    /// The second derivatives of the depth and current are estimated from
+    /// their gradients, thus they are zero within a cell of gridded data with
+    /// one-sided gradients.
+    pub(crate) fn variation_odes(&self, state: &State, variation: &State) -> Result<State> {
+        let position = variation.x().hypot(variation.y());
+        let wavenumber = variation.kx().hypot(variation.ky());
+        // f32 coordinates are used by the data, so the position can not be
+        // perturbed by less than its precision
+        let eps_position = 1e-4 * state.x().abs().max(state.y().abs()).max(1.0);
+        let eps_wavenumber = 1e-6 * state.kx().hypot(state.ky());
+
+        let mut eps = f64::INFINITY;
+        if position > 0.0 {
+            eps = eps.min(eps_position / position);
+        }
+        if wavenumber > 0.0 {
+            eps = eps.min(eps_wavenumber / wavenumber);
+        }
+        if eps.is_infinite() {
+            return Ok(State::zeros());
+        }
+
+        let odes = |s: State| -> Result<State> {
+            let (dxdt, dydt, dkxdt, dkydt) = self.odes(&s.x(), &s.y(), &s.kx(), &s.ky())?;
+            Ok(State::new(dxdt, dydt, dkxdt, dkydt))
+        };
+        Ok((odes(state + variation * eps)? - odes(state - variation * eps)?) / (2.0 * eps))
+    }
+
+    /// Absolute frequency, as seen by a fixed observer, at the given state
+    ///
+    /// The absolute frequency is the intrinsic frequency Doppler shifted by
+    /// the current: $\omega = \sigma + \vec{k} \cdot \vec{U}$.
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state (x, y, kx, ky) of the ray
+    ///
+    /// # Returns
+    /// `Result<f64>` : the absolute angular frequency \[rad/s\] or an error
+    /// getting the depth or current.
+    pub(crate) fn absolute_frequency(&self, state: &State) -> Result<f64> {
+        let sigma = self.intrinsic_frequency(state)?;
+        let current = self
+            .current_data
+            .current(&Point::new(state.x(), state.y()))?;
+        Ok(sigma + state.kx() * current.u() + state.ky() * current.v())
+    }
+
+    /// Set the condition to stop the integration
+    ///
+    /// # Arguments
+    /// `condition` : `StopCondition`
+    /// - the condition checked after each step
+    ///
+    /// # Returns
+    /// `Self` : the `WaveRayPath` with the stop condition
+    pub(crate) fn with_stop_condition(mut self, condition: StopCondition) -> Self {
+        self.stop_condition = Some(condition);
+        self
+    }
+
+    /// Set the bottom friction coefficient
+    ///
+    /// # Arguments
+    /// `coefficient` : `f64`
+    /// - the dimensionless friction coefficient $C_f$, e.g. 0.01 for a
+    ///   sandy bottom
+    ///
+    /// # Returns
+    /// `Self` : the `WaveRayPath` with the bottom friction
+    pub(crate) fn with_bottom_friction(mut self, coefficient: f64) -> Self {
+        self.bottom_friction = coefficient;
+        self
+    }
+
+    /// Rate of change of the energy flux along the ray
+    ///
+    /// The energy flux $F = E c_g$ is conserved along the ray, except for the
+    /// dissipation by bottom friction of monochromatic waves,
+    ///
+    /// $D = \frac{2}{3 \pi} \rho C_f u_b^3$
+    ///
+    /// where $u_b = a \sigma / \sinh(k h)$ is the bottom orbital velocity and
+    /// $E = \rho g a^2 / 2$. Since $dF/ds = -D$ along the ray, $dF/dt = -c_g
+    /// D$. The dissipation grows quickly as the depth decreases.
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state (x, y, kx, ky) of the ray
+    ///
+    /// `flux` : `f64`
+    /// - the energy flux \[W/m\]
+    ///
+    /// # Returns
+    /// `Result<f64>` : dF/dt \[W/m/s\], which is zero without bottom
+    /// friction, or an error getting the depth or the group velocity.
+    pub(crate) fn energy_flux_odes(&self, state: &State, flux: f64) -> Result<f64> {
+        if self.bottom_friction == 0.0 {
+            return Ok(0.0);
+        }
+        let (h, _) = self.depth_and_gradient(&state.x(), &state.y())?;
+        let h = h as f64;
+        let k = state.kx().hypot(state.ky());
+        let cg = self.group_velocity(&k, &h)?;
+        let amplitude = (2.0 * flux / (cg * RHO * G)).max(0.0).sqrt();
+        let velocity = amplitude * dispersion::intrinsic_frequency(k, h) / (k * h).sinh();
+        let dissipation =
+            2.0 / (3.0 * std::f64::consts::PI) * RHO * self.bottom_friction * velocity.powi(3);
+        Ok(-cg * dissipation)
+    }
+
+    /// Whether the wave breaks at the given state
+    ///
+    /// Depth-limited waves break where the wave height $H = 2 a$ exceeds
+    /// `BREAKER_INDEX` times the depth, with the amplitude $a$ from the energy
+    /// flux $F = \rho g a^2 c_g / 2$.
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state (x, y, kx, ky) of the ray
+    ///
+    /// `flux` : `f64`
+    /// - the energy flux \[W/m\]
+    ///
+    /// # Returns
+    /// `Result<bool>` : whether the wave breaks, or an error getting the depth
+    /// or the group velocity.
+    pub(crate) fn is_breaking(&self, state: &State, flux: f64) -> Result<bool> {
+        let (h, _) = self.depth_and_gradient(&state.x(), &state.y())?;
+        let h = h as f64;
+        let k = state.kx().hypot(state.ky());
+        let cg = self.group_velocity(&k, &h)?;
+        let amplitude = (2.0 * flux / (cg * RHO * G)).max(0.0).sqrt();
+        Ok(2.0 * amplitude > BREAKER_INDEX * h)
+    }
+
+    /// How far the state is past the stop condition
+    ///
+    /// # Arguments
+    /// `state` : `&State`
+    /// - the state of the ray, of which only (x, y) are used
+    ///
+    /// # Returns
+    /// `Option<f64>` : negative before the condition is met, and zero or
+    /// positive after, e.g. `x - x0` for `AtXAbove(x0)`. `None` if there is no
+    /// condition, the condition is not on the state (`AtTime` and
+    /// `OnBoundary`), or the depth is not available.
+    pub(crate) fn stop_excess(&self, state: &State) -> Option<f64> {
+        match self.stop_condition? {
+            StopCondition::AtTime(_) | StopCondition::OnBoundary => None,
+            StopCondition::AtDepthBelow(depth) => {
+                let (h, _) = self.depth_and_gradient(&state.x(), &state.y()).ok()?;
+                Some(depth - h as f64)
+            }
+            StopCondition::AtXBelow(x) => Some(x - state.x()),
+            StopCondition::AtXAbove(x) => Some(state.x() - x),
+        }
+    }
+
+    /// Whether the integration should stop after a step, because the ray
+    /// is NaN or the stop condition is met
+    fn should_stop(&self, y: &State, dy: &State) -> bool {
+        if stop_integration(y, dy) {
+            return true;
+        }
+        match self.stop_excess(y) {
+            Some(excess) if excess >= 0.0 => {
+                self.termination.set(Some(TerminationReason::ConditionMet));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The reason the integration was stopped, if any
+    ///
+    /// # Returns
+    /// `Option<TerminationReason>` : the reason recorded during the
+    /// integration, or `None` if the ray was not stopped by a known condition.
+    pub(crate) fn termination_reason(&self) -> Option<TerminationReason> {
+        self.termination.get()
+    }
+
+    /// Record the termination reason, unless one was already recorded
+    ///
+    /// Once a step fails, the following stages of the Rk4 step are evaluated
+    /// on NaN states, which must not replace the reason of the failure.
+    fn record_first(&self, reason: TerminationReason) {
+        if self.termination.get().is_none() {
+            self.termination.set(Some(reason));
+        }
+    }
+
+    /// Record the termination reason corresponding to an error of the data
+    ///
+    /// `left_domain` is recorded if `error` indicates that the ray is outside
+    /// of the domain of the data, and `Land` if it reached a land cell.
+    fn record_data_error(&self, error: &Error, left_domain: TerminationReason) {
+        let reason = match error {
+            Error::IndexOutOfBounds => left_domain,
+            Error::Land => TerminationReason::Land,
+            _ => return,
+        };
+        self.termination.set(Some(reason));
+    }
+
     /// Calculates the group velocity
     ///
     /// # Arguments
@@ -163,9 +638,11 @@ impl<'a> WaveRayPath<'a> {
     ///
     /// `Result<f64>`
     ///
-    /// - `Ok(f64)` : returns the calculated group velocity as a float. Note: if
-    ///   `h` is less then 0, it will return `f64::NAN`. This represents the
-    ///   wave have no water to propagate in.
+    /// - `Ok(f64)` : returns the calculated group velocity as a float, with the
+    ///   dispersion relation of the `dispersion_model`, i.e. `sqrt(g h)` in
+    ///   shallow water and `g / (2 sigma)` in deep water. Note: if `h` is
+    ///   less then 0, it will return `f64::NAN`. This represents the wave have
+    ///   no water to propagate in.
     ///
     /// - `Err(Error::ArgumentOutOfBounds)` : returns this error if k <= 0.
     ///
@@ -175,16 +652,13 @@ impl<'a> WaveRayPath<'a> {
     /// - If k is negative, group velocity will return this error.
     ///
     pub(crate) fn group_velocity(&self, k: &f64, h: &f64) -> Result<f64> {
-        if *h <= 0.0 {
-            return Ok(f64::NAN);
-        }
-        if *k <= 0.0 {
-            return Err(Error::ArgumentOutOfBounds);
+        match self.dispersion_model {
+            DispersionModel::FullLinear => dispersion::group_velocity(*k, *h),
+            _ if *h <= 0.0 => Ok(f64::NAN),
+            _ if *k <= 0.0 => Err(Error::ArgumentOutOfBounds),
+            DispersionModel::ShallowWater => Ok((G * h).sqrt()),
+            DispersionModel::DeepWater => Ok(0.5 * (G / k).sqrt()),
         }
-        let cg = (G / 2.0)
-            * (((k * h).tanh() + (k * h) / (k * h).cosh().powi(2))
-                / (k * G * (k * h).tanh()).sqrt());
-        Ok(cg)
     }
 
     /// calculate the derivative of the wavenumber vector with respect to time
@@ -203,24 +677,38 @@ impl<'a> WaveRayPath<'a> {
     /// - the partial of depth with respect to y
     ///
     /// # Returns
-    /// `(f64, f64)` : values corresponding to (dkx/dt, dky/dt)
+    /// `(f64, f64)` : values corresponding to (dkx/dt, dky/dt), with the
+    /// dispersion relation of the `dispersion_model`
     fn dkdt_bathy(&self, k: &f64, h: &f64, dhdx: &f64, dhdy: &f64) -> (f64, f64) {
-        let dkxdt_bathy = (-0.5) * k * 1.0 / (k * h).sinh() * 1.0 / (k * h).cosh()
-            * (G * k * (k * h).tanh()).sqrt()
-            * dhdx;
-        let dkydt_bathy = (-0.5) * k * 1.0 / (k * h).sinh() * 1.0 / (k * h).cosh()
-            * (G * k * (k * h).tanh()).sqrt()
-            * dhdy;
+        // d(sigma)/dh of the dispersion relation
+        let dsigmadh = match self.dispersion_model {
+            DispersionModel::FullLinear => {
+                0.5 * k * 1.0 / (k * h).sinh() * 1.0 / (k * h).cosh()
+                    * (G * k * (k * h).tanh()).sqrt()
+            }
+            DispersionModel::ShallowWater => 0.5 * k * (G / h).sqrt(),
+            // sigma does not depend on the depth
+            DispersionModel::DeepWater => 0.0,
+        };
+        let dkxdt_bathy = -dsigmadh * dhdx;
+        let dkydt_bathy = -dsigmadh * dhdy;
 
         (dkxdt_bathy, dkydt_bathy)
     }
 }
 
 impl<'a> ode_solvers::System<Time, State> for WaveRayPath<'a> {
+    /// `_t` is the physical time \[s\], starting at the `start_time` of the
+    /// integration. The current and bathymetry data are stationary, so it is
+    /// not used yet, but it is the time at which a time-varying current would
+    /// be evaluated.
     fn system(&self, _t: Time, s: &State, ds: &mut State) {
         // calculate the derivatives using the system of odes
         let (dxdt, dydt, dkxdt, dkydt) = match self.odes(&s[0], &s[1], &s[2], &s[3]) {
-            Err(_) => {
+            Err(e) => {
+                if let Error::Blocked = e {
+                    self.termination.set(Some(TerminationReason::Blocked));
+                }
                 // Error at time t. Setting all further output to NaN.
                 (f64::NAN, f64::NAN, f64::NAN, f64::NAN)
             }
@@ -234,15 +722,282 @@ impl<'a> ode_solvers::System<Time, State> for WaveRayPath<'a> {
     }
 
     fn solout(&mut self, _x: Time, y: &State, dy: &State) -> bool {
-        if (dy[0].is_nan() && dy[1].is_nan() && dy[2].is_nan() && dy[3].is_nan())
-            || (y[0].is_nan() && y[1].is_nan() && y[2].is_nan() && y[3].is_nan())
+        self.should_stop(y, dy)
+    }
+}
+
+/// Allows the integration with a reference to a `WaveRayPath`, so that the
+/// termination reason can be retrieved after the integration.
+impl<'a, 'b> ode_solvers::System<Time, State> for &'b WaveRayPath<'a> {
+    fn system(&self, t: Time, s: &State, ds: &mut State) {
+        (*self).system(t, s, ds)
+    }
+
+    fn solout(&mut self, _x: Time, y: &State, dy: &State) -> bool {
+        self.should_stop(y, dy)
+    }
+}
+
+/// The ray system augmented with its variational equations
+///
+/// Besides the ray, a variation of the state, i.e. the difference to an
+/// infinitesimally close ray, is integrated with the linearized ray
+/// equations (see `WaveRayPath::variation_odes`). For example, the variation
+/// started from the rotation of the wavenumber gives the geometric spreading
+/// of the rays, which is the basis for the amplitude along the ray.
+pub(crate) struct SpreadingRayPath<'a, 'b> {
+    /// the ray system
+    path: &'b WaveRayPath<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a, 'b> SpreadingRayPath<'a, 'b> {
+    /// Construct a new `SpreadingRayPath`
+    ///
+    /// # Arguments
+    /// `path` : `&WaveRayPath`
+    /// - the ray system, which records the termination reason
+    ///
+    /// # Returns
+    /// `Self` : the augmented system
+    pub(crate) fn new(path: &'b WaveRayPath<'a>) -> Self {
+        SpreadingRayPath { path }
+    }
+}
+
+impl<'a, 'b> ode_solvers::System<Time, SpreadingState> for SpreadingRayPath<'a, 'b> {
+    fn system(&self, t: Time, s: &SpreadingState, ds: &mut SpreadingState) {
+        let state: State = s.fixed_rows::<4>(0).into_owned();
+        let variation: State = s.fixed_rows::<4>(4).into_owned();
+
+        let mut dstate = State::zeros();
+        self.path.system(t, &state, &mut dstate);
+        let dvariation = self
+            .path
+            .variation_odes(&state, &variation)
+            .unwrap_or(State::from_element(f64::NAN));
+
+        ds.fixed_rows_mut::<4>(0).copy_from(&dstate);
+        ds.fixed_rows_mut::<4>(4).copy_from(&dvariation);
+    }
+
+    fn solout(&mut self, _x: Time, y: &SpreadingState, dy: &SpreadingState) -> bool {
+        self.path.should_stop(
+            &y.fixed_rows::<4>(0).into_owned(),
+            &dy.fixed_rows::<4>(0).into_owned(),
+        )
+    }
+}
+
+/// The ray system with the absolute frequency enforced at each step
+///
+/// Without a current and with steady bathymetry, the frequency is conserved
+/// along the ray. Instead of integrating (kx, ky), only the direction of the
+/// wavenumber is integrated, and its magnitude is found at each evaluation by
+/// solving the dispersion relation with the initial frequency and the local
+/// depth, so the frequency doesn't drift with the integration error. The
+/// state is (x, y, theta, 0), where theta is the direction of the
+/// wavenumber, and `full_state` converts it back to (x, y, kx, ky).
+#[derive(Clone, Copy)]
+pub(crate) struct FrequencyConservingRayPath<'a, 'b> {
+    /// the ray system, which records the termination reason
+    path: &'b WaveRayPath<'a>,
+    /// the conserved angular frequency \[rad/s\]
+    omega: f64,
+}
+
+#[allow(dead_code)]
+impl<'a, 'b> FrequencyConservingRayPath<'a, 'b> {
+    /// Construct a new `FrequencyConservingRayPath`
+    ///
+    /// # Arguments
+    /// `path` : `&WaveRayPath`
+    /// - the ray system, which records the termination reason
+    ///
+    /// `initial` : `&State`
+    /// - the initial state (x, y, kx, ky), which sets the conserved frequency
+    ///
+    /// # Returns
+    /// `Result<Self>` : the frequency conserving system
+    ///
+    /// # Errors
+    /// - `Error::InvalidArgument` : the current is not zero, thus the
+    ///   intrinsic frequency is not conserved.
+    /// - any error getting the depth at the initial position.
+    pub(crate) fn new(path: &'b WaveRayPath<'a>, initial: &State) -> Result<Self> {
+        let current = path
+            .current_data
+            .current(&Point::new(initial.x(), initial.y()))?;
+        if !path.current_data.is_uniform() || *current.u() != 0.0 || *current.v() != 0.0 {
+            return Err(Error::InvalidArgument);
+        }
+        let omega = path.intrinsic_frequency(initial)?;
+        Ok(FrequencyConservingRayPath { path, omega })
+    }
+
+    /// The initial state (x, y, theta, 0) from the state (x, y, kx, ky)
+    pub(crate) fn initial_state(&self, state: &State) -> State {
+        State::new(state.x(), state.y(), state.ky().atan2(state.kx()), 0.0)
+    }
+
+    /// Convert the state (x, y, theta, 0) to (x, y, kx, ky)
+    ///
+    /// The magnitude of the wavenumber is the solution of the dispersion
+    /// relation at the position. The state is NaN where it can't be found.
+    pub(crate) fn full_state(&self, s: &State) -> State {
+        if s.iter().any(|v| v.is_nan()) {
+            return State::from_element(f64::NAN);
+        }
+        match self.wavenumber(s) {
+            Ok(k) => State::new(s[0], s[1], k * s[2].cos(), k * s[2].sin()),
+            Err(_) => State::from_element(f64::NAN),
+        }
+    }
+
+    /// Magnitude of the wavenumber with the conserved frequency at the
+    /// position of the state (x, y, theta, 0)
+    fn wavenumber(&self, s: &State) -> Result<f64> {
+        let (h, _) = self
+            .path
+            .depth_and_gradient(&s[0], &s[1])
+            .inspect_err(|e| {
+                self.path
+                    .record_data_error(e, TerminationReason::LeftBathymetry)
+            })?;
+        dispersion::wavenumber(self.omega, h as f64)
+    }
+}
+
+impl<'a, 'b> ode_solvers::System<Time, State> for FrequencyConservingRayPath<'a, 'b> {
+    fn system(&self, t: Time, s: &State, ds: &mut State) {
+        let state = self.full_state(s);
+        let mut dstate = State::zeros();
+        self.path.system(t, &state, &mut dstate);
+
+        // rotation of the wavenumber, the change of its magnitude is dropped
+        let k2 = state.kx() * state.kx() + state.ky() * state.ky();
+        ds[0] = dstate[0];
+        ds[1] = dstate[1];
+        ds[2] = (state.kx() * dstate[3] - state.ky() * dstate[2]) / k2;
+        ds[3] = if ds[2].is_nan() { f64::NAN } else { 0.0 };
+    }
+
+    /// The stop condition only uses (x, y), which are the same in both states
+    fn solout(&mut self, _x: Time, y: &State, dy: &State) -> bool {
+        self.path.should_stop(y, dy)
+    }
+}
+
+/// The ray system augmented with the energy flux
+///
+/// Besides the ray, the energy flux is integrated with
+/// `WaveRayPath::energy_flux_odes`, which dissipates it by bottom friction.
+pub(crate) struct EnergyRayPath<'a, 'b> {
+    /// the ray system, which records the termination reason
+    path: &'b WaveRayPath<'a>,
+}
+
+#[allow(dead_code)]
+impl<'a, 'b> EnergyRayPath<'a, 'b> {
+    /// Construct a new `EnergyRayPath`
+    ///
+    /// # Arguments
+    /// `path` : `&WaveRayPath`
+    /// - the ray system, with the bottom friction
+    ///
+    /// # Returns
+    /// `Self` : the augmented system
+    pub(crate) fn new(path: &'b WaveRayPath<'a>) -> Self {
+        EnergyRayPath { path }
+    }
+}
+
+impl<'a, 'b> ode_solvers::System<Time, EnergyState> for EnergyRayPath<'a, 'b> {
+    fn system(&self, t: Time, s: &EnergyState, ds: &mut EnergyState) {
+        let state: State = s.fixed_rows::<4>(0).into_owned();
+
+        let mut dstate = State::zeros();
+        self.path.system(t, &state, &mut dstate);
+        let dflux = self.path.energy_flux_odes(&state, s[4]).unwrap_or(f64::NAN);
+
+        ds.fixed_rows_mut::<4>(0).copy_from(&dstate);
+        ds[4] = dflux;
+    }
+
+    /// The integration also stops where the wave breaks, see `is_breaking`
+    fn solout(&mut self, _x: Time, y: &EnergyState, dy: &EnergyState) -> bool {
+        let state: State = y.fixed_rows::<4>(0).into_owned();
+        if self
+            .path
+            .should_stop(&state, &dy.fixed_rows::<4>(0).into_owned())
         {
-            // NaN in derivatives or output. Likely reached end of current or
-            // bathy domain. Stopping integration.
-            true
-        } else {
-            false
+            return true;
+        }
+        if self.path.is_breaking(&state, y[4]).unwrap_or(false) {
+            self.path.record_first(TerminationReason::Breaking);
+            return true;
+        }
+        false
+    }
+}
+
+/// The ray system calling an observer at each step
+///
+/// The integration is the same as the `WaveRayPath`, but the observer is
+/// called with the time and state of each valid step, e.g. to log derived
+/// quantities along the ray without storing them.
+pub(crate) struct ObservedRayPath<'a, 'b, F: FnMut(Time, &State)> {
+    /// the ray system, which records the termination reason
+    path: &'b WaveRayPath<'a>,
+    /// called with the time and state after each valid step
+    observer: F,
+}
+
+#[allow(dead_code)]
+impl<'a, 'b, F: FnMut(Time, &State)> ObservedRayPath<'a, 'b, F> {
+    /// Construct a new `ObservedRayPath`
+    ///
+    /// # Arguments
+    /// `path` : `&WaveRayPath`
+    /// - the ray system
+    ///
+    /// `observer` : `F`
+    /// - called with the time and state after each valid step
+    ///
+    /// # Returns
+    /// `Self` : the observed system
+    pub(crate) fn new(path: &'b WaveRayPath<'a>, observer: F) -> Self {
+        ObservedRayPath { path, observer }
+    }
+}
+
+impl<'a, 'b, F: FnMut(Time, &State)> ode_solvers::System<Time, State>
+    for ObservedRayPath<'a, 'b, F>
+{
+    fn system(&self, t: Time, s: &State, ds: &mut State) {
+        self.path.system(t, s, ds)
+    }
+
+    /// The NaN steps after the ray left the domain are not observed, since
+    /// they are not part of the `RayResult`
+    fn solout(&mut self, x: Time, y: &State, dy: &State) -> bool {
+        if !x.is_nan() && !y.iter().any(|v| v.is_nan()) {
+            (self.observer)(x, y);
         }
+        self.path.should_stop(y, dy)
+    }
+}
+
+/// Whether the integration should stop at the given state and derivatives
+fn stop_integration(y: &State, dy: &State) -> bool {
+    if (dy[0].is_nan() && dy[1].is_nan() && dy[2].is_nan() && dy[3].is_nan())
+        || (y[0].is_nan() && y[1].is_nan() && y[2].is_nan() && y[3].is_nan())
+    {
+        // NaN in derivatives or output. Likely reached end of current or
+        // bathy domain. Stopping integration.
+        true
+    } else {
+        false
     }
 }
 
@@ -250,6 +1005,8 @@ impl<'a> ode_solvers::System<Time, State> for WaveRayPath<'a> {
 /// tests for constant depth
 mod test_constant_bathymetry {
     use crate::current::ConstantCurrent;
+    use crate::dispersion::{DispersionModel, G};
+    use crate::presets::Preset;
     use crate::wave_ray_path::{State, WaveRayPath};
     use crate::{bathymetry::ArrayDepth, bathymetry::BathymetryData, bathymetry::ConstantDepth};
     use ode_solvers::*;
@@ -294,10 +1051,8 @@ mod test_constant_bathymetry {
     #[test]
     /// testing group velocity function against values generated by wolfram alpha
     fn test_group_velocity() {
-        let depth = ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let wave_ray_path = WaveRayPath::new(&depth, &current);
+        let preset = Preset::deep_water();
+        let wave_ray_path = preset.wave_ray_path();
         let results = [
             (1.0, 1.565247584249853),
             (3.0, 0.9036961141150639),
@@ -317,10 +1072,8 @@ mod test_constant_bathymetry {
     #[test]
     /// verifying a negative k passed to group_velocity will return an error.
     fn test_negative_k() {
-        let depth = ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let wave_ray_path = WaveRayPath::new(&depth, &current);
+        let preset = Preset::deep_water();
+        let wave_ray_path = preset.wave_ray_path();
         assert!(wave_ray_path.group_velocity(&-1.0, &1000.0).is_err());
         assert!(wave_ray_path.group_velocity(&-12.0, &1000.0).is_err())
     }
@@ -337,10 +1090,8 @@ mod test_constant_bathymetry {
             // (0.0, 0.0, 0.0, 0.0) // this would cause panic
         ];
 
-        let data: &dyn BathymetryData = &ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let system = WaveRayPath::new(data, &current);
+        let preset = Preset::deep_water();
+        let system = preset.wave_ray_path();
 
         for (kx, ky, ans_dxdt, ans_dydt) in results {
             let (dxdt, dydt, _, _) = system.odes(&0.0, &0.0, &kx, &ky).unwrap();
@@ -360,10 +1111,8 @@ mod test_constant_bathymetry {
     #[test]
     /// all outputs should be NaN if k starts out of bounds
     fn test_zero_k() {
-        let data: &dyn BathymetryData = &ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let system = WaveRayPath::new(data, &current);
+        let preset = Preset::deep_water();
+        let system = preset.wave_ray_path();
         let y0 = State::new(0.0, 0.0, 0.0, 0.0);
 
         let t0 = 0.0;
@@ -437,10 +1186,8 @@ mod test_constant_bathymetry {
     #[test]
     /// if x input is NAN, the output x should be NaN. if k is zero, it will still error.
     fn test_x_nan() {
-        let data: &dyn BathymetryData = &ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let system = WaveRayPath::new(data, &current);
+        let preset = Preset::deep_water();
+        let system = preset.wave_ray_path();
         let nan = f64::NAN;
         let y0 = State::new(nan, 0.0, 1.0, 0.0);
 
@@ -457,10 +1204,8 @@ mod test_constant_bathymetry {
     #[test]
     /// if y input is NAN, the output x should be NaN. if k is zero, it will still error.
     fn test_y_nan() {
-        let data: &dyn BathymetryData = &ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let system = WaveRayPath::new(data, &current);
+        let preset = Preset::deep_water();
+        let system = preset.wave_ray_path();
         let nan = f64::NAN;
         let y0 = State::new(0.0, nan, 1.0, 0.0);
 
@@ -477,10 +1222,8 @@ mod test_constant_bathymetry {
     #[test]
     /// if either k input is NAN, the output x and y should be NaN.
     fn test_kx_nan() {
-        let data: &dyn BathymetryData = &ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let system = WaveRayPath::new(data, &current);
+        let preset = Preset::deep_water();
+        let system = preset.wave_ray_path();
         let nan = f64::NAN;
         let y0 = State::new(0.0, 0.0, nan, 0.0);
 
@@ -498,10 +1241,8 @@ mod test_constant_bathymetry {
     #[test]
     /// if either k input is NAN, the output x and y should be NaN.
     fn test_ky_nan() {
-        let data: &dyn BathymetryData = &ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let system = WaveRayPath::new(data, &current);
+        let preset = Preset::deep_water();
+        let system = preset.wave_ray_path();
         let nan = f64::NAN;
         let y0 = State::new(0.0, 0.0, 0.0, nan);
 
@@ -577,10 +1318,8 @@ mod test_constant_bathymetry {
         let dhdx = 0.2;
         let dhdy = 0.2;
 
-        let depth = ConstantDepth::new(1000.0);
-        let current = ConstantCurrent::new(0.0, 0.0);
-
-        let wave_ray_path = WaveRayPath::new(&depth, &current);
+        let preset = Preset::deep_water();
+        let wave_ray_path = preset.wave_ray_path();
 
         let ans = wave_ray_path.dkdt_bathy(&k_mag, &h, &dhdx, &dhdy);
 
@@ -596,6 +1335,81 @@ mod test_constant_bathymetry {
         )
     }
 
+    #[test]
+    /// each dispersion model gives its analytic group velocity, and the full
+    /// linear model converges to the shallow and deep water limits
+    fn test_dispersion_model_group_velocity() {
+        let depth = ConstantDepth::new(10.0);
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let path = |model: DispersionModel| {
+            WaveRayPath::builder()
+                .bathymetry_data(&depth)
+                .current_data(&current)
+                .dispersion_model(model)
+                .build()
+                .unwrap()
+        };
+        let full = path(DispersionModel::FullLinear);
+        let shallow = path(DispersionModel::ShallowWater);
+        let deep = path(DispersionModel::DeepWater);
+
+        for (k, h) in [(0.001, 10.0), (0.1, 10.0), (1.0, 1000.0)] {
+            let cg = shallow.group_velocity(&k, &h).unwrap();
+            assert!((cg - (G * h).sqrt()).abs() < 1e-12, "k: {}, h: {}", k, h);
+            let cg = deep.group_velocity(&k, &h).unwrap();
+            let sigma = (G * k).sqrt();
+            assert!((cg - G / (2.0 * sigma)).abs() < 1e-12, "k: {}, h: {}", k, h);
+        }
+
+        // kh = 0.01
+        let cg = full.group_velocity(&0.001, &10.0).unwrap();
+        let limit = shallow.group_velocity(&0.001, &10.0).unwrap();
+        assert!((cg - limit).abs() < 1e-4 * limit);
+        // kh = 1000
+        let cg = full.group_velocity(&1.0, &1000.0).unwrap();
+        let limit = deep.group_velocity(&1.0, &1000.0).unwrap();
+        assert!((cg - limit).abs() < 1e-12 * limit);
+
+        assert!(shallow.group_velocity(&0.1, &-1.0).unwrap().is_nan());
+        assert!(deep.group_velocity(&-0.1, &10.0).is_err());
+    }
+
+    #[test]
+    /// each dispersion model gives its analytic refraction by the bathymetry
+    fn test_dispersion_model_refraction() {
+        let depth = ConstantDepth::new(10.0);
+        let current = ConstantCurrent::new(0.0, 0.0);
+        let path = |model: DispersionModel| {
+            WaveRayPath::builder()
+                .bathymetry_data(&depth)
+                .current_data(&current)
+                .dispersion_model(model)
+                .build()
+                .unwrap()
+        };
+        let full = path(DispersionModel::FullLinear);
+        let shallow = path(DispersionModel::ShallowWater);
+        let deep = path(DispersionModel::DeepWater);
+
+        let (dhdx, dhdy) = (-0.05, 0.02);
+
+        // sigma = k sqrt(g h), thus dk/dt = -k sqrt(g / h) / 2 grad(h)
+        let (k, h) = (0.001, 10.0);
+        let (dkxdt, dkydt) = shallow.dkdt_bathy(&k, &h, &dhdx, &dhdy);
+        let dsigmadh = 0.5 * k * (G / h).sqrt();
+        assert!((dkxdt + dsigmadh * dhdx).abs() < 1e-15);
+        assert!((dkydt + dsigmadh * dhdy).abs() < 1e-15);
+
+        let (full_dkxdt, full_dkydt) = full.dkdt_bathy(&k, &h, &dhdx, &dhdy);
+        assert!((full_dkxdt - dkxdt).abs() < 1e-4 * dkxdt.abs());
+        assert!((full_dkydt - dkydt).abs() < 1e-4 * dkydt.abs());
+
+        // the deep water waves don't feel the bottom
+        assert_eq!(deep.dkdt_bathy(&0.1, &10.0, &dhdx, &dhdy), (0.0, 0.0));
+        let (full_dkxdt, _) = full.dkdt_bathy(&1.0, &1000.0, &dhdx, &dhdy);
+        assert!(full_dkxdt.abs() < 1e-12);
+    }
+
     #[test]
     // test the solout function stops integration early
     fn test_solout() {
@@ -630,10 +1444,13 @@ mod test_constant_bathymetry {
 #[cfg(test)]
 mod test_current {
     use crate::{
-        bathymetry::{BathymetryData, ConstantDepth},
+        bathymetry::{BathymetryData, ConstantDepth, ConstantSlope},
         current::{ConstantCurrent, CurrentData},
-        wave_ray_path::WaveRayPath,
+        datatype::Point,
+        dispersion::{self, G},
+        wave_ray_path::{State, StateView, TerminationReason, WaveRayPath, KX_INDEX, KY_INDEX},
     };
+    use ode_solvers::Rk4;
 
     #[test]
     /// this test I added by copying a test from the module
@@ -694,6 +1511,33 @@ mod test_current {
         }
     }
 
+    #[test]
+    /// without bathymetry data, the group velocity should be the one for
+    /// `default_depth`
+    fn test_default_depth() {
+        let wave = WaveRayPath::builder().default_depth(500.0).build().unwrap();
+
+        // long wave, so that the group velocity depends on the depth
+        let k = 0.005;
+        let (dxdt, dydt, dkxdt, dkydt) = wave.odes(&0.0, &0.0, &k, &0.0).unwrap();
+        let ans = wave.group_velocity(&k, &500.0).unwrap();
+        assert!(
+            (dxdt - ans).abs() < f64::EPSILON,
+            "Expected {}, but got {}",
+            ans,
+            dxdt
+        );
+        assert_eq!(dydt, 0.0);
+        assert_eq!(dkxdt, 0.0);
+        assert_eq!(dkydt, 0.0);
+
+        // the group velocity is different from the one for the default 2000 m
+        let wave2 = WaveRayPath::builder().build().unwrap();
+        let (dxdt2, _, _, _) = wave2.odes(&0.0, &0.0, &k, &0.0).unwrap();
+        assert!((dxdt2 - wave.group_velocity(&k, &2000.0).unwrap()).abs() < f64::EPSILON);
+        assert!((dxdt - dxdt2).abs() > 0.1);
+    }
+
     #[test]
     fn test_constant_depth_current() {
         // test case: initial group velocity in x axis only test 1, -1 for both
@@ -766,4 +1610,120 @@ mod test_current {
             );
         }
     }
+
+    #[test]
+    /// the absolute frequency is the intrinsic frequency Doppler shifted by
+    /// the current
+    fn test_frequencies() {
+        let bd = ConstantDepth::new(10.0);
+        let cd = ConstantCurrent::new(0.5, -1.0);
+        let wave = WaveRayPath::new(&bd, &cd);
+
+        let state = State::new(0.0, 0.0, 0.1, 0.2);
+        let sigma = dispersion::intrinsic_frequency(0.1_f64.hypot(0.2), 10.0);
+        assert!((wave.intrinsic_frequency(&state).unwrap() - sigma).abs() < f64::EPSILON);
+        let omega = sigma + 0.1 * 0.5 - 0.2 * 1.0;
+        assert!((wave.absolute_frequency(&state).unwrap() - omega).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    /// the named accessors match the index constants
+    fn test_state_view() {
+        let state = State::new(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(
+            (state.x(), state.y(), state.kx(), state.ky()),
+            (1.0, 2.0, 3.0, 4.0)
+        );
+        assert_eq!(state[KX_INDEX], state.kx());
+        assert_eq!(state[KY_INDEX], state.ky());
+    }
+
+    #[test]
+    /// without current the energy propagates in the direction of the
+    /// wavenumber, while a cross-current turns the ray but not the wavenumber
+    fn test_energy_direction() {
+        let bd = ConstantDepth::new(1000.0);
+        let state = State::new(0.0, 0.0, 0.1, 0.0);
+
+        let cd = ConstantCurrent::new(0.0, 0.0);
+        let wave = WaveRayPath::new(&bd, &cd);
+        assert_eq!(wave.energy_direction(&state).unwrap(), 0.0);
+
+        // deep water group velocity is 0.5 * sqrt(g / k) = 4.95 m/s
+        let cd = ConstantCurrent::new(0.0, 1.0);
+        let wave = WaveRayPath::new(&bd, &cd);
+        let cg = 0.5 * (G / 0.1).sqrt();
+        let direction = wave.energy_direction(&state).unwrap();
+        assert!((direction - (1.0 / cg).atan()).abs() < 1e-12);
+        assert!(direction > 0.1);
+
+        // the query doesn't record a termination reason, even beyond the
+        // shoreline, where `odes` would
+        let bd = ConstantSlope::builder().build().unwrap();
+        let wave = WaveRayPath::new(&bd, &cd);
+        assert!(wave
+            .energy_direction(&State::new(2000.0, 0.0, 0.1, 0.0))
+            .unwrap()
+            .is_nan());
+        assert_eq!(wave.termination_reason(), None);
+    }
+
+    #[test]
+    /// a short wave against a strong opposing current is blocked, while a long
+    /// wave is faster than the current and is not.
+    fn test_blocking() {
+        let bd = ConstantDepth::new(1000.0);
+        let cd = ConstantCurrent::new(-2.0, 0.0);
+
+        // deep water group velocity is 0.5 * sqrt(g / k) = 1.57 m/s
+        let wave = WaveRayPath::new(&bd, &cd);
+        let mut stepper = Rk4::new(&wave, 0.0, State::new(0.0, 0.0, 1.0, 0.0), 10.0, 1.0);
+        stepper.integrate().unwrap();
+        assert_eq!(wave.termination_reason(), Some(TerminationReason::Blocked));
+        assert!(stepper.y_out().last().unwrap().x.is_nan());
+
+        // the intrinsic group velocity does not include the current, which
+        // reverses the propagation of the short wave
+        let state = State::new(0.0, 0.0, 1.0, 0.0);
+        let cg = wave.intrinsic_group_velocity(&state).unwrap();
+        assert!((cg - 0.5 * (G / 1.0).sqrt()).abs() < 1e-12);
+        assert!(cg + cd.current(&Point::new(0.0, 0.0)).unwrap().u() < 0.0);
+
+        // deep water group velocity is 15.7 m/s
+        let wave = WaveRayPath::new(&bd, &cd);
+        let mut stepper = Rk4::new(&wave, 0.0, State::new(0.0, 0.0, 0.01, 0.0), 10.0, 1.0);
+        stepper.integrate().unwrap();
+        assert_eq!(wave.termination_reason(), None);
+        assert!(stepper.y_out().last().unwrap().x > 0.0);
+    }
+
+    #[test]
+    /// on a linear beach, the ray stops before the shoreline, where the depth
+    /// is zero and the group velocity is NaN, unless the depth is clamped to a
+    /// minimum depth.
+    fn test_min_depth() {
+        // depth is 50 - 0.05 x, so the shoreline is at x = 1000 m
+        let bd = ConstantSlope::builder().build().unwrap();
+
+        let last_x = |min_depth: f64| {
+            let wave = WaveRayPath::builder()
+                .bathymetry_data(&bd)
+                .min_depth(min_depth)
+                .build()
+                .unwrap();
+            let mut stepper = Rk4::new(&wave, 0.0, State::new(900.0, 0.0, 0.05, 0.0), 200.0, 1.0);
+            stepper.integrate().unwrap();
+            stepper
+                .y_out()
+                .iter()
+                .map(|s| s.x)
+                .rfind(|x| !x.is_nan())
+                .unwrap()
+        };
+
+        let without_clamp = last_x(0.0);
+        let with_clamp = last_x(0.1);
+        assert!(without_clamp < 1000.0, "without clamp: {}", without_clamp);
+        assert!(with_clamp > without_clamp, "with clamp: {}", with_clamp);
+    }
 }